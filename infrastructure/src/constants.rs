@@ -7,4 +7,4 @@ pub const RS_CODE_RATE: usize = 5;
 pub const MAX_BIT_LENGTH: usize = 30;
 pub const SIZE: usize = 1000000;
 
-pub const PACKED_SIZE: usize = 4;
+pub use prime_field::packed::PACKED_SIZE;
@@ -1,7 +1,9 @@
-#![feature(slice_as_chunks)]
+#![feature(once_cell)]
 
 pub mod constants;
 pub mod merkle_tree;
 pub mod my_hash;
+pub mod poseidon;
 pub mod rs_polynomial;
+pub mod transcript;
 pub mod utility;
@@ -1,121 +1,330 @@
-use prime_field::FieldElement;
-use std::mem::size_of_val;
-use std::ptr::copy_nonoverlapping;
-use std::vec::Vec;
+use prime_field::Field;
+use serde::{Deserialize, Serialize};
 
-use crate::my_hash::{my_hash, HashDigest};
+use crate::my_hash::{HashDigest, Hasher};
 
-pub static mut size_after_padding: usize = 0;
+/// Bytes of `x` as little endian 128-bit words, a [`FieldElement`] filling
+/// one word with its real part in the low half
+///
+/// [`FieldElement`]: prime_field::FieldElement
+pub fn pack_field_element<F: Field>(x: F) -> Vec<i128> {
+    x.to_bytes()
+        .chunks(16)
+        .map(|chunk| {
+            let mut word = [0u8; 16];
+            word[..chunk.len()].copy_from_slice(chunk);
+            i128::from_le_bytes(word)
+        })
+        .collect()
+}
 
-pub unsafe fn hash_single_field_element(x: FieldElement) -> HashDigest {
+pub fn hash_single_field_element<H: Hasher + ?Sized, F: Field>(hasher: &H, x: F) -> HashDigest {
+    let words = pack_field_element(x);
+    assert!(words.len() <= 4, "field elements of more than 64 bytes");
+    let mut words = words.into_iter();
     let mut data = [HashDigest::default(); 2];
-    copy_nonoverlapping(
-        std::ptr::addr_of!(x) as *const i128,
-        std::ptr::addr_of_mut!(data[0].h0),
-        size_of_val(&x),
-    );
-    assert_eq!(size_of_val(&x), size_of_val(&data[0].h0));
-    my_hash(data)
+    for digest in &mut data {
+        digest.h0 = words.next().unwrap_or(0);
+        digest.h1 = words.next().unwrap_or(0);
+    }
+    hasher.hash(data)
 }
 
-pub unsafe fn hash_double_field_element_merkle_damgard(
-    x: FieldElement,
-    y: FieldElement,
+/// Chains the hash of `x` and `y` to `prev_hash`, two words at a time
+pub fn hash_double_field_element_merkle_damgard<H: Hasher + ?Sized, F: Field>(
+    hasher: &H,
+    x: F,
+    y: F,
     prev_hash: HashDigest,
 ) -> HashDigest {
-    let mut data = [HashDigest::default(); 2];
-    data[0] = prev_hash;
-    let mut element = [x, y];
-    copy_nonoverlapping(
-        std::ptr::addr_of!(element) as *const HashDigest,
-        std::ptr::addr_of_mut!(data[1]),
-        size_of_val(&data[1]),
-    );
-    assert_eq!(size_of_val(&data[1]), size_of_val(&element));
-    my_hash(data)
+    let mut words = pack_field_element(x);
+    words.extend(pack_field_element(y));
+    words.chunks(2).fold(prev_hash, |hash, pair| {
+        let data = [
+            hash,
+            HashDigest {
+                h0: pair[0],
+                h1: pair.get(1).copied().unwrap_or(0),
+            },
+        ];
+        hasher.hash(data)
+    })
+}
+
+/// Binary merkle tree, storing node `k` at index `k` with the root at 1 and
+/// the children of `k` at `2k` and `2k + 1`.
+#[derive(Debug, Clone, Default)]
+pub struct MerkleTree {
+    nodes: Vec<HashDigest>,
+}
+
+/// Authentication of several leaves of a merkle tree at once. Siblings shared
+/// by the paths of the opened leaves, or computable from them, are sent once.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultiProof {
+    /// Number of levels below the root
+    pub depth: usize,
+    /// Missing siblings, level by level from the leaves up, left to right
+    pub siblings: Vec<HashDigest>,
 }
 
-pub unsafe fn create_tree(
-    src_data: Vec<HashDigest>,
-    element_num: usize,
-    dst: &mut Vec<HashDigest>,
-    element_size_: Option<usize>,
-    alloc_required_: Option<bool>,
-) {
-    // init
-    // NOTE: actually the element size is the size_of::<HashDigest>
-    // NOTE: src_data.len() == element_num = true
-    let element_size = element_size_.unwrap_or(256 / 8);
-    let alloc_required = alloc_required_.unwrap_or(false);
-    size_after_padding = 1;
-    while size_after_padding < element_num {
-        size_after_padding *= 2;
+/// Sorts the opened positions, merging duplicates. Returns `None` when a
+/// position is opened to two different values.
+fn sorted_positions(indices: &[usize], leaves: &[HashDigest]) -> Option<Vec<(usize, HashDigest)>> {
+    let mut positions: Vec<_> = indices
+        .iter()
+        .copied()
+        .zip(leaves.iter().copied())
+        .collect();
+    positions.sort_by_key(|(index, _)| *index);
+
+    let mut merged: Vec<(usize, HashDigest)> = Vec::with_capacity(positions.len());
+    for (index, leaf) in positions {
+        match merged.last() {
+            Some(&(last, last_leaf)) if last == index => {
+                if last_leaf != leaf {
+                    return None;
+                }
+            }
+            _ => merged.push((index, leaf)),
+        }
     }
-    if alloc_required {
-        *dst = vec![HashDigest::default(); size_after_padding * 2];
-        /*
-        dst_ptr = (__hhash_digest*)malloc(size_after_padding * 2 * element_size);
-        if(dst_ptr == NULL)
-        {
-            printf("Merkle Tree Bad Alloc\n");
-            abort();
+    Some(merged)
+}
+
+impl MerkleTree {
+    /// Builds the tree over `leaves`, padded to a power of two
+    pub fn commit<H: Hasher + ?Sized>(hasher: &H, leaves: &[HashDigest]) -> Self {
+        let size = leaves.len().max(1).next_power_of_two();
+        let mut nodes = vec![HashDigest::default(); size * 2];
+
+        nodes[size..size + leaves.len()].copy_from_slice(leaves);
+        let padding = hasher.hash([HashDigest::default(), HashDigest::default()]);
+        nodes[size + leaves.len()..].fill(padding);
+
+        for k in (1..size).rev() {
+            nodes[k] = hasher.hash([nodes[2 * k], nodes[2 * k + 1]]);
         }
-        */
+        Self { nodes }
+    }
+
+    pub fn root(&self) -> HashDigest {
+        self.nodes[1]
+    }
+
+    /// Number of leaves, including the padding
+    pub fn leaf_count(&self) -> usize {
+        self.nodes.len() / 2
     }
-    let mut start_idx = size_after_padding;
-    let mut current_lvl_size = size_after_padding;
-    // TODO: parallel
-    for i in (current_lvl_size - 1)..=0 {
-        let mut data = [HashDigest::default(); 2];
-        if i < element_num {
-            dst[i + start_idx] = src_data[i];
-        } else {
-            data = [HashDigest::default(); 2];
-            // my_hash(data, &mut dst[i + start_idx]);
-            dst[i + start_idx] = my_hash(data);
+
+    pub fn depth(&self) -> usize {
+        self.leaf_count().trailing_zeros() as usize
+    }
+
+    pub fn leaf(&self, index: usize) -> HashDigest {
+        self.nodes[self.leaf_count() + index]
+    }
+
+    /// Proves the leaves at `indices`, which may be unordered or repeated
+    pub fn open(&self, indices: &[usize]) -> MultiProof {
+        let leaf_count = self.leaf_count();
+        let mut positions: Vec<usize> = indices
+            .iter()
+            .map(|index| {
+                assert!(*index < leaf_count, "leaf {} out of range", index);
+                leaf_count + index
+            })
+            .collect();
+        positions.sort_unstable();
+        positions.dedup();
+
+        let mut siblings = vec![];
+        for _ in 0..self.depth() {
+            let mut i = 0;
+            while i < positions.len() {
+                let pos = positions[i];
+                if pos & 1 == 0 && positions.get(i + 1) == Some(&(pos | 1)) {
+                    // both children are known to the verifier
+                    i += 1;
+                } else {
+                    siblings.push(self.nodes[pos ^ 1]);
+                }
+                i += 1;
+            }
+            positions = positions.iter().map(|pos| pos / 2).collect();
+            positions.dedup();
+        }
+
+        MultiProof {
+            depth: self.depth(),
+            siblings,
         }
     }
-    current_lvl_size /= 2;
-    start_idx -= current_lvl_size;
-    while current_lvl_size >= 1 {
-        // TODO: parallel
-        for i in 0..current_lvl_size {
-            let mut data = [HashDigest::default(); 2];
-            data[0] = dst[start_idx + current_lvl_size + i * 2];
-            data[1] = dst[start_idx + current_lvl_size + i * 2 + 1];
-            // my_hash(data, &mut dst[start_idx + i]);
-            dst[start_idx + i] = my_hash(data);
+
+    /// Checks that `leaves` are the leaves at `indices` of the tree with root
+    /// `root`, whose depth is given by `proof`
+    pub fn verify<H: Hasher + ?Sized>(
+        hasher: &H,
+        root: HashDigest,
+        indices: &[usize],
+        leaves: &[HashDigest],
+        proof: &MultiProof,
+    ) -> bool {
+        if indices.len() != leaves.len()
+            || indices.is_empty()
+            || proof.depth >= usize::BITS as usize
+        {
+            return false;
+        }
+        let mut level = match sorted_positions(indices, leaves) {
+            Some(level) => level,
+            None => return false,
+        };
+        if level.iter().any(|(index, _)| *index >> proof.depth != 0) {
+            return false;
         }
-        current_lvl_size /= 2;
-        start_idx -= current_lvl_size;
+
+        let mut siblings = proof.siblings.iter();
+        for _ in 0..proof.depth {
+            let mut next = Vec::with_capacity(level.len());
+            let mut i = 0;
+            while i < level.len() {
+                let (pos, hash) = level[i];
+                let parent = if pos & 1 == 0 {
+                    match level.get(i + 1) {
+                        Some(&(right_pos, right)) if right_pos == pos | 1 => {
+                            i += 1;
+                            hasher.hash([hash, right])
+                        }
+                        _ => match siblings.next() {
+                            Some(right) => hasher.hash([hash, *right]),
+                            None => return false,
+                        },
+                    }
+                } else {
+                    match siblings.next() {
+                        Some(left) => hasher.hash([*left, hash]),
+                        None => return false,
+                    }
+                };
+                next.push((pos / 2, parent));
+                i += 1;
+            }
+            level = next;
+        }
+
+        siblings.next().is_none() && level == [(0, root)]
     }
 }
 
-pub fn verify_claim(
-    root_hash: HashDigest,
-    tree: Vec<HashDigest>,
-    leaf_hash: &mut HashDigest,
-    pos_element_arr: usize,
-    N: usize,
-    visited: &mut Vec<bool>,
-    proof_size: &mut usize,
-) -> bool {
-    // check N is power of 2
-    assert_eq!(((N as i64) & -(N as i64)), N as i64);
-    let mut pos_element = pos_element_arr + N;
-    let mut data = [HashDigest::default(); 2];
-    while pos_element != 1 {
-        let pos_1 = pos_element & 1;
-        let pos_2 = pos_element ^ 1;
-        data[pos_1] = *leaf_hash;
-        data[pos_1 ^ 1] = tree[pos_2];
-        if !visited[pos_2] {
-            visited[pos_2] = true;
-            *proof_size += size_of_val(&leaf_hash);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::my_hash::Sha3Hasher;
+
+    fn leaves(n: usize) -> Vec<HashDigest> {
+        (0..n)
+            .map(|i| HashDigest {
+                h0: i as i128,
+                h1: 1,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn opened_leaves_verify() {
+        let leaves = leaves(13);
+        let tree = MerkleTree::commit(&Sha3Hasher, &leaves);
+        assert_eq!(tree.depth(), 4);
+
+        for indices in [vec![0], vec![12], vec![3, 2], vec![7, 1, 7, 12, 0]] {
+            let proof = tree.open(&indices);
+            let opened: Vec<_> = indices.iter().map(|i| leaves[*i]).collect();
+            assert!(MerkleTree::verify(
+                &Sha3Hasher,
+                tree.root(),
+                &indices,
+                &opened,
+                &proof
+            ));
         }
-        *leaf_hash = my_hash(data);
-        // my_hash(data, leaf_hash);
-        pos_element /= 2;
     }
-    root_hash == *leaf_hash
+
+    #[test]
+    fn shared_siblings_are_sent_once() {
+        let tree = MerkleTree::commit(&Sha3Hasher, &leaves(16));
+        assert_eq!(tree.open(&[5]).siblings.len(), 4);
+        // 4 and 5 are siblings, their parent's path is shared
+        assert_eq!(tree.open(&[4, 5]).siblings.len(), 3);
+        assert_eq!(tree.open(&[0, 1, 2, 3]).siblings.len(), 2);
+        assert!(tree.open(&(0..16).collect::<Vec<_>>()).siblings.is_empty());
+    }
+
+    #[test]
+    fn forged_openings_are_rejected() {
+        let leaves = leaves(16);
+        let tree = MerkleTree::commit(&Sha3Hasher, &leaves);
+        let indices = [3, 9];
+        let opened = [leaves[3], leaves[9]];
+        let proof = tree.open(&indices);
+
+        assert!(!MerkleTree::verify(
+            &Sha3Hasher,
+            tree.root(),
+            &indices,
+            &[leaves[3], leaves[8]],
+            &proof
+        ));
+        assert!(!MerkleTree::verify(
+            &Sha3Hasher,
+            tree.root(),
+            &[3, 8],
+            &opened,
+            &proof
+        ));
+        assert!(!MerkleTree::verify(
+            &Sha3Hasher,
+            tree.root(),
+            &[3, 3],
+            &opened,
+            &proof
+        ));
+        assert!(!MerkleTree::verify(
+            &Sha3Hasher,
+            tree.root(),
+            &[3, 9 + 16],
+            &opened,
+            &proof
+        ));
+
+        let mut bad_sibling = proof.clone();
+        bad_sibling.siblings[0] = HashDigest::default();
+        assert!(!MerkleTree::verify(
+            &Sha3Hasher,
+            tree.root(),
+            &indices,
+            &opened,
+            &bad_sibling
+        ));
+
+        let mut extra_sibling = proof.clone();
+        extra_sibling.siblings.push(HashDigest::default());
+        assert!(!MerkleTree::verify(
+            &Sha3Hasher,
+            tree.root(),
+            &indices,
+            &opened,
+            &extra_sibling
+        ));
+
+        let mut shallow = proof;
+        shallow.depth -= 1;
+        assert!(!MerkleTree::verify(
+            &Sha3Hasher,
+            tree.root(),
+            &indices,
+            &opened,
+            &shallow
+        ));
+    }
 }
@@ -1,9 +1,12 @@
-use byteorder::{BigEndian, ReadBytesExt};
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
 use sha3::{Digest, Sha3_256};
-use std::io::Cursor;
+
+use crate::poseidon;
 
 /// TODO: https://doc.rust-lang.org/beta/core/arch/x86_64/struct.__m128i.html
-#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct HashDigest {
   pub h0: i128,
   pub h1: i128,
@@ -15,29 +18,167 @@ impl HashDigest {
   }
 }
 
-#[inline]
-pub fn my_hash(src: [HashDigest; 2]) -> HashDigest {
-  // the original sha256_update_shani type signature is an optimised function for
-  // SHA-NI instruction sets machine, this is the fallback one
-  // https://www.ic.unicamp.br/~ra142685/sok-apkc.pdf
-  let mut hasher = Sha3_256::new();
+/// Two to one compression function behind merkle trees and transcripts
+pub trait Hasher {
+  fn hash(&self, src: [HashDigest; 2]) -> HashDigest;
+}
 
-  for d in src {
-    // NOTE: Big endian serialisation
-    let sixteen_u8_h = d.h0.to_be_bytes();
-    let sixteen_u8_l = d.h1.to_be_bytes();
-    hasher.update(sixteen_u8_h);
-    hasher.update(sixteen_u8_l);
+// NOTE: Big endian serialisation, for all the byte oriented hashes
+fn to_bytes(src: [HashDigest; 2]) -> [u8; 64] {
+  let mut bytes = [0; 64];
+  for (chunk, half) in bytes
+    .chunks_mut(16)
+    .zip(src.iter().flat_map(|d| [d.h0, d.h1]))
+  {
+    chunk.copy_from_slice(&half.to_be_bytes());
   }
+  bytes
+}
 
-  let result = hasher.finalize();
+fn from_bytes(bytes: &[u8]) -> HashDigest {
+  HashDigest {
+    h0: i128::from_be_bytes(bytes[..16].try_into().unwrap()),
+    h1: i128::from_be_bytes(bytes[16..32].try_into().unwrap()),
+  }
+}
 
-  // DO WE REALLY SURE SHA256 produces fixed-length output?
-  let (arr, _) = result.as_chunks::<{ 16 as usize }>();
+/// SHA-256, which the original code computes with the SHA-NI instructions
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sha256Hasher;
 
-  HashDigest {
-    // NOTE: Big endian serialisation
-    h0: Cursor::new(arr[0]).read_i128::<BigEndian>().unwrap(),
-    h1: Cursor::new(arr[1]).read_i128::<BigEndian>().unwrap(),
+impl Hasher for Sha256Hasher {
+  fn hash(&self, src: [HashDigest; 2]) -> HashDigest {
+    from_bytes(&hmac_sha256::Hash::hash(&to_bytes(src)))
+  }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sha3Hasher;
+
+impl Hasher for Sha3Hasher {
+  fn hash(&self, src: [HashDigest; 2]) -> HashDigest {
+    from_bytes(&Sha3_256::digest(to_bytes(src)))
+  }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Blake3Hasher;
+
+impl Hasher for Blake3Hasher {
+  fn hash(&self, src: [HashDigest; 2]) -> HashDigest {
+    from_bytes(blake3::hash(&to_bytes(src)).as_bytes())
+  }
+}
+
+/// Poseidon over the base field, cheap to verify inside a circuit
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PoseidonHasher;
+
+impl Hasher for PoseidonHasher {
+  fn hash(&self, src: [HashDigest; 2]) -> HashDigest {
+    poseidon::hash(src)
+  }
+}
+
+/// Hash function chosen for a proof, and recorded in its header
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HashAlgorithm {
+  Sha256,
+  #[default]
+  Sha3_256,
+  Blake3,
+  Poseidon,
+}
+
+impl HashAlgorithm {
+  pub const ALL: [HashAlgorithm; 4] = [
+    HashAlgorithm::Sha256,
+    HashAlgorithm::Sha3_256,
+    HashAlgorithm::Blake3,
+    HashAlgorithm::Poseidon,
+  ];
+
+  /// Identifier of the algorithm in proof headers
+  pub fn id(self) -> u8 {
+    match self {
+      HashAlgorithm::Sha256 => 0,
+      HashAlgorithm::Sha3_256 => 1,
+      HashAlgorithm::Blake3 => 2,
+      HashAlgorithm::Poseidon => 3,
+    }
+  }
+
+  pub fn from_id(id: u8) -> Option<Self> {
+    Self::ALL.into_iter().find(|algorithm| algorithm.id() == id)
+  }
+
+  pub fn name(self) -> &'static str {
+    match self {
+      HashAlgorithm::Sha256 => "sha256",
+      HashAlgorithm::Sha3_256 => "sha3",
+      HashAlgorithm::Blake3 => "blake3",
+      HashAlgorithm::Poseidon => "poseidon",
+    }
+  }
+}
+
+impl Hasher for HashAlgorithm {
+  fn hash(&self, src: [HashDigest; 2]) -> HashDigest {
+    match self {
+      HashAlgorithm::Sha256 => Sha256Hasher.hash(src),
+      HashAlgorithm::Sha3_256 => Sha3Hasher.hash(src),
+      HashAlgorithm::Blake3 => Blake3Hasher.hash(src),
+      HashAlgorithm::Poseidon => PoseidonHasher.hash(src),
+    }
+  }
+}
+
+impl fmt::Display for HashAlgorithm {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.name())
+  }
+}
+
+impl FromStr for HashAlgorithm {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Self::ALL
+      .into_iter()
+      .find(|algorithm| algorithm.name() == s)
+      .ok_or_else(|| format!("unknown hash {:?}", s))
+  }
+}
+
+/// Hash with the default algorithm, SHA3-256
+#[inline]
+pub fn my_hash(src: [HashDigest; 2]) -> HashDigest {
+  Sha3Hasher.hash(src)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn algorithms_are_distinct_and_roundtrip() {
+    let src = [HashDigest { h0: 1, h1: -2 }, HashDigest { h0: 3, h1: 4 }];
+    let digests: Vec<_> = HashAlgorithm::ALL
+      .iter()
+      .map(|algorithm| algorithm.hash(src))
+      .collect();
+    for (i, algorithm) in HashAlgorithm::ALL.into_iter().enumerate() {
+      assert_eq!(HashAlgorithm::from_id(algorithm.id()), Some(algorithm));
+      assert_eq!(algorithm.name().parse(), Ok(algorithm));
+      assert!(digests[i + 1..].iter().all(|d| *d != digests[i]));
+    }
+    assert_eq!(HashAlgorithm::from_id(4), None);
+  }
+
+  #[test]
+  fn sha256_matches_the_standard() {
+    // SHA-256 of 64 zero bytes
+    let digest = Sha256Hasher.hash([HashDigest::default(); 2]);
+    assert_eq!(digest.h0 as u128, 0xf5a5fd42d16a20302798ef6ed309979b_u128);
   }
 }
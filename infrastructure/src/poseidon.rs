@@ -0,0 +1,150 @@
+//! Poseidon permutation over the base field `2^61 - 1`, used as an
+//! arithmetization friendly compression function.
+
+use std::sync::OnceLock;
+
+use prime_field::{FieldElement, MOD};
+use sha3::{Digest, Sha3_256};
+
+use crate::my_hash::HashDigest;
+
+const WIDTH: usize = 16;
+/// The remaining 4 elements are the capacity
+const RATE: usize = 12;
+const FULL_ROUNDS: usize = 8;
+const PARTIAL_ROUNDS: usize = 22;
+/// Smallest exponent coprime with `2^61 - 2`, making `x^ALPHA` a permutation
+const ALPHA: u128 = 17;
+
+/// Limbs of 60 bits, so that every limb is a canonical field element
+const LIMB_BITS: u32 = 60;
+const LIMBS_PER_HALF: usize = 3;
+
+struct Parameters {
+    round_constants: Vec<[FieldElement; WIDTH]>,
+    mds: [[FieldElement; WIDTH]; WIDTH],
+}
+
+fn parameters() -> &'static Parameters {
+    static PARAMETERS: OnceLock<Parameters> = OnceLock::new();
+    PARAMETERS.get_or_init(|| {
+        // nothing up my sleeve constants, hashing a counter
+        let mut counter = 0u64;
+        let mut next_constant = || loop {
+            let mut hasher = Sha3_256::new();
+            hasher.update(b"poseidon-2^61-1");
+            hasher.update(counter.to_be_bytes());
+            counter += 1;
+            let bytes: [u8; 8] = hasher.finalize()[..8].try_into().unwrap();
+            let x = u64::from_be_bytes(bytes) >> 3;
+            if x < MOD {
+                return FieldElement::from_real(x);
+            }
+        };
+        let round_constants = (0..FULL_ROUNDS + PARTIAL_ROUNDS)
+            .map(|_| [(); WIDTH].map(|_| next_constant()))
+            .collect();
+
+        // Cauchy matrix 1 / (x_i + y_j) with x_i = i and y_j = WIDTH + j,
+        // every square submatrix of which is invertible
+        let mut mds = [[FieldElement::zero(); WIDTH]; WIDTH];
+        for (i, row) in mds.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = FieldElement::from_real((i + WIDTH + j) as u64).inverse();
+            }
+        }
+        Parameters {
+            round_constants,
+            mds,
+        }
+    })
+}
+
+pub fn permute(state: &mut [FieldElement; WIDTH]) {
+    let parameters = parameters();
+    for (round, constants) in parameters.round_constants.iter().enumerate() {
+        for (x, c) in state.iter_mut().zip(constants) {
+            *x = *x + *c;
+        }
+
+        let partial = FULL_ROUNDS / 2..FULL_ROUNDS / 2 + PARTIAL_ROUNDS;
+        if !partial.contains(&round) {
+            for x in state.iter_mut() {
+                *x = x.fast_pow(ALPHA);
+            }
+        } else {
+            state[0] = state[0].fast_pow(ALPHA);
+        }
+
+        let mut mixed = [FieldElement::zero(); WIDTH];
+        for (y, row) in mixed.iter_mut().zip(&parameters.mds) {
+            *y = row
+                .iter()
+                .zip(state.iter())
+                .fold(FieldElement::zero(), |acc, (m, x)| acc + *m * *x);
+        }
+        *state = mixed;
+    }
+}
+
+fn limbs(x: i128) -> [FieldElement; LIMBS_PER_HALF] {
+    let x = x as u128;
+    let mask = (1u128 << LIMB_BITS) - 1;
+    [0, 1, 2].map(|i| FieldElement::from_real(((x >> (i * LIMB_BITS)) & mask) as u64))
+}
+
+/// Compresses two digests into one. Both are split into 60 bits limbs which
+/// fill the rate of a single permutation, and the first four elements of the
+/// state are packed into the output.
+pub fn hash(src: [HashDigest; 2]) -> HashDigest {
+    let mut state = [FieldElement::zero(); WIDTH];
+    let input = src.iter().flat_map(|d| [d.h0, d.h1]).flat_map(limbs);
+    for (x, limb) in state[..RATE].iter_mut().zip(input) {
+        *x = limb;
+    }
+    permute(&mut state);
+
+    let pack =
+        |lo: FieldElement, hi: FieldElement| (lo.real as u128 | (hi.real as u128) << 64) as i128;
+    HashDigest {
+        h0: pack(state[0], state[1]),
+        h1: pack(state[2], state[3]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alpha_is_coprime_with_the_multiplicative_group_order() {
+        let (mut a, mut b) = (ALPHA as u64, MOD - 1);
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        assert_eq!(a, 1);
+    }
+
+    #[test]
+    fn every_input_bit_matters() {
+        let src = [
+            HashDigest { h0: -1, h1: 2 },
+            HashDigest {
+                h0: 3,
+                h1: i128::MIN,
+            },
+        ];
+        let digest = hash(src);
+        for (i, bit) in [(0, 0), (0, 127), (1, 59), (1, 60), (2, 64), (3, 127)] {
+            let mut flipped = src;
+            let half = match i {
+                0 => &mut flipped[0].h0,
+                1 => &mut flipped[0].h1,
+                2 => &mut flipped[1].h0,
+                _ => &mut flipped[1].h1,
+            };
+            *half ^= 1 << bit;
+            assert_ne!(hash(flipped), digest);
+        }
+    }
+}
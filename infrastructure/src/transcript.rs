@@ -0,0 +1,187 @@
+use prime_field::Field;
+
+use crate::my_hash::{HashAlgorithm, HashDigest, Hasher};
+
+/// Mixed into the state before every squeeze so that a challenge can never be
+/// confused with the digest of absorbed data.
+const SQUEEZE_TAG: i128 = 0x6368_616c_6c65_6e67_65; // "challenge"
+
+/// Fiat-Shamir transcript.
+///
+/// Both parties absorb every prover message in the same order and squeeze
+/// their challenges from the running hash state, which replaces the
+/// verifier's coin tosses and makes the proof non-interactive.
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    state: HashDigest,
+    hasher: HashAlgorithm,
+}
+
+/// Values which can be absorbed into a [`Transcript`].
+pub trait Absorb {
+    fn absorb_into(&self, transcript: &mut Transcript);
+}
+
+impl Transcript {
+    /// Starts a transcript bound to a protocol label, hashing with the
+    /// default algorithm.
+    pub fn new(label: &[u8]) -> Self {
+        Self::with_hasher(label, HashAlgorithm::default())
+    }
+
+    pub fn with_hasher(label: &[u8], hasher: HashAlgorithm) -> Self {
+        let mut transcript = Self {
+            state: HashDigest::default(),
+            hasher,
+        };
+        transcript.append_bytes(label);
+        transcript
+    }
+
+    pub fn hasher(&self) -> HashAlgorithm {
+        self.hasher
+    }
+
+    pub fn append_digest(&mut self, digest: HashDigest) {
+        self.state = self.hasher.hash([self.state, digest]);
+    }
+
+    pub fn append_bytes(&mut self, bytes: &[u8]) {
+        // length first, so that different splits of the same bytes don't collide
+        self.append_u64(bytes.len() as u64);
+        for chunk in bytes.chunks(32) {
+            let mut block = [0u8; 32];
+            block[..chunk.len()].copy_from_slice(chunk);
+
+            let (h0, h1) = block.split_at(16);
+            self.append_digest(HashDigest {
+                h0: i128::from_be_bytes(h0.try_into().unwrap()),
+                h1: i128::from_be_bytes(h1.try_into().unwrap()),
+            });
+        }
+    }
+
+    pub fn append_u64(&mut self, x: u64) {
+        self.append_digest(HashDigest {
+            h0: x.into(),
+            h1: 0,
+        });
+    }
+
+    /// Absorbs the bytes of `x` two 64-bit words at a time, the real and
+    /// imaginary parts of a [`FieldElement`](prime_field::FieldElement)
+    pub fn append_field_element<F: Field>(&mut self, x: F) {
+        for chunk in x.to_bytes().chunks(16) {
+            let mut block = [0u8; 16];
+            block[..chunk.len()].copy_from_slice(chunk);
+
+            let (h0, h1) = block.split_at(8);
+            self.append_digest(HashDigest {
+                h0: u64::from_le_bytes(h0.try_into().unwrap()).into(),
+                h1: u64::from_le_bytes(h1.try_into().unwrap()).into(),
+            });
+        }
+    }
+
+    pub fn absorb<T: Absorb + ?Sized>(&mut self, value: &T) {
+        value.absorb_into(self);
+    }
+
+    fn squeeze(&mut self) -> HashDigest {
+        self.state = self.hasher.hash([
+            self.state,
+            HashDigest {
+                h0: SQUEEZE_TAG,
+                h1: 0,
+            },
+        ]);
+        self.state
+    }
+
+    /// Draws a challenge from the whole field, squeezing two 64-bit words at
+    /// a time until `F::UNIFORM_BYTES` bytes are drawn.
+    pub fn challenge<F: Field>(&mut self) -> F {
+        let mut bytes = Vec::with_capacity(F::UNIFORM_BYTES + 15);
+        while bytes.len() < F::UNIFORM_BYTES {
+            let HashDigest { h0, h1 } = self.squeeze();
+            bytes.extend_from_slice(&(h0 as u64).to_le_bytes());
+            bytes.extend_from_slice(&(h1 as u64).to_le_bytes());
+        }
+        F::from_uniform_bytes(&bytes[..F::UNIFORM_BYTES])
+    }
+
+    pub fn challenges<F: Field>(&mut self, size: usize) -> Vec<F> {
+        (0..size).map(|_| self.challenge()).collect()
+    }
+
+    /// Draws an index in `[0, bound)`, used to pick query positions.
+    pub fn challenge_index(&mut self, bound: usize) -> usize {
+        assert!(bound > 0);
+        let HashDigest { h0, .. } = self.squeeze();
+        (h0 as u128 % bound as u128) as usize
+    }
+}
+
+impl<F: Field> Absorb for F {
+    fn absorb_into(&self, transcript: &mut Transcript) {
+        transcript.append_field_element(*self);
+    }
+}
+
+impl Absorb for HashDigest {
+    fn absorb_into(&self, transcript: &mut Transcript) {
+        transcript.append_digest(*self);
+    }
+}
+
+impl<T: Absorb> Absorb for [T] {
+    fn absorb_into(&self, transcript: &mut Transcript) {
+        transcript.append_u64(self.len() as u64);
+        for value in self {
+            value.absorb_into(transcript);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prime_field::FieldElement;
+
+    use super::*;
+
+    #[test]
+    fn same_messages_give_same_challenges() {
+        let mut prover = Transcript::new(b"test");
+        let mut verifier = Transcript::new(b"test");
+
+        let messages = vec![FieldElement::new(3, 4), FieldElement::from_real(7)];
+        prover.absorb(&messages[..]);
+        verifier.absorb(&messages[..]);
+
+        assert_eq!(
+            prover.challenges::<FieldElement>(4),
+            verifier.challenges::<FieldElement>(4)
+        );
+        assert_eq!(prover.challenge_index(1000), verifier.challenge_index(1000));
+    }
+
+    #[test]
+    fn challenges_depend_on_messages_and_label() {
+        let mut a = Transcript::new(b"test");
+        let mut b = Transcript::new(b"test");
+        let mut c = Transcript::new(b"other");
+
+        a.append_field_element(FieldElement::from_real(1));
+        b.append_field_element(FieldElement::from_real(2));
+        c.append_field_element(FieldElement::from_real(1));
+
+        let challenge: FieldElement = a.challenge();
+        assert_ne!(challenge, b.challenge());
+        assert_ne!(challenge, c.challenge());
+        assert_ne!(challenge, a.challenge());
+
+        let mut d = Transcript::with_hasher(b"test", HashAlgorithm::Blake3);
+        d.append_field_element(FieldElement::from_real(1));
+        assert_ne!(challenge, d.challenge());
+    }
+}
@@ -1,11 +1,147 @@
+/// Portable versions of the AVX2 operations on 4 lanes of 64 bits used by
+/// the packed field arithmetic. A register is an `i256` whose little endian
+/// 64 bit words are the lanes, lane 0 being the lowest word. The operations
+/// act on the two 128 bit words of the register at once, masking the bits
+/// that would cross a lane, so values stay in an `i256` across chained
+/// operations.
 pub mod i256 {
-    use ethnum::{i256, u256};
+    use ethnum::i256;
 
+    pub const LANES: usize = 4;
+
+    /// Top bit of both lanes of a word
+    const HIGH: u128 = splat(1 << 63);
+
+    /// `x` in both lanes of a word
+    const fn splat(x: u64) -> u128 {
+        (x as u128) << 64 | x as u128
+    }
+
+    #[inline]
+    fn map(x: &i256, f: impl Fn(u128) -> u128) -> i256 {
+        let (hi, lo) = x.into_words();
+        i256::from_words(f(hi as u128) as i128, f(lo as u128) as i128)
+    }
+
+    #[inline]
+    fn zip(x: &i256, y: &i256, f: impl Fn(u128, u128) -> u128) -> i256 {
+        let ((x_hi, x_lo), (y_hi, y_lo)) = (x.into_words(), y.into_words());
+        i256::from_words(
+            f(x_hi as u128, y_hi as u128) as i128,
+            f(x_lo as u128, y_lo as u128) as i128,
+        )
+    }
+
+    /// Wrapping difference of both lanes of a word
+    #[inline]
+    fn sub_words(x: u128, y: u128) -> u128 {
+        // setting the top bit of the lanes of x keeps the borrows in them
+        ((x | HIGH) - (y & !HIGH)) ^ ((x ^ !y) & HIGH)
+    }
+
+    #[inline]
+    pub fn from_lanes(lanes: [u64; LANES]) -> i256 {
+        let lo = (lanes[1] as u128) << 64 | lanes[0] as u128;
+        let hi = (lanes[3] as u128) << 64 | lanes[2] as u128;
+        i256::from_words(hi as i128, lo as i128)
+    }
+
+    #[inline]
+    pub fn to_lanes(x: &i256) -> [u64; LANES] {
+        let (hi, lo) = x.into_words();
+        let (hi, lo) = (hi as u128, lo as u128);
+        [lo as u64, (lo >> 64) as u64, hi as u64, (hi >> 64) as u64]
+    }
+
+    /// `x` in every lane
+    #[inline]
+    pub const fn set1(x: u64) -> i256 {
+        i256::from_words(splat(x) as i128, splat(x) as i128)
+    }
+
+    /// Logical right shift of every lane
+    #[inline]
     pub fn srl(x: &i256, c: u32) -> i256 {
-        u256::as_i256(x.as_u256() >> c)
+        map(x, |x| (x >> c) & splat(u64::MAX >> c))
     }
 
+    /// Left shift of every lane
+    #[inline]
     pub fn sll(x: &i256, c: u32) -> i256 {
-        u256::as_i256(x.as_u256() << c)
+        map(x, |x| (x << c) & splat(u64::MAX << c))
+    }
+
+    /// Wrapping sum of every lane
+    #[inline]
+    pub fn add(x: &i256, y: &i256) -> i256 {
+        // the sums of the low 63 bits don't carry out of their lane
+        zip(x, y, |x, y| ((x & !HIGH) + (y & !HIGH)) ^ ((x ^ y) & HIGH))
+    }
+
+    /// Wrapping difference of every lane
+    #[inline]
+    pub fn sub(x: &i256, y: &i256) -> i256 {
+        zip(x, y, sub_words)
+    }
+
+    /// Full product of the low 32 bits of every lane
+    #[inline]
+    pub fn mul_epu32(x: &i256, y: &i256) -> i256 {
+        let low = |word: u128, lane: u32| (word >> (64 * lane)) as u32 as u128;
+        zip(x, y, |x, y| {
+            (low(x, 0) * low(y, 0)) | ((low(x, 1) * low(y, 1)) << 64)
+        })
+    }
+
+    /// All ones in the lanes where `x > y` as signed integers, zero elsewhere
+    #[inline]
+    pub fn cmpgt(x: &i256, y: &i256) -> i256 {
+        zip(x, y, |x, y| {
+            let difference = sub_words(y, x);
+            // top bit of every lane set when y - x overflows or is negative
+            let top = (difference ^ ((x ^ y) & (difference ^ y))) & HIGH;
+            // the lanes with their top bit set become 2^63 - 1, then all ones
+            (top - (top >> 63)) | top
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::i256::*;
+
+    #[test]
+    fn operations_agree_with_the_lane_by_lane_ones() {
+        let values = [0, 1, 2, (1 << 63) - 1, 1 << 63, (1 << 63) + 1, u64::MAX];
+        for x in values
+            .iter()
+            .flat_map(|&x| values.map(|y| [x, y, !x, y ^ x]))
+        {
+            let y = [x[3], x[0], x[2], x[1]];
+            let lanes =
+                |f: fn(u64, u64) -> u64| -> [u64; LANES] { std::array::from_fn(|i| f(x[i], y[i])) };
+            let (packed_x, packed_y) = (from_lanes(x), from_lanes(y));
+            assert_eq!(to_lanes(&packed_x), x);
+            assert_eq!(
+                to_lanes(&add(&packed_x, &packed_y)),
+                lanes(u64::wrapping_add)
+            );
+            assert_eq!(
+                to_lanes(&sub(&packed_x, &packed_y)),
+                lanes(u64::wrapping_sub)
+            );
+            assert_eq!(
+                to_lanes(&mul_epu32(&packed_x, &packed_y)),
+                lanes(|x, y| (x as u32 as u64) * (y as u32 as u64))
+            );
+            assert_eq!(
+                to_lanes(&cmpgt(&packed_x, &packed_y)),
+                lanes(|x, y| if (x as i64) > (y as i64) { u64::MAX } else { 0 })
+            );
+            for c in [0, 1, 32, 61, 63] {
+                assert_eq!(to_lanes(&srl(&packed_x, c)), x.map(|x| x >> c));
+                assert_eq!(to_lanes(&sll(&packed_x, c)), x.map(|x| x << c));
+            }
+        }
     }
 }
@@ -0,0 +1,113 @@
+//! Times `ZkProver::evaluate` on a circuit with 2^20 gates per layer, mixing
+//! two-input gates with the fan-in gates (types 5, 12 and 14), for an
+//! increasing number of threads.
+//!
+//! `cargo bench -p linear_gkr --bench evaluate [log_gates [depth]]`
+
+use std::env;
+use std::thread;
+use std::time::Instant;
+
+use linear_gkr::circuit_fast_track::{GateKind, Layer, LayeredCircuit};
+use linear_gkr::prover::ZkProver;
+use prime_field::FieldElement;
+use rayon::ThreadPoolBuilder;
+
+fn layer(bit_length: usize, gates: Vec<GateKind>) -> Layer {
+  Layer {
+    gates,
+    bit_length,
+    ..Layer::new()
+  }
+}
+
+fn mixed_gate(g: usize, size: usize) -> GateKind {
+  let (u, v) = (g, (g * 7 + 3) % size);
+  let start = g.min(size - 8);
+  match g % 8 {
+    0 => GateKind::Add { u, v },
+    1 => GateKind::Mult { u, v },
+    2 => GateKind::Xor { u, v },
+    3 => GateKind::Sum {
+      start,
+      end: start + 8,
+    },
+    4 => GateKind::BitPack {
+      start,
+      end: start + 7,
+    },
+    5 => GateKind::Linear {
+      terms: (0..4)
+        .map(|k| ((v + k) % size, FieldElement::from_real(k as u64 + 1)))
+        .collect(),
+    },
+    6 => GateKind::Not { u },
+    _ => GateKind::Relay { u },
+  }
+}
+
+fn mixed_circuit(log_gates: usize, depth: usize) -> LayeredCircuit {
+  let size = 1 << log_gates;
+  let mut circuit = LayeredCircuit::new();
+  circuit.input_count = size;
+  circuit
+    .circuit
+    .push(layer(log_gates, vec![GateKind::Zero; size]));
+  circuit.circuit.push(layer(
+    log_gates,
+    (0..size).map(|u| GateKind::DirectRelay { u }).collect(),
+  ));
+  for _ in 0..depth {
+    circuit.circuit.push(layer(
+      log_gates,
+      (0..size).map(|g| mixed_gate(g, size)).collect(),
+    ));
+  }
+  circuit.total_depth = circuit.circuit.len();
+  circuit
+}
+
+fn main() {
+  let mut args = env::args()
+    .skip(1)
+    .filter(|arg| arg != "--bench")
+    .map(|arg| arg.parse().expect("expected a number"));
+  let log_gates = args.next().unwrap_or(20);
+  let depth = args.next().unwrap_or(4);
+
+  let mut prover = ZkProver::new();
+  prover.aritmetic_circuit = mixed_circuit(log_gates, depth);
+  let inputs: Vec<_> = (0..1u64 << log_gates)
+    .map(|i| FieldElement::from_real(i % 2))
+    .collect();
+
+  let max_threads = thread::available_parallelism().map_or(1, |n| n.get());
+  let thread_counts = (0..)
+    .map(|i| 1 << i)
+    .take_while(|&n| n < max_threads)
+    .chain([max_threads]);
+
+  let mut single_thread = None;
+  let mut expected = None;
+  for threads in thread_counts {
+    let pool = ThreadPoolBuilder::new()
+      .num_threads(threads)
+      .build()
+      .unwrap();
+    prover.get_witness(inputs.clone());
+    let start = Instant::now();
+    let output = pool.install(|| prover.evaluate());
+    let seconds = start.elapsed().as_secs_f64();
+
+    assert_eq!(*expected.get_or_insert_with(|| output.clone()), output);
+    let single_thread = *single_thread.get_or_insert(seconds);
+    println!(
+      "2^{} gates x {} layers, {:>3} threads: {:.3} s, speedup {:.2}",
+      log_gates,
+      depth,
+      threads,
+      seconds,
+      single_thread / seconds
+    );
+  }
+}
@@ -0,0 +1,132 @@
+//! Times `ZkProver::prove` in both sumcheck modes on an unbalanced circuit,
+//! where layers of 16 gates alternate with layers of 2^18 gates, then the
+//! fold of a multilinear table of 2^18 values, scalar and packed.
+//!
+//! `cargo bench -p linear_gkr --bench sumcheck [log_gates [depth]]`
+
+use std::env;
+use std::time::Instant;
+
+use infrastructure::transcript::Transcript;
+use linear_gkr::circuit_fast_track::{GateKind, Layer, LayeredCircuit};
+use linear_gkr::proof::TRANSCRIPT_LABEL;
+use linear_gkr::prover::{SumcheckMode, ZkProver};
+use prime_field::packed::fold_slices;
+use prime_field::FieldElement;
+
+const LOG_SMALL: usize = 4;
+
+fn layer(bit_length: usize, gates: Vec<GateKind>) -> Layer {
+  Layer {
+    gates,
+    bit_length,
+    ..Layer::new()
+  }
+}
+
+fn unbalanced_circuit(log_gates: usize, depth: usize) -> LayeredCircuit {
+  let (size, small) = (1 << log_gates, 1 << LOG_SMALL);
+  let mut circuit = LayeredCircuit::new();
+  circuit.input_count = size;
+  circuit
+    .circuit
+    .push(layer(log_gates, vec![GateKind::Zero; size]));
+  circuit.circuit.push(layer(
+    log_gates,
+    (0..size).map(|u| GateKind::DirectRelay { u }).collect(),
+  ));
+  for _ in 0..depth {
+    circuit.circuit.push(layer(
+      LOG_SMALL,
+      (0..small)
+        .map(|g| GateKind::Mult {
+          u: g * 997 % size,
+          v: size - 1 - g,
+        })
+        .collect(),
+    ));
+    circuit.circuit.push(layer(
+      log_gates,
+      (0..size)
+        .map(|g| GateKind::Add {
+          u: g % small,
+          v: g * 7 % small,
+        })
+        .collect(),
+    ));
+  }
+  circuit.total_depth = circuit.circuit.len();
+  circuit
+}
+
+/// Folds `table` on its first variable until a single value is left, each
+/// fold writing `lo + r * (hi - lo)` over the two halves with `fold`
+fn fold_table(
+  mut table: Vec<FieldElement>,
+  fold: impl Fn(&[FieldElement], &[FieldElement], FieldElement, &mut [FieldElement]),
+) -> FieldElement {
+  let mut folded = table.clone();
+  while table.len() > 1 {
+    let half = table.len() >> 1;
+    let (lo, hi) = table.split_at(half);
+    fold(
+      lo,
+      hi,
+      FieldElement::from_real(half as u64),
+      &mut folded[..half],
+    );
+    table.truncate(half);
+    table.copy_from_slice(&folded[..half]);
+  }
+  table[0]
+}
+
+fn main() {
+  let mut args = env::args()
+    .skip(1)
+    .filter(|arg| arg != "--bench")
+    .map(|arg| arg.parse().expect("expected a number"));
+  let log_gates = args.next().unwrap_or(18);
+  let depth = args.next().unwrap_or(4);
+
+  let circuit = unbalanced_circuit(log_gates, depth);
+  let inputs: Vec<_> = (0..1u64 << log_gates)
+    .map(FieldElement::from_real)
+    .collect();
+
+  let mut expected = None;
+  for mode in [SumcheckMode::Dense, SumcheckMode::Sparse] {
+    let mut prover = ZkProver::new();
+    prover.init_array(circuit.max_bit_length(), &circuit);
+    prover.set_mode(mode);
+    prover.get_witness(inputs.clone());
+    let start = Instant::now();
+    let proof = prover.prove(&mut Transcript::new(TRANSCRIPT_LABEL));
+    let seconds = start.elapsed().as_secs_f64();
+
+    assert_eq!(*expected.get_or_insert_with(|| proof.clone()), proof);
+    println!(
+      "2^{} / 2^{} gates x {} layers, {:?}: {:.3} s, sumcheck {:.3} s",
+      log_gates, LOG_SMALL, depth, mode, seconds, prover.total_time
+    );
+  }
+
+  let scalar_fold = |lo: &[FieldElement], hi: &[FieldElement], r, dst: &mut [FieldElement]| {
+    for ((dst, &lo), &hi) in dst.iter_mut().zip(lo).zip(hi) {
+      *dst = lo + r * (hi - lo);
+    }
+  };
+  let mut expected = None;
+  for (name, packed) in [("scalar", false), ("packed", true)] {
+    let start = Instant::now();
+    let value = if packed {
+      fold_table(inputs.clone(), fold_slices)
+    } else {
+      fold_table(inputs.clone(), scalar_fold)
+    };
+    let seconds = start.elapsed().as_secs_f64();
+
+    assert_eq!(*expected.get_or_insert(value), value);
+    println!("fold of 2^{} values, {}: {:.3} s", log_gates, name, seconds);
+  }
+}
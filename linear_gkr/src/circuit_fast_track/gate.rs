@@ -0,0 +1,446 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use prime_field::{Field, FieldElement};
+
+/// Operation of a gate together with the wires it reads from the previous
+/// layer.
+///
+/// Every kind is defined once, by [`GateKind::form`] and
+/// [`GateKind::for_each_wire`]: the value of a gate is
+/// `Σ_k weight_k · form(V(u_k), V(v_k))` over its wires. Evaluation, both
+/// sumcheck phases of the prover and the wiring predicates of the verifier
+/// are all derived from these two definitions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GateKind<F = FieldElement> {
+  /// `V(u) + V(v)`, type 0
+  Add { u: usize, v: usize },
+  /// `V(u) * V(v)`, type 1
+  Mult { u: usize, v: usize },
+  /// Constant zero, type 2, also pads the layers
+  Zero,
+  /// Constant `value`, type 3, the input gates of the input layer
+  Constant { value: u64 },
+  /// `V(u)`, type 4, relays the input gates in the first layer
+  DirectRelay { u: usize },
+  /// `Σ V(k)` over `start..end`, type 5
+  Sum { start: usize, end: usize },
+  /// `1 - V(u)`, type 6
+  Not { u: usize },
+  /// `V(u) - V(v)`, type 7
+  Minus { u: usize, v: usize },
+  /// `V(u) + V(v) - 2 V(u) V(v)`, type 8
+  Xor { u: usize, v: usize },
+  /// `(1 - V(u)) V(v)`, type 9
+  Naab { u: usize, v: usize },
+  /// `V(u)`, type 10
+  Relay { u: usize },
+  /// `Σ 2^(k - start) V(k)` over `start..=end`, type 12, recomposes a
+  /// number from its bits
+  BitPack { start: usize, end: usize },
+  /// `V(u) (1 - V(u))`, type 13, zero when the wire carries a bit
+  BitTest { u: usize },
+  /// `Σ weight V(src)` over its terms, type 14
+  Linear { terms: Vec<(usize, F)> },
+  /// Gate registered in a [`GateRegistry`] under type `code`
+  Custom {
+    code: usize,
+    gate: CustomGateRef<F>,
+    u: usize,
+    v: usize,
+  },
+}
+
+/// Gate type defined outside of this crate, see [`GateRegistry`].
+///
+/// The sumcheck messages are quadratic, so the value of a gate must be a sum
+/// of weighted wires, each applying the same [`BilinearForm`] to the values of
+/// its two inputs. Higher degree gates such as `x^5` are built over several
+/// layers, e.g. with a square gate whose only wire is `(u, u)` and form `xy`.
+/// The prover's phase 1 and phase 2 contributions and the verifier's wiring
+/// predicate are derived from `form` and `for_each_wire`, like for the built-in
+/// gates.
+pub trait CustomGate<F = FieldElement>: fmt::Debug + Send + Sync {
+  /// Name of the gate, for error messages
+  fn name(&self) -> &str;
+
+  /// Polynomial applied to the values of each wire
+  fn form(&self) -> BilinearForm<F>;
+
+  /// Calls `f(u, v, weight)` for every wire of a gate whose operands in the
+  /// circuit file are `u` and `v`. Defaults to a single wire `(u, v)`. The
+  /// wires must move along with `u` and `v`, as data-parallel circuits shift
+  /// the operands of every copy of a gate, see [`GateKind::shifted`].
+  fn for_each_wire(&self, u: usize, v: usize, f: &mut dyn FnMut(usize, usize, F))
+  where
+    F: Field,
+  {
+    f(u, v, F::one())
+  }
+}
+
+/// Shared handle on a registered [`CustomGate`], handles on gates with the
+/// same name and form are equal
+#[derive(Debug, Clone)]
+pub struct CustomGateRef<F = FieldElement>(Arc<dyn CustomGate<F>>);
+
+impl<F> Deref for CustomGateRef<F> {
+  type Target = dyn CustomGate<F>;
+
+  fn deref(&self) -> &Self::Target {
+    &*self.0
+  }
+}
+
+impl<F: PartialEq> PartialEq for CustomGateRef<F> {
+  fn eq(&self, other: &Self) -> bool {
+    self.name() == other.name() && self.form() == other.form()
+  }
+}
+
+impl<F: Eq> Eq for CustomGateRef<F> {}
+
+/// Type codes reserved for the built-in gates
+pub const BUILTIN_GATE_CODES: usize = 16;
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RegisterGateError {
+  /// The code belongs to a built-in gate
+  ReservedCode(usize),
+  /// Another gate is registered under the code
+  DuplicateCode(usize),
+}
+
+impl fmt::Display for RegisterGateError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::ReservedCode(code) => write!(
+        f,
+        "gate type {} is reserved, custom gates start at {}",
+        code, BUILTIN_GATE_CODES
+      ),
+      Self::DuplicateCode(code) => write!(f, "gate type {} is already registered", code),
+    }
+  }
+}
+
+impl std::error::Error for RegisterGateError {}
+
+/// Custom gates by the type code they use in circuit files, passed to
+/// [`LayeredCircuit::parse_with_gates`](super::LayeredCircuit::parse_with_gates)
+#[derive(Debug, Clone, Default)]
+pub struct GateRegistry<F = FieldElement> {
+  gates: HashMap<usize, CustomGateRef<F>>,
+}
+
+impl<F: Field> GateRegistry<F> {
+  pub fn new() -> Self {
+    Default::default()
+  }
+
+  /// Registers `gate` under type `code`, which must not be used by a built-in
+  /// or an already registered gate
+  pub fn register(
+    &mut self,
+    code: usize,
+    gate: impl CustomGate<F> + 'static,
+  ) -> Result<(), RegisterGateError> {
+    if code < BUILTIN_GATE_CODES {
+      return Err(RegisterGateError::ReservedCode(code));
+    }
+    if self.gates.contains_key(&code) {
+      return Err(RegisterGateError::DuplicateCode(code));
+    }
+    self.gates.insert(code, CustomGateRef(Arc::new(gate)));
+    Ok(())
+  }
+
+  /// Gate of type `code` with operands `u` and `v`, if `code` is registered
+  pub fn gate(&self, code: usize, u: usize, v: usize) -> Option<GateKind<F>> {
+    self.gates.get(&code).map(|gate| GateKind::Custom {
+      code,
+      gate: gate.clone(),
+      u,
+      v,
+    })
+  }
+}
+
+/// Polynomial `xy · x y + x · x + y · y + one` a gate applies to the values
+/// of its wires
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BilinearForm<F = FieldElement> {
+  pub xy: F,
+  pub x: F,
+  pub y: F,
+  pub one: F,
+}
+
+impl<F: Field> BilinearForm<F> {
+  pub fn new(xy: F, x: F, y: F, one: F) -> Self {
+    Self { xy, x, y, one }
+  }
+
+  /// Form with small integer coefficients
+  pub fn from_ints(xy: i64, x: i64, y: i64, one: i64) -> Self {
+    let from_i64 = |c: i64| match c {
+      c if c < 0 => -F::from_u64(c.unsigned_abs()),
+      c => F::from_u64(c as u64),
+    };
+    Self {
+      xy: from_i64(xy),
+      x: from_i64(x),
+      y: from_i64(y),
+      one: from_i64(one),
+    }
+  }
+
+  pub fn eval(&self, x: F, y: F) -> F {
+    (self.xy * y + self.x) * x + self.y * y + self.one
+  }
+
+  /// Coefficient of `x` once `y` is fixed
+  pub fn x_coef(&self, y: F) -> F {
+    self.xy * y + self.x
+  }
+
+  /// Part of the form that doesn't depend on `x` once `y` is fixed
+  pub fn x_free(&self, y: F) -> F {
+    self.y * y + self.one
+  }
+
+  /// Coefficient of `y` once `x` is fixed
+  pub fn y_coef(&self, x: F) -> F {
+    self.xy * x + self.y
+  }
+
+  /// Part of the form that doesn't depend on `y` once `x` is fixed
+  pub fn y_free(&self, x: F) -> F {
+    self.x * x + self.one
+  }
+}
+
+impl<F: Field> GateKind<F> {
+  /// Type of the gate in the circuit file format
+  pub fn code(&self) -> usize {
+    match self {
+      Self::Add { .. } => 0,
+      Self::Mult { .. } => 1,
+      Self::Zero => 2,
+      Self::Constant { .. } => 3,
+      Self::DirectRelay { .. } => 4,
+      Self::Sum { .. } => 5,
+      Self::Not { .. } => 6,
+      Self::Minus { .. } => 7,
+      Self::Xor { .. } => 8,
+      Self::Naab { .. } => 9,
+      Self::Relay { .. } => 10,
+      Self::BitPack { .. } => 12,
+      Self::BitTest { .. } => 13,
+      Self::Linear { .. } => 14,
+      Self::Custom { code, .. } => *code,
+    }
+  }
+
+  /// Polynomial applied to the values of each wire
+  pub fn form(&self) -> BilinearForm<F> {
+    match self {
+      Self::Add { .. } => BilinearForm::from_ints(0, 1, 1, 0),
+      Self::Mult { .. } => BilinearForm::from_ints(1, 0, 0, 0),
+      Self::Zero => BilinearForm::from_ints(0, 0, 0, 0),
+      Self::Constant { value } => BilinearForm {
+        one: F::from_u64(*value),
+        ..BilinearForm::from_ints(0, 0, 0, 0)
+      },
+      Self::Not { .. } => BilinearForm::from_ints(0, -1, 0, 1),
+      Self::Minus { .. } => BilinearForm::from_ints(0, 1, -1, 0),
+      Self::Xor { .. } => BilinearForm::from_ints(-2, 1, 1, 0),
+      Self::Naab { .. } => BilinearForm::from_ints(-1, 0, 1, 0),
+      Self::BitTest { .. } => BilinearForm::from_ints(-1, 1, 0, 0),
+      Self::DirectRelay { .. }
+      | Self::Sum { .. }
+      | Self::Relay { .. }
+      | Self::BitPack { .. }
+      | Self::Linear { .. } => BilinearForm::from_ints(0, 1, 0, 0),
+      Self::Custom { gate, .. } => gate.form(),
+    }
+  }
+
+  /// Calls `f(u, v, weight)` for every wire of the gate. Gates with a single
+  /// input read `v = 0`, and constants read no input at all but still have a
+  /// wire `(0, 0)` carrying their value.
+  pub fn for_each_wire(&self, mut f: impl FnMut(usize, usize, F)) {
+    let one = F::one();
+    match self {
+      Self::Zero => {}
+      Self::Constant { .. } => f(0, 0, one),
+      Self::Add { u, v }
+      | Self::Mult { u, v }
+      | Self::Minus { u, v }
+      | Self::Xor { u, v }
+      | Self::Naab { u, v } => f(*u, *v, one),
+      Self::BitTest { u } => f(*u, *u, one),
+      Self::DirectRelay { u } | Self::Not { u } | Self::Relay { u } => f(*u, 0, one),
+      Self::Sum { start, end } => (*start..*end).for_each(|k| f(k, 0, one)),
+      Self::BitPack { start, end } => {
+        let mut weight = one;
+        for k in *start..=*end {
+          f(k, 0, weight);
+          weight = weight + weight;
+        }
+      }
+      Self::Linear { terms } => terms.iter().for_each(|&(src, weight)| f(src, 0, weight)),
+      Self::Custom { gate, u, v, .. } => gate.for_each_wire(*u, *v, &mut f),
+    }
+  }
+
+  /// Value of the gate given the values of the previous layer
+  pub fn evaluate(&self, prev: &[F]) -> F {
+    let form = self.form();
+    let mut value = F::zero();
+    self.for_each_wire(|u, v, weight| value = value + weight * form.eval(prev[u], prev[v]));
+    value
+  }
+
+  /// Same gate reading the previous layer `offset` gates further, as in the
+  /// next copy of a data-parallel circuit. Constants and the implicit `v = 0`
+  /// of single input gates don't move.
+  pub fn shifted(&self, offset: usize) -> Self {
+    match self {
+      Self::Zero | Self::Constant { .. } => self.clone(),
+      Self::Add { u, v } => Self::Add {
+        u: u + offset,
+        v: v + offset,
+      },
+      Self::Mult { u, v } => Self::Mult {
+        u: u + offset,
+        v: v + offset,
+      },
+      Self::Minus { u, v } => Self::Minus {
+        u: u + offset,
+        v: v + offset,
+      },
+      Self::Xor { u, v } => Self::Xor {
+        u: u + offset,
+        v: v + offset,
+      },
+      Self::Naab { u, v } => Self::Naab {
+        u: u + offset,
+        v: v + offset,
+      },
+      Self::DirectRelay { u } => Self::DirectRelay { u: u + offset },
+      Self::Not { u } => Self::Not { u: u + offset },
+      Self::Relay { u } => Self::Relay { u: u + offset },
+      Self::BitTest { u } => Self::BitTest { u: u + offset },
+      Self::Sum { start, end } => Self::Sum {
+        start: start + offset,
+        end: end + offset,
+      },
+      Self::BitPack { start, end } => Self::BitPack {
+        start: start + offset,
+        end: end + offset,
+      },
+      Self::Linear { terms } => Self::Linear {
+        terms: terms
+          .iter()
+          .map(|&(src, weight)| (src + offset, weight))
+          .collect(),
+      },
+      Self::Custom { code, gate, u, v } => Self::Custom {
+        code: *code,
+        gate: gate.clone(),
+        u: u + offset,
+        v: v + offset,
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::circuit_fast_track::{CircuitParseError, LayeredCircuit};
+
+  #[test]
+  fn gates_evaluate_to_their_definition() {
+    let prev: Vec<_> = [3, 5, 1, 0, 1]
+      .into_iter()
+      .map(FieldElement::from_real)
+      .collect();
+    let value = |gate: GateKind| gate.evaluate(&prev);
+    let real = |x: i64| BilinearForm::from_ints(0, 0, 0, x).one;
+
+    assert_eq!(value(GateKind::Add { u: 0, v: 1 }), real(8));
+    assert_eq!(value(GateKind::Mult { u: 0, v: 1 }), real(15));
+    assert_eq!(value(GateKind::Zero), real(0));
+    assert_eq!(value(GateKind::Constant { value: 9 }), real(9));
+    assert_eq!(value(GateKind::DirectRelay { u: 1 }), real(5));
+    assert_eq!(value(GateKind::Sum { start: 0, end: 2 }), real(8));
+    assert_eq!(value(GateKind::Not { u: 1 }), real(-4));
+    assert_eq!(value(GateKind::Minus { u: 0, v: 1 }), real(-2));
+    assert_eq!(value(GateKind::Xor { u: 0, v: 1 }), real(-22));
+    assert_eq!(value(GateKind::Naab { u: 0, v: 1 }), real(-10));
+    assert_eq!(value(GateKind::Relay { u: 0 }), real(3));
+    // bits 1, 0, 1 from the lowest
+    assert_eq!(value(GateKind::BitPack { start: 2, end: 4 }), real(5));
+    assert_eq!(value(GateKind::BitTest { u: 0 }), real(-6));
+    let terms = vec![(0, real(2)), (1, real(-1))];
+    assert_eq!(value(GateKind::Linear { terms }), real(1));
+  }
+
+  #[derive(Debug)]
+  struct Square;
+
+  impl CustomGate for Square {
+    fn name(&self) -> &str {
+      "square"
+    }
+
+    fn form(&self) -> BilinearForm {
+      BilinearForm::from_ints(1, 0, 0, 0)
+    }
+
+    fn for_each_wire(&self, u: usize, _v: usize, f: &mut dyn FnMut(usize, usize, FieldElement)) {
+      f(u, u, FieldElement::real_one())
+    }
+  }
+
+  #[test]
+  fn custom_gates_are_registered_and_parsed() {
+    let mut registry = GateRegistry::new();
+    assert!(matches!(
+      registry.register(5, Square),
+      Err(RegisterGateError::ReservedCode(5))
+    ));
+    registry.register(20, Square).unwrap();
+    assert!(matches!(
+      registry.register(20, Square),
+      Err(RegisterGateError::DuplicateCode(20))
+    ));
+
+    let meta = "0 0 1 0 0\n0 0 1 0 0\n";
+    let parse = |circuit: &str, registry: &GateRegistry| {
+      LayeredCircuit::parse_with_gates(circuit, meta, registry)
+    };
+    let circuit = parse("2\n1 3 0 3 0\n1 20 0 0 0\n", &registry).unwrap();
+    let square = &circuit.circuit[2].gates[0];
+    assert_eq!(*square, registry.gate(20, 0, 0).unwrap());
+    assert_eq!(square.code(), 20);
+    assert_eq!(
+      square.evaluate(&[FieldElement::from_real(3)]),
+      FieldElement::from_real(9)
+    );
+
+    assert!(matches!(
+      parse("2\n1 3 0 3 0\n1 20 0 0 0\n", &GateRegistry::new()),
+      Err(CircuitParseError::UnknownGateType { ty: 20, .. })
+    ));
+    assert!(matches!(
+      parse("2\n1 3 0 3 0\n1 20 0 32768 0\n", &registry),
+      Err(CircuitParseError::WireOutOfRange { wire: 32768, .. })
+    ));
+  }
+}
@@ -1,57 +1,30 @@
-use std::collections::HashMap;
+use infrastructure::merkle_tree::pack_field_element;
+use infrastructure::my_hash::{my_hash, HashDigest};
+use prime_field::{Field, FieldElement};
 
-use prime_field::FieldElement;
-#[derive(Debug, Clone, Default)]
+mod gate;
+mod parallel;
+mod parser;
+mod writer;
 
-pub struct Gate {
-  pub ty: usize,
-  pub u: usize,
-  pub v: usize,
-  pub src: Vec<usize>,
-  pub weight: Vec<FieldElement>,
-  pub parameter_length: usize,
-}
+pub use gate::{
+  BilinearForm, CustomGate, CustomGateRef, GateKind, GateRegistry, RegisterGateError,
+  BUILTIN_GATE_CODES,
+};
+pub use parser::{CircuitFile, CircuitParseError, Location};
 
-/*impl Default for Gate {
-    fn default() -> Self {
-        Self {
-            ty: 2,
-            u: 0,
-            v: 0,
-            src: vec![],
-            weight: vec![],
-            parameter_length: 0,
-        }
-    }
-}*/
-impl Gate {
-  pub fn new() -> Self {
-    Self {
-      ty: 2,
-      ..Default::default()
-    }
-  }
-
-  pub fn from_params(ty: usize, u: usize, v: usize) -> Self {
-    Self {
-      ty,
-      u,
-      v,
-      ..Default::default()
-    }
-  }
-}
-
-#[derive(Default, Debug, Clone)]
-pub struct Layer {
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Layer<F = FieldElement> {
   pub src_expander_c_mempool: Vec<i32>,
   pub src_expander_d_mempool: Vec<i32>,
-  pub weight_expander_c_mempool: Vec<FieldElement>,
-  pub weight_expander_d_mempool: Vec<FieldElement>,
-  pub gates: Vec<Gate>,
+  pub weight_expander_c_mempool: Vec<F>,
+  pub weight_expander_d_mempool: Vec<F>,
+  pub gates: Vec<GateKind<F>>,
   pub bit_length: usize,
-  pub u_gates: HashMap<i32, Vec<(i32, (i32, i32))>>,
-  pub v_gates: HashMap<i32, Vec<(i32, (i32, i32))>>,
+  /// Wires of the layer sorted by `u`, filled by `index_wires`
+  pub u_gates: Vec<Wire<F>>,
+  /// Wires of the layer sorted by `v`, filled by `index_wires`
+  pub v_gates: Vec<Wire<F>>,
   pub is_parallel: bool,
   pub block_size: usize,
   pub log_block_size: usize,
@@ -59,21 +32,101 @@ pub struct Layer {
   pub log_repeat_num: usize,
 }
 
-impl Layer {
+/// Wire of gate `g` reading gates `u` and `v` of the previous layer, see
+/// `GateKind::for_each_wire`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Wire<F = FieldElement> {
+  pub g: usize,
+  pub u: usize,
+  pub v: usize,
+  pub weight: F,
+}
+
+impl<F: Field> Layer<F> {
   pub fn new() -> Self {
     Default::default()
   }
+
+  /// Lists the wires of every gate into `u_gates` and `v_gates`, so that the
+  /// sparse prover visits the wires in the order of the table entries they
+  /// add to
+  pub fn index_wires(&mut self) {
+    let mut wires = Vec::with_capacity(self.gates.len());
+    for (g, gate) in self.gates.iter().enumerate() {
+      gate.for_each_wire(|u, v, weight| wires.push(Wire { g, u, v, weight }));
+    }
+    self.v_gates = wires.clone();
+    self.v_gates.sort_by_key(|wire| wire.v);
+    wires.sort_by_key(|wire| wire.u);
+    self.u_gates = wires;
+  }
 }
 
-#[derive(Default, Debug, Clone)]
-pub struct LayeredCircuit {
-  pub circuit: Vec<Layer>,
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct LayeredCircuit<F = FieldElement> {
+  pub circuit: Vec<Layer<F>>,
   pub total_depth: usize,
-  pub nputs: Vec<FieldElement>,
+  pub nputs: Vec<F>,
+  /// Number of input gates declared by the circuit file, before the input
+  /// layer is padded
+  pub input_count: usize,
 }
 
-impl LayeredCircuit {
+impl<F: Field> LayeredCircuit<F> {
   pub fn new() -> Self {
     Default::default()
   }
+
+  /// Indexes the wires of every layer, see `Layer::index_wires`
+  pub fn index_wires(&mut self) {
+    self.circuit.iter_mut().for_each(Layer::index_wires);
+  }
+
+  /// Hash of the wiring and the meta data of every layer, used to bind a
+  /// proof to the circuit it was produced for
+  pub fn digest(&self) -> HashDigest {
+    // field elements enter the hash as the words of their encoding
+    let words = |elements: &[F]| -> Vec<i128> {
+      elements
+        .iter()
+        .flat_map(|x| pack_field_element(*x))
+        .collect()
+    };
+    let mut digest = HashDigest::default();
+    let mut absorb = |h0: i128, h1: i128| digest = my_hash([digest, HashDigest { h0, h1 }]);
+
+    absorb(self.total_depth as i128, self.circuit.len() as i128);
+    absorb(self.input_count as i128, 0);
+    for layer in &self.circuit {
+      absorb(layer.bit_length as i128, layer.is_parallel as i128);
+      absorb(layer.block_size as i128, layer.log_block_size as i128);
+      absorb(layer.repeat_num as i128, layer.log_repeat_num as i128);
+      absorb(layer.gates.len() as i128, 0);
+      for gate in &layer.gates {
+        let form = gate.form();
+        absorb(gate.code() as i128, 0);
+        for pair in words(&[form.xy, form.x, form.y, form.one]).chunks(2) {
+          absorb(pair[0], pair.get(1).copied().unwrap_or(0));
+        }
+        gate.for_each_wire(|u, v, weight| {
+          let mut weight = words(&[weight]).into_iter();
+          absorb((u as i128) << 64 | v as i128, weight.next().unwrap_or(0));
+          weight.for_each(|word| absorb(word, 0));
+        });
+      }
+    }
+    digest
+  }
+
+  /// Values of the input layer as hardcoded in the circuit file
+  pub fn input_values(&self) -> Vec<F> {
+    self.circuit[0]
+      .gates
+      .iter()
+      .map(|gate| match gate {
+        GateKind::Constant { value } => F::from_u64(*value),
+        _ => F::zero(),
+      })
+      .collect()
+  }
 }
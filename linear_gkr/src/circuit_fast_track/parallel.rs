@@ -0,0 +1,181 @@
+use prime_field::Field;
+
+use super::parser::input_layer_size;
+use super::{GateKind, Layer, LayeredCircuit};
+
+/// Checks that the parallel layer `i` of `circuit` is made of `repeat_num`
+/// copies of its first block, copy `b` reading block `b` of the previous
+/// layer, which the previous layer splits evenly
+pub(crate) fn check_blocks<F: Field>(
+  circuit: &LayeredCircuit<F>,
+  i: usize,
+) -> Result<(), &'static str> {
+  let layer = &circuit.circuit[i];
+  if 1usize.checked_shl(layer.log_block_size as u32) != Some(layer.block_size) {
+    return Err("block size is not 2^log_block_size");
+  }
+  if layer.log_block_size + layer.log_repeat_num > layer.bit_length {
+    return Err("the blocks don't fit in the layer");
+  }
+  // the first layer relays the input layer, the verifier accounts for it in
+  // closed form
+  if i == 1 {
+    return Ok(());
+  }
+  let prev_bit_length = circuit.circuit[i - 1]
+    .bit_length
+    .checked_sub(layer.log_repeat_num)
+    .ok_or("the previous layer has fewer gates than blocks")?;
+  let prev_block_size = 1 << prev_bit_length;
+
+  let (first, rest) = layer.gates.split_at(layer.block_size);
+  let mut outside = false;
+  for gate in first {
+    gate.for_each_wire(|u, v, _| outside |= u >= prev_block_size || v >= prev_block_size);
+  }
+  if outside {
+    return Err("a gate of the first block reads outside of its block");
+  }
+  let (copies, padding) = rest.split_at((layer.repeat_num - 1) * layer.block_size);
+  for (b, block) in copies.chunks(layer.block_size).enumerate() {
+    let offset = (b + 1) * prev_block_size;
+    if block
+      .iter()
+      .zip(first)
+      .any(|(gate, original)| *gate != original.shifted(offset))
+    {
+      return Err("a block doesn't repeat the first one");
+    }
+  }
+  if padding.iter().any(|gate| *gate != GateKind::Zero) {
+    return Err("a gate lies outside of the blocks");
+  }
+  Ok(())
+}
+
+impl<F: Field> LayeredCircuit<F> {
+  /// Circuit running `repeat_num` copies of this one side by side, with
+  /// every layer marked as parallel, so that the verifier's wiring
+  /// predicates cost one copy rather than the whole width.
+  ///
+  /// Copy `b` reads its inputs from gate `b * stride` of the input layer
+  /// onwards, where the stride is the size of the input layer of this
+  /// circuit, or more when the padding of the input layer calls for it.
+  /// `repeat_num` must be a power of two.
+  pub fn replicate(&self, repeat_num: usize) -> LayeredCircuit<F> {
+    assert!(repeat_num.is_power_of_two());
+    let log_repeat_num = repeat_num.trailing_zeros() as usize;
+    let input_size = input_layer_size(
+      self.total_depth - 1,
+      repeat_num << self.circuit[0].bit_length,
+    );
+    let stride = input_size / repeat_num;
+
+    let mut circuit = LayeredCircuit::new();
+    circuit.total_depth = self.total_depth;
+    circuit.input_count = (repeat_num - 1) * stride + self.input_count;
+
+    let mut inputs = Vec::with_capacity(input_size);
+    for b in 0..repeat_num {
+      inputs.extend_from_slice(&self.circuit[0].gates);
+      inputs.resize((b + 1) * stride, GateKind::Constant { value: 0 });
+    }
+    let bit_length = input_size.trailing_zeros() as usize;
+    circuit.circuit.push(Layer {
+      gates: inputs,
+      bit_length,
+      ..Layer::new()
+    });
+    circuit.circuit.push(Layer {
+      gates: (0..input_size)
+        .map(|u| GateKind::DirectRelay { u })
+        .collect(),
+      bit_length,
+      ..parallel_layer(stride.trailing_zeros() as usize, log_repeat_num)
+    });
+
+    for i in 2..self.total_depth {
+      let layer = &self.circuit[i];
+      let prev_block_size = 1 << (circuit.circuit[i - 1].bit_length - log_repeat_num);
+      let gates = (0..repeat_num)
+        .flat_map(|b| {
+          layer
+            .gates
+            .iter()
+            .map(move |gate| gate.shifted(b * prev_block_size))
+        })
+        .collect();
+      circuit.circuit.push(Layer {
+        gates,
+        bit_length: layer.bit_length + log_repeat_num,
+        ..parallel_layer(layer.bit_length, log_repeat_num)
+      });
+    }
+    circuit
+  }
+}
+
+fn parallel_layer<F: Field>(log_block_size: usize, log_repeat_num: usize) -> Layer<F> {
+  Layer {
+    is_parallel: true,
+    block_size: 1 << log_block_size,
+    log_block_size,
+    repeat_num: 1 << log_repeat_num,
+    log_repeat_num,
+    ..Layer::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use prime_field::FieldElement;
+
+  use crate::circuit_fast_track::{CircuitParseError, GateKind, LayeredCircuit};
+
+  // inputs 3, 5, 7, 2, then 3 * 5 and 7 + 2, then their difference
+  const CIRCUIT: &str = "3\n4 3 0 3 0 3 1 5 0 3 2 7 0 3 3 2 0\n2 1 0 0 1 0 1 2 3\n1 7 0 0 1\n";
+  const META: &str = "0 0 1 0 0\n0 0 1 0 0\n0 0 1 0 0\n";
+
+  fn write(circuit: &LayeredCircuit) -> (String, String) {
+    let (mut circuit_out, mut meta_out) = (vec![], vec![]);
+    circuit.write_circuit(&mut circuit_out).unwrap();
+    circuit.write_meta(&mut meta_out).unwrap();
+    (
+      String::from_utf8(circuit_out).unwrap(),
+      String::from_utf8(meta_out).unwrap(),
+    )
+  }
+
+  #[test]
+  fn replicated_circuits_are_parsed_back_and_checked() {
+    let sub = LayeredCircuit::parse(CIRCUIT, META).unwrap();
+    let circuit = sub.replicate(4);
+    assert_eq!(circuit.input_count, 3 * (1 << 15) + 4);
+    assert_eq!(circuit.circuit[2].bit_length, 3);
+    assert_eq!(
+      circuit.circuit[2].gates[2..4],
+      [
+        GateKind::Mult {
+          u: 1 << 15,
+          v: (1 << 15) + 1
+        },
+        GateKind::Add {
+          u: (1 << 15) + 2,
+          v: (1 << 15) + 3
+        },
+      ]
+    );
+    assert_eq!(circuit.circuit[3].gates[6], GateKind::Minus { u: 6, v: 7 });
+
+    let (text, meta) = write(&circuit);
+    assert_eq!(LayeredCircuit::parse(&text, &meta).unwrap(), circuit);
+
+    let mut uneven = circuit;
+    uneven.circuit[3].gates[6] = GateKind::Minus { u: 7, v: 6 };
+    let (text, meta) = write(&uneven);
+    assert!(matches!(
+      LayeredCircuit::<FieldElement>::parse(&text, &meta),
+      Err(CircuitParseError::BadBlocks { .. })
+    ));
+  }
+}
@@ -0,0 +1,665 @@
+use std::fmt;
+use std::fs::read_to_string;
+use std::io;
+use std::str::FromStr;
+
+use prime_field::Field;
+
+use super::parallel::check_blocks;
+use super::{GateKind, GateRegistry, Layer, LayeredCircuit};
+use crate::prover::from_string_radix;
+
+/// The input layer is padded to at least `1 << 15` gates, or `1 << 17` for
+/// circuits deeper than 3 layers
+const INPUT_PAD_SHALLOW: usize = 15;
+const INPUT_PAD_DEEP: usize = 17;
+
+/// One of the two files describing a layered circuit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitFile {
+  Circuit,
+  Meta,
+}
+
+impl fmt::Display for CircuitFile {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Circuit => write!(f, "circuit"),
+      Self::Meta => write!(f, "meta"),
+    }
+  }
+}
+
+/// Position of the offending token, with the layer and the gate it belongs to
+/// when there is one. Lines and columns start at 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+  pub file: CircuitFile,
+  pub line: usize,
+  pub column: usize,
+  pub layer: Option<usize>,
+  pub gate: Option<usize>,
+}
+
+impl fmt::Display for Location {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} file {}:{}", self.file, self.line, self.column)?;
+    if let Some(layer) = self.layer {
+      write!(f, ", layer {}", layer)?;
+    }
+    if let Some(gate) = self.gate {
+      write!(f, ", gate {}", gate)?;
+    }
+    Ok(())
+  }
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CircuitParseError {
+  Io {
+    file: CircuitFile,
+    err: io::Error,
+  },
+  /// The file ended while `expected` was still to be read
+  UnexpectedEnd {
+    at: Location,
+    expected: &'static str,
+  },
+  BadNumber {
+    at: Location,
+    token: String,
+  },
+  /// The circuit has no layer besides the input layer
+  NoLayers {
+    at: Location,
+  },
+  EmptyLayer {
+    at: Location,
+  },
+  /// Gates of a layer must be listed in order, starting from 0
+  UnsortedGate {
+    at: Location,
+    expected: usize,
+    found: usize,
+  },
+  UnknownGateType {
+    at: Location,
+    ty: usize,
+  },
+  /// The first layer must only hold input (3) and zero (2) gates
+  NotAnInputGate {
+    at: Location,
+    ty: usize,
+  },
+  /// A gate reads a wire beyond the previous layer
+  WireOutOfRange {
+    at: Location,
+    wire: usize,
+    layer_size: usize,
+  },
+  /// The inputs of a gate are inconsistent with its type
+  InvalidOperands {
+    at: Location,
+    reason: &'static str,
+  },
+  /// A parallel layer must repeat its block a power of two times
+  BadRepeatNum {
+    at: Location,
+    repeat_num: usize,
+    log_repeat_num: usize,
+  },
+  /// A parallel layer doesn't repeat its first block, see
+  /// [`LayeredCircuit::replicate`]
+  BadBlocks {
+    at: Location,
+    reason: &'static str,
+  },
+  TrailingData {
+    at: Location,
+  },
+}
+
+impl fmt::Display for CircuitParseError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Io { file, err } => write!(f, "cannot read the {} file: {}", file, err),
+      Self::UnexpectedEnd { at, expected } => write!(f, "{}: expected {}", at, expected),
+      Self::BadNumber { at, token } => write!(f, "{}: bad number {:?}", at, token),
+      Self::NoLayers { at } => write!(f, "{}: the circuit has no layer", at),
+      Self::EmptyLayer { at } => write!(f, "{}: the layer has no gate", at),
+      Self::UnsortedGate {
+        at,
+        expected,
+        found,
+      } => write!(
+        f,
+        "{}: expected gate {}, found gate {}",
+        at, expected, found
+      ),
+      Self::UnknownGateType { at, ty } => write!(f, "{}: unknown gate type {}", at, ty),
+      Self::NotAnInputGate { at, ty } => write!(
+        f,
+        "{}: gate type {} in the first layer, expected 2 or 3",
+        at, ty
+      ),
+      Self::WireOutOfRange {
+        at,
+        wire,
+        layer_size,
+      } => write!(
+        f,
+        "{}: wire {} is out of the previous layer of {} gates",
+        at, wire, layer_size
+      ),
+      Self::InvalidOperands { at, reason } => write!(f, "{}: {}", at, reason),
+      Self::BadRepeatNum {
+        at,
+        repeat_num,
+        log_repeat_num,
+      } => write!(
+        f,
+        "{}: repeat number {} is not 2^{}",
+        at, repeat_num, log_repeat_num
+      ),
+      Self::BadBlocks { at, reason } => write!(f, "{}: {}", at, reason),
+      Self::TrailingData { at } => write!(f, "{}: trailing data", at),
+    }
+  }
+}
+
+impl std::error::Error for CircuitParseError {}
+
+/// Whitespace separated tokens of a file, tracking the position of the last
+/// one and the layer and gate being parsed
+struct Tokens<'a> {
+  tokens: Box<dyn Iterator<Item = (usize, usize, &'a str)> + 'a>,
+  at: Location,
+}
+
+impl<'a> Tokens<'a> {
+  fn new(file: CircuitFile, text: &'a str) -> Self {
+    let tokens = text.lines().enumerate().flat_map(|(i, line)| {
+      line.split_whitespace().map(move |token| {
+        (
+          i + 1,
+          token.as_ptr() as usize - line.as_ptr() as usize + 1,
+          token,
+        )
+      })
+    });
+    Self {
+      tokens: Box::new(tokens),
+      at: Location {
+        file,
+        line: 1,
+        column: 1,
+        layer: None,
+        gate: None,
+      },
+    }
+  }
+
+  fn next_token(&mut self, expected: &'static str) -> Result<&'a str, CircuitParseError> {
+    match self.tokens.next() {
+      Some((line, column, token)) => {
+        self.at.line = line;
+        self.at.column = column;
+        Ok(token)
+      }
+      None => Err(CircuitParseError::UnexpectedEnd {
+        at: self.at,
+        expected,
+      }),
+    }
+  }
+
+  fn next<T: FromStr>(&mut self, expected: &'static str) -> Result<T, CircuitParseError> {
+    let token = self.next_token(expected)?;
+    token.parse().map_err(|_| CircuitParseError::BadNumber {
+      at: self.at,
+      token: token.to_string(),
+    })
+  }
+
+  /// A decimal field element, possibly negative
+  fn next_field_element<F: Field>(
+    &mut self,
+    expected: &'static str,
+  ) -> Result<F, CircuitParseError> {
+    let token = self.next_token(expected)?;
+    let value = match token.strip_prefix('-') {
+      Some(abs) => from_string_radix(abs, 10).map(|x: F| -x),
+      None => from_string_radix(token, 10),
+    };
+    value.ok_or_else(|| CircuitParseError::BadNumber {
+      at: self.at,
+      token: token.to_string(),
+    })
+  }
+
+  fn finish(mut self) -> Result<(), CircuitParseError> {
+    self.at.layer = None;
+    self.at.gate = None;
+    match self.next_token("") {
+      Ok(_) => Err(CircuitParseError::TrailingData { at: self.at }),
+      Err(_) => Ok(()),
+    }
+  }
+}
+
+/// Number of bits addressing `size` gates, at least 1
+fn bit_length(size: usize) -> usize {
+  (usize::BITS - (size - 1).leading_zeros()).max(1) as usize
+}
+
+/// Number of gates of the input layer of a circuit file with `depth` layers
+/// and `input_count` input gates, once padded
+pub(crate) fn input_layer_size(depth: usize, input_count: usize) -> usize {
+  let pad_requirement = if depth > 3 {
+    INPUT_PAD_DEEP
+  } else {
+    INPUT_PAD_SHALLOW
+  };
+  input_count.max(1 << pad_requirement).next_power_of_two()
+}
+
+/// Reads gate `j` of a layer, whose wires point into a previous layer of
+/// `1 << prev_bit_length` gates unless it is the first layer. Types unknown
+/// to this crate are looked up in `registry`.
+fn parse_gate<F: Field>(
+  tokens: &mut Tokens,
+  j: usize,
+  first_layer: bool,
+  prev_bit_length: usize,
+  registry: &GateRegistry<F>,
+) -> Result<GateKind<F>, CircuitParseError> {
+  let ty: usize = tokens.next("gate type")?;
+  let ty_at = tokens.at;
+  let g: usize = tokens.next("gate index")?;
+  if g != j {
+    return Err(CircuitParseError::UnsortedGate {
+      at: tokens.at,
+      expected: j,
+      found: g,
+    });
+  }
+  let u: usize = tokens.next("left input")?;
+  let u_at = tokens.at;
+  let v: usize = tokens.next("right input")?;
+  let v_at = tokens.at;
+
+  if first_layer {
+    // the value of an input gate is in u
+    return match ty {
+      2 => Ok(GateKind::Zero),
+      3 => Ok(GateKind::Constant { value: u as u64 }),
+      _ => Err(CircuitParseError::NotAnInputGate { at: ty_at, ty }),
+    };
+  }
+
+  let layer_size = 1 << prev_bit_length;
+  let check_wire = |wire: usize, at: Location| {
+    if wire < layer_size {
+      Ok(())
+    } else {
+      Err(CircuitParseError::WireOutOfRange {
+        at,
+        wire,
+        layer_size,
+      })
+    }
+  };
+  let invalid = |reason| CircuitParseError::InvalidOperands { at: ty_at, reason };
+
+  let gate = match ty {
+    // the value of a constant gate is in u
+    3 => GateKind::Constant { value: u as u64 },
+    2 => GateKind::Zero,
+    // relay and not gates have a single input
+    6 | 10 => {
+      check_wire(u, u_at)?;
+      match ty {
+        6 => GateKind::Not { u },
+        _ => GateKind::Relay { u },
+      }
+    }
+    // sum of the range [u, v)
+    5 => {
+      check_wire(u, u_at)?;
+      if v <= u || v > layer_size {
+        return Err(invalid(
+          "sum gates need u < v <= size of the previous layer",
+        ));
+      }
+      GateKind::Sum { start: u, end: v }
+    }
+    // bit recomposition of the range [u, v]
+    12 => {
+      check_wire(u, u_at)?;
+      check_wire(v, v_at)?;
+      if v < u || v - u >= 60 {
+        return Err(invalid("bit recomposition gates need u <= v < u + 60"));
+      }
+      GateKind::BitPack { start: u, end: v }
+    }
+    13 => {
+      check_wire(u, u_at)?;
+      if u != v {
+        return Err(invalid("u and v must be equal"));
+      }
+      GateKind::BitTest { u }
+    }
+    // only the first layer, built by the parser, relays the input layer
+    4 => return Err(invalid("direct relay gates only appear in the first layer")),
+    0 | 1 | 7 | 8 | 9 => {
+      check_wire(u, u_at)?;
+      check_wire(v, v_at)?;
+      match ty {
+        0 => GateKind::Add { u, v },
+        1 => GateKind::Mult { u, v },
+        7 => GateKind::Minus { u, v },
+        8 => GateKind::Xor { u, v },
+        _ => GateKind::Naab { u, v },
+      }
+    }
+    // custom linear combination, followed by its terms
+    14 => {
+      let parameter_length: usize = tokens.next("number of terms")?;
+      let mut terms = Vec::with_capacity(parameter_length);
+      for _ in 0..parameter_length {
+        let src: usize = tokens.next("term source")?;
+        check_wire(src, tokens.at)?;
+        terms.push((src, tokens.next_field_element("term weight")?));
+      }
+      GateKind::Linear { terms }
+    }
+    _ => {
+      let gate = registry
+        .gate(ty, u, v)
+        .ok_or(CircuitParseError::UnknownGateType { at: ty_at, ty })?;
+      let mut out_of_range = None;
+      gate.for_each_wire(|u, v, _| {
+        out_of_range = out_of_range.or_else(|| [u, v].into_iter().find(|&wire| wire >= layer_size))
+      });
+      if let Some(wire) = out_of_range {
+        check_wire(wire, u_at)?;
+      }
+      gate
+    }
+  };
+  Ok(gate)
+}
+
+fn parse_circuit<F: Field>(
+  text: &str,
+  registry: &GateRegistry<F>,
+) -> Result<LayeredCircuit<F>, CircuitParseError> {
+  let mut tokens = Tokens::new(CircuitFile::Circuit, text);
+  let d: usize = tokens.next("number of layers")?;
+  if d == 0 {
+    return Err(CircuitParseError::NoLayers { at: tokens.at });
+  }
+
+  let mut circuit = LayeredCircuit::new();
+  circuit.circuit = vec![Layer::new(); d + 1];
+  circuit.total_depth = d + 1;
+
+  for i in 1..=d {
+    tokens.at.layer = Some(i);
+    tokens.at.gate = None;
+    let number_gates: usize = tokens.next("number of gates")?;
+    if number_gates == 0 {
+      return Err(CircuitParseError::EmptyLayer { at: tokens.at });
+    }
+
+    let prev_bit_length = circuit.circuit[i - 1].bit_length;
+    let mut gates = Vec::with_capacity(number_gates);
+    for j in 0..number_gates {
+      tokens.at.gate = Some(j);
+      gates.push(parse_gate(
+        &mut tokens,
+        j,
+        i == 1,
+        prev_bit_length,
+        registry,
+      )?);
+    }
+
+    if i == 1 {
+      // the first layer relays the input gates, padded with zeros
+      circuit.input_count = number_gates;
+      let n_pad = input_layer_size(d, number_gates);
+      gates.resize(n_pad, GateKind::Constant { value: 0 });
+      circuit.circuit[0].gates = gates;
+      circuit.circuit[0].bit_length = bit_length(n_pad);
+      circuit.circuit[1].gates = (0..n_pad).map(|u| GateKind::DirectRelay { u }).collect();
+    } else {
+      let bit_length = bit_length(number_gates);
+      gates.resize(1 << bit_length, GateKind::Zero);
+      circuit.circuit[i].gates = gates;
+    }
+    circuit.circuit[i].bit_length = bit_length(circuit.circuit[i].gates.len());
+  }
+  tokens.finish()?;
+  Ok(circuit)
+}
+
+fn parse_meta<F: Field>(
+  circuit: &mut LayeredCircuit<F>,
+  text: &str,
+) -> Result<(), CircuitParseError> {
+  let mut tokens = Tokens::new(CircuitFile::Meta, text);
+  circuit.circuit[0].is_parallel = false;
+  for i in 1..circuit.total_depth {
+    tokens.at.layer = Some(i);
+    let is_para: usize = tokens.next("parallel flag")?;
+    let layer = &mut circuit.circuit[i];
+    layer.block_size = tokens.next("block size")?;
+    layer.repeat_num = tokens.next("repeat number")?;
+    layer.log_block_size = tokens.next("log of the block size")?;
+    layer.log_repeat_num = tokens.next("log of the repeat number")?;
+    layer.is_parallel = is_para != 0;
+
+    let power = 1usize.checked_shl(layer.log_repeat_num as u32);
+    if layer.is_parallel && power != Some(layer.repeat_num) {
+      return Err(CircuitParseError::BadRepeatNum {
+        at: tokens.at,
+        repeat_num: layer.repeat_num,
+        log_repeat_num: layer.log_repeat_num,
+      });
+    }
+    if layer.is_parallel {
+      check_blocks(circuit, i).map_err(|reason| CircuitParseError::BadBlocks {
+        at: tokens.at,
+        reason,
+      })?;
+    }
+  }
+  tokens.finish()
+}
+
+impl<F: Field> LayeredCircuit<F> {
+  /// Parses a circuit and its meta data from their text formats.
+  ///
+  /// The circuit file holds the number of layers, then for every layer its
+  /// number of gates followed by `type index u v` for each gate, in index
+  /// order. Type 14 gates carry their number of terms next, then a `source
+  /// weight` pair per term. The meta file holds `parallel block_size
+  /// repeat_num log_block_size log_repeat_num` for every layer, parallel
+  /// layers repeating their first block of `block_size` gates `repeat_num`
+  /// times, see [`LayeredCircuit::replicate`].
+  pub fn parse(circuit: &str, meta: &str) -> Result<Self, CircuitParseError> {
+    Self::parse_with_gates(circuit, meta, &GateRegistry::new())
+  }
+
+  /// Parses a circuit that may use the custom gates of `registry`, see
+  /// [`LayeredCircuit::parse`]
+  pub fn parse_with_gates(
+    circuit: &str,
+    meta: &str,
+    registry: &GateRegistry<F>,
+  ) -> Result<Self, CircuitParseError> {
+    let mut circuit = parse_circuit(circuit, registry)?;
+    parse_meta(&mut circuit, meta)?;
+    Ok(circuit)
+  }
+
+  /// Reads and parses the circuit and meta files
+  pub fn read(circuit_path: &str, meta_path: &str) -> Result<Self, CircuitParseError> {
+    Self::read_with_gates(circuit_path, meta_path, &GateRegistry::new())
+  }
+
+  /// Reads and parses circuit and meta files that may use the custom gates of
+  /// `registry`
+  pub fn read_with_gates(
+    circuit_path: &str,
+    meta_path: &str,
+    registry: &GateRegistry<F>,
+  ) -> Result<Self, CircuitParseError> {
+    let read = |path, file| read_to_string(path).map_err(|err| CircuitParseError::Io { file, err });
+    Self::parse_with_gates(
+      &read(circuit_path, CircuitFile::Circuit)?,
+      &read(meta_path, CircuitFile::Meta)?,
+      registry,
+    )
+  }
+
+  /// Largest bit length of the layers above the input layer
+  pub fn max_bit_length(&self) -> usize {
+    self.circuit[1..]
+      .iter()
+      .map(|layer| layer.bit_length)
+      .max()
+      .unwrap_or(0)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use prime_field::FieldElement;
+
+  use super::{CircuitFile, CircuitParseError, Location};
+  use crate::circuit_fast_track::{GateKind, LayeredCircuit};
+
+  // inputs 3, 5, 7, 2, then 3 * 5 and 7 + 2, then their linear combination
+  const CIRCUIT: &str =
+    "3\n4 3 0 3 0 3 1 5 0 3 2 7 0 3 3 2 0\n2 1 0 0 1 0 1 2 3\n1 14 0 0 0 2 0 2 1 -1\n";
+  const META: &str = "0 0 1 0 0\n0 0 1 0 0\n1 1 1 0 0\n";
+
+  fn location(
+    file: CircuitFile,
+    line: usize,
+    column: usize,
+    layer: usize,
+    gate: usize,
+  ) -> Location {
+    Location {
+      file,
+      line,
+      column,
+      layer: Some(layer),
+      gate: Some(gate),
+    }
+  }
+
+  #[test]
+  fn circuit_is_parsed() {
+    let circuit = LayeredCircuit::parse(CIRCUIT, META).unwrap();
+    assert_eq!(circuit.total_depth, 4);
+    assert_eq!(circuit.input_count, 4);
+    assert_eq!(circuit.circuit[0].bit_length, 15);
+    assert_eq!(circuit.circuit[0].gates[1], GateKind::Constant { value: 5 });
+    assert_eq!(circuit.circuit[1].gates[7], GateKind::DirectRelay { u: 7 });
+    assert_eq!(circuit.circuit[2].bit_length, 1);
+
+    let terms = vec![
+      (0, FieldElement::from_real(2)),
+      (1, -FieldElement::real_one()),
+    ];
+    assert_eq!(circuit.circuit[3].gates[0], GateKind::Linear { terms });
+    assert_eq!(circuit.circuit[3].gates.len(), 2);
+    assert!(circuit.circuit[3].is_parallel);
+    assert_eq!(circuit.max_bit_length(), 15);
+
+    let mat: LayeredCircuit = LayeredCircuit::parse(
+      include_str!("../mat_16_circuit.txt"),
+      include_str!("../mat_16_meta.txt"),
+    )
+    .unwrap();
+    assert_eq!(mat.input_count, 512);
+  }
+
+  #[test]
+  fn errors_are_located() {
+    let err =
+      |circuit: &str, meta: &str| LayeredCircuit::<FieldElement>::parse(circuit, meta).unwrap_err();
+
+    match err("2\n4 3 0 3 0 3 1 x 0", META) {
+      CircuitParseError::BadNumber { at, token } => {
+        assert_eq!(at, location(CircuitFile::Circuit, 2, 15, 1, 1));
+        assert_eq!(token, "x");
+      }
+      other => panic!("{}", other),
+    }
+    match err("2\n4 3 0 3 0 3 2 5 0", META) {
+      CircuitParseError::UnsortedGate {
+        at,
+        expected: 1,
+        found: 2,
+      } => assert_eq!(at, location(CircuitFile::Circuit, 2, 13, 1, 1)),
+      other => panic!("{}", other),
+    }
+    match err("2\n1 3 0 1 0\n2 1 0 0 1\n", META) {
+      CircuitParseError::UnexpectedEnd { at, expected } => {
+        assert_eq!(at, location(CircuitFile::Circuit, 3, 9, 2, 1));
+        assert_eq!(expected, "gate type");
+      }
+      other => panic!("{}", other),
+    }
+    assert!(matches!(
+      err("2\n1 3 0 1 0\n1 0 0 0 32768", META),
+      CircuitParseError::WireOutOfRange {
+        wire: 32768,
+        layer_size: 32768,
+        ..
+      }
+    ));
+    assert!(matches!(
+      err("2\n1 3 0 1 0\n1 11 0 0 0", META),
+      CircuitParseError::UnknownGateType { ty: 11, .. }
+    ));
+    assert!(matches!(
+      err("2\n1 1 0 1 0\n1 0 0 0 0", META),
+      CircuitParseError::NotAnInputGate { ty: 1, .. }
+    ));
+    assert!(matches!(
+      err("2\n1 3 0 1 0\n1 13 0 0 1", META),
+      CircuitParseError::InvalidOperands { .. }
+    ));
+    assert!(matches!(
+      err("2\n1 3 0 1 0\n1 4 0 0 0", META),
+      CircuitParseError::InvalidOperands { .. }
+    ));
+    assert!(matches!(err("0", META), CircuitParseError::NoLayers { .. }));
+    assert!(matches!(
+      err(&format!("{} 7", CIRCUIT), META),
+      CircuitParseError::TrailingData { .. }
+    ));
+
+    match err(CIRCUIT, "0 0 1 0 0\n0 0 1 0 0\n1 1 3 0 1\n") {
+      CircuitParseError::BadRepeatNum { at, .. } => {
+        assert_eq!(at.file, CircuitFile::Meta);
+        assert_eq!((at.line, at.layer), (3, Some(3)));
+      }
+      other => panic!("{}", other),
+    }
+    assert!(matches!(
+      err(CIRCUIT, "0 0 1 0 0\n"),
+      CircuitParseError::UnexpectedEnd {
+        expected: "parallel flag",
+        ..
+      }
+    ));
+  }
+}
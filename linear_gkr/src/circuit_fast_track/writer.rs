@@ -0,0 +1,139 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use prime_field::Field;
+
+use super::{GateKind, LayeredCircuit};
+
+fn invalid_data(msg: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// The `u` and `v` fields of a gate in the circuit file
+fn operands<F: Field>(gate: &GateKind<F>) -> (usize, usize) {
+  match gate {
+    GateKind::Zero | GateKind::Linear { .. } => (0, 0),
+    GateKind::Constant { value } => (*value as usize, 0),
+    GateKind::DirectRelay { u } | GateKind::Not { u } | GateKind::Relay { u } => (*u, 0),
+    GateKind::BitTest { u } => (*u, *u),
+    GateKind::Sum { start, end } | GateKind::BitPack { start, end } => (*start, *end),
+    GateKind::Add { u, v }
+    | GateKind::Mult { u, v }
+    | GateKind::Minus { u, v }
+    | GateKind::Xor { u, v }
+    | GateKind::Naab { u, v }
+    | GateKind::Custom { u, v, .. } => (*u, *v),
+  }
+}
+
+impl<F: Field> LayeredCircuit<F> {
+  /// Writes the circuit file in the format read by [`LayeredCircuit::parse`].
+  ///
+  /// The first layer must relay the `input_count` input gates of the input
+  /// layer, as built by the parser, since the file lists the input gates in
+  /// its place.
+  pub fn write_circuit<W: Write>(&self, mut out: W) -> io::Result<()> {
+    let inputs = self.circuit[0]
+      .gates
+      .get(..self.input_count)
+      .filter(|inputs| !inputs.is_empty())
+      .ok_or_else(|| invalid_data(format!("bad number of input gates {}", self.input_count)))?;
+    let relays_inputs = self.circuit[1]
+      .gates
+      .iter()
+      .take(self.input_count)
+      .enumerate()
+      .all(|(g, gate)| *gate == GateKind::DirectRelay { u: g });
+    if !relays_inputs {
+      return Err(invalid_data(
+        "the first layer doesn't relay the input layer".to_string(),
+      ));
+    }
+
+    writeln!(out, "{}", self.total_depth - 1)?;
+    for i in 1..self.total_depth {
+      let gates = if i == 1 {
+        inputs
+      } else {
+        &self.circuit[i].gates
+      };
+      write!(out, "{}", gates.len())?;
+      for (g, gate) in gates.iter().enumerate() {
+        let (u, v) = operands(gate);
+        write!(out, " {} {} {} {}", gate.code(), g, u, v)?;
+        if let GateKind::Linear { terms } = gate {
+          write!(out, " {}", terms.len())?;
+          for (src, weight) in terms {
+            let weight = weight.to_canonical_u64().ok_or_else(|| {
+              invalid_data(format!(
+                "weight {:?} of gate {} in layer {} is not in the base field",
+                weight, g, i
+              ))
+            })?;
+            write!(out, " {} {}", src, weight)?;
+          }
+        }
+      }
+      writeln!(out)?;
+    }
+    out.flush()
+  }
+
+  /// Writes the meta file, one line per layer above the input layer
+  pub fn write_meta<W: Write>(&self, mut out: W) -> io::Result<()> {
+    for layer in &self.circuit[1..] {
+      writeln!(
+        out,
+        "{} {} {} {} {}",
+        layer.is_parallel as usize,
+        layer.block_size,
+        layer.repeat_num,
+        layer.log_block_size,
+        layer.log_repeat_num
+      )?;
+    }
+    out.flush()
+  }
+
+  /// Writes the circuit and meta files read by [`LayeredCircuit::read`]
+  pub fn write(&self, circuit_path: &str, meta_path: &str) -> io::Result<()> {
+    self.write_circuit(BufWriter::new(File::create(circuit_path)?))?;
+    self.write_meta(BufWriter::new(File::create(meta_path)?))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::circuit_fast_track::LayeredCircuit;
+
+  fn roundtrip(circuit: &str, meta: &str) {
+    let parsed: LayeredCircuit = LayeredCircuit::parse(circuit, meta).unwrap();
+    let (mut circuit_out, mut meta_out) = (vec![], vec![]);
+    parsed.write_circuit(&mut circuit_out).unwrap();
+    parsed.write_meta(&mut meta_out).unwrap();
+    let circuit_out = String::from_utf8(circuit_out).unwrap();
+    let meta_out = String::from_utf8(meta_out).unwrap();
+
+    let reparsed = LayeredCircuit::parse(&circuit_out, &meta_out).unwrap();
+    assert_eq!(reparsed, parsed);
+
+    let (mut circuit_again, mut meta_again) = (vec![], vec![]);
+    reparsed.write_circuit(&mut circuit_again).unwrap();
+    reparsed.write_meta(&mut meta_again).unwrap();
+    assert_eq!(circuit_again, circuit_out.as_bytes());
+    assert_eq!(meta_again, meta_out.as_bytes());
+  }
+
+  #[test]
+  fn parse_write_parse_is_idempotent() {
+    // a padded layer of 3 gates, and a parallel layer of linear combinations
+    roundtrip(
+      "3\n4 3 0 3 0 3 1 5 0 3 2 7 0 3 3 2 0\n3 1 0 0 1 0 1 2 3 5 2 0 4\n2 14 0 0 0 2 0 2 1 -1 14 1 0 0 2 2 2 3 -1\n",
+      "0 0 1 0 0\n0 0 1 0 0\n1 1 2 0 1\n",
+    );
+    roundtrip(
+      include_str!("../mat_16_circuit.txt"),
+      include_str!("../mat_16_meta.txt"),
+    );
+  }
+}
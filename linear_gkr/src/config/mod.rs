@@ -1,35 +1,75 @@
-use std::error::Error;
-use std::fs;
+use infrastructure::my_hash::HashAlgorithm;
 
-pub struct Paths {
-  pub file_path: String,
-  pub meta_path: String,
+pub enum Command {
+  /// Evaluates the circuit on a witness and writes the proof along with the
+  /// public inputs and outputs
+  Prove {
+    circuit_path: String,
+    meta_path: String,
+    witness_path: String,
+    proof_path: String,
+    public_io_path: String,
+    /// Hash of the transcript and merkle trees, recorded in the proof
+    hash: HashAlgorithm,
+  },
+  /// Checks a proof against the circuit and the public inputs and outputs
+  Verify {
+    circuit_path: String,
+    meta_path: String,
+    public_io_path: String,
+    proof_path: String,
+  },
 }
 
-impl Paths {
-  pub fn build(mut args: impl Iterator<Item = String>) -> Result<Paths, &'static str> {
-    args.next();
+pub const USAGE: &str = "usage:
+  linear_gkr prove [--hash sha256|sha3|blake3|poseidon] <circuit> <meta> <witness> <proof out> <public io out>
+  linear_gkr verify <circuit> <meta> <public io> <proof>";
+
+impl Command {
+  pub fn build(mut args: impl Iterator<Item = String>) -> Result<Command, &'static str> {
     args.next();
 
-    let file_path = match args.next() {
+    let command = match args.next() {
       Some(arg) => arg,
-      None => return Err("Didn't get a circuit file path"),
+      None => return Err("Didn't get a command"),
     };
+    if command != "prove" && command != "verify" {
+      return Err("Unknown command, expected prove or verify");
+    }
+    let mut args = args.peekable();
+    let mut hash = HashAlgorithm::default();
+    if command == "prove" && args.peek().map(String::as_str) == Some("--hash") {
+      args.next();
+      hash = args
+        .next()
+        .and_then(|name| name.parse().ok())
+        .ok_or("Unknown hash, expected sha256, sha3, blake3 or poseidon")?;
+    }
+    let mut next_path = |err| args.next().ok_or(err);
 
-    let meta_path = match args.next() {
-      Some(arg) => arg,
-      None => return Err("Didn't get a meta file path"),
-    };
+    let circuit_path = next_path("Didn't get a circuit file path")?;
+    let meta_path = next_path("Didn't get a meta file path")?;
 
-    Ok(Paths {
-      file_path,
-      meta_path,
-    })
+    let command = match command.as_str() {
+      "prove" => Command::Prove {
+        circuit_path,
+        meta_path,
+        witness_path: next_path("Didn't get a witness file path")?,
+        proof_path: next_path("Didn't get a proof file path")?,
+        public_io_path: next_path("Didn't get a public io file path")?,
+        hash,
+      },
+      "verify" => Command::Verify {
+        circuit_path,
+        meta_path,
+        public_io_path: next_path("Didn't get a public io file path")?,
+        proof_path: next_path("Didn't get a proof file path")?,
+      },
+      _ => unreachable!(),
+    };
+    if args.next().is_some() {
+      return Err("Too many arguments");
+    }
+    Ok(command)
   }
 }
-
-pub fn run(config: Paths) -> Result<(), Box<dyn Error>> {
-  let contents = fs::read_to_string(config.file_path)?;
-
-  Ok(())
-}
@@ -1,5 +1,9 @@
 pub mod circuit_fast_track;
 pub mod config;
+pub mod multilinear;
 pub mod polynomial;
+pub mod proof;
 pub mod prover;
 pub mod verifier;
+pub mod witness;
+mod zk;
@@ -1,24 +1,110 @@
+use infrastructure::my_hash::HashAlgorithm;
+use infrastructure::transcript::Transcript;
+use linear_gkr::config::{Command, USAGE};
+use linear_gkr::proof::{GkrProof, TRANSCRIPT_LABEL};
+use linear_gkr::prover::ZkProver;
 use linear_gkr::verifier::ZkVerifier;
+use linear_gkr::witness::{PublicIo, Witness};
 use prime_field::FieldElementContext;
 
-use linear_gkr::config::Paths;
-use std::{env, process};
-fn main() {
-  let args: Vec<String> = env::args().collect();
-  let paths = Paths::build(env::args()).unwrap_or_else(|err| {
-    eprintln!("Problem parsing arguments: {err}");
-    process::exit(1)
-  });
+use std::error::Error;
+use std::{env, fs, process};
+
+fn prove(
+  circuit_path: &str,
+  meta_path: &str,
+  witness_path: &str,
+  proof_path: &str,
+  public_io_path: &str,
+  hash: HashAlgorithm,
+) -> Result<(), Box<dyn Error>> {
+  let mut zk_verifier: ZkVerifier = ZkVerifier::new();
+  let bit_length = zk_verifier.read_circuit(circuit_path, meta_path)?;
+  let circuit = &zk_verifier.aritmetic_circuit;
+
+  let witness = Witness::read(witness_path)?;
 
-  //Below unsafe function set packed 64-bit integers, it is mandatory?
-  unsafe {
-    FieldElementContext::init();
+  let mut zk_prover = ZkProver::new();
+  zk_prover.init_array(bit_length, circuit);
+  zk_prover.load_witness(&witness)?;
+  let proof = zk_prover.prove(&mut Transcript::with_hasher(TRANSCRIPT_LABEL, hash));
+  println!("GKR Prove Time: {}", zk_prover.total_time);
+
+  fs::write(proof_path, proof.encode(circuit.digest()))?;
+
+  // the private inputs are only known to the verifier through the commitment
+  PublicIo {
+    inputs: witness.public,
+    outputs: proof.output,
   }
-  let mut zk_verifier = ZkVerifier::new();
+  .write(public_io_path)?;
+  Ok(())
+}
 
-  let bit_length = zk_verifier.read_circuit(&paths.file_path, &paths.meta_path);
+fn verify(
+  circuit_path: &str,
+  meta_path: &str,
+  public_io_path: &str,
+  proof_path: &str,
+) -> Result<bool, Box<dyn Error>> {
+  let mut zk_verifier: ZkVerifier = ZkVerifier::new();
+  zk_verifier.read_circuit(circuit_path, meta_path)?;
+  let public_io = PublicIo::read(public_io_path)?;
+  let proof = GkrProof::decode(
+    &fs::read(proof_path)?,
+    zk_verifier.aritmetic_circuit.digest(),
+  )?;
 
-  let result = zk_verifier.verify(&args[4], bit_length.unwrap());
-  //let result = zk_verifier.virgo_verify(&args[4], bit_length);
-  println!("Pass verification? : {}", result);
+  Ok(zk_verifier.verify(
+    &proof,
+    &public_io,
+    &mut Transcript::with_hasher(TRANSCRIPT_LABEL, proof.hash),
+  ))
+}
+
+fn main() {
+  let command = Command::build(env::args()).unwrap_or_else(|err| {
+    eprintln!("Problem parsing arguments: {err}\n{USAGE}");
+    process::exit(2)
+  });
+  FieldElementContext::init();
+
+  match command {
+    Command::Prove {
+      circuit_path,
+      meta_path,
+      witness_path,
+      proof_path,
+      public_io_path,
+      hash,
+    } => {
+      if let Err(err) = prove(
+        &circuit_path,
+        &meta_path,
+        &witness_path,
+        &proof_path,
+        &public_io_path,
+        hash,
+      ) {
+        eprintln!("Problem proving: {err}");
+        process::exit(2);
+      }
+    }
+    Command::Verify {
+      circuit_path,
+      meta_path,
+      public_io_path,
+      proof_path,
+    } => {
+      let result =
+        verify(&circuit_path, &meta_path, &public_io_path, &proof_path).unwrap_or_else(|err| {
+          eprintln!("Problem verifying: {err}");
+          process::exit(2)
+        });
+      println!("Pass verification? : {}", result);
+      if !result {
+        process::exit(1);
+      }
+    }
+  }
 }
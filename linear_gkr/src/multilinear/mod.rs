@@ -0,0 +1,293 @@
+//! Multilinear extensions of tables over the boolean hypercube, shared by the
+//! prover and the verifier. Everywhere in the crate, variable `j` of a point
+//! goes with bit `j` of the index of a table entry.
+
+use prime_field::{Field, FieldElement};
+
+/// `eq(r, x) = prod_j (r_j x_j + (1 - r_j) (1 - x_j))`, the multilinear
+/// extension of the indicator of `r` over the hypercube
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EqPolynomial<'a, F = FieldElement> {
+  r: &'a [F],
+}
+
+impl<'a, F: Field> EqPolynomial<'a, F> {
+  pub fn new(r: &'a [F]) -> Self {
+    Self { r }
+  }
+
+  pub fn num_vars(&self) -> usize {
+    self.r.len()
+  }
+
+  /// `eq(r, x)` at any point `x`
+  pub fn evaluate(&self, x: &[F]) -> F {
+    assert_eq!(x.len(), self.r.len());
+    let one = F::one();
+    self
+      .r
+      .iter()
+      .zip(x)
+      .fold(one, |ret, (&r, &x)| ret * (r * x + (one - r) * (one - x)))
+  }
+
+  /// `eq(r, 0)`
+  pub fn evaluate_at_zero(&self) -> F {
+    self.r.iter().fold(F::one(), |ret, &r| ret * (F::one() - r))
+  }
+
+  /// `eq(r, x)` for every `x` of the hypercube
+  pub fn evaluations(&self) -> Vec<F> {
+    let mut table = vec![F::zero(); 1 << self.r.len()];
+    self.fill(&mut table, F::one());
+    table
+  }
+
+  /// Writes `scale * eq(r, x)` to `table[x]` for every `x`, doubling the
+  /// filled part of the table once per variable
+  pub fn fill(&self, table: &mut [F], scale: F) {
+    table[0] = scale;
+    for (i, &r) in self.r.iter().enumerate() {
+      for j in 0..1 << i {
+        table[j | 1 << i] = table[j] * r;
+        table[j] = table[j] * (F::one() - r);
+      }
+    }
+  }
+
+  /// Fills `first_half` and `second_half` so that `scale * eq(r, x)` is
+  /// `first_half[x & mask] * second_half[x >> first_half_len]`, with
+  /// `first_half_len` half the number of variables, rounded down and `mask`
+  /// its low bits. The two tables hold about `2^(n / 2)` entries each instead
+  /// of the `2^n` of `evaluations`.
+  pub fn fill_halves(&self, first_half: &mut [F], second_half: &mut [F], scale: F) {
+    let (r_first, r_second) = self.r.split_at(self.r.len() / 2);
+    EqPolynomial::new(r_first).fill(first_half, scale);
+    EqPolynomial::new(r_second).fill(second_half, F::one());
+  }
+}
+
+/// Multilinear extension of a table of `2^num_vars` evaluations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DenseMultilinear<F = FieldElement> {
+  evaluations: Vec<F>,
+  num_vars: usize,
+}
+
+impl<F: Field> DenseMultilinear<F> {
+  /// Extension of `evaluations`, padded with zeros up to a power of two
+  pub fn new(mut evaluations: Vec<F>) -> Self {
+    let len = evaluations.len().max(1).next_power_of_two();
+    evaluations.resize(len, F::zero());
+    Self {
+      num_vars: len.trailing_zeros() as usize,
+      evaluations,
+    }
+  }
+
+  pub fn num_vars(&self) -> usize {
+    self.num_vars
+  }
+
+  pub fn evaluations(&self) -> &[F] {
+    &self.evaluations
+  }
+
+  /// Binds the first variable, the low bit of the indices, to `r`, halving
+  /// the table
+  pub fn fix_first_variable(&mut self, r: F) {
+    assert!(self.num_vars > 0, "no variable left to fix");
+    let half = self.evaluations.len() >> 1;
+    for j in 0..half {
+      let zero = self.evaluations[j << 1];
+      self.evaluations[j] = zero + (self.evaluations[j << 1 | 1] - zero) * r;
+    }
+    self.evaluations.truncate(half);
+    self.num_vars -= 1;
+  }
+
+  /// The extension at `r`, in time linear in the table
+  pub fn evaluate(&self, r: &[F]) -> F {
+    Self::evaluate_table(&self.evaluations, r)
+  }
+
+  /// The extension of `table`, of length `1 << r.len()`, at `r`, without
+  /// copying the table first
+  pub fn evaluate_table(table: &[F], r: &[F]) -> F {
+    assert_eq!(table.len(), 1 << r.len());
+    let (&first, rest) = match r.split_first() {
+      Some(split) => split,
+      None => return table[0],
+    };
+    let mut folded: Vec<_> = table
+      .chunks(2)
+      .map(|pair| pair[0] + (pair[1] - pair[0]) * first)
+      .collect();
+    let mut len = folded.len();
+    for &r in rest {
+      len >>= 1;
+      for j in 0..len {
+        folded[j] = folded[j << 1] + (folded[j << 1 | 1] - folded[j << 1]) * r;
+      }
+    }
+    folded[0]
+  }
+}
+
+/// Multilinear extension of a table of `2^num_vars` entries which is zero but
+/// at a few indices
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SparseMultilinear<F = FieldElement> {
+  entries: Vec<(usize, F)>,
+  num_vars: usize,
+}
+
+impl<F: Field> SparseMultilinear<F> {
+  /// Extension of the table which is `value` at `index` for every entry of
+  /// `entries`, summing the values of repeated indices, and zero elsewhere
+  pub fn new(num_vars: usize, entries: Vec<(usize, F)>) -> Self {
+    assert!(
+      entries.iter().all(|&(index, _)| index >> num_vars == 0),
+      "index out of the hypercube"
+    );
+    Self { entries, num_vars }
+  }
+
+  pub fn num_vars(&self) -> usize {
+    self.num_vars
+  }
+
+  /// The extension at `r`, in time linear in the entries and in `2^(n / 2)`
+  pub fn evaluate(&self, r: &[F]) -> F {
+    assert_eq!(r.len(), self.num_vars);
+    let first_half_len = r.len() / 2;
+    let mut first_half = vec![F::zero(); 1 << first_half_len];
+    let mut second_half = vec![F::zero(); 1 << (r.len() - first_half_len)];
+    EqPolynomial::new(r).fill_halves(&mut first_half, &mut second_half, F::one());
+    let mask = (1 << first_half_len) - 1;
+    self.entries.iter().fold(F::zero(), |ret, &(index, value)| {
+      ret + value * first_half[index & mask] * second_half[index >> first_half_len]
+    })
+  }
+
+  pub fn to_dense(&self) -> DenseMultilinear<F> {
+    let mut evaluations = vec![F::zero(); 1 << self.num_vars];
+    for &(index, value) in &self.entries {
+      evaluations[index] = evaluations[index] + value;
+    }
+    DenseMultilinear::new(evaluations)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn random_point(len: usize) -> Vec<FieldElement> {
+    (0..len).map(|_| FieldElement::new_random()).collect()
+  }
+
+  /// `sum_x table[x] eq(r, x)`, each `eq(r, x)` computed on its own from the
+  /// bits of `x`
+  fn naive_evaluate(table: &[FieldElement], r: &[FieldElement]) -> FieldElement {
+    let one = FieldElement::real_one();
+    table
+      .iter()
+      .enumerate()
+      .fold(FieldElement::zero(), |ret, (x, &value)| {
+        let eq = r.iter().enumerate().fold(one, |eq, (j, &r)| {
+          eq * if x >> j & 1 == 1 { r } else { one - r }
+        });
+        ret + value * eq
+      })
+  }
+
+  fn boolean_point(x: usize, len: usize) -> Vec<FieldElement> {
+    (0..len)
+      .map(|j| FieldElement::from_real((x >> j & 1) as u64))
+      .collect()
+  }
+
+  #[test]
+  fn eq_tables_match_naive_evaluation() {
+    for len in 0..8 {
+      let r = random_point(len);
+      let eq = EqPolynomial::new(&r);
+      let table = eq.evaluations();
+      let mut first_half = vec![FieldElement::zero(); 1 << (len / 2)];
+      let mut second_half = vec![FieldElement::zero(); 1 << (len - len / 2)];
+      let scale = FieldElement::new_random();
+      eq.fill_halves(&mut first_half, &mut second_half, scale);
+      let mask = (1 << (len / 2)) - 1;
+      for (x, &entry) in table.iter().enumerate() {
+        assert_eq!(entry, eq.evaluate(&boolean_point(x, len)));
+        assert_eq!(
+          scale * entry,
+          first_half[x & mask] * second_half[x >> (len / 2)]
+        );
+      }
+      assert_eq!(eq.evaluate_at_zero(), table[0]);
+      // eq(r, .) is symmetric and its extension sums to one over the cube
+      let x = random_point(len);
+      assert_eq!(eq.evaluate(&x), EqPolynomial::new(&x).evaluate(&r));
+      assert_eq!(
+        table
+          .iter()
+          .fold(FieldElement::zero(), |ret, &entry| ret + entry),
+        FieldElement::real_one()
+      );
+    }
+  }
+
+  #[test]
+  fn dense_extension_matches_naive_evaluation() {
+    for len in 0..8 {
+      let table = random_point(1 << len);
+      let r = random_point(len);
+      let poly = DenseMultilinear::new(table.clone());
+      assert_eq!(poly.num_vars(), len);
+      assert_eq!(poly.evaluate(&r), naive_evaluate(&table, &r));
+      assert_eq!(
+        poly.evaluate(&r),
+        DenseMultilinear::evaluate_table(&table, &r)
+      );
+      // the extension agrees with the table on the hypercube
+      let x = table.len() - 1;
+      assert_eq!(poly.evaluate(&boolean_point(x, len)), table[x]);
+
+      let mut fixed = poly.clone();
+      for (j, &r_j) in r.iter().enumerate() {
+        fixed.fix_first_variable(r_j);
+        assert_eq!(fixed.num_vars(), len - j - 1);
+        assert_eq!(fixed.evaluate(&r[j + 1..]), poly.evaluate(&r));
+      }
+      assert_eq!(fixed.evaluations(), &[poly.evaluate(&r)]);
+    }
+  }
+
+  #[test]
+  fn dense_extension_pads_with_zeros() {
+    let table = random_point(5);
+    let r = random_point(3);
+    let mut padded = table.clone();
+    padded.resize(8, FieldElement::zero());
+    assert_eq!(
+      DenseMultilinear::new(table).evaluate(&r),
+      naive_evaluate(&padded, &r)
+    );
+  }
+
+  #[test]
+  fn sparse_extension_matches_dense_one() {
+    for len in 0..9 {
+      let entries: Vec<_> = (0..len + 2)
+        .map(|i| ((i * 37 + len) % (1 << len), FieldElement::new_random()))
+        .collect();
+      let sparse = SparseMultilinear::new(len, entries);
+      let dense = sparse.to_dense();
+      let r = random_point(len);
+      assert_eq!(sparse.evaluate(&r), dense.evaluate(&r));
+      assert_eq!(sparse.evaluate(&r), naive_evaluate(dense.evaluations(), &r));
+    }
+  }
+}
@@ -1,40 +1,52 @@
-use prime_field::FieldElement;
-#[derive(Debug, Clone)]
+use infrastructure::transcript::{Absorb, Transcript};
+use prime_field::{Field, FieldElement};
+use serde::{Deserialize, Serialize};
 
-pub struct LinearPoly {
-  pub a: FieldElement,
-  pub b: FieldElement,
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinearPoly<F = FieldElement> {
+  pub a: F,
+  pub b: F,
 }
 
-impl LinearPoly {
-  //maps from FieldElement to LinearPoly
-  // unfinished, check C/C++ repo
-  pub fn maps(arg: FieldElement) -> Self {
-    Self { a: arg, b: arg }
+impl<F: Field> LinearPoly<F> {
+  //maps from FieldElement to LinearPoly, the constant polynomial `arg`
+  pub fn maps(arg: F) -> Self {
+    Self {
+      a: F::zero(),
+      b: arg,
+    }
   }
 
   pub fn zero() -> Self {
     Self {
-      a: FieldElement::zero(),
-      b: FieldElement::zero(),
+      a: F::zero(),
+      b: F::zero(),
     }
   }
-  pub fn new(a: FieldElement, b: FieldElement) -> Self {
+  pub fn new(a: F, b: F) -> Self {
     Self { a, b }
   }
   // Create a monomial with no variables
-  pub fn new_constant_monomial(b: FieldElement) -> Self {
+  pub fn new_constant_monomial(b: F) -> Self {
     Self {
-      a: FieldElement::from_real(0),
+      a: F::from_u64(0),
       b,
     }
   }
 
-  pub fn eval(&self, x: FieldElement) -> FieldElement {
+  pub fn eval(&self, x: F) -> F {
     self.a * x + self.b
   }
 }
-impl core::ops::Add for LinearPoly {
+
+impl<F: Field> Absorb for LinearPoly<F> {
+  fn absorb_into(&self, transcript: &mut Transcript) {
+    transcript.append_field_element(self.a);
+    transcript.append_field_element(self.b);
+  }
+}
+
+impl<F: Field> core::ops::Add for LinearPoly<F> {
   type Output = Self;
 
   fn add(self, x: Self) -> Self::Output {
@@ -44,8 +56,8 @@ impl core::ops::Add for LinearPoly {
   }
 }
 
-impl core::ops::Mul for LinearPoly {
-  type Output = QuadraticPoly;
+impl<F: Field> core::ops::Mul for LinearPoly<F> {
+  type Output = QuadraticPoly<F>;
 
   fn mul(self, x: Self) -> Self::Output {
     let a = self.a * x.a;
@@ -54,30 +66,34 @@ impl core::ops::Mul for LinearPoly {
     QuadraticPoly::new(a, b, c)
   }
 }
-#[derive(Debug, Clone, Copy)]
-pub struct QuadraticPoly {
-  pub a: FieldElement,
-  pub b: FieldElement,
-  pub c: FieldElement,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuadraticPoly<F = FieldElement> {
+  pub a: F,
+  pub b: F,
+  pub c: F,
 }
 
-impl QuadraticPoly {
+impl<F: Field> QuadraticPoly<F> {
   pub fn zero() -> Self {
     Self {
-      a: FieldElement::zero(),
-      b: FieldElement::zero(),
-      c: FieldElement::zero(),
+      a: F::zero(),
+      b: F::zero(),
+      c: F::zero(),
     }
   }
-  pub fn new(a: FieldElement, b: FieldElement, c: FieldElement) -> Self {
+  pub fn new(a: F, b: F, c: F) -> Self {
     Self { a, b, c }
   }
   //todo: debug function
-  pub fn eval(self, x: &FieldElement) -> FieldElement {
+  pub fn eval(self, x: &F) -> F {
     (self.a * *x + self.b) * *x + self.c
   }
+}
+
+impl<F: Field> core::ops::Mul<LinearPoly<F>> for QuadraticPoly<F> {
+  type Output = CubicPoly<F>;
 
-  pub fn mul(self, x: LinearPoly) -> CubicPoly {
+  fn mul(self, x: LinearPoly<F>) -> Self::Output {
     let a = self.a * x.a;
     let b = self.a * x.b + self.b * x.a;
     let c = self.b * x.b + self.c * x.a;
@@ -86,7 +102,15 @@ impl QuadraticPoly {
   }
 }
 
-impl core::ops::Add for QuadraticPoly {
+impl<F: Field> Absorb for QuadraticPoly<F> {
+  fn absorb_into(&self, transcript: &mut Transcript) {
+    transcript.append_field_element(self.a);
+    transcript.append_field_element(self.b);
+    transcript.append_field_element(self.c);
+  }
+}
+
+impl<F: Field> core::ops::Add for QuadraticPoly<F> {
   type Output = Self;
 
   fn add(self, x: Self) -> Self::Output {
@@ -97,23 +121,24 @@ impl core::ops::Add for QuadraticPoly {
   }
 }
 
-pub struct CubicPoly {
-  pub a: FieldElement,
-  pub b: FieldElement,
-  pub c: FieldElement,
-  pub d: FieldElement,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CubicPoly<F = FieldElement> {
+  pub a: F,
+  pub b: F,
+  pub c: F,
+  pub d: F,
 }
 
-impl CubicPoly {
-  pub fn new(a: FieldElement, b: FieldElement, c: FieldElement, d: FieldElement) -> Self {
+impl<F: Field> CubicPoly<F> {
+  pub fn new(a: F, b: F, c: F, d: F) -> Self {
     Self { a, b, c, d }
   }
-  pub fn eval(self, x: FieldElement) -> FieldElement {
+  pub fn eval(self, x: F) -> F {
     ((self.a * x + self.b) * x + self.c) * x + self.d
   }
 }
 
-impl core::ops::Add for CubicPoly {
+impl<F: Field> core::ops::Add for CubicPoly<F> {
   type Output = Self;
 
   fn add(self, x: Self) -> Self::Output {
@@ -124,30 +149,26 @@ impl core::ops::Add for CubicPoly {
     Self { a, b, c, d }
   }
 }
-pub struct QuadruplePoly {
-  pub a: FieldElement,
-  pub b: FieldElement,
-  pub c: FieldElement,
-  pub d: FieldElement,
-  pub e: FieldElement,
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuadruplePoly<F = FieldElement> {
+  pub a: F,
+  pub b: F,
+  pub c: F,
+  pub d: F,
+  pub e: F,
 }
 
-impl QuadruplePoly {
-  pub fn new(
-    a: FieldElement,
-    b: FieldElement,
-    c: FieldElement,
-    d: FieldElement,
-    e: FieldElement,
-  ) -> Self {
+impl<F: Field> QuadruplePoly<F> {
+  pub fn new(a: F, b: F, c: F, d: F, e: F) -> Self {
     Self { a, b, c, d, e }
   }
-  pub fn eval(self, x: FieldElement) -> FieldElement {
+  pub fn eval(self, x: F) -> F {
     (((self.a * x + self.b) * x + self.c) * x + self.d) * x + self.e
   }
 }
 
-impl core::ops::Add for QuadruplePoly {
+impl<F: Field> core::ops::Add for QuadruplePoly<F> {
   type Output = Self;
 
   fn add(self, x: Self) -> Self::Output {
@@ -160,24 +181,25 @@ impl core::ops::Add for QuadruplePoly {
   }
 }
 
-pub struct QuintuplePoly {
-  pub a: FieldElement,
-  pub b: FieldElement,
-  pub c: FieldElement,
-  pub d: FieldElement,
-  pub e: FieldElement,
-  pub f: FieldElement,
+impl<F: Field> Absorb for QuadruplePoly<F> {
+  fn absorb_into(&self, transcript: &mut Transcript) {
+    for x in [self.a, self.b, self.c, self.d, self.e] {
+      transcript.append_field_element(x);
+    }
+  }
+}
+
+pub struct QuintuplePoly<F = FieldElement> {
+  pub a: F,
+  pub b: F,
+  pub c: F,
+  pub d: F,
+  pub e: F,
+  pub f: F,
 }
 
-impl QuintuplePoly {
-  pub fn new(
-    a: FieldElement,
-    b: FieldElement,
-    c: FieldElement,
-    d: FieldElement,
-    e: FieldElement,
-    f: FieldElement,
-  ) -> Self {
+impl<F: Field> QuintuplePoly<F> {
+  pub fn new(a: F, b: F, c: F, d: F, e: F, f: F) -> Self {
     Self { a, b, c, d, e, f }
   }
 
@@ -187,12 +209,12 @@ impl QuintuplePoly {
   //     }
   // }
 
-  pub fn eval(self, x: FieldElement) -> FieldElement {
+  pub fn eval(self, x: F) -> F {
     return (((((self.a * x) + self.b) * x + self.c) * x + self.d) * x + self.e) * x + self.f;
   }
 }
 
-impl core::ops::Add for QuintuplePoly {
+impl<F: Field> core::ops::Add for QuintuplePoly<F> {
   type Output = Self;
 
   fn add(self, x: Self) -> Self::Output {
@@ -0,0 +1,404 @@
+use std::fmt;
+
+use bincode::Options;
+use infrastructure::constants::LOG_SLICE_NUMBER;
+use infrastructure::my_hash::{HashAlgorithm, HashDigest};
+use poly_commitment::PolyCommitProof;
+use prime_field::{batch, Field, FieldElement};
+use serde::{Deserialize, Serialize};
+
+use crate::circuit_fast_track::LayeredCircuit;
+use crate::polynomial::{LinearPoly, QuadraticPoly, QuadruplePoly};
+
+/// Label the GKR transcript is started with
+pub const TRANSCRIPT_LABEL: &[u8] = b"linear_gkr";
+
+/// First bytes of every encoded proof
+pub const PROOF_MAGIC: [u8; 4] = *b"GKRP";
+/// Version of the proof container written by [`GkrProof::encode`]
+pub const PROOF_VERSION: u16 = 4;
+
+const SECTION_OUTPUT: u8 = 0;
+const SECTION_LAYER: u8 = 1;
+const SECTION_ZK: u8 = 2;
+const SECTION_OPENING: u8 = 3;
+
+/// Log length of the array a proof outside of the zero knowledge mode
+/// commits to: the input layer of `circuit` up to the input gates, past which
+/// it is zero padding, and at least as long as the commitment takes
+pub(crate) fn committed_log_len<F: Field>(circuit: &LayeredCircuit<F>) -> usize {
+  let input_bit_length = circuit.input_count.next_power_of_two().trailing_zeros() as usize;
+  input_bit_length
+    .min(circuit.circuit[0].bit_length)
+    .max(LOG_SLICE_NUMBER + 1)
+}
+
+/// First `len` variables of the point `r`, padded with zeros, and the factor
+/// of `eq(r, .)` over the others, for arrays which are zero past their first
+/// `1 << len` entries
+pub(crate) fn prefix_point<F: Field>(r: &[F], len: usize) -> (Vec<F>, F) {
+  let (point, rest) = r.split_at(r.len().min(len));
+  let mut point = point.to_vec();
+  point.resize(len, F::zero());
+  let scale = rest.iter().fold(F::one(), |ret, &r| ret * (F::one() - r));
+  (point, scale)
+}
+
+/// Adds `rho^(i + 1)` to entry `i` of `public` for each of the
+/// `public_count` public inputs, which lead the input layer and are left to
+/// zero in the committed array. The inner product of `public` with the
+/// committed array then only stays the same if the committed array is zero
+/// there, but with probability `public_count / |F|` over `rho`.
+pub(crate) fn weigh_public_inputs<F: Field>(public: &mut [F], public_count: usize, rho: F) {
+  let weights = batch::powers(rho, public_count + 1);
+  for (entry, &weight) in public.iter_mut().zip(&weights[1..]) {
+    *entry = *entry + weight;
+  }
+}
+
+/// Prover messages for the sumcheck reducing the claims on layer `i` to
+/// claims on layer `i - 1`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LayerProof<F = FieldElement> {
+  /// One polynomial per bit of `u`, but the last one in zero knowledge
+  /// proofs, see [`ZkLayerProof::phase1_last`]
+  pub phase1: Vec<QuadraticPoly<F>>,
+  /// One polynomial per bit of `v`, likewise
+  pub phase2: Vec<QuadraticPoly<F>>,
+  /// Claimed values of layer `i - 1` at `r_u` and `r_v`
+  pub v_u: F,
+  pub v_v: F,
+}
+
+/// Messages the zero knowledge mode adds to the sumcheck of a layer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZkLayerProof<F = FieldElement> {
+  /// Sum of the masking polynomial over the hypercube
+  pub mask_sum: F,
+  /// Message of the round binding `w`, the variable of the mask of the
+  /// randomized extension of the layer
+  pub w_message: LinearPoly<F>,
+  /// Messages of the last rounds of both phases, which also carry the
+  /// randomized extension of layer `i - 1`
+  pub phase1_last: QuadruplePoly<F>,
+  pub phase2_last: QuadruplePoly<F>,
+  /// Mask of the randomized extension of the layer at the first bit of `r_0`
+  /// and `r_1`, and at `r_w`, zero for the output layer
+  pub lde_mask: [F; 2],
+  /// Value of the masking polynomial at the point the sumcheck ends on
+  pub mask_value: F,
+}
+
+/// Messages the zero knowledge mode adds to a proof
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZkProof<F = FieldElement> {
+  /// Messages added to the sumcheck of every layer, in the order of
+  /// [`GkrProof::layers`]
+  pub layers: Vec<ZkLayerProof<F>>,
+}
+
+/// A self-contained non-interactive GKR proof
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GkrProof<F = FieldElement> {
+  /// Hash function of the transcript the proof was produced with
+  pub hash: HashAlgorithm,
+  /// Claimed values of the output layer
+  pub output: Vec<F>,
+  /// Sumcheck messages, from the output layer down to layer 1
+  pub layers: Vec<LayerProof<F>>,
+  /// Commitment to the input layer, and in zero knowledge proofs to the masks
+  /// of every layer as well
+  pub commitment: HashDigest,
+  /// Opening of the commitment on a random combination of the claims left
+  /// on the committed values
+  pub opening: PolyCommitProof<F>,
+  /// Present in proofs produced in the zero knowledge mode
+  pub zk: Option<ZkProof<F>>,
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ProofFormatError {
+  /// The input ended in the middle of the header or of a section
+  Truncated,
+  BadMagic([u8; 4]),
+  UnsupportedVersion(u16),
+  UnknownHash(u8),
+  /// The proof was produced for another circuit
+  CircuitMismatch {
+    expected: HashDigest,
+    found: HashDigest,
+  },
+  UnexpectedSection {
+    index: usize,
+    expected: u8,
+    found: u8,
+  },
+  /// The payload of a section doesn't decode to the expected message
+  MalformedSection {
+    index: usize,
+    err: bincode::Error,
+  },
+  TrailingBytes(usize),
+}
+
+impl fmt::Display for ProofFormatError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Truncated => write!(f, "proof is truncated"),
+      Self::BadMagic(magic) => write!(f, "bad magic bytes {:?}", magic),
+      Self::UnsupportedVersion(version) => write!(f, "unsupported proof version {}", version),
+      Self::UnknownHash(id) => write!(f, "unknown hash algorithm {}", id),
+      Self::CircuitMismatch { expected, found } => write!(
+        f,
+        "proof is for circuit {:?}, expected {:?}",
+        found, expected
+      ),
+      Self::UnexpectedSection {
+        index,
+        expected,
+        found,
+      } => write!(
+        f,
+        "section {} has kind {}, expected {}",
+        index, found, expected
+      ),
+      Self::MalformedSection { index, err } => write!(f, "section {} is malformed: {}", index, err),
+      Self::TrailingBytes(len) => write!(f, "{} trailing bytes after the last section", len),
+    }
+  }
+}
+
+impl std::error::Error for ProofFormatError {}
+
+fn bincode_options() -> impl Options {
+  bincode::DefaultOptions::new()
+    .with_fixint_encoding()
+    .with_little_endian()
+    .reject_trailing_bytes()
+}
+
+/// Cursor over an encoded proof which fails on truncation instead of panicking
+struct Reader<'a> {
+  bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+  fn take(&mut self, len: usize) -> Result<&'a [u8], ProofFormatError> {
+    if self.bytes.len() < len {
+      return Err(ProofFormatError::Truncated);
+    }
+    let (head, tail) = self.bytes.split_at(len);
+    self.bytes = tail;
+    Ok(head)
+  }
+
+  fn take_array<const N: usize>(&mut self) -> Result<[u8; N], ProofFormatError> {
+    Ok(self.take(N)?.try_into().unwrap())
+  }
+
+  fn section<T: for<'de> Deserialize<'de>>(
+    &mut self,
+    index: usize,
+    expected: u8,
+  ) -> Result<T, ProofFormatError> {
+    let [found] = self.take_array::<1>()?;
+    if found != expected {
+      return Err(ProofFormatError::UnexpectedSection {
+        index,
+        expected,
+        found,
+      });
+    }
+    let len = u64::from_le_bytes(self.take_array()?);
+    let len = usize::try_from(len).map_err(|_| ProofFormatError::Truncated)?;
+    let payload = self.take(len)?;
+    bincode_options()
+      .deserialize(payload)
+      .map_err(|err| ProofFormatError::MalformedSection { index, err })
+  }
+}
+
+fn write_section<T: Serialize>(bytes: &mut Vec<u8>, kind: u8, payload: &T) {
+  let payload = bincode_options().serialize(payload).unwrap();
+  bytes.push(kind);
+  bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+  bytes.extend_from_slice(&payload);
+}
+
+impl<F: Field> GkrProof<F> {
+  /// Encodes the proof as `magic | version | hash id | circuit digest |
+  /// number of layers`, followed by the output section, one section per
+  /// layer, the section of the commitment and its opening and, for zero
+  /// knowledge proofs, a last section with [`GkrProof::zk`].
+  /// Every section is a kind byte and a length prefixed payload.
+  pub fn encode(&self, circuit_digest: HashDigest) -> Vec<u8> {
+    let mut bytes = vec![];
+    bytes.extend_from_slice(&PROOF_MAGIC);
+    bytes.extend_from_slice(&PROOF_VERSION.to_le_bytes());
+    bytes.push(self.hash.id());
+    bytes.extend_from_slice(&circuit_digest.h0.to_le_bytes());
+    bytes.extend_from_slice(&circuit_digest.h1.to_le_bytes());
+    bytes.extend_from_slice(&(self.layers.len() as u64).to_le_bytes());
+
+    write_section(&mut bytes, SECTION_OUTPUT, &self.output);
+    for layer in &self.layers {
+      write_section(&mut bytes, SECTION_LAYER, layer);
+    }
+    write_section(
+      &mut bytes,
+      SECTION_OPENING,
+      &(&self.commitment, &self.opening),
+    );
+    if let Some(zk) = &self.zk {
+      write_section(&mut bytes, SECTION_ZK, zk);
+    }
+    bytes
+  }
+
+  /// Decodes a proof written by [`GkrProof::encode`], checking that it was
+  /// produced for the circuit with digest `circuit_digest`
+  pub fn decode(bytes: &[u8], circuit_digest: HashDigest) -> Result<Self, ProofFormatError> {
+    let mut reader = Reader { bytes };
+
+    let magic = reader.take_array()?;
+    if magic != PROOF_MAGIC {
+      return Err(ProofFormatError::BadMagic(magic));
+    }
+    let version = u16::from_le_bytes(reader.take_array()?);
+    if version != PROOF_VERSION {
+      return Err(ProofFormatError::UnsupportedVersion(version));
+    }
+    let [id] = reader.take_array()?;
+    let hash = HashAlgorithm::from_id(id).ok_or(ProofFormatError::UnknownHash(id))?;
+    let found = HashDigest {
+      h0: i128::from_le_bytes(reader.take_array()?),
+      h1: i128::from_le_bytes(reader.take_array()?),
+    };
+    if found != circuit_digest {
+      return Err(ProofFormatError::CircuitMismatch {
+        expected: circuit_digest,
+        found,
+      });
+    }
+    let layer_count = u64::from_le_bytes(reader.take_array()?);
+
+    let output = reader.section(0, SECTION_OUTPUT)?;
+    let mut layers = vec![];
+    for i in 0..layer_count {
+      // don't trust the count beyond what the input can hold
+      if reader.bytes.is_empty() {
+        return Err(ProofFormatError::Truncated);
+      }
+      layers.push(reader.section(i as usize + 1, SECTION_LAYER)?);
+    }
+    let (commitment, opening) = reader.section(layers.len() + 1, SECTION_OPENING)?;
+    let zk = match reader.bytes.first() {
+      Some(&SECTION_ZK) => Some(reader.section(layers.len() + 2, SECTION_ZK)?),
+      _ => None,
+    };
+    if !reader.bytes.is_empty() {
+      return Err(ProofFormatError::TrailingBytes(reader.bytes.len()));
+    }
+    Ok(Self {
+      hash,
+      output,
+      layers,
+      commitment,
+      opening,
+      zk,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use infrastructure::my_hash::{HashAlgorithm, HashDigest};
+  use prime_field::{FieldElement, MOD};
+
+  use super::{GkrProof, LayerProof, ProofFormatError};
+  use crate::polynomial::QuadraticPoly;
+
+  const DIGEST: HashDigest = HashDigest { h0: 1, h1: 2 };
+
+  fn sample_proof() -> GkrProof {
+    let x = |n| FieldElement::new(n, n + 1);
+    GkrProof {
+      hash: HashAlgorithm::Blake3,
+      output: vec![x(1), x(2)],
+      layers: vec![
+        LayerProof {
+          phase1: vec![QuadraticPoly::new(x(3), x(4), x(5))],
+          phase2: vec![QuadraticPoly::new(x(6), x(7), x(8))],
+          v_u: x(9),
+          v_v: x(10),
+        },
+        LayerProof::default(),
+      ],
+      commitment: HashDigest { h0: 11, h1: 12 },
+      zk: None,
+      ..GkrProof::default()
+    }
+  }
+
+  #[test]
+  fn encode_decode_roundtrip() {
+    let proof = sample_proof();
+    let bytes = proof.encode(DIGEST);
+    assert_eq!(
+      GkrProof::<FieldElement>::decode(&bytes, DIGEST).unwrap(),
+      proof
+    );
+  }
+
+  #[test]
+  fn malformed_input_is_rejected() {
+    let bytes = sample_proof().encode(DIGEST);
+
+    for len in 0..bytes.len() {
+      assert!(GkrProof::<FieldElement>::decode(&bytes[..len], DIGEST).is_err());
+    }
+
+    let mut bad_magic = bytes.clone();
+    bad_magic[0] ^= 1;
+    assert!(matches!(
+      GkrProof::<FieldElement>::decode(&bad_magic, DIGEST),
+      Err(ProofFormatError::BadMagic(_))
+    ));
+
+    let mut bad_version = bytes.clone();
+    bad_version[4] = 0xff;
+    assert!(matches!(
+      GkrProof::<FieldElement>::decode(&bad_version, DIGEST),
+      Err(ProofFormatError::UnsupportedVersion(_))
+    ));
+
+    let mut bad_hash = bytes.clone();
+    bad_hash[6] = 0xff;
+    assert!(matches!(
+      GkrProof::<FieldElement>::decode(&bad_hash, DIGEST),
+      Err(ProofFormatError::UnknownHash(0xff))
+    ));
+
+    assert!(matches!(
+      GkrProof::<FieldElement>::decode(&bytes, HashDigest::default()),
+      Err(ProofFormatError::CircuitMismatch { .. })
+    ));
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert!(matches!(
+      GkrProof::<FieldElement>::decode(&trailing, DIGEST),
+      Err(ProofFormatError::TrailingBytes(1))
+    ));
+
+    // the first output coordinate starts after the header (47 bytes), the
+    // section header (9 bytes) and the vector length (8 bytes)
+    let mut unreduced = bytes;
+    unreduced[64..72].copy_from_slice(&MOD.to_le_bytes());
+    assert!(matches!(
+      GkrProof::<FieldElement>::decode(&unreduced, DIGEST),
+      Err(ProofFormatError::MalformedSection { index: 0, .. })
+    ));
+  }
+}
@@ -1,46 +1,141 @@
-use crate::circuit_fast_track::LayeredCircuit;
+use crate::circuit_fast_track::{GateKind, LayeredCircuit, Wire};
+use crate::multilinear::{DenseMultilinear, EqPolynomial};
 use crate::polynomial::{LinearPoly, QuadraticPoly};
+use crate::proof::{committed_log_len, prefix_point, weigh_public_inputs, GkrProof, LayerProof};
+use crate::witness::{Witness, WitnessError};
+use crate::zk::LdeMask;
 
 use infrastructure::constants::SIZE;
+use infrastructure::transcript::Transcript;
 use poly_commitment::PolyCommitProver;
-use prime_field::FieldElement;
+use prime_field::{Field, FieldElement};
+use rayon::prelude::*;
 
 use std::borrow::Cow;
+use std::collections::BTreeSet;
 use std::mem::swap;
 use std::time::{self, SystemTime};
 
-pub fn from_string(s: &str) -> FieldElement {
-  let mut ret = FieldElement::from_real(0);
+mod zk;
 
-  for byte in s.bytes() {
-    let digit = byte - b'0';
-    ret = ret * FieldElement::from_real(10) + FieldElement::from_real(digit.into());
+/// Smallest number of gates a thread evaluates at once
+const EVALUATE_MIN_GATES: usize = 1 << 10;
+
+/// Number of gates or table entries a thread handles at once in the
+/// sumcheck. Partial results are combined in chunk order, which doesn't depend
+/// on the number of threads, so neither does the proof.
+const SUMCHECK_CHUNK: usize = 1 << 12;
+
+type Contribution<F> = (usize, F, F);
+
+/// Entry `index` of `add_mult_sum` and `add_v_array` in the sparse mode
+type SparseEntry<F> = (usize, LinearPoly<F>, LinearPoly<F>);
+
+/// How the prover stores `add_mult_sum` and `add_v_array` during the sumcheck
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SumcheckMode {
+  /// Full tables over the previous layer, folded entry by entry
+  #[default]
+  Dense,
+  /// Only the entries reached by a wire, sorted by index. Apart from the
+  /// values of the previous layer, which are needed for `V(r_u)` anyway, a
+  /// layer costs time linear in its number of wires rather than in the size
+  /// of the previous layer, which pays off on unbalanced circuits.
+  Sparse,
+}
+
+/// Adds the `(index, coefficient, constant)` contributions of every gate to
+/// the linear and constant parts of entry `index` of `mult` and `add`. The
+/// contributions are computed in parallel, then added in gate order.
+fn add_contributions<F: Field>(
+  gates: &[GateKind<F>],
+  mult: &mut [LinearPoly<F>],
+  add: &mut [LinearPoly<F>],
+  contributions: impl Fn(usize, &GateKind<F>, &mut Vec<Contribution<F>>) + Sync,
+) {
+  let chunks: Vec<Vec<Contribution<F>>> = gates
+    .par_chunks(SUMCHECK_CHUNK)
+    .enumerate()
+    .map(|(c, chunk)| {
+      let mut out = Vec::with_capacity(chunk.len());
+      for (i, gate) in chunk.iter().enumerate() {
+        contributions(c * SUMCHECK_CHUNK + i, gate, &mut out);
+      }
+      out
+    })
+    .collect();
+  for (index, coefficient, constant) in chunks.into_iter().flatten() {
+    mult[index].b = mult[index].b + coefficient;
+    add[index].b = add[index].b + constant;
   }
+}
+
+/// Computes the `(index, coefficient, constant)` contribution of every wire,
+/// in parallel, and adds up the ones to the same index. The wires must be
+/// sorted by index.
+fn sparse_contributions<F: Field>(
+  wires: &[Wire<F>],
+  contribution: impl Fn(&Wire<F>) -> Option<Contribution<F>> + Sync,
+) -> Vec<SparseEntry<F>> {
+  let contributions: Vec<Option<Contribution<F>>> = wires
+    .par_iter()
+    .with_min_len(SUMCHECK_CHUNK)
+    .map(&contribution)
+    .collect();
+  let mut table: Vec<SparseEntry<F>> = Vec::with_capacity(contributions.len());
+  for (index, coefficient, constant) in contributions.into_iter().flatten() {
+    match table.last_mut() {
+      Some((last, mult, add)) if *last == index => {
+        mult.b = mult.b + coefficient;
+        add.b = add.b + constant;
+      }
+      _ => table.push((
+        index,
+        LinearPoly::maps(coefficient),
+        LinearPoly::maps(constant),
+      )),
+    }
+  }
+  table
+}
 
-  ret
+pub fn from_string<F: Field>(s: &str) -> F {
+  from_string_radix(s, 10).expect("not a decimal number")
+}
+
+/// Parses a number of any size written in base `radix`, reduced modulo the
+/// field prime. Returns `None` on an empty string or an invalid digit.
+pub fn from_string_radix<F: Field>(s: &str, radix: u32) -> Option<F> {
+  if s.is_empty() {
+    return None;
+  }
+  let base = F::from_u64(radix.into());
+  s.chars().try_fold(F::from_u64(0), |ret, c| {
+    let digit = c.to_digit(radix)?;
+    Some(ret * base + F::from_u64(digit.into()))
+  })
 }
 
 #[derive(Default, Debug, Clone)]
-pub struct ProverContext {
-  pub inv_2: FieldElement,
-  pub v_mult_add_new: Vec<LinearPoly>,
-  pub add_v_array_new: Vec<LinearPoly>,
-  pub add_mult_sum_new: Vec<LinearPoly>,
-  pub gate_meet: Vec<bool>,
-  pub rets_prev: Vec<QuadraticPoly>,
-  pub rets_cur: Vec<QuadraticPoly>,
+pub struct ProverContext<F = FieldElement> {
+  pub inv_2: F,
+  pub v_mult_add_new: Vec<LinearPoly<F>>,
+  pub add_v_array_new: Vec<LinearPoly<F>>,
+  pub add_mult_sum_new: Vec<LinearPoly<F>>,
+  pub gate_meet: BTreeSet<usize>,
+  pub sparse_tables_new: Vec<SparseEntry<F>>,
 }
 #[derive(Default, Debug, Clone)]
-pub struct ZkProver {
-  pub aritmetic_circuit: LayeredCircuit,
-  pub poly_prover: PolyCommitProver,
+pub struct ZkProver<F = FieldElement> {
+  pub aritmetic_circuit: LayeredCircuit<F>,
+  pub poly_prover: PolyCommitProver<F>,
   /** @name Basic
   	* Basic information and variables about the arithmetic circuit*/
   //< two random gates v_u and v_v queried by V in each layer    v_u: FieldElement,
-  pub v_v: FieldElement,
-  pub v_u: FieldElement,
+  pub v_v: F,
+  pub v_u: F,
   pub total_uv: usize,
-  pub circuit_value: Vec<Vec<FieldElement>>,
+  pub circuit_value: Vec<Vec<F>>,
   sumcheck_layer_id: usize,
   length_g: usize,
   length_u: usize,
@@ -48,34 +143,43 @@ pub struct ZkProver {
 
   /** @name Randomness
   	* Some randomness or values during the proof phase. */
-  alpha: FieldElement,
-  beta: FieldElement,
+  alpha: F,
+  beta: F,
 
   //< c++ code: const prime_field::field_element *r_0, *r_1; How to deal with "const"
-  r_0: Vec<FieldElement>,
-  r_1: Vec<FieldElement>,
-  one_minus_r_0: Vec<FieldElement>,
-  one_minus_r_1: Vec<FieldElement>,
-
-  pub add_v_array: Vec<LinearPoly>,
-  pub v_mult_add0: Vec<LinearPoly>,
-  pub beta_g_r0_fhalf: Vec<FieldElement>,
-  beta_g_r0_shalf: Vec<FieldElement>,
-  beta_g_r1_fhalf: Vec<FieldElement>,
-  beta_g_r1_shalf: Vec<FieldElement>,
-  beta_u_fhalf: Vec<FieldElement>,
-  beta_u_shalf: Vec<FieldElement>,
-  /*beta_u: Vec<FieldElement>,
-  beta_v: Vec<FieldElement>,
-  beta_g: Vec<FieldElement>,*/ //Variables never used
-  pub add_mult_sum: Vec<LinearPoly>,
+  r_0: Vec<F>,
+  r_1: Vec<F>,
+
+  pub add_v_array: Vec<LinearPoly<F>>,
+  pub v_mult_add0: Vec<LinearPoly<F>>,
+  pub beta_g_r0_fhalf: Vec<F>,
+  beta_g_r0_shalf: Vec<F>,
+  beta_g_r1_fhalf: Vec<F>,
+  beta_g_r1_shalf: Vec<F>,
+  beta_u_fhalf: Vec<F>,
+  beta_u_shalf: Vec<F>,
+  /*beta_u: Vec<F>,
+  beta_v: Vec<F>,
+  beta_g: Vec<F>,*/ //Variables never used
+  pub add_mult_sum: Vec<LinearPoly<F>>,
+  /// `add_mult_sum` and `add_v_array` in the sparse mode
+  sparse_tables: Vec<SparseEntry<F>>,
+  pub mode: SumcheckMode,
+  /// Whether `prove` hides the witness, see `prove_zero_knowledge`
+  pub zero_knowledge: bool,
+  /// Mask of the randomized extension of the layer below the one being
+  /// proved, zero outside of the zero knowledge mode
+  lde_mask: LdeMask<F>,
+  /// Number of leading input gates holding public inputs, which the
+  /// verifier folds in itself rather than reading them from the commitment
+  public_input_count: usize,
 
   pub total_time: f64,
 
-  pub ctx: ProverContext,
+  pub ctx: ProverContext<F>,
 }
 
-impl ZkProver {
+impl<F: Field> ZkProver<F> {
   pub fn new() -> Self {
     Self {
       circuit_value: Vec::with_capacity(SIZE),
@@ -83,24 +187,22 @@ impl ZkProver {
     }
   }
 
-  pub fn init_array(&mut self, max_bit_length: usize, aritmetic_circuit: &LayeredCircuit) {
+  pub fn init_array(&mut self, max_bit_length: usize, aritmetic_circuit: &LayeredCircuit<F>) {
     self.total_time = 0.0;
 
     let half_length = (max_bit_length >> 1) + 1;
 
-    self.ctx.gate_meet = vec![false; 15];
+    self.ctx.gate_meet.clear();
     self.ctx.v_mult_add_new = vec![LinearPoly::zero(); 1 << max_bit_length];
     self.ctx.add_v_array_new = vec![LinearPoly::zero(); 1 << max_bit_length];
     self.ctx.add_mult_sum_new = vec![LinearPoly::zero(); 1 << max_bit_length];
-    self.ctx.rets_prev = vec![QuadraticPoly::zero(); 1 << max_bit_length];
-    self.ctx.rets_cur = vec![QuadraticPoly::zero(); 1 << max_bit_length];
-
-    self.beta_g_r0_fhalf = vec![FieldElement::zero(); 1 << half_length];
-    self.beta_g_r0_shalf = vec![FieldElement::zero(); 1 << half_length];
-    self.beta_g_r1_fhalf = vec![FieldElement::zero(); 1 << half_length];
-    self.beta_g_r1_shalf = vec![FieldElement::zero(); 1 << half_length];
-    self.beta_u_fhalf = vec![FieldElement::zero(); 1 << half_length];
-    self.beta_u_shalf = vec![FieldElement::zero(); 1 << half_length];
+
+    self.beta_g_r0_fhalf = vec![F::zero(); 1 << half_length];
+    self.beta_g_r0_shalf = vec![F::zero(); 1 << half_length];
+    self.beta_g_r1_fhalf = vec![F::zero(); 1 << half_length];
+    self.beta_g_r1_shalf = vec![F::zero(); 1 << half_length];
+    self.beta_u_fhalf = vec![F::zero(); 1 << half_length];
+    self.beta_u_shalf = vec![F::zero(); 1 << half_length];
     self.add_mult_sum = vec![LinearPoly::zero(); 1 << max_bit_length];
     self.v_mult_add0 = vec![LinearPoly::zero(); 1 << max_bit_length];
     self.add_v_array = vec![LinearPoly::zero(); 1 << max_bit_length];
@@ -108,124 +210,59 @@ impl ZkProver {
     self.get_circuit(aritmetic_circuit);
   }
 
-  pub fn get_circuit(&mut self, from_verifier: &LayeredCircuit) {
+  pub fn get_circuit(&mut self, from_verifier: &LayeredCircuit<F>) {
     self.aritmetic_circuit = from_verifier.clone();
+    if self.mode == SumcheckMode::Sparse {
+      self.aritmetic_circuit.index_wires();
+    }
 
-    self.ctx.inv_2 = FieldElement::from_real(2);
+    self.ctx.inv_2 = F::from_u64(2);
   }
 
-  pub fn v_res(
-    &mut self,
-    one_minus_r_0: Vec<FieldElement>,
-    r_0: Vec<FieldElement>,
-    output_raw: Vec<FieldElement>,
-    r_0_size: usize,
-    output_size: usize,
-  ) -> FieldElement {
+  /// Switches the way the sumcheck tables are stored, indexing the wires of
+  /// the circuit for the sparse mode. Both modes produce the same proofs.
+  pub fn set_mode(&mut self, mode: SumcheckMode) {
+    self.mode = mode;
+    self.get_circuit(&self.aritmetic_circuit.clone());
+  }
+
+  /// Multilinear extension of the first `1 << r_0.len()` entries of `output`
+  /// at `r_0`
+  pub fn v_res(&mut self, r_0: &[F], output: &[F]) -> F {
     let t0 = time::Instant::now();
-    let mut outputsize = output_size;
-    let mut output = vec![FieldElement::zero(); outputsize];
-    for i in 0..output_size {
-      output.push(output_raw[i]);
-    }
-    for i in 0..r_0_size {
-      for j in 0..(outputsize >> 1) {
-        output[j] = output[j << 1] * one_minus_r_0[i] + output[j << 1 | 1] * r_0[i];
-      }
-      outputsize >>= 1;
-    }
+    let res = DenseMultilinear::evaluate_table(&output[..1 << r_0.len()], r_0);
     let time_span = t0.elapsed();
     self.total_time += time_span.as_secs_f64();
-    let res = output[0];
     res
   }
 
-  pub fn evaluate(&mut self) -> Vec<FieldElement> {
+  pub fn evaluate(&mut self) -> Vec<F> {
     let t0 = time::Instant::now();
 
     // Below code was commented in the original repo, here we need it
-    self.circuit_value.push(vec![
-      FieldElement::zero();
-      1 << self.aritmetic_circuit.circuit[0].bit_length
-    ]);
-    for i in 0..(1 << self.aritmetic_circuit.circuit[0].bit_length) {
-      let g = i;
-      //todo: Could delete below variable, never used
-      //let u = self.aritmetic_circuit.circuit[0].gates[g].u;
-      let ty = self.aritmetic_circuit.circuit[0].gates[g].ty;
-      assert!(ty == 3 || ty == 2);
+    // Keep the input layer if a witness was loaded, otherwise take it from the circuit
+    self.circuit_value.truncate(1);
+    if self.circuit_value.is_empty() {
+      for gate in &self.aritmetic_circuit.circuit[0].gates {
+        assert!(matches!(gate, GateKind::Constant { .. } | GateKind::Zero));
+      }
+      self
+        .circuit_value
+        .push(self.aritmetic_circuit.input_values());
     }
     assert!(self.aritmetic_circuit.total_depth < 1000000);
 
+    // gates of a layer only read the previous one, so they are evaluated in
+    // parallel, rayon balancing the fan-in gates across threads
     for i in 1..(self.aritmetic_circuit.total_depth) {
-      self.circuit_value.push(vec![
-        FieldElement::zero();
-        1 << self.aritmetic_circuit.circuit[i].bit_length
-      ]);
-      for j in 0..self.aritmetic_circuit.circuit[i].bit_length {
-        let g = j;
-        let ty: usize = self.aritmetic_circuit.circuit[i].gates[g].ty;
-        let u = self.aritmetic_circuit.circuit[i].gates[g].u;
-        let v = self.aritmetic_circuit.circuit[i].gates[g].v;
-
-        if ty == 0 {
-          self.circuit_value[i][g] = self.circuit_value[i - 1][u] + self.circuit_value[i - 1][v];
-        } else if ty == 1 {
-          assert!(u < (1 << self.aritmetic_circuit.circuit[i - 1].bit_length));
-          assert!(v < (1 << self.aritmetic_circuit.circuit[i - 1].bit_length));
-          self.circuit_value[i][g] = self.circuit_value[i - 1][u] * self.circuit_value[i - 1][v];
-        } else if ty == 2 {
-          self.circuit_value[i][g] = FieldElement::from_real(0);
-        } else if ty == 3 {
-          // It suppose to be input gate, it just read the 'u' input, what about 'v' input
-          self.circuit_value[i][g] = FieldElement::from_real(u);
-        } else if ty == 4 {
-          self.circuit_value[i][g] = self.circuit_value[i - 1][u];
-        } else if ty == 5 {
-          self.circuit_value[i][g] = FieldElement::from_real(0);
-          for k in u..v {
-            self.circuit_value[i][g] = self.circuit_value[i][g] + self.circuit_value[i - 1][k];
-          }
-        } else if ty == 6 {
-          self.circuit_value[i][g] = FieldElement::from_real(1) - self.circuit_value[i - 1][u];
-        } else if ty == 7 {
-          self.circuit_value[i][g] = self.circuit_value[i - 1][u] - self.circuit_value[i - 1][v];
-        } else if ty == 8 {
-          let x = self.circuit_value[i - 1][u];
-          let y = self.circuit_value[i - 1][v];
-          self.circuit_value[i][g] = x + y - FieldElement::from_real(2) * x * y;
-        } else if ty == 9 {
-          assert!(u < (1 << self.aritmetic_circuit.circuit[i - 1].bit_length));
-          assert!(v < (1 << self.aritmetic_circuit.circuit[i - 1].bit_length));
-          let x = self.circuit_value[i - 1][u];
-          let y = self.circuit_value[i - 1][v];
-          self.circuit_value[i][g] = y - x * y;
-        } else if ty == 10 {
-          self.circuit_value[i][g] = self.circuit_value[i - 1][u];
-        } else if ty == 12 {
-          self.circuit_value[i][g] = FieldElement::from_real(0);
-          assert!(v - u + 1 <= 60);
-          for k in u..=v {
-            self.circuit_value[i][g] = self.circuit_value[i][g]
-              + self.circuit_value[i - 1][k] * FieldElement::from_real(1 << (k - u));
-          }
-        } else if ty == 13 {
-          assert!(u == v);
-          assert!(u < (1 << self.aritmetic_circuit.circuit[i - 1].bit_length),);
-          self.circuit_value[i][g] = self.circuit_value[i - 1][u]
-            * (FieldElement::from_real(1) - self.circuit_value[i - 1][v]);
-        } else if ty == 14 {
-          self.circuit_value[i][g] = FieldElement::from_real(0);
-          for k in 0..self.aritmetic_circuit.circuit[i].gates[g].parameter_length {
-            let weight = self.aritmetic_circuit.circuit[i].gates[g].weight[k];
-            let idx = self.aritmetic_circuit.circuit[i].gates[g].src[k];
-            self.circuit_value[i][g] =
-              self.circuit_value[i][g] + self.circuit_value[i - 1][idx] * weight;
-          }
-        } else {
-          assert!(false);
-        }
-      }
+      let layer = &self.aritmetic_circuit.circuit[i];
+      let prev = &self.circuit_value[i - 1];
+      let values = layer.gates[..1 << layer.bit_length]
+        .par_iter()
+        .with_min_len(EVALUATE_MIN_GATES)
+        .map(|gate| gate.evaluate(prev))
+        .collect();
+      self.circuit_value.push(values);
     }
 
     let time_span = t0.elapsed();
@@ -237,13 +274,40 @@ impl ZkProver {
     self.circuit_value[self.aritmetic_circuit.total_depth - 1].clone()
   }
 
-  pub fn get_witness(&mut self, inputs: Vec<FieldElement>, _n: u32) {
-    // Do we really need this line of code?
-    //self.circuit_value[0] =
-    // Vec::with_capacity(1 << self.aritmetic_circuit.circuit[0].bit_length);
-    self.circuit_value[0] = inputs.clone();
-    // todo()
-    //self.circuit_value[0] = inputs[..n].to_vec();
+  /// Loads the input layer, padding it with zeros up to the layer size
+  pub fn get_witness(&mut self, inputs: Vec<F>) {
+    let mut input_layer = inputs;
+    input_layer.resize(1 << self.aritmetic_circuit.circuit[0].bit_length, F::zero());
+    self.circuit_value = vec![input_layer];
+    self.public_input_count = 0;
+  }
+
+  /// Loads `witness` into the input gates, the public values first, failing
+  /// when their number differs from the number of input gates of the circuit
+  pub fn load_witness(&mut self, witness: &Witness<F>) -> Result<(), WitnessError> {
+    let expected = self.aritmetic_circuit.input_count;
+    if witness.len() != expected {
+      return Err(WitnessError::CountMismatch {
+        expected,
+        found: witness.len(),
+      });
+    }
+    self.get_witness(witness.values().collect());
+    self.public_input_count = witness.public.len();
+    Ok(())
+  }
+
+  /// Public inputs of the loaded witness
+  pub fn public_inputs(&self) -> &[F] {
+    &self.circuit_value[0][..self.public_input_count]
+  }
+
+  /// Input layer with the public inputs set to zero, the part of it the
+  /// prover commits to
+  fn private_input(&self) -> Vec<F> {
+    let mut input = self.circuit_value[0].clone();
+    input[..self.public_input_count].fill(F::zero());
+    input
   }
 
   pub fn sumcheck_init(
@@ -252,12 +316,10 @@ impl ZkProver {
     length_g: usize,
     length_u: usize,
     length_v: usize,
-    alpha: FieldElement,
-    beta: FieldElement,
-    r_0: Vec<FieldElement>,
-    r_1: Vec<FieldElement>,
-    one_minus_r_0: Vec<FieldElement>,
-    one_minus_r_1: Vec<FieldElement>,
+    alpha: F,
+    beta: F,
+    r_0: Vec<F>,
+    r_1: Vec<F>,
   ) {
     self.r_0 = r_0;
     self.r_1 = r_1;
@@ -267,314 +329,178 @@ impl ZkProver {
     self.length_g = length_g;
     self.length_u = length_u;
     self.length_v = length_v;
-    self.one_minus_r_0 = one_minus_r_0;
-    self.one_minus_r_1 = one_minus_r_1;
   }
   pub fn total_time(&mut self, val: f64) {
     self.total_time = val;
   }
 
-  pub fn sumcheck_phase1_init(&mut self) {
-    let t0 = time::Instant::now();
-    self.total_uv = 1 << self.aritmetic_circuit.circuit[self.sumcheck_layer_id - 1].bit_length;
-    let zero = FieldElement::zero();
-    for i in 0..self.total_uv {
-      //todo! linear_poly != FieldElement
-      let x = LinearPoly::maps(self.circuit_value[self.sumcheck_layer_id - 1][i]);
-      self.v_mult_add0[i] = x;
-
-      //self.v_mult_add[i] = self.circuit_value[self.sumcheck_layer_id - 1][i];
-      self.add_v_array[i].a = zero;
-      self.add_v_array[i].b = zero;
-      self.add_mult_sum[i].a = zero;
-      self.add_mult_sum[i].b = zero;
+  /// Loads the values of the layer below the one being proved into
+  /// `v_mult_add0` and clears `add_mult_sum` and `add_v_array`
+  fn reset_tables(&mut self) {
+    let n = self.total_uv;
+    let prev = &self.circuit_value[self.sumcheck_layer_id - 1][..n];
+    self.v_mult_add0[..n]
+      .par_iter_mut()
+      .zip(prev.par_iter())
+      .with_min_len(SUMCHECK_CHUNK)
+      .for_each(|(entry, value)| *entry = LinearPoly::maps(*value));
+    if self.mode == SumcheckMode::Sparse {
+      self.sparse_tables.clear();
+      return;
     }
+    for table in [&mut self.add_mult_sum, &mut self.add_v_array] {
+      table[..n]
+        .par_iter_mut()
+        .with_min_len(SUMCHECK_CHUNK)
+        .for_each(|entry| *entry = LinearPoly::zero());
+    }
+  }
 
-    self.beta_g_r0_fhalf[0] = self.alpha;
-    self.beta_g_r1_fhalf[0] = self.beta;
-    self.beta_g_r0_shalf[0] = FieldElement::real_one();
-    self.beta_g_r1_shalf[0] = FieldElement::real_one();
-
-    let first_half = self.length_g >> 1;
-    let _second_half = self.length_g - first_half;
-
-    for i in 0..first_half {
-      for j in 0..1 << i {
-        self.beta_g_r0_fhalf[j | (1 << i)] = self.beta_g_r0_fhalf[j] * self.r_0[i];
-        self.beta_g_r0_fhalf[j] = self.beta_g_r0_fhalf[j] * self.one_minus_r_0[i];
-        self.beta_g_r1_fhalf[j | (1 << i)] = self.beta_g_r1_fhalf[j] * self.r_1[i];
-        self.beta_g_r1_fhalf[j] = self.beta_g_r1_fhalf[j] * self.one_minus_r_1[i];
+  /// Binds the variable of the previous round to `previous_random`, halving
+  /// the tables, unless `current_bit` is the first one, and returns the
+  /// message of the current round
+  fn sumcheck_fold(&mut self, previous_random: F, current_bit: usize) -> QuadraticPoly<F> {
+    let half = self.total_uv >> 1;
+    let at = |entry: &LinearPoly<F>| {
+      if current_bit == 0 {
+        entry.b
+      } else {
+        entry.eval(previous_random)
       }
+    };
+    let tables = [
+      (&mut self.v_mult_add0, &mut self.ctx.v_mult_add_new),
+      (&mut self.add_v_array, &mut self.ctx.add_v_array_new),
+      (&mut self.add_mult_sum, &mut self.ctx.add_mult_sum_new),
+    ];
+    let dense_tables = match self.mode {
+      SumcheckMode::Dense => tables.len(),
+      SumcheckMode::Sparse => 1,
+    };
+    for (table, folded) in tables.into_iter().take(dense_tables) {
+      folded[..half]
+        .par_iter_mut()
+        .enumerate()
+        .with_min_len(SUMCHECK_CHUNK)
+        .for_each(|(i, entry)| {
+          let zero = at(&table[i << 1]);
+          *entry = LinearPoly::new(at(&table[i << 1 | 1]) - zero, zero);
+        });
+      swap(table, folded);
+    }
+    if self.mode == SumcheckMode::Sparse {
+      return self.sparse_fold(at);
     }
 
-    let mask_fhalf = (1 << first_half) - 1;
-
-    let mut intermediates0 = vec![FieldElement::zero(); 1 << self.length_g];
-    //let mut ptr_lock = &vec![FieldElement::zero(); 1 << self.length_g];
-
-    //let ptr_raw = UnsafeSendSyncRawPtr(&mut ptr_lock as *const _ as *mut Vec<FieldElement>);
-
-    let mut intermediates1 = vec![FieldElement::zero(); 1 << self.length_g];
-
-    //todo
-    //	#pragma omp parallel for
-    //let intermediates0 = unsafe { &mut *(*ptr_raw).cast::<Vec<FieldElement>>() };
-    //let intermediates1 = unsafe { &mut *(*ptr_raw).cast::<Vec<FieldElement>>() };
-
-    //intermediates0._iter_mut().for_each(|element| {
-    for i in 0..(1 << self.length_g) {
-      let u = self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].u;
-      let v = self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].v;
+    // sum of add_mult_sum * v_mult_add0 + add_v_array over the table
+    let partial_sums: Vec<QuadraticPoly<F>> = self.add_mult_sum[..half]
+      .par_chunks(SUMCHECK_CHUNK)
+      .zip(self.v_mult_add0[..half].par_chunks(SUMCHECK_CHUNK))
+      .zip(self.add_v_array[..half].par_chunks(SUMCHECK_CHUNK))
+      .map(|((mult, value), add)| {
+        mult
+          .iter()
+          .zip(value)
+          .zip(add)
+          .fold(QuadraticPoly::zero(), |ret, ((m, v), add)| {
+            ret + QuadraticPoly::new(m.a * v.a, m.a * v.b + m.b * v.a + add.a, m.b * v.b + add.b)
+          })
+      })
+      .collect();
+    partial_sums
+      .into_iter()
+      .fold(QuadraticPoly::zero(), |ret, partial| ret + partial)
+  }
 
-      match self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty {
-        0 => {
-          //add gate
-          let tmp = self.beta_g_r0_fhalf[i & mask_fhalf] * self.beta_g_r0_shalf[i >> first_half]
-            + self.beta_g_r1_fhalf[i & mask_fhalf] * self.beta_g_r1_shalf[i >> first_half];
-          //intermediates0[i] = self.circuit_value[self.sumcheck_layer_id - 1][v] * tmp;
-          intermediates0[i] = self.circuit_value[self.sumcheck_layer_id - 1][v] * tmp;
-          intermediates1[i] = tmp;
-        }
-        1 => {
-          //mult gate
-          let tmp = self.beta_g_r0_fhalf[i & mask_fhalf] * self.beta_g_r0_shalf[i >> first_half]
-            + self.beta_g_r1_fhalf[i & mask_fhalf] * self.beta_g_r1_shalf[i >> first_half];
-          intermediates0[i] = self.circuit_value[self.sumcheck_layer_id - 1][v] * tmp;
-        }
-        2 => {}
-        5 => {
-          //sum gate
-          let tmp = self.beta_g_r0_fhalf[i & mask_fhalf] * self.beta_g_r0_shalf[i >> first_half]
-            + self.beta_g_r1_fhalf[i & mask_fhalf] * self.beta_g_r1_shalf[i >> first_half];
-          intermediates1[i] = tmp;
-        }
-        12 => {
-          //exp sum gate
-          let tmp = self.beta_g_r0_fhalf[i & mask_fhalf] * self.beta_g_r0_shalf[i >> first_half]
-            + self.beta_g_r1_fhalf[i & mask_fhalf] * self.beta_g_r1_shalf[i >> first_half];
-          intermediates1[i] = tmp;
-        }
-        4 => {
-          //direct relay gate
-          let tmp = self.beta_g_r0_fhalf[u & mask_fhalf] * self.beta_g_r0_shalf[u >> first_half]
-            + self.beta_g_r1_fhalf[u & mask_fhalf] * self.beta_g_r1_shalf[u >> first_half];
-          intermediates1[i] = tmp;
-        }
-        6 => {
-          //NOT gate
-          let tmp = self.beta_g_r0_fhalf[i & mask_fhalf] * self.beta_g_r0_shalf[i >> first_half]
-            + self.beta_g_r1_fhalf[i & mask_fhalf] * self.beta_g_r1_shalf[i >> first_half];
-          intermediates1[i] = tmp;
-        }
-        7 => {
-          //minus gate
-          let tmp = self.beta_g_r0_fhalf[i & mask_fhalf] * self.beta_g_r0_shalf[i >> first_half]
-            + self.beta_g_r1_fhalf[i & mask_fhalf] * self.beta_g_r1_shalf[i >> first_half];
-          intermediates0[i] = self.circuit_value[self.sumcheck_layer_id - 1][v] * tmp;
-          intermediates1[i] = tmp;
-        }
-        8 => {
-          //XOR gate
-          let tmp = self.beta_g_r0_fhalf[i & mask_fhalf] * self.beta_g_r0_shalf[i >> first_half]
-            + self.beta_g_r1_fhalf[i & mask_fhalf] * self.beta_g_r1_shalf[i >> first_half];
-          let tmp_v = tmp * self.circuit_value[self.sumcheck_layer_id - 1][v];
-          //let _tmp_2_v = tmp_v + tmp_v; // Never used
-          intermediates0[i] = tmp_v;
-          intermediates1[i] = tmp;
-        }
-        13 => {
-          //bit-test gate
-          let tmp = self.beta_g_r0_fhalf[i & mask_fhalf] * self.beta_g_r0_shalf[i >> first_half]
-            + self.beta_g_r1_fhalf[i & mask_fhalf] * self.beta_g_r1_shalf[i >> first_half];
-          let tmp_v = tmp * self.circuit_value[self.sumcheck_layer_id - 1][v];
-          intermediates0[i] = tmp_v;
-          intermediates1[i] = tmp;
-        }
-        9 => {
-          //NAAB gate
-          let tmp = self.beta_g_r0_fhalf[i & mask_fhalf] * self.beta_g_r0_shalf[i >> first_half]
-            + self.beta_g_r1_fhalf[i & mask_fhalf] * self.beta_g_r1_shalf[i >> first_half];
-          let tmp_v = tmp * self.circuit_value[self.sumcheck_layer_id - 1][v];
-          intermediates1[i] = tmp_v;
-        }
-        10 => {
-          //relay gate
-          let tmp = self.beta_g_r0_fhalf[i & mask_fhalf] * self.beta_g_r0_shalf[i >> first_half]
-            + self.beta_g_r1_fhalf[i & mask_fhalf] * self.beta_g_r1_shalf[i >> first_half];
-          intermediates0[i] = tmp;
-        }
-        14 => {
-          //custom comb
-          let tmp = self.beta_g_r0_fhalf[i & mask_fhalf] * self.beta_g_r0_shalf[i >> first_half]
-            + self.beta_g_r1_fhalf[i & mask_fhalf] * self.beta_g_r1_shalf[i >> first_half];
-          intermediates1[i] = tmp;
-        }
-        _ => {
-          println!(
-            "Warning Unknown gate {}",
-            self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty
-          )
+  /// Folds `sparse_tables` like `sumcheck_fold` does the dense tables, once
+  /// `v_mult_add0` is folded. Entries missing from the table are zero, so
+  /// they add nothing to the message.
+  fn sparse_fold(&mut self, at: impl Fn(&LinearPoly<F>) -> F) -> QuadraticPoly<F> {
+    let folded = &mut self.ctx.sparse_tables_new;
+    folded.clear();
+    let mut ret = QuadraticPoly::zero();
+    let mut entries = self.sparse_tables.iter().peekable();
+    while let Some((index, mult, add)) = entries.next() {
+      let index = *index;
+      let (mut mult_zero, mut add_zero) = (F::zero(), F::zero());
+      let (mut mult_one, mut add_one) = (F::zero(), F::zero());
+      if index & 1 == 0 {
+        (mult_zero, add_zero) = (at(mult), at(add));
+        if let Some((_, mult, add)) = entries.next_if(|next| next.0 == index | 1) {
+          (mult_one, add_one) = (at(mult), at(add));
         }
+      } else {
+        (mult_one, add_one) = (at(mult), at(add));
       }
+      let mult = LinearPoly::new(mult_one - mult_zero, mult_zero);
+      let add = LinearPoly::new(add_one - add_zero, add_zero);
+      let v = &self.v_mult_add0[index >> 1];
+      ret = ret
+        + QuadraticPoly::new(
+          mult.a * v.a,
+          mult.a * v.b + mult.b * v.a + add.a,
+          mult.b * v.b + add.b,
+        );
+      folded.push((index >> 1, mult, add));
     }
+    swap(&mut self.sparse_tables, folded);
+    ret
+  }
 
-    for i in 0..1 << self.length_g {
-      let u = self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].u;
-      let v = self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].v;
-
-      match self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty {
-        0 => {
-          //add gate
-          if !self.ctx.gate_meet[self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty]
-          {
-            //prletf("first meet %d gate\n", C -> circuit[self.sumcheck_layer_id].gates[i].ty);
-            self.ctx.gate_meet
-              [self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty] = true;
-          }
-          self.add_v_array[u].b = self.add_v_array[u].b + intermediates0[i];
-          self.add_mult_sum[u].b = self.add_mult_sum[u].b + intermediates1[i];
-        }
-        2 => {}
-        1 => {
-          //mult gate
-          if !self.ctx.gate_meet[self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty]
-          {
-            //prletf("first meet %d gate\n", C -> circuit[self.sumcheck_layer_id].gates[i].ty);
-            self.ctx.gate_meet
-              [self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty] = true;
-          }
-          self.add_mult_sum[u].b = (self.add_mult_sum[u].b + intermediates0[i]);
-        }
-        5 => {
-          //sum gate
-          if !self.ctx.gate_meet[self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty]
-          {
-            //prletf("first meet %d gate\n", C -> circuit[self.sumcheck_layer_id].gates[i].ty);
-            self.ctx.gate_meet
-              [self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty] = true;
-          }
-          for j in u..v {
-            self.add_mult_sum[j].b = self.add_mult_sum[j].b + intermediates1[i];
-          }
-        }
+  pub fn sumcheck_phase1_init(&mut self) {
+    let t0 = time::Instant::now();
+    self.total_uv = 1 << self.aritmetic_circuit.circuit[self.sumcheck_layer_id - 1].bit_length;
+    self.reset_tables();
 
-        12 =>
-        //exp sum gate
-        {
-          if !self.ctx.gate_meet[self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty]
-          {
-            //prletf("first meet %d gate\n", C -> circuit[self.sumcheck_layer_id].gates[i].ty);
-            self.ctx.gate_meet
-              [self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty] = true;
-          }
-          let mut tmp = intermediates1[i];
-          for j in u..v {
-            self.add_mult_sum[j].b = self.add_mult_sum[j].b + tmp;
-            tmp = tmp + tmp;
-          }
-        }
-        14 => {
-          if !self.ctx.gate_meet[self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty]
-          {
-            //prletf("first meet %d gate\n", C -> circuit[self.sumcheck_layer_id].gates[i].ty);
-            self.ctx.gate_meet
-              [self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty] = true;
-          }
-          let tmp = intermediates1[i];
-          for j in
-            0..self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].parameter_length
-          {
-            let src = self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].src[j];
-            let weight = self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].weight[j];
-            self.add_mult_sum[src].b = self.add_mult_sum[src].b + weight * tmp;
-          }
-        }
-        4 =>
-        //direct relay gate
-        {
-          if !self.ctx.gate_meet[self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty]
-          {
-            //prletf("first meet %d gate\n", C -> circuit[self.sumcheck_layer_id].gates[i].ty);
-            self.ctx.gate_meet
-              [self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty] = true;
-          }
-          self.add_mult_sum[u].b = self.add_mult_sum[u].b + intermediates1[i];
-        }
-        6 =>
-        //NOT gate
-        {
-          if !self.ctx.gate_meet[self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty]
-          {
-            //prletf("first meet %d gate\n", C -> circuit[self.sumcheck_layer_id].gates[i].ty);
-            self.ctx.gate_meet
-              [self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty] = true;
-          }
-          self.add_mult_sum[u].b = self.add_mult_sum[u].b - intermediates1[i];
-          self.add_v_array[u].b = self.add_v_array[u].b + intermediates1[i];
-        }
-        7 =>
-        //minus gate
-        {
-          if !self.ctx.gate_meet[self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty]
-          {
-            //prletf("first meet %d gate\n", C -> circuit[self.sumcheck_layer_id].gates[i].ty);
-            self.ctx.gate_meet
-              [self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty] = true;
-          }
-          self.add_v_array[u].b = self.add_v_array[u].b - (intermediates0[i]);
-          self.add_mult_sum[u].b = self.add_mult_sum[u].b + intermediates1[i];
-        }
-        8 =>
-        //XOR gate
-        {
-          if !self.ctx.gate_meet[self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty]
-          {
-            //prletf("first meet %d gate\n", C -> circuit[self.sumcheck_layer_id].gates[i].ty);
-            self.ctx.gate_meet
-              [self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty] = true;
-          }
-          self.add_v_array[u].b = self.add_v_array[u].b + intermediates0[i];
-          self.add_mult_sum[u].b =
-            self.add_mult_sum[u].b + intermediates1[i] - intermediates0[i] - intermediates0[i];
-        }
-        13 =>
-        //bit-test gate
-        {
-          if !self.ctx.gate_meet[self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty]
-          {
-            //prletf("first meet %d gate\n", C -> circuit[self.sumcheck_layer_id].gates[i].ty);
-            self.ctx.gate_meet
-              [self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty] = true;
-          }
-          self.add_mult_sum[u].b = self.add_mult_sum[u].b - intermediates0[i] + intermediates1[i];
-        }
-        9 =>
-        //NAAB gate
-        {
-          if !self.ctx.gate_meet[self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty]
-          {
-            //prletf("first meet %d gate\n", C -> circuit[self.sumcheck_layer_id].gates[i].ty);
-            self.ctx.gate_meet
-              [self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty] = true;
-          }
-          self.add_v_array[u].b = self.add_v_array[u].b + intermediates1[i];
-          self.add_mult_sum[u].b = self.add_mult_sum[u].b - intermediates1[i];
-        }
-        10 =>
-        //relay gate
-        {
-          if !self.ctx.gate_meet[self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty]
-          {
-            //prletf("first meet %d gate\n", C -> circuit[self.sumcheck_layer_id].gates[i].ty);
-            self.ctx.gate_meet
-              [self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty] = true;
-          }
-          self.add_mult_sum[u].b = self.add_mult_sum[u].b + intermediates0[i];
-        }
-        _ => println!(
-          "Warning Unknown gate {}",
-          self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty
-        ),
+    let first_half = self.length_g >> 1;
+    EqPolynomial::new(&self.r_0[..self.length_g]).fill_halves(
+      &mut self.beta_g_r0_fhalf,
+      &mut self.beta_g_r0_shalf,
+      self.alpha,
+    );
+    EqPolynomial::new(&self.r_1[..self.length_g]).fill_halves(
+      &mut self.beta_g_r1_fhalf,
+      &mut self.beta_g_r1_shalf,
+      self.beta,
+    );
+
+    let mask_fhalf = (1 << first_half) - 1;
+    let layer = &self.aritmetic_circuit.circuit[self.sumcheck_layer_id];
+    let prev = &self.circuit_value[self.sumcheck_layer_id - 1];
+
+    // Summing over v first, each wire (u, v) of a gate g leaves
+    // beta(g) * weight * form(V(u), V(v)), linear in V(u)
+    let gates = &layer.gates[..1 << self.length_g];
+    for gate in gates {
+      self.ctx.gate_meet.insert(gate.code());
+    }
+    let (beta_r0_fhalf, beta_r0_shalf) = (&self.beta_g_r0_fhalf, &self.beta_g_r0_shalf);
+    let (beta_r1_fhalf, beta_r1_shalf) = (&self.beta_g_r1_fhalf, &self.beta_g_r1_shalf);
+    let beta_g = |i: usize| {
+      beta_r0_fhalf[i & mask_fhalf] * beta_r0_shalf[i >> first_half]
+        + beta_r1_fhalf[i & mask_fhalf] * beta_r1_shalf[i >> first_half]
+    };
+    match self.mode {
+      SumcheckMode::Dense => add_contributions(
+        gates,
+        &mut self.add_mult_sum,
+        &mut self.add_v_array,
+        |i, gate, out| {
+          let tmp = beta_g(i);
+          let form = gate.form();
+          gate.for_each_wire(|u, v, weight| {
+            let tmp = tmp * weight;
+            out.push((u, tmp * form.x_coef(prev[v]), tmp * form.x_free(prev[v])));
+          });
+        },
+      ),
+      SumcheckMode::Sparse => {
+        self.sparse_tables = sparse_contributions(&layer.u_gates, |wire| {
+          let form = layer.gates[wire.g].form();
+          let tmp = beta_g(wire.g) * wire.weight;
+          let value = prev[wire.v];
+          Some((wire.u, tmp * form.x_coef(value), tmp * form.x_free(value)))
+        })
       }
     }
     let time_span = t0.elapsed();
@@ -583,81 +509,11 @@ impl ZkProver {
 
   pub fn sumcheck_phase1_update(
     &mut self,
-    previous_random: FieldElement,
+    previous_random: F,
     current_bit: usize,
-  ) -> QuadraticPoly {
+  ) -> QuadraticPoly<F> {
     let t0 = time::Instant::now();
-    let mut ret = QuadraticPoly::zero();
-
-    //todo
-    //#pragma omp parallel for
-
-    for i in 0..(self.total_uv >> 1) {
-      //prime_field::field_element zero_value, one_value; //never used
-      let g_zero = i << 1;
-      let g_one = i << 1 | 1;
-      if current_bit == 0 {
-        self.ctx.v_mult_add_new[i].b = self.v_mult_add0[g_zero].b;
-        self.ctx.v_mult_add_new[i].a = self.v_mult_add0[g_one].b - self.ctx.v_mult_add_new[i].b;
-
-        self.ctx.add_v_array_new[i].b = self.add_v_array[g_zero].b;
-        self.ctx.add_v_array_new[i].a = self.add_v_array[g_one].b - self.ctx.add_v_array_new[i].b;
-
-        self.ctx.add_mult_sum_new[i].b = self.add_mult_sum[g_zero].b;
-        self.ctx.add_mult_sum_new[i].a =
-          self.add_mult_sum[g_one].b - self.ctx.add_mult_sum_new[i].b;
-      } else {
-        self.ctx.v_mult_add_new[i].b =
-          self.v_mult_add0[g_zero].a * previous_random + self.v_mult_add0[g_zero].b;
-        self.ctx.v_mult_add_new[i].a = self.v_mult_add0[g_one].a * previous_random
-          + self.v_mult_add0[g_one].b
-          - self.ctx.v_mult_add_new[i].b;
-
-        self.ctx.add_v_array_new[i].b =
-          self.add_v_array[g_zero].a * previous_random + self.add_v_array[g_zero].b;
-        self.ctx.add_v_array_new[i].a = self.add_v_array[g_one].a * previous_random
-          + self.add_v_array[g_one].b
-          - self.ctx.add_v_array_new[i].b;
-
-        self.ctx.add_mult_sum_new[i].b =
-          self.add_mult_sum[g_zero].a * previous_random + self.add_mult_sum[g_zero].b;
-        self.ctx.add_mult_sum_new[i].a = self.add_mult_sum[g_one].a * previous_random
-          + self.add_mult_sum[g_one].b
-          - self.ctx.add_mult_sum_new[i].b;
-      }
-    }
-
-    swap(&mut self.v_mult_add0, &mut self.ctx.v_mult_add_new);
-    swap(&mut self.add_v_array, &mut self.ctx.add_v_array_new);
-    swap(&mut self.add_mult_sum, &mut self.ctx.add_mult_sum_new);
-
-    //parallel addition tree
-    //todo
-    //#pragma omp parallel for
-    for i in 0..(self.total_uv >> 1) {
-      self.ctx.rets_prev[i].a = self.add_mult_sum[i].a * self.v_mult_add0[i].a;
-      self.ctx.rets_prev[i].b = self.add_mult_sum[i].a * self.v_mult_add0[i].b
-        + self.add_mult_sum[i].b * self.v_mult_add0[i].a
-        + self.add_v_array[i].a;
-      self.ctx.rets_prev[i].c =
-        self.add_mult_sum[i].b * self.v_mult_add0[i].b + self.add_v_array[i].b;
-    }
-
-    let tot = self.total_uv >> 1;
-    let mut iter = 1;
-    while (1 << iter) <= (self.total_uv >> 1) {
-      //todo
-      //#pragma omp parallel for
-      for j in 0..(tot >> iter) {
-        self.ctx.rets_cur[j] = self.ctx.rets_prev[j * 2] + self.ctx.rets_prev[j * 2 + 1];
-      }
-      //todo
-      //#pragma omp barrier
-      swap(&mut self.ctx.rets_prev, &mut self.ctx.rets_cur);
-      iter += 1;
-    }
-    ret = self.ctx.rets_prev[0];
-
+    let ret = self.sumcheck_fold(previous_random, current_bit);
     self.total_uv >>= 1;
 
     let time_span = t0.elapsed();
@@ -666,34 +522,16 @@ impl ZkProver {
     ret
   }
 
-  pub fn sumcheck_phase2_init(
-    &mut self,
-    previous_random: FieldElement,
-    r_u: Vec<FieldElement>,
-    one_minus_r_u: Vec<FieldElement>,
-  ) {
+  pub fn sumcheck_phase2_init(&mut self, previous_random: F, r_u: Vec<F>) {
     let _t0 = SystemTime::now();
-    self.v_u = self.v_mult_add0[0].eval(previous_random);
+    self.v_u = self.v_mult_add0[0].eval(previous_random) + self.lde_mask.extension(&r_u);
 
     let first_half = self.length_u >> 1;
-    let second_half = self.length_u - first_half;
-
-    self.beta_u_fhalf[0] = FieldElement::real_one();
-    self.beta_u_shalf[0] = FieldElement::real_one();
-
-    for i in 0..first_half {
-      for j in 0..(1 << i) {
-        self.beta_u_fhalf[j | (1 << i)] = self.beta_u_fhalf[j] * r_u[i];
-        self.beta_u_fhalf[j] = self.beta_u_fhalf[j] * one_minus_r_u[i];
-      }
-    }
-
-    for i in 0..second_half {
-      for j in 0..(1 << i) {
-        self.beta_u_shalf[j | (1 << i)] = self.beta_u_shalf[j] * r_u[i + first_half];
-        self.beta_u_shalf[j] = self.beta_u_shalf[j] * one_minus_r_u[i + first_half];
-      }
-    }
+    EqPolynomial::new(&r_u[..self.length_u]).fill_halves(
+      &mut self.beta_u_fhalf,
+      &mut self.beta_u_shalf,
+      F::one(),
+    );
 
     let mask_fhalf = (1 << first_half) - 1;
     let first_g_half = self.length_g >> 1;
@@ -701,337 +539,311 @@ impl ZkProver {
 
     self.total_uv = 1 << self.aritmetic_circuit.circuit[self.sumcheck_layer_id - 1].bit_length;
     let total_g = 1 << self.aritmetic_circuit.circuit[self.sumcheck_layer_id].bit_length;
-    let zero = FieldElement::zero();
-
-    for i in 0..self.total_uv {
-      self.add_mult_sum[i].a = zero;
-      self.add_mult_sum[i].b = zero;
-      self.add_v_array[i].a = zero;
-      self.add_v_array[i].b = zero;
-      //todo! linear_poly != FieldElement
-      //self.v_mult_add[i] = self.circuit_value[self.sumcheck_layer_id - 1][i];
-      self.v_mult_add0[i] = LinearPoly::maps(self.circuit_value[self.sumcheck_layer_id - 1][i]);
-    }
-
-    let mut intermediates0 = vec![FieldElement::zero(); total_g];
-    let mut intermediates1 = vec![FieldElement::zero(); total_g];
-    //let mut intermediates2 = vec![FieldElement::zero(); total_g]; //never used
-
-    //todo
-    //#pragma omp parallel for
-    for i in 0..total_g {
-      let ty = self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty;
-      let u = self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].u;
-      let _v = self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].v;
-      match ty {
-        1 =>
-        //mult gate
-        {
-          let tmp_u = self.beta_u_fhalf[u & mask_fhalf] * self.beta_u_shalf[u >> first_half];
-          let tmp_g = self.beta_g_r0_fhalf[i & mask_g_fhalf]
-            * self.beta_g_r0_shalf[i >> first_g_half]
-            + self.beta_g_r1_fhalf[i & mask_g_fhalf] * self.beta_g_r1_shalf[i >> first_g_half];
-          intermediates0[i] = tmp_g * tmp_u * self.v_u;
-        }
-        0 =>
-        //add gate
-        {
-          let tmp_u = self.beta_u_fhalf[u & mask_fhalf] * self.beta_u_shalf[u >> first_half];
-          let tmp_g = self.beta_g_r0_fhalf[i & mask_g_fhalf]
-            * self.beta_g_r0_shalf[i >> first_g_half]
-            + self.beta_g_r1_fhalf[i & mask_g_fhalf] * self.beta_g_r1_shalf[i >> first_g_half];
-          let tmp_g_u = tmp_g * tmp_u;
-          intermediates0[i] = tmp_g_u;
-          intermediates1[i] = tmp_g_u * self.v_u;
-        }
-        2 => {}
-        4 => {}
-        5 =>
-        //sum gate
-        {
-          let tmp_g = self.beta_g_r0_fhalf[i & mask_g_fhalf]
-            * self.beta_g_r0_shalf[i >> first_g_half]
-            + self.beta_g_r1_fhalf[i & mask_g_fhalf] * self.beta_g_r1_shalf[i >> first_g_half];
-          let tmp_g_vu = tmp_g * self.v_u;
-          intermediates0[i] = tmp_g_vu;
-        }
-        12 =>
-        //exp sum gate
-        {
-          let tmp_g = self.beta_g_r0_fhalf[i & mask_g_fhalf]
-            * self.beta_g_r0_shalf[i >> first_g_half]
-            + self.beta_g_r1_fhalf[i & mask_g_fhalf] * self.beta_g_r1_shalf[i >> first_g_half];
-          let tmp_g_vu = tmp_g * self.v_u;
-          intermediates0[i] = tmp_g_vu;
-        }
-        14 =>
-        //custom comb gate
-        {
-          let tmp_g = self.beta_g_r0_fhalf[i & mask_g_fhalf]
-            * self.beta_g_r0_shalf[i >> first_g_half]
-            + self.beta_g_r1_fhalf[i & mask_g_fhalf] * self.beta_g_r1_shalf[i >> first_g_half];
-          let tmp_g_vu = tmp_g * self.v_u;
-          intermediates0[i] = tmp_g_vu;
-        }
-
-        6 =>
-        //not gate
-        {
-          let tmp_u = self.beta_u_fhalf[u & mask_fhalf] * self.beta_u_shalf[u >> first_half];
-          let tmp_g = self.beta_g_r0_fhalf[i & mask_g_fhalf]
-            * self.beta_g_r0_shalf[i >> first_g_half]
-            + self.beta_g_r1_fhalf[i & mask_g_fhalf] * self.beta_g_r1_shalf[i >> first_g_half];
-          let tmp_g_u = tmp_g * tmp_u;
-          intermediates0[i] = tmp_g_u - tmp_g_u * self.v_u;
-        }
-        7 =>
-        //minus gate
-        {
-          let tmp_u = self.beta_u_fhalf[u & mask_fhalf] * self.beta_u_shalf[u >> first_half];
-          let tmp_g = self.beta_g_r0_fhalf[i & mask_g_fhalf]
-            * self.beta_g_r0_shalf[i >> first_g_half]
-            + self.beta_g_r1_fhalf[i & mask_g_fhalf] * self.beta_g_r1_shalf[i >> first_g_half];
-          let tmp = tmp_g * tmp_u;
-          intermediates0[i] = tmp;
-          intermediates1[i] = tmp * self.v_u;
-        }
-        8 =>
-        //xor gate
-        {
-          let tmp_u = self.beta_u_fhalf[u & mask_fhalf] * self.beta_u_shalf[u >> first_half];
-          let tmp_g = self.beta_g_r0_fhalf[i & mask_g_fhalf]
-            * self.beta_g_r0_shalf[i >> first_g_half]
-            + self.beta_g_r1_fhalf[i & mask_g_fhalf] * self.beta_g_r1_shalf[i >> first_g_half];
-          let tmp = tmp_g * tmp_u;
-          let tmp_v_u = tmp * self.v_u;
-          intermediates0[i] = tmp - tmp_v_u - tmp_v_u;
-          intermediates1[i] = tmp_v_u;
-        }
-        13 =>
-        //bit-test gate
-        {
-          let tmp_u = self.beta_u_fhalf[u & mask_fhalf] * self.beta_u_shalf[u >> first_half];
-          let tmp_g = self.beta_g_r0_fhalf[i & mask_g_fhalf]
-            * self.beta_g_r0_shalf[i >> first_g_half]
-            + self.beta_g_r1_fhalf[i & mask_g_fhalf] * self.beta_g_r1_shalf[i >> first_g_half];
-          let tmp = tmp_g * tmp_u;
-          let tmp_v_u = tmp * self.v_u;
-          intermediates0[i] = tmp_v_u;
-        }
-        9 =>
-        //NAAB gate
-        {
-          let tmp_u = self.beta_u_fhalf[u & mask_fhalf] * self.beta_u_shalf[u >> first_half];
-          let tmp_g = self.beta_g_r0_fhalf[i & mask_g_fhalf]
-            * self.beta_g_r0_shalf[i >> first_g_half]
-            + self.beta_g_r1_fhalf[i & mask_g_fhalf] * self.beta_g_r1_shalf[i >> first_g_half];
-          let tmp = tmp_g * tmp_u;
-          intermediates0[i] = tmp - self.v_u * tmp;
-        }
-        10 =>
-        //relay gate
-        {
-          let tmp_u = self.beta_u_fhalf[u & mask_fhalf] * self.beta_u_shalf[u >> first_half];
-          let tmp_g = self.beta_g_r0_fhalf[i & mask_g_fhalf]
-            * self.beta_g_r0_shalf[i >> first_g_half]
-            + self.beta_g_r1_fhalf[i & mask_g_fhalf] * self.beta_g_r1_shalf[i >> first_g_half];
-          let tmp = tmp_g * tmp_u;
-          intermediates0[i] = tmp * self.v_u;
-        }
-        _ => {
-          println!("Warning Unknown gate {}", ty);
-        }
-      }
-    }
-
-    for i in 0..total_g {
-      let ty = self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].ty;
-      let u = self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].u;
-      let v = self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].v;
-      match ty {
-        1 =>
-        //mult gate
-        {
-          self.add_mult_sum[v].b = self.add_mult_sum[v].b + intermediates0[i];
-        }
-        0 =>
-        //add gate
-        {
-          self.add_mult_sum[v].b = self.add_mult_sum[v].b + intermediates0[i];
-          self.add_v_array[v].b = intermediates1[i] + self.add_v_array[v].b;
-        }
-        2 => {}
-        4 => {}
-        5 =>
-        //sum gate
-        {
-          for j in u..v {
-            let tmp_u = self.beta_u_fhalf[j & mask_fhalf] * self.beta_u_shalf[j >> first_half];
-            self.add_v_array[0].b = self.add_v_array[0].b + intermediates0[i] * tmp_u;
+    self.reset_tables();
+
+    let layer = &self.aritmetic_circuit.circuit[self.sumcheck_layer_id];
+    let v_u = self.v_u;
+
+    // With u fixed to r_u, each wire (u, v) of a gate g leaves
+    // beta(g) * eq(r_u, u) * weight * form(v_u, V(v)), linear in V(v)
+    let (beta_r0_fhalf, beta_r0_shalf) = (&self.beta_g_r0_fhalf, &self.beta_g_r0_shalf);
+    let (beta_r1_fhalf, beta_r1_shalf) = (&self.beta_g_r1_fhalf, &self.beta_g_r1_shalf);
+    let (beta_u_fhalf, beta_u_shalf) = (&self.beta_u_fhalf, &self.beta_u_shalf);
+    let beta_g = |i: usize| {
+      beta_r0_fhalf[i & mask_g_fhalf] * beta_r0_shalf[i >> first_g_half]
+        + beta_r1_fhalf[i & mask_g_fhalf] * beta_r1_shalf[i >> first_g_half]
+    };
+    let beta_u = |u: usize| beta_u_fhalf[u & mask_fhalf] * beta_u_shalf[u >> first_half];
+    // the verifier accounts for the first layer in closed form, see
+    // `ZkVerifier::direct_relay`
+    let direct_relay = |gate: &GateKind<F>| matches!(gate, GateKind::DirectRelay { .. });
+    match self.mode {
+      SumcheckMode::Dense => add_contributions(
+        &layer.gates[..total_g],
+        &mut self.add_mult_sum,
+        &mut self.add_v_array,
+        |i, gate, out| {
+          if direct_relay(gate) {
+            return;
           }
-        }
-        12 =>
-        //exp sum gate
-        {
-          let mut tmp_g_vu = intermediates0[i];
-
-          for j in u..v {
-            let tmp_u = self.beta_u_fhalf[j & mask_fhalf] * self.beta_u_shalf[j >> first_half];
-            self.add_v_array[0].b = self.add_v_array[0].b + tmp_g_vu * tmp_u;
-            tmp_g_vu = tmp_g_vu + tmp_g_vu;
+          let tmp_g = beta_g(i);
+          let form = gate.form();
+          gate.for_each_wire(|u, v, weight| {
+            let tmp = tmp_g * beta_u(u) * weight;
+            out.push((v, tmp * form.y_coef(v_u), tmp * form.y_free(v_u)));
+          });
+        },
+      ),
+      SumcheckMode::Sparse => {
+        self.sparse_tables = sparse_contributions(&layer.v_gates, |wire| {
+          let gate = &layer.gates[wire.g];
+          if direct_relay(gate) {
+            return None;
           }
-        }
-        14 =>
-        //custom comb gate
-        {
-          let tmp_g_vu = intermediates0[i];
-
-          for j in
-            0..self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].parameter_length
-          {
-            let src = self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].src[j];
-            let tmp_u = self.beta_u_fhalf[src & mask_fhalf] * self.beta_u_shalf[src >> first_half];
-            let weight = self.aritmetic_circuit.circuit[self.sumcheck_layer_id].gates[i].weight[j];
-            self.add_v_array[0].b = self.add_v_array[0].b + tmp_g_vu * tmp_u * weight;
-          }
-        }
-        6 =>
-        //not gate
-        {
-          self.add_v_array[v].b = self.add_v_array[v].b + intermediates0[i];
-        }
-        7 =>
-        //minus gate
-        {
-          self.add_mult_sum[v].b = self.add_mult_sum[v].b - intermediates0[i];
-          self.add_v_array[v].b = intermediates1[i] + self.add_v_array[v].b;
-        }
-        8 =>
-        //xor gate
-        {
-          self.add_mult_sum[v].b = self.add_mult_sum[v].b + intermediates0[i];
-          self.add_v_array[v].b = self.add_v_array[v].b + intermediates1[i];
-        }
-        13 =>
-        //bit-test gate
-        {
-          self.add_mult_sum[v].b = self.add_mult_sum[v].b - intermediates0[i];
-          self.add_v_array[v].b = self.add_v_array[v].b + intermediates0[i];
-        }
-        9 =>
-        //NAAB gate
-        {
-          self.add_mult_sum[v].b = self.add_mult_sum[v].b + intermediates0[i];
-        }
-        10 =>
-        //relay gate
-        {
-          self.add_v_array[v].b = self.add_v_array[v].b + intermediates0[i];
-        }
-        _ => {
-          println!("Warning Unknown gate {}", ty);
-        }
+          let form = gate.form();
+          let tmp = beta_g(wire.g) * beta_u(wire.u) * wire.weight;
+          Some((wire.v, tmp * form.y_coef(v_u), tmp * form.y_free(v_u)))
+        })
       }
     }
   }
 
   pub fn sumcheck_phase2_update(
     &mut self,
-    previous_random: FieldElement,
+    previous_random: F,
     current_bit: usize,
-  ) -> QuadraticPoly {
+  ) -> QuadraticPoly<F> {
     let t0 = time::Instant::now();
-    let mut ret = QuadraticPoly::zero();
+    let ret = self.sumcheck_fold(previous_random, current_bit);
+    self.total_uv >>= 1;
 
-    //todo
-    //#pragma omp parallel for
+    let time_span = t0.elapsed();
+    self.total_time += time_span.as_secs_f64();
 
-    for i in 0..(self.total_uv >> 1) {
-      let g_zero = i << 1;
-      let g_one = i << 1 | 1;
+    ret
+  }
 
-      if current_bit == 0 {
-        self.ctx.v_mult_add_new[i].b = self.v_mult_add0[g_zero].b;
-        self.ctx.v_mult_add_new[i].a = self.v_mult_add0[g_one].b - self.ctx.v_mult_add_new[i].b;
+  pub fn sumcheck_finalize(&mut self, previous_random: F) -> (F, F) {
+    self.v_v = self.v_mult_add0[0].eval(previous_random);
+    (self.v_u, self.v_v)
+  }
 
-        self.ctx.add_v_array_new[i].b = self.add_v_array[g_zero].b;
-        self.ctx.add_v_array_new[i].a = self.add_v_array[g_one].b - self.ctx.add_v_array_new[i].b;
+  pub fn proof_init() {} //Used, but is not implemented neither in virgo repo nor orion repo
 
-        self.ctx.add_mult_sum_new[i].b = self.add_mult_sum[g_zero].b;
-        self.ctx.add_mult_sum_new[i].a =
-          self.add_mult_sum[g_one].b - self.ctx.add_mult_sum_new[i].b;
-      } else {
-        self.ctx.v_mult_add_new[i].b =
-          self.v_mult_add0[g_zero].a * previous_random + self.v_mult_add0[g_zero].b;
-        self.ctx.v_mult_add_new[i].a = self.v_mult_add0[g_one].a * previous_random
-          + self.v_mult_add0[g_one].b
-          - self.ctx.v_mult_add_new[i].b;
-
-        self.ctx.add_v_array_new[i].b =
-          self.add_v_array[g_zero].a * previous_random + self.add_v_array[g_zero].b;
-        self.ctx.add_v_array_new[i].a = self.add_v_array[g_one].a * previous_random
-          + self.add_v_array[g_one].b
-          - self.ctx.add_v_array_new[i].b;
-
-        self.ctx.add_mult_sum_new[i].b =
-          self.add_mult_sum[g_zero].a * previous_random + self.add_mult_sum[g_zero].b;
-        self.ctx.add_mult_sum_new[i].a = self.add_mult_sum[g_one].a * previous_random
-          + self.add_mult_sum[g_one].b
-          - self.ctx.add_mult_sum_new[i].b;
-      }
-      ret.a = ret.a + self.add_mult_sum[i].a * self.v_mult_add0[i].a;
-      ret.b = ret.b
-        + self.add_mult_sum[i].a * self.v_mult_add0[i].b
-        + self.add_mult_sum[i].b * self.v_mult_add0[i].a
-        + self.add_v_array[i].a;
-      ret.c = ret.c + self.add_mult_sum[i].b * self.v_mult_add0[i].b + self.add_v_array[i].b;
+  /// Runs the whole GKR protocol, drawing every challenge from `transcript`
+  /// instead of asking the verifier, and returns the messages the verifier
+  /// needs to replay it. The input layer but the public inputs is committed
+  /// to up to the input gates, the rest being zero padding, and opened on
+  /// the claims the sumcheck of layer 1 leaves on it and on its public inputs
+  /// being zero.
+  pub fn prove(&mut self, transcript: &mut Transcript) -> GkrProof<F> {
+    if self.zero_knowledge {
+      return self.prove_zero_knowledge(transcript);
     }
-    swap(&mut self.v_mult_add0, &mut self.ctx.v_mult_add_new);
-    swap(&mut self.add_v_array, &mut self.ctx.add_v_array_new);
-    swap(&mut self.add_mult_sum, &mut self.ctx.add_mult_sum_new);
-
-    //parallel addition tree
-    //todo
-    //#pragma omp parallel for
-    for i in 0..(self.total_uv >> 1) {
-      self.ctx.rets_prev[i].a = self.add_mult_sum[i].a * self.v_mult_add0[i].a;
-      self.ctx.rets_prev[i].b = self.add_mult_sum[i].a * self.v_mult_add0[i].b
-        + self.add_mult_sum[i].b * self.v_mult_add0[i].a
-        + self.add_v_array[i].a;
-      self.ctx.rets_prev[i].c =
-        self.add_mult_sum[i].b * self.v_mult_add0[i].b + self.add_v_array[i].b;
+    let output = self.evaluate();
+    transcript.absorb(&output[..]);
+    transcript.absorb(self.public_inputs());
+
+    let log_len = committed_log_len(&self.aritmetic_circuit);
+    let mut committed = self.private_input();
+    committed.resize(1 << log_len, F::zero());
+    self.poly_prover = PolyCommitProver::new(transcript.hasher());
+    let commitment = self.poly_prover.commit_private_array(&committed, log_len);
+    transcript.append_digest(commitment);
+
+    let (layers, r_0, r_1) = self.prove_layers(transcript);
+    let public = self.input_opening_array(&r_0, &r_1, transcript);
+    let opening = self.poly_prover.open(&public, transcript);
+
+    GkrProof {
+      hash: transcript.hasher(),
+      output,
+      layers,
+      commitment,
+      opening,
+      zk: None,
     }
+  }
 
-    let tot = self.total_uv >> 1;
-    let mut iter = 1;
-    while (1 << iter) <= (self.total_uv >> 1) {
-      //todo
-      //#pragma omp parallel for
-      for j in 0..(tot >> iter) {
-        self.ctx.rets_cur[j] = self.ctx.rets_prev[j * 2] + self.ctx.rets_prev[j * 2 + 1];
+  /// Sumchecks of every layer, from the output one down to layer 1, which
+  /// leave claims on the input layer at the returned `r_u` and `r_v`
+  pub(crate) fn prove_layers(
+    &mut self,
+    transcript: &mut Transcript,
+  ) -> (Vec<LayerProof<F>>, Vec<F>, Vec<F>) {
+    let total_depth = self.aritmetic_circuit.total_depth;
+    let capacity = self.aritmetic_circuit.circuit[total_depth - 1].bit_length;
+
+    let mut alpha = F::one();
+    let mut beta = F::zero();
+    let mut r_0 = transcript.challenges(capacity);
+    let mut r_1 = transcript.challenges(capacity);
+
+    let mut layers = Vec::with_capacity(total_depth - 1);
+    for i in (1..total_depth).rev() {
+      let bit_length = self.aritmetic_circuit.circuit[i - 1].bit_length;
+      self.sumcheck_init(
+        i,
+        self.aritmetic_circuit.circuit[i].bit_length,
+        bit_length,
+        bit_length,
+        alpha,
+        beta,
+        r_0,
+        r_1,
+      );
+      self.sumcheck_phase1_init();
+
+      let mut layer = LayerProof::default();
+      let mut r_u = Vec::with_capacity(bit_length);
+      let mut previous_random = F::zero();
+      for j in 0..bit_length {
+        let poly = self.sumcheck_phase1_update(previous_random, j);
+        transcript.absorb(&poly);
+        layer.phase1.push(poly);
+
+        previous_random = transcript.challenge();
+        r_u.push(previous_random);
       }
-      //todo
-      //#pragma omp barrier
-      swap(&mut self.ctx.rets_prev, &mut self.ctx.rets_cur);
-      iter += 1;
+      self.sumcheck_phase2_init(previous_random, r_u.clone());
+      transcript.append_field_element(self.v_u);
+
+      let mut r_v = Vec::with_capacity(bit_length);
+      let mut previous_random = F::zero();
+      for j in 0..bit_length {
+        let poly = self.sumcheck_phase2_update(previous_random, j);
+        transcript.absorb(&poly);
+        layer.phase2.push(poly);
+
+        // layer 1 only relays the input layer, so v is fixed to zero there
+        previous_random = if i == 1 {
+          F::zero()
+        } else {
+          transcript.challenge()
+        };
+        r_v.push(previous_random);
+      }
+
+      let (v_u, v_v) = self.sumcheck_finalize(previous_random);
+      transcript.append_field_element(v_v);
+      layer.v_u = v_u;
+      layer.v_v = v_v;
+      layers.push(layer);
+
+      alpha = transcript.challenge();
+      beta = transcript.challenge();
+      r_0 = r_u;
+      r_1 = r_v;
     }
-    ret = self.ctx.rets_prev[0];
+    (layers, r_0, r_1)
+  }
 
-    self.total_uv >>= 1;
+  /// Public array the committed one is opened against: V_in(r_u) and
+  /// V_in(r_v) of layer 1 at once, the second one weighted by lambda, and the
+  /// committed array being zero on the public inputs
+  pub(crate) fn input_opening_array(
+    &self,
+    r_0: &[F],
+    r_1: &[F],
+    transcript: &mut Transcript,
+  ) -> Vec<F> {
+    let log_len = committed_log_len(&self.aritmetic_circuit);
+    let lambda: F = transcript.challenge();
+    let rho: F = transcript.challenge();
+    let (r_u, scale_u) = prefix_point(r_0, log_len);
+    let (r_v, scale_v) = prefix_point(r_1, log_len);
+    let scale_v = lambda * scale_v;
+    let mut public: Vec<_> = EqPolynomial::new(&r_u)
+      .evaluations()
+      .into_iter()
+      .zip(EqPolynomial::new(&r_v).evaluations())
+      .map(|(eq_u, eq_v)| scale_u * eq_u + scale_v * eq_v)
+      .collect();
+    weigh_public_inputs(&mut public, self.public_input_count, rho);
+    public
+  }
+}
 
-    let time_span = t0.elapsed();
-    self.total_time += time_span.as_secs_f64();
+#[cfg(test)]
+mod tests {
+  use infrastructure::transcript::Transcript;
+  use rayon::ThreadPoolBuilder;
 
-    ret
+  use prime_field::{FieldElement, Fp61};
+
+  use crate::circuit_fast_track::{GateKind, Layer, LayeredCircuit};
+  use crate::proof::TRANSCRIPT_LABEL;
+  use crate::prover::{SumcheckMode, ZkProver};
+
+  fn layer(bit_length: usize, gates: Vec<GateKind>) -> Layer {
+    Layer {
+      gates,
+      bit_length,
+      ..Layer::new()
+    }
   }
 
-  pub fn sumcheck_finalize(
-    &mut self,
-    previous_random: FieldElement,
-  ) -> (FieldElement, FieldElement) {
-    self.v_v = self.v_mult_add0[0].eval(previous_random);
-    (self.v_u, self.v_v)
+  #[test]
+  fn proofs_do_not_depend_on_the_thread_count() {
+    let circuit: LayeredCircuit = LayeredCircuit::parse(
+      include_str!("../mat_16_circuit.txt"),
+      include_str!("../mat_16_meta.txt"),
+    )
+    .unwrap();
+    let prove = |threads| {
+      let pool = ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+      let mut prover = ZkProver::new();
+      prover.init_array(circuit.max_bit_length(), &circuit);
+      pool.install(|| prover.prove(&mut Transcript::new(TRANSCRIPT_LABEL)))
+    };
+    assert_eq!(prove(1), prove(3));
   }
 
-  pub fn proof_init() {} //Used, but is not implemented neither in virgo repo nor orion repo
+  #[test]
+  fn base_field_evaluation_lifts_to_the_extension_one() {
+    let circuit_text = include_str!("../mat_16_circuit.txt");
+    let meta_text = include_str!("../mat_16_meta.txt");
+    let base: LayeredCircuit<Fp61> = LayeredCircuit::parse(circuit_text, meta_text).unwrap();
+    let extension: LayeredCircuit = LayeredCircuit::parse(circuit_text, meta_text).unwrap();
+
+    let mut base_prover = ZkProver::new();
+    base_prover.init_array(base.max_bit_length(), &base);
+    let mut prover = ZkProver::new();
+    prover.init_array(extension.max_bit_length(), &extension);
+
+    let lifted: Vec<FieldElement> = base_prover
+      .evaluate()
+      .into_iter()
+      .map(FieldElement::from)
+      .collect();
+    assert_eq!(lifted, prover.evaluate());
+  }
+
+  #[test]
+  fn sparse_mode_proves_like_dense_mode() {
+    // two gates reading a large layer, read back by a large layer
+    let mut circuit = LayeredCircuit::new();
+    circuit.input_count = 1 << 8;
+    circuit.circuit = vec![
+      layer(8, vec![GateKind::Zero; 1 << 8]),
+      layer(
+        8,
+        (0..1 << 8).map(|u| GateKind::DirectRelay { u }).collect(),
+      ),
+      layer(
+        1,
+        vec![
+          GateKind::Sum { start: 3, end: 200 },
+          GateKind::Mult { u: 17, v: 255 },
+        ],
+      ),
+      layer(
+        6,
+        (0..1 << 6)
+          .map(|g| match g % 4 {
+            0 => GateKind::Add { u: 0, v: 1 },
+            1 => GateKind::Xor { u: 1, v: 1 },
+            2 => GateKind::Constant { value: g as u64 },
+            _ => GateKind::Zero,
+          })
+          .collect(),
+      ),
+    ];
+    circuit.total_depth = circuit.circuit.len();
+    let inputs: Vec<_> = (0..1 << 8)
+      .map(|i| FieldElement::from_real(i * i))
+      .collect();
+
+    let prove = |mode| {
+      let mut prover = ZkProver::new();
+      prover.init_array(circuit.max_bit_length(), &circuit);
+      prover.set_mode(mode);
+      prover.get_witness(inputs.clone());
+      prover.prove(&mut Transcript::new(TRANSCRIPT_LABEL))
+    };
+    assert_eq!(prove(SumcheckMode::Dense), prove(SumcheckMode::Sparse));
+  }
 }
@@ -0,0 +1,311 @@
+use infrastructure::my_hash::HashDigest;
+use infrastructure::transcript::Transcript;
+use poly_commitment::PolyCommitProver;
+use prime_field::Field;
+
+use super::{SumcheckMode, ZkProver};
+use crate::multilinear::DenseMultilinear;
+use crate::polynomial::{LinearPoly, QuadraticPoly, QuadruplePoly};
+use crate::proof::{weigh_public_inputs, GkrProof, LayerProof, ZkLayerProof, ZkProof};
+use crate::zk::{vanishing, LayerClaims, LdeMask, MaskingPolynomial, ZkLayout};
+
+/// Adds `scale * poly` to `sum`, both given as coefficients from degree 0 up
+fn add_scaled<F: Field>(sum: &mut Vec<F>, poly: &[F], scale: F) {
+  if sum.len() < poly.len() {
+    sum.resize(poly.len(), F::zero());
+  }
+  for (sum, &c) in sum.iter_mut().zip(poly) {
+    *sum = *sum + scale * c;
+  }
+}
+
+fn coefficients<F: Field, const N: usize>(poly: &[F]) -> [F; N] {
+  assert!(poly.len() <= N);
+  let mut c = [F::zero(); N];
+  c[..poly.len()].copy_from_slice(poly);
+  c
+}
+
+fn quadratic<F: Field>(poly: &[F]) -> QuadraticPoly<F> {
+  let [c, b, a] = coefficients(poly);
+  QuadraticPoly::new(a, b, c)
+}
+
+fn quadruple<F: Field>(poly: &[F]) -> QuadruplePoly<F> {
+  let [e, d, c, b, a] = coefficients(poly);
+  QuadruplePoly::new(a, b, c, d, e)
+}
+
+/// State of the zero knowledge sumcheck of a layer across its rounds
+struct ZkRounds<'a, F> {
+  masking: &'a MaskingPolynomial<F>,
+  rho: F,
+  /// `T(r_w)`
+  t_w: F,
+  /// `eq((u, v), 0)` over the variables bound so far, the weight of `T(r_w)`
+  eq_zero: F,
+  /// Challenges so far, `r_w` first
+  point: Vec<F>,
+}
+
+impl<F: Field> ZkRounds<'_, F> {
+  fn bind(&mut self, r: F) {
+    self.point.push(r);
+    self.eq_zero = self.eq_zero * (F::one() - r);
+  }
+}
+
+impl<F: Field> ZkProver<F> {
+  /// Runs the protocol with every message masked, so that the proof reveals
+  /// nothing about the input layer but the output, see `crate::zk` for the
+  /// polynomials the sumchecks run on. The input layer is committed to along
+  /// with the masks, by a hiding commitment, and opened at the end on the
+  /// claims the sumchecks leave.
+  pub(super) fn prove_zero_knowledge(&mut self, transcript: &mut Transcript) -> GkrProof<F> {
+    self.prove_masked(transcript, PolyCommitProver::commit_hiding_array)
+  }
+
+  /// [`Self::prove_zero_knowledge`] with the input layer and the masks
+  /// committed to by `commit`. Only a hiding commitment keeps the opening
+  /// from revealing the input layer, while the other messages are masked
+  /// whatever the commitment, which lets the tests sample them cheaply.
+  pub(crate) fn prove_masked(
+    &mut self,
+    transcript: &mut Transcript,
+    commit: fn(&mut PolyCommitProver<F>, &[F], usize) -> HashDigest,
+  ) -> GkrProof<F> {
+    let output = self.evaluate();
+    transcript.absorb(&output[..]);
+    transcript.absorb(self.public_inputs());
+
+    let total_depth = self.aritmetic_circuit.total_depth;
+    let layout = ZkLayout::new(&self.aritmetic_circuit);
+    // every layer but the output one gets a randomized extension
+    let lde_masks: Vec<_> = (1..total_depth).map(|_| LdeMask::random()).collect();
+    let maskings: Vec<_> = (1..total_depth)
+      .map(|i| MaskingPolynomial::random(layout.degrees(i).to_vec()))
+      .collect();
+    let committed = layout.committed_array(&self.private_input(), &lde_masks, &maskings);
+    self.poly_prover = PolyCommitProver::new(transcript.hasher());
+    let commitment = commit(&mut self.poly_prover, &committed, layout.log_len);
+    transcript.append_digest(commitment);
+
+    let (zero, one) = (F::zero(), F::one());
+    let capacity = self.aritmetic_circuit.circuit[total_depth - 1].bit_length;
+    let mut alpha = one;
+    let mut beta = zero;
+    let mut r_0 = transcript.challenges(capacity);
+    let mut r_1 = transcript.challenges(capacity);
+    let mut claim = DenseMultilinear::evaluate_table(&output, &r_0);
+
+    let mut layers = Vec::with_capacity(total_depth - 1);
+    let mut zk_layers = Vec::with_capacity(total_depth - 1);
+    let mut claims = Vec::with_capacity(total_depth - 1);
+    for i in (1..total_depth).rev() {
+      let bit_length = self.aritmetic_circuit.circuit[i - 1].bit_length;
+      let masking = &maskings[i - 1];
+      let upper = lde_masks.get(i).copied();
+      self.lde_mask = lde_masks[i - 1];
+
+      let mask_sum = masking.sum();
+      transcript.append_field_element(mask_sum);
+      let rho = transcript.challenge();
+
+      // T(w) = alpha Z(r_0) R(r_0[0], w) + beta Z(r_1) R(r_1[0], w)
+      let weights = [(alpha, &r_0), (beta, &r_1)].map(|(scale, r)| (scale * vanishing(r), r[0]));
+      let t = upper.map_or_else(LinearPoly::zero, |mask| {
+        weights.iter().fold(LinearPoly::zero(), |t, &(weight, z)| {
+          let at_zero = mask.eval(z, zero);
+          t + LinearPoly::new(weight * (mask.eval(z, one) - at_zero), weight * at_zero)
+        })
+      });
+      // the claim, but for T, is the sum of P
+      let p_sum = claim - t.eval(zero) - t.eval(one);
+      let mut message = vec![p_sum + t.b, t.a - p_sum];
+      add_scaled(&mut message, &masking.message(&[]), rho);
+      let w_message = LinearPoly::new(message[1], message[0]);
+      transcript.absorb(&w_message);
+      let r_w = transcript.challenge();
+
+      let mut rounds = ZkRounds {
+        masking,
+        rho,
+        t_w: t.eval(r_w),
+        eq_zero: one,
+        point: vec![r_w],
+      };
+      self.sumcheck_init(
+        i,
+        self.aritmetic_circuit.circuit[i].bit_length,
+        bit_length,
+        bit_length,
+        alpha * (one - r_w),
+        beta * (one - r_w),
+        r_0.clone(),
+        r_1.clone(),
+      );
+      self.sumcheck_phase1_init();
+      let (phase1, phase1_last, r_u) = self.zk_phase(&mut rounds, transcript, false);
+
+      self.sumcheck_phase2_init(r_u[bit_length - 1], r_u.clone());
+      transcript.append_field_element(self.v_u);
+      let (phase2, phase2_last, r_v) = self.zk_phase(&mut rounds, transcript, true);
+
+      let (v_u, v_v) = self.sumcheck_finalize(r_v[bit_length - 1]);
+      // at layer 1, v is fixed to zero and the verifier doesn't need V(0),
+      // an input gate
+      let v_v = if i == 1 {
+        zero
+      } else {
+        v_v + self.lde_mask.extension(&r_v)
+      };
+      transcript.append_field_element(v_v);
+
+      let lde = upper.map(|mask| weights.map(|(_, z)| (z, mask.eval(z, r_w))));
+      let lde_mask = lde.map_or([zero; 2], |lde| lde.map(|(_, value)| value));
+      let mask_value = masking.eval(&rounds.point);
+      transcript.absorb(&lde_mask[..]);
+      transcript.append_field_element(mask_value);
+
+      layers.push(LayerProof {
+        phase1,
+        phase2,
+        v_u,
+        v_v,
+      });
+      zk_layers.push(ZkLayerProof {
+        mask_sum,
+        w_message,
+        phase1_last,
+        phase2_last,
+        lde_mask,
+        mask_value,
+      });
+      claims.push(LayerClaims {
+        layer: i,
+        lde,
+        point: rounds.point,
+        mask_value,
+      });
+
+      alpha = transcript.challenge();
+      beta = transcript.challenge();
+      claim = if i != 1 {
+        alpha * v_u + beta * v_v
+      } else {
+        v_u
+      };
+      r_0 = r_u;
+      r_1 = r_v;
+    }
+    self.lde_mask = LdeMask::default();
+
+    let lambda = transcript.challenge();
+    let rho = transcript.challenge();
+    let (mut public, _) = layout.opening(&r_0, claim, &claims, lambda);
+    weigh_public_inputs(&mut public, self.public_input_count, rho);
+    let opening = self.poly_prover.open(&public, transcript);
+
+    GkrProof {
+      hash: transcript.hasher(),
+      output,
+      layers,
+      commitment,
+      opening,
+      zk: Some(ZkProof { layers: zk_layers }),
+    }
+  }
+
+  /// Runs the rounds of phase 1, or phase 2, of the zero knowledge sumcheck,
+  /// returning the messages of all rounds but the last one, the message of
+  /// the last round and the challenges
+  fn zk_phase(
+    &mut self,
+    rounds: &mut ZkRounds<F>,
+    transcript: &mut Transcript,
+    phase2: bool,
+  ) -> (Vec<QuadraticPoly<F>>, QuadruplePoly<F>, Vec<F>) {
+    let bit_length = self.length_u;
+    let mut messages = Vec::with_capacity(bit_length);
+    let mut challenges = Vec::with_capacity(bit_length);
+    // layer 1 only relays the input layer, so v is fixed to zero there
+    let fixed = phase2 && self.sumcheck_layer_id == 1;
+    let challenge = |transcript: &mut Transcript, rounds: &mut ZkRounds<F>| {
+      let r = if fixed {
+        F::zero()
+      } else {
+        transcript.challenge()
+      };
+      rounds.bind(r);
+      r
+    };
+
+    let mut previous_random = F::zero();
+    for j in 0..bit_length - 1 {
+      let poly = quadratic(&self.zk_message(rounds, phase2, previous_random, j));
+      transcript.absorb(&poly);
+      messages.push(poly);
+      previous_random = challenge(transcript, rounds);
+      challenges.push(previous_random);
+    }
+    let mut last = self.zk_message(rounds, phase2, previous_random, bit_length - 1);
+    add_scaled(&mut last, &self.extension_share(&challenges), F::one());
+    let last = quadruple(&last);
+    transcript.absorb(&last);
+    challenges.push(challenge(transcript, rounds));
+    (messages, last, challenges)
+  }
+
+  /// Message of round `current_bit` of a phase, from degree 0 up: the message
+  /// of the plain sumcheck plus the shares of `T(r_w) eq((u, v), 0)` and of
+  /// the masking polynomial
+  fn zk_message(
+    &mut self,
+    rounds: &ZkRounds<F>,
+    phase2: bool,
+    previous_random: F,
+    current_bit: usize,
+  ) -> Vec<F> {
+    let poly = if phase2 {
+      self.sumcheck_phase2_update(previous_random, current_bit)
+    } else {
+      self.sumcheck_phase1_update(previous_random, current_bit)
+    };
+    let mut message = vec![poly.c, poly.b, poly.a];
+    let t_share = rounds.t_w * rounds.eq_zero;
+    add_scaled(&mut message, &[t_share, -t_share], F::one());
+    add_scaled(
+      &mut message,
+      &rounds.masking.message(&rounds.point),
+      rounds.rho,
+    );
+    message
+  }
+
+  /// Share of the randomized extension of the previous layer in the message
+  /// of the last round of a phase, from degree 0 up: `A(x) Z(bound, x) S(y)`,
+  /// with `A` the entry left of `add_mult_sum`, `S(z) = R(z, 0) + R(z, 1)`
+  /// and `y` the first variable of the phase. `bound` holds the challenges of
+  /// the previous rounds of the phase.
+  fn extension_share(&self, bound: &[F]) -> Vec<F> {
+    let (zero, one) = (F::zero(), F::one());
+    let s_0 = self.lde_mask.sum(zero);
+    let s = match bound.first() {
+      Some(&y) => LinearPoly::maps(self.lde_mask.sum(y)),
+      None => LinearPoly::new(self.lde_mask.sum(one) - s_0, s_0),
+    };
+    let mult = match self.mode {
+      SumcheckMode::Dense => self.add_mult_sum[0].clone(),
+      SumcheckMode::Sparse => self
+        .sparse_tables
+        .first()
+        .map_or_else(LinearPoly::zero, |(_, mult, _)| mult.clone()),
+    };
+    let q = mult * s;
+    let scale = vanishing(bound);
+    // q(x) (x - x^2)
+    [zero, q.c, q.b - q.c, q.a - q.b, -q.a]
+      .map(|c| scale * c)
+      .to_vec()
+  }
+}
@@ -1,488 +1,215 @@
 //#![feature(core_intrinsics)]
 use infrastructure::constants::LOG_SLICE_NUMBER;
-use infrastructure::constants::SLICE_NUMBER;
-use infrastructure::my_hash::HashDigest;
+use infrastructure::rs_polynomial::inverse_fast_fourier_transform;
+use infrastructure::transcript::Transcript;
 use std::fs;
-use std::fs::read_to_string;
-use std::io;
-use std::process;
-use vpd::fri::FRIContext;
 // use std::borrow::Borrow;
 // use std::clone;
 
 use poly_commitment::PolyCommitVerifier;
-use prime_field::FieldElement;
+use prime_field::{Field, FieldElement};
 use std::fs::File;
-use std::io::BufRead;
-use std::io::BufReader;
 use std::io::{Error, Write};
 use std::mem;
 use std::time;
 
-use crate::circuit_fast_track::Gate;
-use crate::circuit_fast_track::Layer;
-use crate::circuit_fast_track::LayeredCircuit;
+use crate::circuit_fast_track::CircuitParseError;
+use crate::circuit_fast_track::{GateKind, GateRegistry, LayeredCircuit};
+use crate::multilinear::{DenseMultilinear, EqPolynomial, SparseMultilinear};
 use crate::polynomial::QuadraticPoly;
-use crate::prover::ZkProver;
+use crate::proof::{committed_log_len, prefix_point, weigh_public_inputs, GkrProof};
+use crate::witness::PublicIo;
+
+mod zk;
 
 #[derive(Default, Debug)]
-pub struct VerifierContext {
-  pub q_eval_real: Vec<FieldElement>,
-  pub q_eval_verifier: Vec<FieldElement>,
-  pub q_ratio: Vec<FieldElement>,
+pub struct VerifierContext<F = FieldElement> {
+  pub q_eval_verifier: Vec<F>,
+  pub q_ratio: Vec<F>,
 }
 
 #[derive(Default, Debug)]
 
-pub struct ZkVerifier {
+pub struct ZkVerifier<F = FieldElement> {
   //pub prover: zk_prover, // ZY suggestion
   pub proof_size: usize,
   pub v_time: f64,
+  pub verification_time: f64,
+  pub predicates_calc_time: f64,
+  pub verification_rdl_time: f64,
   pub poly_verifier: PolyCommitVerifier,
   /** @name Randomness&Const
   	* Storing randomness or constant for simplifying computation*/
-  beta_g_r0_first_half: Vec<FieldElement>,
-  beta_g_r0_second_half: Vec<FieldElement>,
-  beta_g_r1_first_half: Vec<FieldElement>,
-  beta_g_r1_second_half: Vec<FieldElement>,
-  beta_u_first_half: Vec<FieldElement>,
-  beta_u_second_half: Vec<FieldElement>,
-  beta_v_first_half: Vec<FieldElement>,
-  beta_v_second_half: Vec<FieldElement>,
-
-  beta_g_r0_block_first_half: Vec<FieldElement>,
-  beta_g_r0_block_second_half: Vec<FieldElement>,
-  beta_g_r1_block_first_half: Vec<FieldElement>,
-  beta_g_r1_block_second_half: Vec<FieldElement>,
-  beta_u_block_first_half: Vec<FieldElement>,
-  beta_u_block_second_half: Vec<FieldElement>,
-  beta_v_block_first_half: Vec<FieldElement>,
-  beta_v_block_second_half: Vec<FieldElement>,
-
-  pub aritmetic_circuit: LayeredCircuit, // The circuit
+  beta_g_r0_first_half: Vec<F>,
+  beta_g_r0_second_half: Vec<F>,
+  beta_g_r1_first_half: Vec<F>,
+  beta_g_r1_second_half: Vec<F>,
+  beta_u_first_half: Vec<F>,
+  beta_u_second_half: Vec<F>,
+  beta_v_first_half: Vec<F>,
+  beta_v_second_half: Vec<F>,
+
+  beta_g_r0_block_first_half: Vec<F>,
+  beta_g_r0_block_second_half: Vec<F>,
+  beta_g_r1_block_first_half: Vec<F>,
+  beta_g_r1_block_second_half: Vec<F>,
+  beta_u_block_first_half: Vec<F>,
+  beta_u_block_second_half: Vec<F>,
+  beta_v_block_first_half: Vec<F>,
+  beta_v_block_second_half: Vec<F>,
+  /// `repeat_factors` of the blocks of a parallel layer, for `r_0` and `r_1`
+  repeat_r0: [[F; 2]; 2],
+  repeat_r1: [[F; 2]; 2],
+
+  pub aritmetic_circuit: LayeredCircuit<F>, // The circuit
   //zk_prover *p; //!< The prover
-  vpd_randomness: Vec<FieldElement>,
-  one_minus_vpd_randomness: Vec<FieldElement>,
+  vpd_randomness: Vec<F>,
 
-  pub ctx: VerifierContext,
+  pub ctx: VerifierContext<F>,
 }
 
-impl ZkVerifier {
+impl<F: Field> ZkVerifier<F> {
   pub fn new() -> Self {
     Default::default()
   }
-  //ToDo!: Improve unwrap() handling, use "?" operator. Improve println!(), could use eprintln()
-  pub fn read_circuit(&mut self, circuit_path: &str, meta_path: &str) -> Result<usize, io::Error> {
-    let d: usize;
-    let circuit_content = read_to_string(&circuit_path)?;
-    let mut circuit_lines = circuit_content.lines();
-    if let Some(value) = circuit_lines.next() {
-      d = value.parse().unwrap_or_else(|err| {
-        eprintln!("Problem parsing total number of layers from file: {}", err);
-        process::exit(1)
-      });
-    }
-    let circuit_file = File::open(circuit_path)?;
-    let circuit_reader = BufReader::new(circuit_file);
-    let mut lines_iter = circuit_reader.lines().map(|l| l.unwrap());
-    let d: usize = lines_iter.next().unwrap().parse().unwrap();
-
-    self.aritmetic_circuit.circuit = vec![Layer::new(); d + 1];
-    self.aritmetic_circuit.total_depth = d + 1;
-
-    let mut max_bit_length: Option<usize> = None;
-    let mut n_pad: usize;
-    for i in 1..=d {
-      let pad_requirement: usize;
-
-      let next_line = lines_iter.next().unwrap();
-      let mut next_line_splited = next_line.split_whitespace();
-      let mut number_gates: usize = next_line_splited.next().unwrap().parse().unwrap();
-      //println!("number_gates: {}", number_gates);
-      if d > 3 {
-        pad_requirement = 17;
-      } else {
-        pad_requirement = 15;
-      }
-      if i == 1 && number_gates < (1 << pad_requirement) {
-        n_pad = 1 << pad_requirement;
-      } else {
-        n_pad = number_gates;
-      }
-
-      if i != 1 {
-        if number_gates == 1 {
-          self.aritmetic_circuit.circuit[i].gates = vec![Gate::new(); 2];
-        } else {
-          self.aritmetic_circuit.circuit[i].gates = vec![Gate::new(); n_pad];
-        }
-      } else {
-        if number_gates == 1 {
-          self.aritmetic_circuit.circuit[0].gates = vec![Gate::new(); 2];
-          self.aritmetic_circuit.circuit[1].gates = vec![Gate::new(); 2];
-        } else {
-          self.aritmetic_circuit.circuit[0].gates = vec![Gate::new(); n_pad];
-          self.aritmetic_circuit.circuit[1].gates = vec![Gate::new(); n_pad];
-        }
-      }
-      let mut max_gate: Option<usize> = None;
-      let mut previous_g: Option<usize> = None;
-
-      for j in 0..number_gates {
-        let ty: usize = next_line_splited.next().unwrap().parse().unwrap();
-        let g: usize = next_line_splited.next().unwrap().parse().unwrap();
-        let u: usize = next_line_splited.next().unwrap().parse().unwrap();
-        let mut v: usize = next_line_splited.next().unwrap().parse().unwrap();
-        //println!("{} {} {} {} ", ty, g, u, v);
-        /*panic!();*/
-        if ty != 3 {
-          if ty == 5 {
-            assert!(u < (1 << self.aritmetic_circuit.circuit[i - 1].bit_length));
-            assert!(v > u && v <= (1 << self.aritmetic_circuit.circuit[i - 1].bit_length));
-          } else {
-            if !(u < (1 << self.aritmetic_circuit.circuit[i - 1].bit_length)) {
-              println!(
-                "{} {} {} {} {} ",
-                ty,
-                g,
-                u,
-                v,
-                (1 << self.aritmetic_circuit.circuit[i - 1].bit_length)
-              );
-            }
-            assert!(u < (1 << self.aritmetic_circuit.circuit[i - 1].bit_length));
-            if !(v < (1 << self.aritmetic_circuit.circuit[i - 1].bit_length)) {
-              println!(
-                "{} {} {} {} {} ",
-                ty,
-                g,
-                u,
-                v,
-                (1 << self.aritmetic_circuit.circuit[i - 1].bit_length)
-              );
-            }
-            assert!(v < (1 << self.aritmetic_circuit.circuit[i - 1].bit_length));
-          }
-        }
-        if ty == 6 {
-          if v != 0 {
-            //todo: improve error handling
-            println!("WARNING, v!=0 for NOT gate")
-          }
-          v = 0;
-        }
-        if ty == 10 {
-          if v != 0 {
-            //todo: improve error handling
-            println!("WARNING, v!=0 for relay gate {}", i)
-          }
-          v = 0;
-        }
-        if ty == 13 {
-          assert!(u == v);
-        }
-        match previous_g {
-          Some(v) => {
-            if v + 1 != g {
-              println!(
-                "Error, gates must be in sorted order, and full [0, 2^n - 1]. {} {} {} {}",
-                i,
-                j,
-                g,
-                previous_g.unwrap()
-              );
-              panic!()
-            }
-          }
-          None => {
-            if g != 0 {
-              println!(
-                "Error, gates must be in sorted order, and full [0, 2^n - 1]. {} {} {} {}",
-                i,
-                j,
-                g,
-                previous_g.unwrap()
-              );
-              panic!()
-            }
-          }
-        }
-        previous_g = Some(g);
-        if i != 1 {
-          self.aritmetic_circuit.circuit[i].gates[g] = Gate::from_params(ty, u, v);
-          /*println!("{} {} {} {} ", ty, g, u, v);
-          panic!();*/
-        } else {
-          assert!(ty == 2 || ty == 3);
-          self.aritmetic_circuit.circuit[1].gates[g] = Gate::from_params(4, g, 0);
-          self.aritmetic_circuit.circuit[0].gates[g] = Gate::from_params(ty, u, v);
-        }
-      }
-      if i == 1 {
-        for g in number_gates..n_pad {
-          self.aritmetic_circuit.circuit[1].gates[g] = Gate::from_params(4, g, 0);
-          self.aritmetic_circuit.circuit[0].gates[g] = Gate::from_params(3, 0, 0);
-        }
-        number_gates = n_pad;
-        previous_g = Some(n_pad - 1);
-      }
-
-      max_gate = previous_g;
-      let mut cnt = 0;
-      while max_gate > Some(0) {
-        cnt += 1;
-        match max_gate.as_mut() {
-          Some(v) => *v >>= 1,
-          None => {}
-        }
-      }
-      max_gate = Some(1);
-      while cnt > 0 {
-        match max_gate.as_mut() {
-          Some(v) => *v <<= 1,
-          None => {}
-        }
-        cnt -= 1;
-      }
-      let mut mx_gate = max_gate;
-      while mx_gate > Some(0) {
-        cnt += 1;
-        match mx_gate.as_mut() {
-          Some(v) => *v >>= 1,
-          None => {}
-        }
-      }
-      if number_gates == 1 {
-        //add a dummy gate to avoid ill-defined layer.
-        if i != 1 {
-          self.aritmetic_circuit.circuit[i].gates[max_gate.unwrap()] = Gate::from_params(2, 0, 0);
-          self.aritmetic_circuit.circuit[i].bit_length = cnt;
-        } else {
-          self.aritmetic_circuit.circuit[0].gates[max_gate.unwrap()] = Gate::from_params(2, 0, 0);
-          self.aritmetic_circuit.circuit[0].bit_length = cnt;
-          self.aritmetic_circuit.circuit[1].gates[max_gate.unwrap()] = Gate::from_params(4, 1, 0);
-          self.aritmetic_circuit.circuit[1].bit_length = cnt;
-        }
-      } else {
-        self.aritmetic_circuit.circuit[i].bit_length = cnt - 1;
-        if i == 1 {
-          self.aritmetic_circuit.circuit[0].bit_length = cnt - 1;
-        }
-      }
-      //todo: improve error handling
-      println!(
-        "layer {}, bit_length {}",
-        i, self.aritmetic_circuit.circuit[i].bit_length
-      );
-      match max_bit_length.as_mut() {
-        Some(v) => {
-          if self.aritmetic_circuit.circuit[i].bit_length > *v {
-            *v = self.aritmetic_circuit.circuit[i].bit_length
-          }
-        }
-        None => max_bit_length = Some(self.aritmetic_circuit.circuit[i].bit_length),
-      }
-    }
-    self.aritmetic_circuit.circuit[0].is_parallel = false;
-
-    let meta_file = File::open(meta_path).unwrap();
-    let meta_reader = BufReader::new(meta_file);
-
-    let mut meta_lines_iter = meta_reader.lines().map(|l| l.unwrap());
-    for i in 1..=d {
-      let meta_line = meta_lines_iter.next().unwrap();
-      let mut meta_line_splited = meta_line.split_whitespace();
-
-      let is_para: usize = meta_line_splited.next().unwrap().parse().unwrap();
-      self.aritmetic_circuit.circuit[i].block_size =
-        meta_line_splited.next().unwrap().parse().unwrap();
-      self.aritmetic_circuit.circuit[i].repeat_num =
-        meta_line_splited.next().unwrap().parse().unwrap();
-      self.aritmetic_circuit.circuit[i].log_block_size =
-        meta_line_splited.next().unwrap().parse().unwrap();
-      self.aritmetic_circuit.circuit[i].log_repeat_num =
-        meta_line_splited.next().unwrap().parse().unwrap();
-
-      if is_para != 0 {
-        assert!(
-          1 << self.aritmetic_circuit.circuit[i].log_repeat_num
-            == self.aritmetic_circuit.circuit[i].repeat_num
-        );
-      }
-      if is_para != 0 {
-        self.aritmetic_circuit.circuit[i].is_parallel = true;
-      } else {
-        self.aritmetic_circuit.circuit[i].is_parallel = false;
-      }
-    }
+  /// Reads the circuit and meta files, returning the largest bit length of
+  /// the layers
+  pub fn read_circuit(
+    &mut self,
+    circuit_path: &str,
+    meta_path: &str,
+  ) -> Result<usize, CircuitParseError> {
+    self.read_circuit_with_gates(circuit_path, meta_path, &GateRegistry::new())
+  }
 
-    Self::init_array(self, max_bit_length.unwrap());
-    Ok(max_bit_length.unwrap())
-    //todo!();
+  /// Same as `read_circuit`, for circuits using the custom gates of
+  /// `registry`
+  pub fn read_circuit_with_gates(
+    &mut self,
+    circuit_path: &str,
+    meta_path: &str,
+    registry: &GateRegistry<F>,
+  ) -> Result<usize, CircuitParseError> {
+    self.aritmetic_circuit = LayeredCircuit::read_with_gates(circuit_path, meta_path, registry)?;
+    let max_bit_length = self.aritmetic_circuit.max_bit_length();
+    self.init_array(max_bit_length);
+    Ok(max_bit_length)
   }
 
   pub fn init_array(&mut self, max_bit_length: usize) {
     let first_half_len = max_bit_length / 2;
     let second_half_len = max_bit_length - first_half_len;
 
-    self.beta_g_r0_first_half = vec![FieldElement::zero(); 1 << first_half_len];
-    self.beta_g_r0_second_half = vec![FieldElement::zero(); 1 << second_half_len];
-    self.beta_g_r1_first_half = vec![FieldElement::zero(); 1 << first_half_len];
-    self.beta_g_r1_second_half = vec![FieldElement::zero(); 1 << second_half_len];
-    self.beta_v_first_half = vec![FieldElement::zero(); 1 << first_half_len];
-    self.beta_v_second_half = vec![FieldElement::zero(); 1 << second_half_len];
-    self.beta_u_first_half = vec![FieldElement::zero(); 1 << first_half_len];
-    self.beta_u_second_half = vec![FieldElement::zero(); 1 << second_half_len];
-
-    self.beta_g_r0_block_first_half = vec![FieldElement::zero(); 1 << first_half_len];
-    self.beta_g_r0_block_second_half = vec![FieldElement::zero(); 1 << second_half_len];
-    self.beta_g_r1_block_first_half = vec![FieldElement::zero(); 1 << first_half_len];
-    self.beta_g_r1_block_second_half = vec![FieldElement::zero(); 1 << second_half_len];
-    self.beta_v_block_first_half = vec![FieldElement::zero(); 1 << first_half_len];
-    self.beta_v_block_second_half = vec![FieldElement::zero(); 1 << second_half_len];
-    self.beta_u_block_first_half = vec![FieldElement::zero(); 1 << first_half_len];
-    self.beta_u_block_second_half = vec![FieldElement::zero(); 1 << second_half_len];
+    self.beta_g_r0_first_half = vec![F::zero(); 1 << first_half_len];
+    self.beta_g_r0_second_half = vec![F::zero(); 1 << second_half_len];
+    self.beta_g_r1_first_half = vec![F::zero(); 1 << first_half_len];
+    self.beta_g_r1_second_half = vec![F::zero(); 1 << second_half_len];
+    self.beta_v_first_half = vec![F::zero(); 1 << first_half_len];
+    self.beta_v_second_half = vec![F::zero(); 1 << second_half_len];
+    self.beta_u_first_half = vec![F::zero(); 1 << first_half_len];
+    self.beta_u_second_half = vec![F::zero(); 1 << second_half_len];
+
+    self.beta_g_r0_block_first_half = vec![F::zero(); 1 << first_half_len];
+    self.beta_g_r0_block_second_half = vec![F::zero(); 1 << second_half_len];
+    self.beta_g_r1_block_first_half = vec![F::zero(); 1 << first_half_len];
+    self.beta_g_r1_block_second_half = vec![F::zero(); 1 << second_half_len];
+    self.beta_v_block_first_half = vec![F::zero(); 1 << first_half_len];
+    self.beta_v_block_second_half = vec![F::zero(); 1 << second_half_len];
+    self.beta_u_block_first_half = vec![F::zero(); 1 << first_half_len];
+    self.beta_u_block_second_half = vec![F::zero(); 1 << second_half_len];
   }
 
   //Decided to implemente the verify() function from orion repo
 
-  pub fn verify(&mut self, output_path: &String, bit_length: usize) -> bool {
-    // initialize the prover
-    let mut zk_prover = ZkProver::new();
-    //println!("zk {:?}", zk_prover.circuit_value[0]);
-    zk_prover.init_array(bit_length, &self.aritmetic_circuit);
-
+  /// Replays the GKR protocol on `proof`, drawing the challenges from
+  /// `transcript` in the same order as `ZkProver::prove`, against the
+  /// outputs and public inputs claimed in `public`. The claims left on the
+  /// input layer are checked against the public inputs and the opening of the
+  /// commitment to the rest of it. Zero knowledge proofs are checked by
+  /// `verify_zero_knowledge` instead.
+  pub fn verify(
+    &mut self,
+    proof: &GkrProof<F>,
+    public: &PublicIo<F>,
+    transcript: &mut Transcript,
+  ) -> bool {
     self.proof_size = 0;
     //there is a way to compress binlinear pairing element
     let mut verification_time: f64 = 0.0;
     let mut predicates_calc_time: f64 = 0.0;
     let mut verification_rdl_time: f64 = 0.0;
 
-    //Below function is not implemented neither in virgo repo nor orion repo
-    //prime_field::init_random();
-
-    //Below function is not implemented neither in virgo repo nor orion repo
-    //self.prover.unwrap().proof_init();
-
-    let result = zk_prover.evaluate();
-    let mut alpha = FieldElement::real_one();
-    let mut beta = FieldElement::zero();
-    //	random_oracle oracle; // Orion just declare the variable but dont use it later
-    let capacity =
-      self.aritmetic_circuit.circuit[self.aritmetic_circuit.total_depth - 1].bit_length;
-    let mut r_0 = Self::generate_randomness(capacity);
-    let mut r_1 = Self::generate_randomness(capacity);
-    let mut one_minus_r_0 = vec![FieldElement::zero(); capacity];
-    let mut one_minus_r_1 = vec![FieldElement::zero(); capacity];
-
-    for i in 0..capacity {
-      one_minus_r_0.push(FieldElement::real_one() - r_0[i]);
-      one_minus_r_1.push(FieldElement::real_one() - r_1[i]);
+    let total_depth = self.aritmetic_circuit.total_depth;
+    let capacity = self.aritmetic_circuit.circuit[total_depth - 1].bit_length;
+    if proof.hash != transcript.hasher() {
+      println!("Verification fail, proof uses the {} hash", proof.hash);
+      return false;
     }
-    let t_a = time::Instant::now();
-
-    println!("Calc V_output(r)");
-    let mut a_0 = zk_prover.v_res(
-      one_minus_r_0.clone(),
-      r_0.clone(),
-      result.clone(),
-      capacity,
-      1 << capacity,
-    );
-    // }
-    let time_span = t_a.elapsed();
-    println!("    Time:: {}", time_span.as_secs_f64());
-    a_0 = alpha * a_0;
-    let mut alpha_beta_sum = a_0;
-    let _direct_relay_value: FieldElement;
-
-    for i in (1..=(self.aritmetic_circuit.total_depth - 1)).rev() {
-      let _rho = FieldElement::new_random();
-
-      zk_prover.sumcheck_init(
-        i,
-        self.aritmetic_circuit.circuit[i].bit_length,
-        self.aritmetic_circuit.circuit[i - 1].bit_length,
-        self.aritmetic_circuit.circuit[i - 1].bit_length,
-        alpha.clone(),
-        beta.clone(),
-        r_0.clone(),
-        r_1.clone(),
-        one_minus_r_0.clone(),
-        one_minus_r_1.clone(),
-      );
-
-      zk_prover.sumcheck_phase1_init();
-
-      let mut previous_random = FieldElement::from_real(0);
-      //next level random
-      let r_u = Self::generate_randomness(self.aritmetic_circuit.circuit[i - 1].bit_length);
-      let mut r_v = Self::generate_randomness(self.aritmetic_circuit.circuit[i - 1].bit_length);
-
-      let direct_relay_value = alpha * Self::direct_relay(self, i, &r_0, &r_u)
-        + beta * Self::direct_relay(self, i, &r_1, &r_u);
-
-      if i == 1 {
-        for j in 0..self.aritmetic_circuit.circuit[i - 1].bit_length {
-          r_v[j] = FieldElement::zero();
-        }
-      }
-
-      //V should test the maskR for two points, V does random linear combination of these points first
-      let _random_combine = Self::generate_randomness(1)[0];
+    if proof.zk.is_some() {
+      println!("Verification fail, proof is zero knowledge");
+      return false;
+    }
+    if proof.output.len() != 1 << capacity || proof.layers.len() != total_depth - 1 {
+      println!("Verification fail, malformed proof");
+      return false;
+    }
+    let output = match self.claimed_output(proof, public) {
+      Some(output) => output,
+      None => return false,
+    };
+    transcript.absorb(&output[..]);
+    transcript.absorb(&public.inputs[..]);
+    transcript.append_digest(proof.commitment);
+    self.proof_size += proof.output.len() * mem::size_of::<F>();
+
+    let mut alpha = F::one();
+    let mut beta = F::zero();
+    let mut r_0 = transcript.challenges(capacity);
+    let mut r_1 = transcript.challenges(capacity);
 
-      //Every time all one test to V, V needs to do a linear combination for security.
-      let _linear_combine = Self::generate_randomness(1)[0]; // mem leak
+    let t_a = time::Instant::now();
+    let a_0 = Self::v_in(&r_0, &output);
+    verification_time += t_a.elapsed().as_secs_f64();
 
-      let mut one_minus_r_u =
-        vec![FieldElement::zero(); self.aritmetic_circuit.circuit[i - 1].bit_length];
-      let mut one_minus_r_v =
-        vec![FieldElement::zero(); self.aritmetic_circuit.circuit[i - 1].bit_length];
+    let mut alpha_beta_sum = alpha * a_0;
 
-      for j in 0..(self.aritmetic_circuit.circuit[i - 1].bit_length) {
-        one_minus_r_u.push(FieldElement::from_real(1) - r_u[j]);
-        one_minus_r_v.push(FieldElement::from_real(1) - r_v[j]);
+    for (i, layer) in (1..total_depth).rev().zip(proof.layers.iter()) {
+      let bit_length = self.aritmetic_circuit.circuit[i - 1].bit_length;
+      if layer.phase1.len() != bit_length || layer.phase2.len() != bit_length {
+        println!("Verification fail, malformed proof, circuit {}", i);
+        return false;
       }
 
-      for j in 0..(self.aritmetic_circuit.circuit[i - 1].bit_length) {
-        let poly = zk_prover.sumcheck_phase1_update(previous_random, j);
+      let mut r_u = Vec::with_capacity(bit_length);
+      for (j, poly) in layer.phase1.iter().enumerate() {
+        transcript.absorb(poly);
+        self.proof_size += mem::size_of::<QuadraticPoly<F>>();
 
-        self.proof_size += mem::size_of::<QuadraticPoly>();
-        previous_random = r_u[j];
-        //todo: Debug eval() fn
-        if poly.eval(&FieldElement::zero()) + poly.eval(&FieldElement::real_one()) != alpha_beta_sum
-        {
+        if poly.eval(&F::zero()) + poly.eval(&F::one()) != alpha_beta_sum {
           //todo: Improve error handling
           println!(
             "Verification fail, phase1, circuit {}, current bit {}",
             i, j
           );
           return false;
-        } else {
-          //println!(
-          //  "Verification fail, phase1, circuit {}, current bit {}",
-          //i, j
-          //);
         }
-        alpha_beta_sum = poly.eval(&r_u[j].clone());
+        let r = transcript.challenge();
+        alpha_beta_sum = poly.eval(&r);
+        r_u.push(r);
       }
-      //	std::cerr << "Bound v start" << std::endl;
 
-      zk_prover.sumcheck_phase2_init(previous_random, r_u.clone(), one_minus_r_u.clone());
-      let mut previous_random = FieldElement::zero();
-      for j in 0..self.aritmetic_circuit.circuit[i - 1].bit_length {
-        if i == 1 {
-          r_v[j] = FieldElement::zero();
-        }
-        let poly = zk_prover.sumcheck_phase2_update(previous_random, j);
-        self.proof_size += mem::size_of::<QuadraticPoly>();
-        //poly.c = poly.c; ???
+      let direct_relay_value = alpha * Self::direct_relay(self, i, &r_0, &r_u)
+        + beta * Self::direct_relay(self, i, &r_1, &r_u);
+
+      transcript.append_field_element(layer.v_u);
+      self.proof_size += mem::size_of::<F>();
 
-        previous_random = r_v[j].clone();
+      let mut r_v = Vec::with_capacity(bit_length);
+      for (j, poly) in layer.phase2.iter().enumerate() {
+        transcript.absorb(poly);
+        self.proof_size += mem::size_of::<QuadraticPoly<F>>();
 
-        if poly.eval(&FieldElement::zero())
-          + poly.eval(&FieldElement::real_one())
-          + direct_relay_value * zk_prover.v_u
+        if poly.eval(&F::zero()) + poly.eval(&F::one()) + direct_relay_value * layer.v_u
           != alpha_beta_sum
         {
           //todo: Improve error handling
@@ -491,101 +218,40 @@ impl ZkVerifier {
             i, j
           );
           return false;
-        } else {
-          //println!(
-          //  "Verification fail, phase1, circuit {}, current bit {}",
-          //i, j
-          //);
         }
-        alpha_beta_sum = poly.eval(&r_v[j]) + direct_relay_value * zk_prover.v_u;
+        let r = if i == 1 {
+          F::zero()
+        } else {
+          transcript.challenge()
+        };
+        alpha_beta_sum = poly.eval(&r) + direct_relay_value * layer.v_u;
+        r_v.push(r);
       }
-      //Add one more round for maskR
-      //quadratic_poly poly p->sumcheck_finalroundR(previous_random, C.current[i - 1].bit_length);
-
-      let final_claims = zk_prover.sumcheck_finalize(previous_random);
 
-      let v_u = final_claims.0;
-      let v_v = final_claims.1;
+      let v_u = layer.v_u;
+      let v_v = layer.v_v;
+      transcript.append_field_element(v_v);
+      self.proof_size += mem::size_of::<F>();
 
       let predicates_calc = time::Instant::now();
-      Self::beta_init(
-        self,
-        i,
-        alpha,
-        beta,
-        &r_0,
-        &r_1,
-        &r_u,
-        &r_v,
-        &one_minus_r_0,
-        &one_minus_r_1,
-        &one_minus_r_u,
-        &one_minus_r_v,
-      );
-
-      let predicates_value = Self::predicates(
-        self,
-        i,
-        r_0.clone(),
-        r_1.clone(),
-        r_u.clone(),
-        r_v.clone(),
-        alpha,
-        beta,
-      );
+      Self::beta_init(self, i, alpha, beta, &r_0, &r_1, &r_u, &r_v);
 
-      //todo
+      let predicates_value = Self::predicates(self, i, v_u, v_v);
 
       let predicates_calc_span = predicates_calc.elapsed();
-      //println!("predicates_calc_span: {:?}", predicates_calc_span);
-      if self.aritmetic_circuit.circuit[i].is_parallel == false {
+      if !self.aritmetic_circuit.circuit[i].is_parallel {
         verification_rdl_time += predicates_calc_span.as_secs_f64();
       }
       verification_time += predicates_calc_span.as_secs_f64();
       predicates_calc_time += predicates_calc_span.as_secs_f64();
 
-      let mult_value = predicates_value[1];
-      let add_value = predicates_value[0];
-      let not_value = predicates_value[6];
-      let minus_value = predicates_value[7];
-      let xor_value = predicates_value[8];
-      let naab_value = predicates_value[9];
-      let sum_value = predicates_value[5];
-      let relay_value = predicates_value[10];
-      let exp_sum_value = predicates_value[12];
-      let bit_test_value = predicates_value[13];
-      let custom_comb_value = predicates_value[14];
-
-      let mut r = Vec::new();
-      for j in 0..self.aritmetic_circuit.circuit[i - 1].bit_length {
-        r.push(r_u[j].clone());
-      }
-      for j in 0..self.aritmetic_circuit.circuit[i - 1].bit_length {
-        r.push(r_v[j].clone());
-      }
-
-      if alpha_beta_sum
-        != (add_value * (v_u + v_v)
-          + mult_value * v_u * v_v
-          + not_value * (FieldElement::real_one() - v_u)
-          + minus_value * (v_u - v_v)
-          + xor_value * (v_u + v_v - FieldElement::from_real(2) * v_u * v_v)
-          + naab_value * (v_v - v_u * v_v)
-          + sum_value * v_u
-          + custom_comb_value * v_u
-          + relay_value * v_u
-          + exp_sum_value * v_u
-          + bit_test_value * (FieldElement::real_one() - v_v) * v_u)
-          + direct_relay_value * v_u
-      {
+      if alpha_beta_sum != predicates_value + direct_relay_value * v_u {
         //Todo: improve error handling
         println!("Verification fail, semi final, circuit level {}", i,);
         return false;
       }
-      let tmp_alpha = Self::generate_randomness(1);
-      let tmp_beta = Self::generate_randomness(1);
-      alpha = tmp_alpha[0];
-      beta = tmp_beta[0];
+      alpha = transcript.challenge();
+      beta = transcript.challenge();
 
       if i != 1 {
         alpha_beta_sum = alpha * v_u + beta * v_v;
@@ -594,117 +260,79 @@ impl ZkVerifier {
       }
       r_0 = r_u;
       r_1 = r_v;
-      one_minus_r_0 = one_minus_r_u;
-      one_minus_r_1 = one_minus_r_v;
     }
 
-    println!("GKR Prove Time: {}", zk_prover.total_time);
-    let mut all_sum = vec![FieldElement::zero(); SLICE_NUMBER];
-    println!(
-      "GKR witness size: {}",
-      1 << self.aritmetic_circuit.circuit[0].bit_length
-    );
-
-    todo!();
-    //From here need to change a lot
-    let mut fri_context = FRIContext::new();
-    let (merkle_root_l, scratch_pad) = commit_private_array(
-      &mut fri_context,
-      &mut zk_prover.poly_prover,
-      zk_prover.circuit_value[0].clone(),
-      self.aritmetic_circuit.circuit[0].bit_length,
-    );
-
-    println!("Merkle_root_l: {:?}", merkle_root_l);
-
-    self.ctx.q_eval_real =
-      vec![FieldElement::zero(); 1 << self.aritmetic_circuit.circuit[0].bit_length];
-    Self::dfs_for_public_eval(
-      self,
-      0,
-      FieldElement::real_one(),
-      r_0.clone(),
-      one_minus_r_0.clone(),
-      self.aritmetic_circuit.circuit[0].bit_length,
-      0,
-    );
-
-    let merkle_root_h = commit_public_array(
-      &mut fri_context,
-      &mut zk_prover.poly_prover,
-      self.ctx.q_eval_real.clone(),
-      self.aritmetic_circuit.circuit[0].bit_length,
-      alpha_beta_sum,
-      &mut all_sum,
-      scratch_pad,
-    );
-
-    println!("Merkle_root_h: {:?}", merkle_root_h);
-
-    self.proof_size += 2 * mem::size_of::<HashDigest>();
-    self.vpd_randomness = r_0.clone();
-    self.one_minus_vpd_randomness = one_minus_r_0.clone();
-
-    //Todo! Debug reference-borrowing
-
-    self.poly_verifier.pc_prover = zk_prover.poly_prover.clone();
-
-    let public_array = Self::public_array_prepare(
-      self,
-      r_0.clone(),
-      one_minus_r_0,
-      self.aritmetic_circuit.circuit[0].bit_length,
-    );
-    //prime_field::field_element *public_array = public_array_prepare_generic(q_eval_real, C.circuit[0].bit_length);
-
-    //let input_0_verify = true;
-    //let mut zk_pp = PolyCommitProver::default();
-
-    let input_0_verify = verify_poly_commitment(
-      &mut fri_context,
-      &mut zk_prover.poly_prover,
-      all_sum,
-      self.aritmetic_circuit.circuit[0].bit_length,
-      public_array,
-      &mut verification_time,
-      &mut self.proof_size,
-      &mut zk_prover.total_time,
-      merkle_root_l,
-      merkle_root_h,
-    );
-
-    //Todo! Debug time
-
-    zk_prover.total_time += self.poly_verifier.pc_prover.total_time_pc_p;
-    //let input_0_verify = input_0_verify.unwrap();
-    if !(input_0_verify.as_ref().ok() == Some(&0)) {
-      println!("Verification fail, input vpd");
-      println!("where:{:?} ", input_0_verify);
+    // V_in(r_u) and V_in(r_v) of layer 1, but for the public inputs, are
+    // checked against the commitment at once, the second one weighted by
+    // lambda, along with the committed array being zero on the public inputs
+    let t_in = time::Instant::now();
+    let lambda: F = transcript.challenge();
+    let rho: F = transcript.challenge();
+    let log_len = committed_log_len(&self.aritmetic_circuit);
+    let (r_u, scale_u) = prefix_point(&r_0, log_len);
+    let (r_v, scale_v) = prefix_point(&r_1, log_len);
+    let coefficients_u = self.public_array_prepare(&r_u, log_len);
+    let coefficients_v = self.public_array_prepare(&r_v, log_len);
+    let scale_v = lambda * scale_v;
+    let mut coefficients: Vec<_> = coefficients_u
+      .into_iter()
+      .zip(coefficients_v)
+      .map(|(u, v)| scale_u * u + scale_v * v)
+      .collect();
+    Self::weigh_public_coefficients(&mut coefficients, log_len, public.inputs.len(), rho);
+    let value = alpha_beta_sum - Self::public_input_value(&r_0, &public.inputs)
+      + lambda
+        * (proof.layers[total_depth - 2].v_v - Self::public_input_value(&r_1, &public.inputs));
+    verification_time += t_in.elapsed().as_secs_f64();
+
+    self.vpd_randomness = r_0;
+
+    self.verification_time = verification_time;
+    self.predicates_calc_time = predicates_calc_time;
+    self.verification_rdl_time = verification_rdl_time;
+    //verification rdl time is the non-parallel part of the circuit. In all of our experiments and most applications, it can be calculated in O(log n) or O(log^2 n) time. We didn't implement the fast method due to the deadline.
+    self.v_time = verification_time - verification_rdl_time;
+
+    if !self.verify_poly_commitment(proof, &coefficients, log_len, value, transcript) {
       return false;
-    } else {
-      println!("Verification pass");
-      println!("Prove Time: {}", zk_prover.total_time);
-      println!("Verification rdl time: {}", verification_rdl_time);
-      //verification rdl time is the non-parallel part of the circuit. In all of our experiments and most applications, it can be calculated in O(log n) or O(log^2 n) time. We didn't implement the fast method due to the deadline.
-      println!(
-        "Verification Time: {}",
-        verification_time - verification_rdl_time
-      );
-      self.v_time = verification_time - verification_rdl_time;
-      println!("Proof size(bytes): {} ", self.proof_size);
-
-      Self::write_file(
-        output_path,
-        zk_prover.total_time,
-        verification_time,
-        predicates_calc_time,
-        verification_rdl_time,
-        self.proof_size,
-      );
     }
+    println!("Verification pass");
+    println!("Verification rdl time: {}", verification_rdl_time);
+    println!("Verification Time: {}", self.v_time);
+    println!("Proof size(bytes): {} ", self.proof_size);
     true
   }
 
+  /// Claimed outputs of `public`, padded with zeros to the output layer,
+  /// if they are the outputs of `proof` and the public inputs fit in the
+  /// input gates
+  fn claimed_output(&self, proof: &GkrProof<F>, public: &PublicIo<F>) -> Option<Vec<F>> {
+    if public.inputs.len() > self.aritmetic_circuit.input_count {
+      println!("Verification fail, too many public inputs");
+      return None;
+    }
+    let mut output = public.outputs.clone();
+    if output.len() > proof.output.len() {
+      println!("Verification fail, too many claimed outputs");
+      return None;
+    }
+    output.resize(proof.output.len(), F::zero());
+    if output != proof.output {
+      println!("Verification fail, proof does not match the claimed outputs");
+      return None;
+    }
+    Some(output)
+  }
+
+  /// Part of the input layer's multilinear extension at `r` made of the
+  /// public inputs, which lead the input layer
+  fn public_input_value(r: &[F], inputs: &[F]) -> F {
+    let bit_length = inputs.len().next_power_of_two().trailing_zeros() as usize;
+    let (point, scale) = prefix_point(r, bit_length);
+    let inputs = SparseMultilinear::new(bit_length, inputs.iter().copied().enumerate().collect());
+    scale * inputs.evaluate(&point)
+  }
+
   pub fn write_file(
     output_path: &String,
     total_time: f64,
@@ -729,174 +357,70 @@ impl ZkVerifier {
     Ok(())
   }
 
-  pub fn public_array_prepare(
-    &mut self,
-    r: Vec<FieldElement>,
-    one_minus_r: Vec<FieldElement>,
-    log_length: usize,
-  ) -> Vec<FieldElement> {
-    self.ctx.q_eval_verifier = vec![FieldElement::zero(); 1 << (log_length - LOG_SLICE_NUMBER)];
-    self.ctx.q_ratio = vec![FieldElement::zero(); 1 << LOG_SLICE_NUMBER];
-    //Todo: Debug aritmetic pointes
+  /// Slice coefficients of `eq(r, .)`, of length `1 << log_length`, as
+  /// [`poly_commitment::public_array_coefficients`] computes them. The array is the tensor
+  /// product of `q_ratio`, over the variables picking a slice, and of
+  /// `q_eval_verifier`, over the ones within a slice, so only one slice is
+  /// interpolated.
+  pub fn public_array_prepare(&mut self, r: &[F], log_length: usize) -> Vec<F> {
     let mov_pos = log_length - LOG_SLICE_NUMBER;
-    Self::dfs_ratio(
-      self,
-      0,
-      FieldElement::real_one(),
-      r.clone(),
-      mov_pos,
-      one_minus_r.clone(),
-      0,
-    );
-    Self::dfs_coef(
-      self,
-      0,
-      FieldElement::real_one(),
-      r.clone(),
-      one_minus_r.clone(),
-      0,
-      log_length - LOG_SLICE_NUMBER,
+    let coef_slice_size = 1 << mov_pos;
+    self.ctx.q_eval_verifier = EqPolynomial::new(&r[..mov_pos]).evaluations();
+    self.ctx.q_ratio = EqPolynomial::new(&r[mov_pos..log_length]).evaluations();
+
+    let mut q_coef_verifier = vec![F::zero(); coef_slice_size];
+    inverse_fast_fourier_transform(
+      &self.ctx.q_eval_verifier,
+      coef_slice_size,
+      F::root_of_unity(mov_pos).unwrap(),
+      &mut q_coef_verifier,
     );
-    let q_coef_verifier = vec![FieldElement::zero(); 1 << (log_length - LOG_SLICE_NUMBER)];
-    //    inverse_fast_fourier_transform(q_eval_verifier, (1 << (log_length - log_slice_number)), (1 << (log_length - log_slice_number)), prime_field::get_root_of_unity(log_length - log_slice_number), q_coef_verifier);
-    let mut q_coef_arr = vec![FieldElement::zero(); 1 << log_length];
-    let coef_slice_size = 1 << (log_length - LOG_SLICE_NUMBER);
-    for i in 0..(1 << LOG_SLICE_NUMBER) {
-      for j in 0..coef_slice_size {
-        q_coef_arr[i * coef_slice_size + j] = q_coef_verifier[j] * self.ctx.q_ratio[i];
-        assert!(
-          self.ctx.q_eval_real[i * coef_slice_size + j]
-            == self.ctx.q_ratio[i] * self.ctx.q_eval_verifier[j]
-        );
+    let mut q_coef_arr = vec![F::zero(); 1 << log_length];
+    for (slice, &ratio) in q_coef_arr
+      .chunks_mut(coef_slice_size)
+      .zip(&self.ctx.q_ratio)
+    {
+      for (c, &q) in slice.iter_mut().zip(&q_coef_verifier) {
+        *c = q * ratio;
       }
     }
     q_coef_arr
   }
 
-  pub fn dfs_coef(
-    &mut self,
-    dep: usize,
-    val: FieldElement,
-    r: Vec<FieldElement>,
-    one_minus_r: Vec<FieldElement>,
-    pos: usize,
-    r_len: usize,
-  ) {
-    if dep == r_len {
-      self.ctx.q_eval_verifier[pos] = val;
-    } else {
-      Self::dfs_coef(
-        self,
-        dep + 1,
-        val * one_minus_r[r_len - 1 - dep],
-        r.clone(),
-        one_minus_r.clone(),
-        pos << 1,
-        r_len,
-      );
-      Self::dfs_coef(
-        self,
-        dep + 1,
-        val * r[r_len - 1 - dep],
-        r,
-        one_minus_r,
-        pos << 1 | 1,
-        r_len,
-      );
-    }
-  }
-
-  //Todo: Debug aritmetic pointes
-  pub fn dfs_ratio(
-    &mut self,
-    dep: usize,
-    val: FieldElement,
-    r: Vec<FieldElement>,
-    mov_pos: usize,
-    one_minus_r: Vec<FieldElement>,
-    pos: usize,
-  ) {
-    if dep == LOG_SLICE_NUMBER {
-      self.ctx.q_ratio[pos] = val;
-    } else {
-      Self::dfs_ratio(
-        self,
-        dep + 1,
-        val * one_minus_r[mov_pos + LOG_SLICE_NUMBER - 1 - dep],
-        r.clone(),
-        0,
-        one_minus_r.clone(),
-        pos << 1,
-      );
-      Self::dfs_ratio(
-        self,
-        dep + 1,
-        val * r[mov_pos + LOG_SLICE_NUMBER - 1 - dep],
-        r,
-        0,
-        one_minus_r,
-        pos << 1 | 1,
-      );
-    }
-  }
-  pub fn dfs_for_public_eval(
-    &mut self,
-    dep: usize,
-    val: FieldElement,
-    r_0: Vec<FieldElement>,
-    one_minus_r_0: Vec<FieldElement>,
-    r_0_len: usize,
-    pos: usize,
+  /// Adds to the slice coefficients of length `1 << log_length` the ones of
+  /// [`weigh_public_inputs`], interpolating only the slices holding public
+  /// inputs
+  fn weigh_public_coefficients(
+    coefficients: &mut [F],
+    log_length: usize,
+    public_count: usize,
+    rho: F,
   ) {
-    if dep == r_0_len {
-      self.ctx.q_eval_real[pos] = val;
-    } else {
-      Self::dfs_for_public_eval(
-        self,
-        dep + 1,
-        val * one_minus_r_0[r_0_len - 1 - dep],
-        r_0.clone(),
-        one_minus_r_0.clone(),
-        r_0_len,
-        pos << 1,
-      );
-      Self::dfs_for_public_eval(
-        self,
-        dep + 1,
-        val * r_0[r_0_len - 1 - dep],
-        r_0,
-        one_minus_r_0,
-        r_0_len,
-        pos << 1 | 1,
-      );
-    }
-  }
-
-  pub fn generate_randomness(size: usize) -> Vec<FieldElement> {
-    let k = size;
-    let mut ret = vec![FieldElement::zero(); k];
-
-    for _i in 0..k {
-      ret.push(FieldElement::new_random());
+    let slice_len = 1 << (log_length - LOG_SLICE_NUMBER);
+    let mut weights = vec![F::zero(); (public_count + slice_len - 1) / slice_len * slice_len];
+    weigh_public_inputs(&mut weights, public_count, rho);
+
+    let root_of_unity = F::root_of_unity(log_length - LOG_SLICE_NUMBER).unwrap();
+    let mut slice_coefficients = vec![F::zero(); slice_len];
+    for (slice, coefficients) in weights
+      .chunks(slice_len)
+      .zip(coefficients.chunks_mut(slice_len))
+    {
+      inverse_fast_fourier_transform(slice, slice_len, root_of_unity, &mut slice_coefficients);
+      for (c, &w) in coefficients.iter_mut().zip(&slice_coefficients) {
+        *c = *c + w;
+      }
     }
-    ret
   }
 
-  pub fn direct_relay(
-    &mut self,
-    depth: usize,
-    r_g: &Vec<FieldElement>,
-    r_u: &Vec<FieldElement>,
-  ) -> FieldElement {
+  pub fn direct_relay(&mut self, depth: usize, r_g: &[F], r_u: &[F]) -> F {
     if depth != 1 {
-      let ret = FieldElement::from_real(0);
+      let ret = F::from_u64(0);
       return ret;
     } else {
-      let mut ret = FieldElement::from_real(1);
+      let mut ret = F::from_u64(1);
       for i in 0..(self.aritmetic_circuit.circuit[depth].bit_length) {
-        ret = ret
-          * (FieldElement::from_real(1) - r_g[i] - r_u[i]
-            + FieldElement::from_real(2) * r_g[i] * r_u[i]);
+        ret = ret * (F::from_u64(1) - r_g[i] - r_u[i] + F::from_u64(2) * r_g[i] * r_u[i]);
       }
       return ret;
     }
@@ -905,710 +429,722 @@ impl ZkVerifier {
   pub fn beta_init(
     &mut self,
     depth: usize,
-    alpha: FieldElement,
-    beta: FieldElement,
-    r_0: &Vec<FieldElement>,
-    r_1: &Vec<FieldElement>,
-    r_u: &Vec<FieldElement>,
-    r_v: &Vec<FieldElement>,
-    one_minus_r_0: &Vec<FieldElement>,
-    one_minus_r_1: &Vec<FieldElement>,
-    one_minus_r_u: &Vec<FieldElement>,
-    one_minus_r_v: &Vec<FieldElement>,
+    alpha: F,
+    beta: F,
+    r_0: &[F],
+    r_1: &[F],
+    r_u: &[F],
+    r_v: &[F],
   ) {
-    let debug_mode = false;
-    if !self.aritmetic_circuit.circuit[depth].is_parallel || debug_mode {
-      self.beta_g_r0_first_half[0] = alpha;
-      self.beta_g_r1_first_half[0] = beta;
-      self.beta_g_r0_second_half[0] = FieldElement::from_real(1);
-      self.beta_g_r1_second_half[0] = FieldElement::from_real(1);
-
-      let first_half_len = self.aritmetic_circuit.circuit[depth].bit_length / 2;
-      let second_half_len = self.aritmetic_circuit.circuit[depth].bit_length - first_half_len;
-
-      for i in 0..first_half_len {
-        let r0 = r_0[i];
-        let r1 = r_1[i];
-        let or0 = one_minus_r_0[i];
-        let or1 = one_minus_r_1[i];
-
-        for j in 0..(1 << i) {
-          self.beta_g_r0_first_half[j | (1 << i)] = self.beta_g_r0_first_half[j] * r0;
-          self.beta_g_r1_first_half[j | (1 << i)] = self.beta_g_r1_first_half[j] * r1;
-        }
+    if depth > 1 && self.aritmetic_circuit.circuit[depth].is_parallel {
+      return self.block_beta_init(depth, [(alpha, r_0), (beta, r_1)], r_u, r_v);
+    }
+    let one = F::one();
+    let bit_length = self.aritmetic_circuit.circuit[depth].bit_length;
+    for (first_half, second_half, scale, r) in [
+      (
+        &mut self.beta_g_r0_first_half,
+        &mut self.beta_g_r0_second_half,
+        alpha,
+        &r_0[..bit_length],
+      ),
+      (
+        &mut self.beta_g_r1_first_half,
+        &mut self.beta_g_r1_second_half,
+        beta,
+        &r_1[..bit_length],
+      ),
+    ] {
+      EqPolynomial::new(r).fill_halves(first_half, second_half, scale);
+    }
 
-        for j in 0..(1 << i) {
-          self.beta_g_r0_first_half[j] = self.beta_g_r0_first_half[j] * or0;
-          self.beta_g_r1_first_half[j] = self.beta_g_r1_first_half[j] * or1;
-        }
-      }
+    let bit_length = self.aritmetic_circuit.circuit[depth - 1].bit_length;
+    for (first_half, second_half, r) in [
+      (
+        &mut self.beta_u_first_half,
+        &mut self.beta_u_second_half,
+        &r_u[..bit_length],
+      ),
+      (
+        &mut self.beta_v_first_half,
+        &mut self.beta_v_second_half,
+        &r_v[..bit_length],
+      ),
+    ] {
+      EqPolynomial::new(r).fill_halves(first_half, second_half, one);
+    }
+  }
 
-      for i in 0..second_half_len {
-        let r0 = r_0[i + first_half_len];
-        let r1 = r_1[i + first_half_len];
-        let or0 = one_minus_r_0[i + first_half_len];
-        let or1 = one_minus_r_1[i + first_half_len];
+  /// Sets the tables of a parallel layer over the gates of a block of the
+  /// layer and of the previous one, and the factors accounting for the
+  /// repetition of the blocks, see `repeat_factors`. `claims` holds `alpha,
+  /// r_0` and `beta, r_1`.
+  fn block_beta_init(&mut self, depth: usize, claims: [(F, &[F]); 2], r_u: &[F], r_v: &[F]) {
+    let [(alpha, r_0), (beta, r_1)] = claims;
+    let layer = &self.aritmetic_circuit.circuit[depth];
+    let (log_block_size, log_repeat_num) = (layer.log_block_size, layer.log_repeat_num);
+    let log_prev_block_size = self.aritmetic_circuit.circuit[depth - 1].bit_length - log_repeat_num;
+    let one = F::one();
+
+    EqPolynomial::new(&r_0[..log_block_size]).fill_halves(
+      &mut self.beta_g_r0_block_first_half,
+      &mut self.beta_g_r0_block_second_half,
+      alpha,
+    );
+    EqPolynomial::new(&r_1[..log_block_size]).fill_halves(
+      &mut self.beta_g_r1_block_first_half,
+      &mut self.beta_g_r1_block_second_half,
+      beta,
+    );
+    EqPolynomial::new(&r_u[..log_prev_block_size]).fill_halves(
+      &mut self.beta_u_block_first_half,
+      &mut self.beta_u_block_second_half,
+      one,
+    );
+    EqPolynomial::new(&r_v[..log_prev_block_size]).fill_halves(
+      &mut self.beta_v_block_first_half,
+      &mut self.beta_v_block_second_half,
+      one,
+    );
 
-        for j in 0..(1 << i) {
-          self.beta_g_r0_second_half[j | (1 << i)] = self.beta_g_r0_second_half[j] * r0;
-          self.beta_g_r1_second_half[j | (1 << i)] = self.beta_g_r1_second_half[j] * r1;
-        }
+    let blocks = log_block_size..log_block_size + log_repeat_num;
+    for (factors, r_g) in [(&mut self.repeat_r0, r_0), (&mut self.repeat_r1, r_1)] {
+      // gates past the last block are padding
+      let padding = EqPolynomial::new(&r_g[blocks.end..]).evaluate_at_zero();
+      *factors = repeat_factors(
+        &r_g[blocks.clone()],
+        &r_u[log_prev_block_size..],
+        &r_v[log_prev_block_size..],
+      )
+      .map(|row| row.map(|factor| factor * padding));
+    }
+  }
 
-        for j in 0..(1 << i) {
-          self.beta_g_r0_second_half[j] = self.beta_g_r0_second_half[j] * or0;
-          self.beta_g_r1_second_half[j] = self.beta_g_r1_second_half[j] * or1;
-        }
-      }
+  /// Sum over the gates g of layer `depth` and their wires (u, v) of
+  /// `beta(g) * eq(r_u, u) * eq(r_v, v) * weight * form(v_u, v_v)`, with the
+  /// tables set by `beta_init`. The first layer only relays the input layer,
+  /// it is accounted for by `direct_relay` instead.
+  pub fn predicates(&mut self, depth: usize, v_u: F, v_v: F) -> F {
+    let mut ret = F::zero();
+    if depth == 1 {
+      return ret;
+    }
+    if self.aritmetic_circuit.circuit[depth].is_parallel {
+      return self.block_predicates(depth, v_u, v_v);
+    }
 
-      self.beta_u_first_half[0] = FieldElement::real_one();
-      self.beta_v_first_half[0] = FieldElement::real_one();
-      self.beta_u_second_half[0] = FieldElement::real_one();
-      self.beta_v_second_half[0] = FieldElement::real_one();
-      let first_half_len = self.aritmetic_circuit.circuit[depth - 1].bit_length / 2;
-      let second_half_len = self.aritmetic_circuit.circuit[depth - 1].bit_length - first_half_len;
-
-      for i in 0..first_half_len {
-        let ru = r_u[i];
-        let rv = r_v[i];
-        let oru = one_minus_r_u[i];
-        let orv = one_minus_r_v[i];
-
-        for j in 0..(1 << i) {
-          self.beta_u_first_half[j | (1 << i)] = self.beta_u_first_half[j] * ru;
-          self.beta_v_first_half[j | (1 << i)] = self.beta_v_first_half[j] * rv;
-        }
+    let first_half_g = self.aritmetic_circuit.circuit[depth].bit_length / 2;
+    let first_half_uv = self.aritmetic_circuit.circuit[depth - 1].bit_length / 2;
+    let mask_g = (1 << first_half_g) - 1;
+    let mask_uv = (1 << first_half_uv) - 1;
+    let layer = &self.aritmetic_circuit.circuit[depth];
 
-        for j in 0..(1 << i) {
-          self.beta_u_first_half[j] = self.beta_u_first_half[j] * oru;
-          self.beta_v_first_half[j] = self.beta_v_first_half[j] * orv;
-        }
+    for (g, gate) in layer.gates[..1 << layer.bit_length].iter().enumerate() {
+      if let GateKind::DirectRelay { .. } = gate {
+        continue;
       }
+      let mut wiring = F::zero();
+      gate.for_each_wire(|u, v, weight| {
+        wiring = wiring
+          + weight
+            * (self.beta_u_first_half[u & mask_uv] * self.beta_u_second_half[u >> first_half_uv])
+            * (self.beta_v_first_half[v & mask_uv] * self.beta_v_second_half[v >> first_half_uv]);
+      });
+      let beta_g = self.beta_g_r0_first_half[g & mask_g]
+        * self.beta_g_r0_second_half[g >> first_half_g]
+        + self.beta_g_r1_first_half[g & mask_g] * self.beta_g_r1_second_half[g >> first_half_g];
+      ret = ret + beta_g * wiring * gate.form().eval(v_u, v_v);
+    }
+    ret
+  }
 
-      for i in 0..second_half_len {
-        let ru = r_u[i + first_half_len];
-        let rv = r_v[i + first_half_len];
-        let oru = one_minus_r_u[i + first_half_len];
-        let orv = one_minus_r_v[i + first_half_len];
+  /// `predicates` of a parallel layer, summing over the gates of its first
+  /// block only. A wire of gate `g` of the first block moves to `u + b
+  /// prev_block_size` in block `b` if it follows the operands of the gate
+  /// (and likewise for `v`), or stays in the first block otherwise, which
+  /// selects the factor of `repeat_factors` it is weighted by.
+  fn block_predicates(&self, depth: usize, v_u: F, v_v: F) -> F {
+    let layer = &self.aritmetic_circuit.circuit[depth];
+    let log_prev_block_size =
+      self.aritmetic_circuit.circuit[depth - 1].bit_length - layer.log_repeat_num;
+    let first_half_g = layer.log_block_size / 2;
+    let first_half_uv = log_prev_block_size / 2;
+    let mask_g = (1 << first_half_g) - 1;
+    let mask_uv = (1 << first_half_uv) - 1;
+
+    let mut ret = F::zero();
+    let mut wires = vec![];
+    for (g, gate) in layer.gates[..layer.block_size].iter().enumerate() {
+      wires.clear();
+      gate.for_each_wire(|u, v, weight| wires.push((u, v, weight)));
+      let (mut wiring_r0, mut wiring_r1) = (F::zero(), F::zero());
+      let mut wire = wires.iter();
+      gate
+        .shifted(1 << log_prev_block_size)
+        .for_each_wire(|shifted_u, shifted_v, _| {
+          let &(u, v, weight) = wire.next().expect("shifting a gate kept its wires");
+          let eq = weight
+            * (self.beta_u_block_first_half[u & mask_uv]
+              * self.beta_u_block_second_half[u >> first_half_uv])
+            * (self.beta_v_block_first_half[v & mask_uv]
+              * self.beta_v_block_second_half[v >> first_half_uv]);
+          let (su, sv) = ((shifted_u != u) as usize, (shifted_v != v) as usize);
+          wiring_r0 = wiring_r0 + eq * self.repeat_r0[su][sv];
+          wiring_r1 = wiring_r1 + eq * self.repeat_r1[su][sv];
+        });
+      let beta_r0 = self.beta_g_r0_block_first_half[g & mask_g]
+        * self.beta_g_r0_block_second_half[g >> first_half_g];
+      let beta_r1 = self.beta_g_r1_block_first_half[g & mask_g]
+        * self.beta_g_r1_block_second_half[g >> first_half_g];
+      ret = ret + (beta_r0 * wiring_r0 + beta_r1 * wiring_r1) * gate.form().eval(v_u, v_v);
+    }
+    ret
+  }
 
-        for j in 0..(1 << i) {
-          self.beta_u_second_half[j | (1 << i)] = self.beta_u_second_half[j] * ru;
-          self.beta_v_second_half[j | (1 << i)] = self.beta_v_second_half[j] * rv;
-        }
+  /// Evaluates the multilinear extension of the first `1 << r.len()` entries
+  /// of `values` at `r`
+  pub fn v_in(r: &[F], values: &[F]) -> F {
+    DenseMultilinear::evaluate_table(&values[..1 << r.len()], r)
+  }
+  pub fn read_r1cs() {} //Never used, original code is all commented in Orion, empty in Virgo
+  pub fn self_inner_product_test() {} //Never used, implemented only in Virgo, empty in Orion
 
-        for j in 0..(1 << i) {
-          self.beta_u_second_half[j] = self.beta_u_second_half[j] * oru;
-          self.beta_v_second_half[j] = self.beta_v_second_half[j] * orv;
-        }
-      }
+  /// Checks that the array committed to in `proof` has inner product `value`
+  /// with the public array whose slice coefficients are
+  /// `public_coefficients`, both of length `1 << log_length`
+  pub fn verify_poly_commitment(
+    &mut self,
+    proof: &GkrProof<F>,
+    public_coefficients: &[F],
+    log_length: usize,
+    value: F,
+    transcript: &mut Transcript,
+  ) -> bool {
+    self.poly_verifier = PolyCommitVerifier::new(proof.hash);
+    if let Err(err) = self.poly_verifier.verify_poly_commitment(
+      proof.commitment,
+      &proof.opening,
+      public_coefficients,
+      log_length,
+      value,
+      transcript,
+    ) {
+      println!("Verification fail, input layer: {}", err);
+      return false;
     }
+    true
+  }
+}
 
-    if self.aritmetic_circuit.circuit[depth].is_parallel {
-      self.beta_g_r0_block_first_half[0] = alpha;
-      self.beta_g_r1_block_first_half[0] = beta;
-      self.beta_g_r0_block_second_half[0] = FieldElement::from_real(1);
-      self.beta_g_r1_block_second_half[0] = FieldElement::from_real(1);
-
-      let first_half_len = self.aritmetic_circuit.circuit[depth - 1].log_block_size / 2;
-      let second_half_len =
-        self.aritmetic_circuit.circuit[depth - 1].log_block_size - first_half_len;
-
-      for i in 0..first_half_len {
-        let r0 = r_0[i + first_half_len];
-        let r1 = r_1[i + first_half_len];
-        let or0 = one_minus_r_0[i + first_half_len];
-        let or1 = one_minus_r_1[i + first_half_len];
-
-        for j in 0..(1 << i) {
-          self.beta_g_r0_block_first_half[j | (1 << i)] = self.beta_g_r0_block_first_half[j] * r0;
-          self.beta_g_r1_block_first_half[j | (1 << i)] = self.beta_g_r1_block_first_half[j] * r1;
-        }
+/// `Σ_b eq(r_g, b) eq(r_u, su b) eq(r_v, sv b)` at `[su][sv]`, summing over
+/// the blocks `b`: block `b` of a parallel layer reads block `b` of the
+/// previous layer through wires that move with the block, and the first
+/// block through the others
+fn repeat_factors<F: Field>(r_g: &[F], r_u: &[F], r_v: &[F]) -> [[F; 2]; 2] {
+  let one = F::one();
+  let (mut gu, mut gv, mut guv) = (one, one, one);
+  for ((&g, &u), &v) in r_g.iter().zip(r_u).zip(r_v) {
+    gu = gu * (g * u + (one - g) * (one - u));
+    gv = gv * (g * v + (one - g) * (one - v));
+    guv = guv * (g * u * v + (one - g) * (one - u) * (one - v));
+  }
+  let (zu, zv) = (
+    EqPolynomial::new(r_u).evaluate_at_zero(),
+    EqPolynomial::new(r_v).evaluate_at_zero(),
+  );
+  [[zu * zv, zu * gv], [gu * zv, guv]]
+}
 
-        for j in 0..(1 << i) {
-          self.beta_g_r0_block_first_half[j] = self.beta_g_r0_block_first_half[j] * or0;
-          self.beta_g_r1_block_first_half[j] = self.beta_g_r1_block_first_half[j] * or1;
-        }
-      }
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use infrastructure::my_hash::HashAlgorithm;
+  use infrastructure::transcript::Transcript;
+  use poly_commitment::{public_array_coefficients, PolyCommitProver};
+  use prime_field::FieldElement;
+
+  use crate::circuit_fast_track::{
+    BilinearForm, CustomGate, GateKind, GateRegistry, Layer, LayeredCircuit,
+  };
+  use crate::multilinear::EqPolynomial;
+  use crate::proof::{committed_log_len, GkrProof, TRANSCRIPT_LABEL};
+  use crate::prover::ZkProver;
+  use crate::verifier::ZkVerifier;
+  use crate::witness::{PublicIo, Witness, WitnessError};
+
+  // inputs 3, 5, 7, 2 and one layer computing 3 * 5 and 7 + 2
+  const CIRCUIT: &str = "2\n4 3 0 3 0 3 1 5 0 3 2 7 0 3 3 2 0\n2 1 0 0 1 0 1 2 3\n";
+  const META: &str = "0 0 1 0 0\n0 0 1 0 0\n";
+
+  /// Outputs of `proof`, for circuits without public inputs
+  fn outputs_of(proof: &GkrProof) -> PublicIo {
+    PublicIo {
+      inputs: vec![],
+      outputs: proof.output.clone(),
+    }
+  }
 
-      for i in 0..second_half_len {
-        let r0 = r_0[i + first_half_len];
-        let r1 = r_1[i + first_half_len];
-        let or0 = one_minus_r_0[i + first_half_len];
-        let or1 = one_minus_r_1[i + first_half_len];
+  fn read_circuit(name: &str) -> (ZkVerifier, usize) {
+    let dir = std::env::temp_dir().join(format!("linear_gkr_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let circuit_path = dir.join("circuit.txt");
+    let meta_path = dir.join("meta.txt");
+    fs::write(&circuit_path, CIRCUIT).unwrap();
+    fs::write(&meta_path, META).unwrap();
+
+    let mut verifier = ZkVerifier::new();
+    let bit_length = verifier
+      .read_circuit(circuit_path.to_str().unwrap(), meta_path.to_str().unwrap())
+      .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    (verifier, bit_length)
+  }
 
-        for j in 0..(1 << i) {
-          self.beta_g_r0_block_second_half[j | (1 << i)] = self.beta_g_r0_block_second_half[j] * r0;
-          self.beta_g_r1_block_second_half[j | (1 << i)] = self.beta_g_r1_block_second_half[j] * r1;
-        }
+  #[test]
+  fn proof_verifies_and_tampering_is_rejected() {
+    let (mut verifier, bit_length) = read_circuit("e2e");
+    let mut prover = ZkProver::new();
+    prover.init_array(bit_length, &verifier.aritmetic_circuit);
+    let proof = prover.prove(&mut Transcript::new(TRANSCRIPT_LABEL));
+    let public = outputs_of(&proof);
+
+    assert_eq!(proof.output[0], FieldElement::from_real(15));
+    assert_eq!(proof.output[1], FieldElement::from_real(9));
+    assert!(verifier.verify(&proof, &public, &mut Transcript::new(TRANSCRIPT_LABEL)));
+
+    let mut tampered = proof.clone();
+    tampered.output[1] = FieldElement::from_real(10);
+    assert!(!verifier.verify(&tampered, &public, &mut Transcript::new(TRANSCRIPT_LABEL)));
+
+    let mut tampered = proof.clone();
+    tampered.layers[0].phase1[0].c = tampered.layers[0].phase1[0].c + FieldElement::real_one();
+    assert!(!verifier.verify(&tampered, &public, &mut Transcript::new(TRANSCRIPT_LABEL)));
+
+    let mut tampered = proof;
+    tampered.layers[0].v_v = tampered.layers[0].v_v + FieldElement::real_one();
+    assert!(!verifier.verify(&tampered, &public, &mut Transcript::new(TRANSCRIPT_LABEL)));
+  }
 
-        for j in 0..(1 << 1) {
-          self.beta_g_r0_block_second_half[j] = self.beta_g_r0_block_second_half[j] * or0;
-          self.beta_g_r1_block_second_half[j] = self.beta_g_r1_block_second_half[j] * or1;
-        }
-      }
+  #[test]
+  fn public_array_is_interpolated_from_one_slice() {
+    let r: Vec<_> = (0..9).map(|_| FieldElement::new_random()).collect();
+    assert_eq!(
+      ZkVerifier::new().public_array_prepare(&r, r.len()),
+      public_array_coefficients(&EqPolynomial::new(&r).evaluations(), r.len())
+    );
+  }
 
-      self.beta_u_block_first_half[0] = FieldElement::real_one();
-      self.beta_v_block_first_half[0] = FieldElement::real_one();
-      self.beta_u_block_second_half[0] = FieldElement::real_one();
-      self.beta_v_block_second_half[0] = FieldElement::real_one();
-      let first_half_len = self.aritmetic_circuit.circuit[depth - 1].bit_length / 2;
-      let second_half_len = self.aritmetic_circuit.circuit[depth - 1].bit_length - first_half_len;
-
-      for i in 0..first_half_len {
-        let ru = r_u[i];
-        let rv = r_v[i];
-        let oru = one_minus_r_u[i];
-        let orv = one_minus_r_v[i];
-
-        for j in 0..(1 << i) {
-          self.beta_u_block_first_half[j | (1 << i)] = self.beta_u_block_first_half[j] * ru;
-          self.beta_v_block_first_half[j | (1 << i)] = self.beta_v_block_first_half[j] * rv;
-        }
+  #[test]
+  fn proof_is_bound_to_its_hash() {
+    let (mut verifier, bit_length) = read_circuit("hash");
+    let mut prover = ZkProver::new();
+    prover.init_array(bit_length, &verifier.aritmetic_circuit);
+    let transcript = || Transcript::with_hasher(TRANSCRIPT_LABEL, HashAlgorithm::Poseidon);
+    let proof = prover.prove(&mut transcript());
+    let public = outputs_of(&proof);
+
+    assert_eq!(proof.hash, HashAlgorithm::Poseidon);
+    assert!(verifier.verify(&proof, &public, &mut transcript()));
+    assert!(!verifier.verify(&proof, &public, &mut Transcript::new(TRANSCRIPT_LABEL)));
+
+    let mut relabeled = proof;
+    relabeled.hash = HashAlgorithm::default();
+    assert!(!verifier.verify(&relabeled, &public, &mut Transcript::new(TRANSCRIPT_LABEL)));
+  }
 
-        for j in 0..(1 << i) {
-          self.beta_u_block_first_half[j] = self.beta_u_block_first_half[j] * oru;
-          self.beta_v_block_first_half[j] = self.beta_v_block_first_half[j] * orv;
-        }
-      }
+  #[test]
+  fn proof_is_bound_to_the_witness() {
+    let (mut verifier, bit_length) = read_circuit("witness");
+    let witness: Vec<FieldElement> = [4, 6, 1, 1].map(FieldElement::from_real).to_vec();
+    let mut prover = ZkProver::new();
+    prover.init_array(bit_length, &verifier.aritmetic_circuit);
+    prover.get_witness(witness);
+    let proof = prover.prove(&mut Transcript::new(TRANSCRIPT_LABEL));
+    let public = outputs_of(&proof);
+
+    assert_eq!(proof.output[0], FieldElement::from_real(24));
+    assert_eq!(proof.output[1], FieldElement::from_real(2));
+    assert!(verifier.verify(&proof, &public, &mut Transcript::new(TRANSCRIPT_LABEL)));
+
+    // layers claiming the circuit's own inputs don't match the commitment
+    let mut honest = ZkProver::new();
+    honest.init_array(bit_length, &verifier.aritmetic_circuit);
+    let other = honest.prove(&mut Transcript::new(TRANSCRIPT_LABEL));
+    let public = outputs_of(&other);
+    let mut spliced = other.clone();
+    spliced.commitment = proof.commitment;
+    spliced.opening = proof.opening.clone();
+    assert!(!verifier.verify(&spliced, &public, &mut Transcript::new(TRANSCRIPT_LABEL)));
+    let mut spliced = other;
+    spliced.opening = proof.opening;
+    assert!(!verifier.verify(&spliced, &public, &mut Transcript::new(TRANSCRIPT_LABEL)));
+  }
 
-      for i in 0..second_half_len {
-        let ru = r_u[i + first_half_len];
-        let rv = r_v[i + first_half_len];
-        let oru = one_minus_r_u[i + first_half_len];
-        let orv = one_minus_r_v[i + first_half_len];
+  #[test]
+  fn forged_opening_value_is_rejected() {
+    let (mut verifier, bit_length) = read_circuit("forged_opening");
+    let witness = "public 4 6 private 1 1";
+    let [zero, one, two] = [0, 1, 2].map(FieldElement::from_real);
+    let public = PublicIo {
+      inputs: [4, 6].map(FieldElement::from_real).to_vec(),
+      outputs: [24, 2].map(FieldElement::from_real).to_vec(),
+    };
+
+    // the layers claim the committed private inputs are 1 and 1, the opening
+    // sums up to match
+    let forged = cheat(
+      &verifier,
+      bit_length,
+      witness,
+      &public.inputs,
+      vec![zero, zero, one, two],
+      Some(vec![zero, zero, one, one]),
+    );
+    assert!(!verifier.verify(&forged, &public, &mut Transcript::new(TRANSCRIPT_LABEL)));
+  }
 
-        for j in 0..(1 << i) {
-          self.beta_u_block_second_half[j | (1 << i)] = self.beta_u_block_second_half[j] * ru;
-          self.beta_v_block_second_half[j | (1 << i)] = self.beta_v_block_second_half[j] * rv;
-        }
+  #[test]
+  fn witness_must_fill_the_input_gates() {
+    let (mut verifier, bit_length) = read_circuit("load_witness");
+    let mut prover = ZkProver::new();
+    prover.init_array(bit_length, &verifier.aritmetic_circuit);
+
+    let short = Witness::from_text("public 4 6 private 1").unwrap();
+    assert!(matches!(
+      prover.load_witness(&short),
+      Err(WitnessError::CountMismatch {
+        expected: 4,
+        found: 3
+      })
+    ));
+
+    let witness = Witness::from_text("public 4 6 private 1 1").unwrap();
+    prover.load_witness(&witness).unwrap();
+    let proof = prover.prove(&mut Transcript::new(TRANSCRIPT_LABEL));
+    let public = PublicIo {
+      inputs: witness.public,
+      outputs: proof.output.clone(),
+    };
+    assert_eq!(proof.output[0], FieldElement::from_real(24));
+    assert!(verifier.verify(&proof, &public, &mut Transcript::new(TRANSCRIPT_LABEL)));
+  }
 
-        for j in 0..(1 << i) {
-          self.beta_u_block_second_half[j] = self.beta_u_block_second_half[j] * oru;
-          self.beta_v_block_second_half[j] = self.beta_v_block_second_half[j] * orv;
+  #[test]
+  fn proof_is_bound_to_the_public_io() {
+    let (mut verifier, bit_length) = read_circuit("public_io");
+    let witness = Witness::from_text("public 4 6 private 1 1").unwrap();
+    let values = |values: &[u64]| values.iter().map(|&x| FieldElement::from_real(x)).collect();
+    let public = |inputs: &[u64], outputs: &[u64]| PublicIo {
+      inputs: values(inputs),
+      outputs: values(outputs),
+    };
+
+    for zero_knowledge in [false, true] {
+      let mut prover = ZkProver::new();
+      prover.init_array(bit_length, &verifier.aritmetic_circuit);
+      prover.load_witness(&witness).unwrap();
+      let transcript = &mut Transcript::new(TRANSCRIPT_LABEL);
+      let proof = if zero_knowledge {
+        // the binding doesn't rest on the commitment being hiding, and a
+        // plain one keeps the proof cheap
+        prover.prove_masked(transcript, PolyCommitProver::commit_private_array)
+      } else {
+        prover.prove(transcript)
+      };
+      let mut verify = |public: PublicIo| {
+        let transcript = &mut Transcript::new(TRANSCRIPT_LABEL);
+        if zero_knowledge {
+          verifier.verify_zero_knowledge(&proof, &public, transcript)
+        } else {
+          verifier.verify(&proof, &public, transcript)
         }
-      }
+      };
+
+      assert!(verify(public(&[4, 6], &[24, 2])));
+      assert!(!verify(public(&[4, 6], &[24, 3])));
+      assert!(!verify(public(&[4, 6], &[24])));
+      assert!(!verify(public(&[4, 6], &[24, 2, 0])));
+      assert!(!verify(public(&[4, 7], &[24, 2])));
+      assert!(!verify(public(&[], &[24, 2])));
+      assert!(!verify(public(&[4, 6, 1, 1, 0], &[24, 2])));
     }
   }
 
-  pub fn predicates(
-    &mut self,
-    depth: usize,
-    r_0: Vec<FieldElement>,
-    r_1: Vec<FieldElement>,
-    r_u: Vec<FieldElement>,
-    r_v: Vec<FieldElement>,
-    _alpha: FieldElement,
-    _beta: FieldElement,
-  ) -> Vec<FieldElement> {
-    let gate_type_count = 15;
-    let mut ret_para = vec![FieldElement::zero(); gate_type_count];
-    let mut ret = vec![FieldElement::zero(); gate_type_count];
-
-    for i in 0..gate_type_count {
-      ret[i] = FieldElement::zero();
-      ret_para[i] = FieldElement::zero();
-    }
-
-    if depth == 1 {
-      return ret;
+  /// Proof of a prover running the sumchecks on `witness` which claims the
+  /// public inputs `inputs` and commits to `committed` instead of the private
+  /// part of `witness`. With `claimed`, the sums of the opening are shifted
+  /// to the inner product of `claimed` rather than of `committed`.
+  fn cheat(
+    verifier: &ZkVerifier,
+    bit_length: usize,
+    witness: &str,
+    inputs: &[FieldElement],
+    mut committed: Vec<FieldElement>,
+    claimed: Option<Vec<FieldElement>>,
+  ) -> GkrProof {
+    let mut prover = ZkProver::new();
+    prover.init_array(bit_length, &verifier.aritmetic_circuit);
+    prover
+      .load_witness(&Witness::from_text(witness).unwrap())
+      .unwrap();
+    let transcript = &mut Transcript::new(TRANSCRIPT_LABEL);
+    let output = prover.evaluate();
+    transcript.absorb(&output[..]);
+    transcript.absorb(inputs);
+
+    let log_len = committed_log_len(&verifier.aritmetic_circuit);
+    committed.resize(1 << log_len, FieldElement::zero());
+    prover.poly_prover = PolyCommitProver::new(transcript.hasher());
+    let commitment = prover.poly_prover.commit_private_array(&committed, log_len);
+    transcript.append_digest(commitment);
+
+    let (layers, r_0, r_1) = prover.prove_layers(transcript);
+    let public = prover.input_opening_array(&r_0, &r_1, transcript);
+    let opening = match claimed {
+      Some(claimed) => {
+        let inner_product = |array: &[FieldElement]| {
+          array
+            .iter()
+            .zip(&public)
+            .fold(FieldElement::zero(), |acc, (a, b)| acc + *a * *b)
+        };
+        let (h_root, mut all_sum) = prover.poly_prover.commit_public_array(&public);
+        all_sum[0] = all_sum[0] + inner_product(&claimed) - inner_product(&committed);
+        prover
+          .poly_prover
+          .prove_low_degree(h_root, all_sum, transcript)
+      }
+      None => prover.poly_prover.open(&public, transcript),
+    };
+    GkrProof {
+      hash: transcript.hasher(),
+      output,
+      layers,
+      commitment,
+      opening,
+      zk: None,
     }
+  }
 
-    let debug_mode = false;
-    if self.aritmetic_circuit.circuit[depth].is_parallel {
-      let first_half_g = self.aritmetic_circuit.circuit[depth].log_block_size / 2;
-      let first_half_uv = self.aritmetic_circuit.circuit[depth - 1].log_block_size / 2;
+  #[test]
+  fn committed_public_inputs_must_be_zero() {
+    let (mut verifier, bit_length) = read_circuit("committed_public");
+    let witness = "public 4 6 private 1 1";
+    let one = FieldElement::real_one();
+    let [four, six, seven] = [4, 6, 7].map(FieldElement::from_real);
+    let outputs = [24, 2].map(FieldElement::from_real).to_vec();
+
+    let honest = cheat(
+      &verifier,
+      bit_length,
+      witness,
+      &[four, six],
+      vec![FieldElement::zero(), FieldElement::zero(), one, one],
+      None,
+    );
+    let public = PublicIo {
+      inputs: vec![four, six],
+      outputs: outputs.clone(),
+    };
+    assert!(verifier.verify(&honest, &public, &mut Transcript::new(TRANSCRIPT_LABEL)));
+
+    // 4 * 7 isn't 24, but the committed array makes up for the second public
+    // input
+    let swapped = cheat(
+      &verifier,
+      bit_length,
+      witness,
+      &[four, seven],
+      vec![FieldElement::zero(), six - seven, one, one],
+      None,
+    );
+    let public = PublicIo {
+      inputs: vec![four, seven],
+      outputs,
+    };
+    assert!(!verifier.verify(&swapped, &public, &mut Transcript::new(TRANSCRIPT_LABEL)));
+  }
 
-      let mut one_block_alpha = vec![FieldElement::zero(); gate_type_count];
-      let mut one_block_beta = vec![FieldElement::zero(); gate_type_count];
+  #[test]
+  fn every_gate_kind_proves() {
+    // inputs 1, 0, 1, 3 and one gate of each type
+    let circuit =
+      "2\n4 3 0 1 0 3 1 0 0 3 2 1 0 3 3 3 0\n13 0 0 0 1 1 1 2 3 2 2 0 0 3 3 7 0 5 4 0 4 \
+      6 5 0 0 7 6 3 0 8 7 0 2 9 8 1 3 10 9 3 0 12 10 0 2 13 11 1 1 14 12 0 0 2 3 2 0 -1\n";
+    let mut verifier = ZkVerifier::new();
+    verifier.aritmetic_circuit = LayeredCircuit::parse(circuit, META).unwrap();
+    let bit_length = verifier.aritmetic_circuit.max_bit_length();
+    verifier.init_array(bit_length);
+    let mut prover = ZkProver::new();
+    prover.init_array(bit_length, &verifier.aritmetic_circuit);
+    let proof = prover.prove(&mut Transcript::new(TRANSCRIPT_LABEL));
+    let public = outputs_of(&proof);
+
+    let expected = [1, 3, 0, 7, 5, 0, 2, 0, 3, 3, 5, 0, 5, 0, 0, 0];
+    assert_eq!(proof.output, expected.map(FieldElement::from_real));
+    assert!(verifier.verify(&proof, &public, &mut Transcript::new(TRANSCRIPT_LABEL)));
+  }
 
-      for _i in 0..gate_type_count {
-        one_block_alpha.push(FieldElement::zero());
-        one_block_beta.push(FieldElement::zero());
+  #[test]
+  fn custom_gates_prove() {
+    #[derive(Debug)]
+    struct Square;
+    impl CustomGate for Square {
+      fn name(&self) -> &str {
+        "square"
       }
-
-      assert!(
-        (1 << self.aritmetic_circuit.circuit[depth].log_block_size)
-          == self.aritmetic_circuit.circuit[depth].block_size
-      );
-
-      for i in 0..self.aritmetic_circuit.circuit[depth].log_block_size {
-        let mut g = i;
-        let mut u = self.aritmetic_circuit.circuit[depth].gates[i].u;
-        let mut v = self.aritmetic_circuit.circuit[depth].gates[i].v;
-        g = g & ((1 << self.aritmetic_circuit.circuit[depth].log_block_size) - 1);
-        u = u & ((1 << self.aritmetic_circuit.circuit[depth - 1].log_block_size) - 1);
-        v = v & ((1 << self.aritmetic_circuit.circuit[depth - 1].log_block_size) - 1);
-
-        match self.aritmetic_circuit.circuit[depth].gates[i].ty {
-          0 => {
-            let g_first_half = g & ((1 << first_half_g) - 1);
-            let g_second_half = g >> first_half_g;
-            let u_first_half = u & ((1 << first_half_uv) - 1);
-            let u_second_half = u >> first_half_uv;
-            let v_first_half = v & ((1 << first_half_uv) - 1);
-            let v_second_half = v >> first_half_uv;
-            let uv_value = (self.beta_u_block_first_half[u_first_half]
-              * self.beta_u_block_second_half[u_second_half])
-              * (self.beta_v_block_first_half[v_first_half]
-                * self.beta_v_block_second_half[v_second_half]);
-            one_block_alpha[0] = one_block_alpha[0]
-              + (self.beta_g_r0_block_first_half[g_first_half]
-                * self.beta_g_r0_block_second_half[g_second_half])
-                * uv_value;
-            one_block_beta[0] = one_block_beta[0]
-              + (self.beta_g_r1_block_first_half[g_first_half]
-                * self.beta_g_r1_block_second_half[g_second_half])
-                * uv_value;
-          }
-          1 => {
-            let g_first_half = g & ((1 << first_half_g) - 1);
-            let g_second_half = g >> first_half_g;
-            let u_first_half = u & ((1 << first_half_uv) - 1);
-            let u_second_half = u >> first_half_uv;
-            let v_first_half = v & ((1 << first_half_uv) - 1);
-            let v_second_half = v >> first_half_uv;
-            let uv_value = (self.beta_u_block_first_half[u_first_half]
-              * self.beta_u_block_second_half[u_second_half])
-              * (self.beta_v_block_first_half[v_first_half]
-                * self.beta_v_block_second_half[v_second_half]);
-            one_block_alpha[1] = one_block_alpha[1]
-              + (self.beta_g_r0_block_first_half[g_first_half]
-                * self.beta_g_r0_block_second_half[g_second_half])
-                * uv_value;
-            one_block_beta[1] = one_block_beta[1]
-              + (self.beta_g_r1_block_first_half[g_first_half]
-                * self.beta_g_r1_block_second_half[g_second_half])
-                * uv_value;
-          }
-          2 => {}
-          3 => {}
-          4 => {}
-          5 => {
-            let g_first_half = g & ((1 << first_half_g) - 1);
-            let g_second_half = g >> first_half_g;
-
-            let beta_g_val_alpha = self.beta_g_r0_block_first_half[g_first_half]
-              * self.beta_g_r0_block_second_half[g_second_half];
-            let beta_g_val_beta = self.beta_g_r1_block_first_half[g_first_half]
-              * self.beta_g_r1_block_second_half[g_second_half];
-            let beta_v_0 = self.beta_v_block_first_half[0] * self.beta_v_block_second_half[0];
-            for j in u..v {
-              let u_first_half = j & ((1 << first_half_uv) - 1);
-              let u_second_half = j >> first_half_uv;
-              one_block_alpha[5] = one_block_alpha[5]
-                + beta_g_val_alpha
-                  * beta_v_0
-                  * (self.beta_u_block_first_half[u_first_half]
-                    * self.beta_u_block_second_half[u_second_half]);
-              one_block_beta[5] = one_block_beta[5]
-                + beta_g_val_beta
-                  * beta_v_0
-                  * (self.beta_u_block_first_half[u_first_half]
-                    * self.beta_u_block_second_half[u_second_half]);
-            }
-          }
-          12 => {
-            let g_first_half = g & ((1 << first_half_g) - 1);
-            let g_second_half = g >> first_half_g;
-
-            let beta_g_val_alpha = self.beta_g_r0_block_first_half[g_first_half]
-              * self.beta_g_r0_block_second_half[g_second_half];
-            let beta_g_val_beta = self.beta_g_r1_block_first_half[g_first_half]
-              * self.beta_g_r1_block_second_half[g_second_half];
-            let mut beta_v_0 = self.beta_v_block_first_half[0] * self.beta_v_block_second_half[0];
-            for j in u..=v {
-              let u_first_half = j & ((1 << first_half_uv) - 1);
-              let u_second_half = j >> first_half_uv;
-              one_block_alpha[12] = one_block_alpha[12]
-                + beta_g_val_alpha
-                  * beta_v_0
-                  * (self.beta_u_block_first_half[u_first_half]
-                    * self.beta_u_block_second_half[u_second_half]);
-              one_block_beta[12] = one_block_beta[12]
-                + beta_g_val_beta
-                  * beta_v_0
-                  * (self.beta_u_block_first_half[u_first_half]
-                    * self.beta_u_block_second_half[u_second_half]);
-
-              beta_v_0 = beta_v_0 + beta_v_0;
-            }
-          }
-          6 => {
-            let g_first_half = g & ((1 << first_half_g) - 1);
-            let g_second_half = g >> first_half_g;
-            let u_first_half = u & ((1 << first_half_uv) - 1);
-            let u_second_half = u >> first_half_uv;
-            let v_first_half = v & ((1 << first_half_uv) - 1);
-            let v_second_half = v >> first_half_uv;
-            let uv_value = (self.beta_u_block_first_half[u_first_half]
-              * self.beta_u_block_second_half[u_second_half])
-              * (self.beta_v_block_first_half[v_first_half]
-                * self.beta_v_block_second_half[v_second_half]);
-            one_block_alpha[6] = one_block_alpha[6]
-              + (self.beta_g_r0_block_first_half[g_first_half]
-                * self.beta_g_r0_block_second_half[g_second_half])
-                * uv_value;
-            one_block_beta[6] = one_block_beta[6]
-              + (self.beta_g_r1_block_first_half[g_first_half]
-                * self.beta_g_r1_block_second_half[g_second_half])
-                * uv_value;
-          }
-          7 => {
-            let g_first_half = g & ((1 << first_half_g) - 1);
-            let g_second_half = g >> first_half_g;
-            let u_first_half = u & ((1 << first_half_uv) - 1);
-            let u_second_half = u >> first_half_uv;
-            let v_first_half = v & ((1 << first_half_uv) - 1);
-            let v_second_half = v >> first_half_uv;
-            let uv_value = (self.beta_u_block_first_half[u_first_half]
-              * self.beta_u_block_second_half[u_second_half])
-              * (self.beta_v_block_first_half[v_first_half]
-                * self.beta_v_block_second_half[v_second_half]);
-            one_block_alpha[7] = one_block_alpha[7]
-              + (self.beta_g_r0_block_first_half[g_first_half]
-                * self.beta_g_r0_block_second_half[g_second_half])
-                * uv_value;
-            one_block_beta[7] = one_block_beta[7]
-              + (self.beta_g_r1_block_first_half[g_first_half]
-                * self.beta_g_r1_block_second_half[g_second_half])
-                * uv_value;
-          }
-          8 => {
-            let g_first_half = g & ((1 << first_half_g) - 1);
-            let g_second_half = g >> first_half_g;
-            let u_first_half = u & ((1 << first_half_uv) - 1);
-            let u_second_half = u >> first_half_uv;
-            let v_first_half = v & ((1 << first_half_uv) - 1);
-            let v_second_half = v >> first_half_uv;
-            let uv_value = (self.beta_u_block_first_half[u_first_half]
-              * self.beta_u_block_second_half[u_second_half])
-              * (self.beta_v_block_first_half[v_first_half]
-                * self.beta_v_block_second_half[v_second_half]);
-            one_block_alpha[8] = one_block_alpha[8]
-              + (self.beta_g_r0_block_first_half[g_first_half]
-                * self.beta_g_r0_block_second_half[g_second_half])
-                * uv_value;
-            one_block_beta[8] = one_block_beta[8]
-              + (self.beta_g_r1_block_first_half[g_first_half]
-                * self.beta_g_r1_block_second_half[g_second_half])
-                * uv_value;
-          }
-          9 => {
-            let g_first_half = g & ((1 << first_half_g) - 1);
-            let g_second_half = g >> first_half_g;
-            let u_first_half = u & ((1 << first_half_uv) - 1);
-            let u_second_half = u >> first_half_uv;
-            let v_first_half = v & ((1 << first_half_uv) - 1);
-            let v_second_half = v >> first_half_uv;
-            let uv_value = (self.beta_u_block_first_half[u_first_half]
-              * self.beta_u_block_second_half[u_second_half])
-              * (self.beta_v_block_first_half[v_first_half]
-                * self.beta_v_block_second_half[v_second_half]);
-            one_block_alpha[9] = one_block_alpha[9]
-              + (self.beta_g_r0_block_first_half[g_first_half]
-                * self.beta_g_r0_block_second_half[g_second_half])
-                * uv_value;
-            one_block_beta[9] = one_block_beta[9]
-              + (self.beta_g_r1_block_first_half[g_first_half]
-                * self.beta_g_r1_block_second_half[g_second_half])
-                * uv_value;
-          }
-          10 => {
-            let g_first_half = g & ((1 << first_half_g) - 1);
-            let g_second_half = g >> first_half_g;
-            let u_first_half = u & ((1 << first_half_uv) - 1);
-            let u_second_half = u >> first_half_uv;
-            let v_first_half = v & ((1 << first_half_uv) - 1);
-            let v_second_half = v >> first_half_uv;
-            let uv_value = (self.beta_u_block_first_half[u_first_half]
-              * self.beta_u_block_second_half[u_second_half])
-              * (self.beta_v_block_first_half[v_first_half]
-                * self.beta_v_block_second_half[v_second_half]);
-            one_block_alpha[10] = one_block_alpha[10]
-              + (self.beta_g_r0_block_first_half[g_first_half]
-                * self.beta_g_r0_block_second_half[g_second_half])
-                * uv_value;
-            one_block_beta[10] = one_block_beta[10]
-              + (self.beta_g_r1_block_first_half[g_first_half]
-                * self.beta_g_r1_block_second_half[g_second_half])
-                * uv_value;
-          }
-          13 => {
-            let g_first_half = g & ((1 << first_half_g) - 1);
-            let g_second_half = g >> first_half_g;
-            let u_first_half = u & ((1 << first_half_uv) - 1);
-            let u_second_half = u >> first_half_uv;
-            let v_first_half = v & ((1 << first_half_uv) - 1);
-            let v_second_half = v >> first_half_uv;
-            let uv_value = (self.beta_u_block_first_half[u_first_half]
-              * self.beta_u_block_second_half[u_second_half])
-              * (self.beta_v_block_first_half[v_first_half]
-                * self.beta_v_block_second_half[v_second_half]);
-            one_block_alpha[13] = one_block_alpha[13]
-              + (self.beta_g_r0_block_first_half[g_first_half]
-                * self.beta_g_r0_block_second_half[g_second_half])
-                * uv_value;
-            one_block_beta[13] = one_block_beta[13]
-              + (self.beta_g_r1_block_first_half[g_first_half]
-                * self.beta_g_r1_block_second_half[g_second_half])
-                * uv_value;
-          }
-          _ => {}
-        }
+      fn form(&self) -> BilinearForm {
+        BilinearForm::from_ints(1, 0, 0, 0)
       }
-      let one = FieldElement::real_one();
-      for i in 0..self.aritmetic_circuit.circuit[depth].repeat_num {
-        let mut prefix_alpha = one;
-        let mut prefix_beta = one;
-        let mut prefix_alpha_v0 = one;
-        let mut prefix_beta_v0 = one;
-
-        for j in 0..self.aritmetic_circuit.circuit[depth].log_repeat_num {
-          if (i >> j) > 0 {
-            let uv_value = r_u[j + self.aritmetic_circuit.circuit[depth - 1].log_block_size]
-              * r_v[j + self.aritmetic_circuit.circuit[depth - 1].log_block_size];
-            prefix_alpha = prefix_alpha
-              * r_0[j + self.aritmetic_circuit.circuit[depth].log_block_size]
-              * uv_value;
-            prefix_beta = prefix_beta
-              * r_1[j + self.aritmetic_circuit.circuit[depth].log_block_size]
-              * uv_value;
-          } else {
-            let uv_value = (one
-              - r_u[j + self.aritmetic_circuit.circuit[depth - 1].log_block_size])
-              * (one - r_v[j + self.aritmetic_circuit.circuit[depth - 1].log_block_size]);
-            prefix_alpha = prefix_alpha
-              * (one - r_0[j + self.aritmetic_circuit.circuit[depth].log_block_size])
-              * uv_value;
-            prefix_beta = prefix_beta
-              * (one - r_1[j + self.aritmetic_circuit.circuit[depth].log_block_size])
-              * uv_value;
-          }
-        }
-        for j in 0..self.aritmetic_circuit.circuit[depth].log_repeat_num {
-          if (i >> j) > 0 {
-            let uv_value = r_u[j + self.aritmetic_circuit.circuit[depth - 1].log_block_size]
-              * (one - r_v[j + self.aritmetic_circuit.circuit[depth - 1].log_block_size]);
-            prefix_alpha_v0 = prefix_alpha_v0
-              * r_0[j + self.aritmetic_circuit.circuit[depth].log_block_size]
-              * uv_value;
-            prefix_beta_v0 = prefix_beta_v0
-              * r_1[j + self.aritmetic_circuit.circuit[depth].log_block_size]
-              * uv_value;
-          } else {
-            let uv_value = (one
-              - r_u[j + self.aritmetic_circuit.circuit[depth - 1].log_block_size])
-              * (one - r_v[j + self.aritmetic_circuit.circuit[depth - 1].log_block_size]);
-            prefix_alpha_v0 = prefix_alpha_v0
-              * (one - r_0[j + self.aritmetic_circuit.circuit[depth].log_block_size])
-              * uv_value;
-            prefix_beta_v0 = prefix_beta_v0
-              * (one - r_1[j + self.aritmetic_circuit.circuit[depth].log_block_size])
-              * uv_value;
-          }
-        }
-        for j in 0..gate_type_count {
-          if (j == 6 || j == 10 || j == 5 || j == 12) {
-            ret_para[j] = ret_para[j]
-              + prefix_alpha_v0 * one_block_alpha[j]
-              + prefix_beta_v0 * one_block_beta[j];
-          } else {
-            ret_para[j] =
-              ret_para[j] + prefix_alpha * one_block_alpha[j] + prefix_beta * one_block_beta[j];
-          }
-        }
-      }
-      if (!debug_mode) {
-        ret = ret_para.clone();
+      fn for_each_wire(&self, u: usize, _v: usize, f: &mut dyn FnMut(usize, usize, FieldElement)) {
+        f(u, u, FieldElement::real_one())
       }
     }
-    if !self.aritmetic_circuit.circuit[depth].is_parallel || debug_mode {
-      let first_half_g = self.aritmetic_circuit.circuit[depth].bit_length / 2;
-      let first_half_uv = self.aritmetic_circuit.circuit[depth - 1].bit_length / 2;
-
-      //Todo: Debug tmp_u_val
-      let mut tmp_u_val =
-        vec![FieldElement::zero(); 1 << self.aritmetic_circuit.circuit[depth - 1].bit_length];
-      let zero_v = self.beta_v_first_half[0] * self.beta_v_second_half[0];
-      let mut relay_set = false;
-      for i in 0..(1 << self.aritmetic_circuit.circuit[depth].bit_length) {
-        let g = i;
-        let u = self.aritmetic_circuit.circuit[depth].gates[i].u;
-        let v = self.aritmetic_circuit.circuit[depth].gates[i].v;
-
-        let g_first_half = g & ((1 << first_half_g) - 1);
-        let g_second_half = (g >> first_half_g);
-        let u_first_half = u & ((1 << first_half_uv) - 1);
-        let u_second_half = u >> first_half_uv;
-        let v_first_half = v & ((1 << first_half_uv) - 1);
-        let v_second_half = v >> first_half_uv;
-
-        match (self.aritmetic_circuit.circuit[depth].gates[i].ty) {
-          0 => {
-            ret[0] = ret[0]
-              + (self.beta_g_r0_first_half[g_first_half]
-                * self.beta_g_r0_second_half[g_second_half]
-                + self.beta_g_r1_first_half[g_first_half]
-                  * self.beta_g_r1_second_half[g_second_half])
-                * (self.beta_u_first_half[u_first_half] * self.beta_u_second_half[u_second_half])
-                * (self.beta_v_first_half[v_first_half] * self.beta_v_second_half[v_second_half]);
-          }
-          1 => {
-            ret[1] = ret[1]
-              + (self.beta_g_r0_first_half[g_first_half]
-                * self.beta_g_r0_second_half[g_second_half]
-                + self.beta_g_r1_first_half[g_first_half]
-                  * self.beta_g_r1_second_half[g_second_half])
-                * (self.beta_u_first_half[u_first_half] * self.beta_u_second_half[u_second_half])
-                * (self.beta_v_first_half[v_first_half] * self.beta_v_second_half[v_second_half]);
-          }
-          2 => {}
-          3 => {}
-          4 => {}
-          5 => {
-            let beta_g_val = self.beta_g_r0_first_half[g_first_half]
-              * self.beta_g_r0_second_half[g_second_half]
-              + self.beta_g_r1_first_half[g_first_half] * self.beta_g_r1_second_half[g_second_half];
-            let beta_v_0 = self.beta_v_first_half[0] * self.beta_v_second_half[0];
-            for j in u..v {
-              let u_first_half = j & ((1 << first_half_uv) - 1);
-              let u_second_half = j >> first_half_uv;
-              ret[5] = ret[5]
-                + beta_g_val
-                  * beta_v_0
-                  * (self.beta_u_first_half[u_first_half] * self.beta_u_second_half[u_second_half]);
-            }
-          }
-          12 => {
-            let g_first_half = g & ((1 << first_half_g) - 1);
-            let g_second_half = g >> first_half_g;
-
-            let beta_g_val = self.beta_g_r0_first_half[g_first_half]
-              * self.beta_g_r0_second_half[g_second_half]
-              + self.beta_g_r1_first_half[g_first_half] * self.beta_g_r1_second_half[g_second_half];
-            let mut beta_v_0 = self.beta_v_first_half[0] * self.beta_v_second_half[0];
-            for j in u..=v {
-              let u_first_half = j & ((1 << first_half_uv) - 1);
-              let u_second_half = j >> first_half_uv;
-              ret[12] = ret[12]
-                + beta_g_val
-                  * beta_v_0
-                  * (self.beta_u_first_half[u_first_half] * self.beta_u_second_half[u_second_half]);
-              beta_v_0 = beta_v_0 + beta_v_0;
-            }
-          }
-          14 => {
-            let g_first_half = g & ((1 << first_half_g) - 1);
-            let g_second_half = g >> first_half_g;
-
-            let beta_g_val = self.beta_g_r0_first_half[g_first_half]
-              * self.beta_g_r0_second_half[g_second_half]
-              + self.beta_g_r1_first_half[g_first_half] * self.beta_g_r1_second_half[g_second_half];
-            let beta_v_0 = self.beta_v_first_half[0] * self.beta_v_second_half[0];
-            for j in 0..self.aritmetic_circuit.circuit[depth].gates[i].parameter_length {
-              let src = self.aritmetic_circuit.circuit[depth].gates[i].src[j];
-              let u_first_half = src & ((1 << first_half_uv) - 1);
-              let u_second_half = src >> first_half_uv;
-              let weight = self.aritmetic_circuit.circuit[depth].gates[i].weight[j];
-              ret[14] = ret[14]
-                + beta_g_val
-                  * beta_v_0
-                  * (self.beta_u_first_half[u_first_half] * self.beta_u_second_half[u_second_half])
-                  * weight;
-            }
-          }
-          6 => {
-            ret[6] = ret[6]
-              + (self.beta_g_r0_first_half[g_first_half]
-                * self.beta_g_r0_second_half[g_second_half]
-                + self.beta_g_r1_first_half[g_first_half]
-                  * self.beta_g_r1_second_half[g_second_half])
-                * (self.beta_u_first_half[u_first_half] * self.beta_u_second_half[u_second_half])
-                * (self.beta_v_first_half[v_first_half] * self.beta_v_second_half[v_second_half]);
-          }
-          7 => {
-            ret[7] = ret[7]
-              + (self.beta_g_r0_first_half[g_first_half]
-                * self.beta_g_r0_second_half[g_second_half]
-                + self.beta_g_r1_first_half[g_first_half]
-                  * self.beta_g_r1_second_half[g_second_half])
-                * (self.beta_u_first_half[u_first_half] * self.beta_u_second_half[u_second_half])
-                * (self.beta_v_first_half[v_first_half] * self.beta_v_second_half[v_second_half]);
-          }
-          8 => {
-            ret[8] = ret[8]
-              + (self.beta_g_r0_first_half[g_first_half]
-                * self.beta_g_r0_second_half[g_second_half]
-                + self.beta_g_r1_first_half[g_first_half]
-                  * self.beta_g_r1_second_half[g_second_half])
-                * (self.beta_u_first_half[u_first_half] * self.beta_u_second_half[u_second_half])
-                * (self.beta_v_first_half[v_first_half] * self.beta_v_second_half[v_second_half]);
-          }
-          9 => {
-            ret[9] = ret[9]
-              + (self.beta_g_r0_first_half[g_first_half]
-                * self.beta_g_r0_second_half[g_second_half]
-                + self.beta_g_r1_first_half[g_first_half]
-                  * self.beta_g_r1_second_half[g_second_half])
-                * (self.beta_u_first_half[u_first_half] * self.beta_u_second_half[u_second_half])
-                * (self.beta_v_first_half[v_first_half] * self.beta_v_second_half[v_second_half]);
-          }
-          10 => {
-            if relay_set == false {
-              tmp_u_val = vec![
-                FieldElement::zero();
-                1 << self.aritmetic_circuit.circuit[depth - 1].bit_length
-              ];
-
-              for i in 0..(1 << self.aritmetic_circuit.circuit[depth - 1].bit_length) {
-                let u_first_half = i & ((1 << first_half_uv) - 1);
-                let u_second_half = i >> first_half_uv;
-                tmp_u_val[i] =
-                  self.beta_u_first_half[u_first_half] * self.beta_u_second_half[u_second_half];
-              }
-
-              relay_set = true;
-            }
-            let g_first_half = g & ((1 << first_half_g) - 1);
-            let g_second_half = g >> first_half_g;
-            ret[10] = ret[10]
-              + (self.beta_g_r0_first_half[g_first_half]
-                * self.beta_g_r0_second_half[g_second_half]
-                + self.beta_g_r1_first_half[g_first_half]
-                  * self.beta_g_r1_second_half[g_second_half])
-                * tmp_u_val[u];
-          }
-          13 => {
-            let g_first_half = g & ((1 << first_half_g) - 1);
-            let g_second_half = g >> first_half_g;
-            let u_first_half = u & ((1 << first_half_uv) - 1);
-            let u_second_half = u >> first_half_uv;
-            let v_first_half = v & ((1 << first_half_uv) - 1);
-            let v_second_half = v >> first_half_uv;
-            ret[13] = ret[13]
-              + (self.beta_g_r0_first_half[g_first_half]
-                * self.beta_g_r0_second_half[g_second_half]
-                + self.beta_g_r1_first_half[g_first_half]
-                  * self.beta_g_r1_second_half[g_second_half])
-                * (self.beta_u_first_half[u_first_half] * self.beta_u_second_half[u_second_half])
-                * (self.beta_v_first_half[v_first_half] * self.beta_v_second_half[v_second_half]);
-          }
-          _ => {}
+
+    // x + c
+    #[derive(Debug)]
+    struct Shift(u64);
+    impl CustomGate for Shift {
+      fn name(&self) -> &str {
+        "shift"
+      }
+      fn form(&self) -> BilinearForm {
+        BilinearForm {
+          one: FieldElement::from_real(self.0),
+          ..BilinearForm::from_ints(0, 1, 0, 0)
         }
       }
-      ret[10] = ret[10] * zero_v;
     }
-    for i in 0..gate_type_count {
-      if self.aritmetic_circuit.circuit[depth].is_parallel {
-        assert!(ret[i] == ret_para[i]);
+
+    // u v + (u + 1) (v + 1)
+    #[derive(Debug)]
+    struct InnerProduct;
+    impl CustomGate for InnerProduct {
+      fn name(&self) -> &str {
+        "inner product"
+      }
+      fn form(&self) -> BilinearForm {
+        BilinearForm::from_ints(1, 0, 0, 0)
+      }
+      fn for_each_wire(&self, u: usize, v: usize, f: &mut dyn FnMut(usize, usize, FieldElement)) {
+        f(u, v, FieldElement::real_one());
+        f(u + 1, v + 1, FieldElement::real_one());
       }
     }
-    ret
+
+    let mut registry = GateRegistry::new();
+    registry.register(20, Square).unwrap();
+    registry.register(21, Shift(7)).unwrap();
+    registry.register(22, InnerProduct).unwrap();
+
+    // inputs 3, 5, 2, 4, then 3^2, 5 + 7 and 3 * 2 + 5 * 4 followed by 9^2
+    let circuit =
+      "3\n4 3 0 3 0 3 1 5 0 3 2 2 0 3 3 4 0\n3 20 0 0 0 21 1 1 0 22 2 0 2\n1 20 0 0 0\n";
+    let meta = "0 0 1 0 0\n0 0 1 0 0\n0 0 1 0 0\n";
+    let mut verifier = ZkVerifier::new();
+    verifier.aritmetic_circuit =
+      LayeredCircuit::parse_with_gates(circuit, meta, &registry).unwrap();
+    let bit_length = verifier.aritmetic_circuit.max_bit_length();
+    verifier.init_array(bit_length);
+    let mut prover = ZkProver::new();
+    prover.init_array(bit_length, &verifier.aritmetic_circuit);
+    let proof = prover.prove(&mut Transcript::new(TRANSCRIPT_LABEL));
+    let public = outputs_of(&proof);
+
+    assert_eq!(
+      prover.circuit_value[2][..3],
+      [9, 12, 26].map(FieldElement::from_real)
+    );
+    assert_eq!(proof.output[0], FieldElement::from_real(81));
+    assert!(verifier.verify(&proof, &public, &mut Transcript::new(TRANSCRIPT_LABEL)));
   }
 
-  //Todo:
-  pub fn V_in() {} //Never used
-  pub fn read_r1cs() {} //Never used, original code is all commented in Orion, empty in Virgo
-  pub fn self_inner_product_test() {} //Never used, implemented only in Virgo, empty in Orion
-  pub fn verify_poly_commitment() {} //Todo in VPD, their imput parameter is the output of
-                                     // commit_public_array() and commit_private_array()
+  #[test]
+  fn parallel_circuits_prove() {
+    let layer = |bit_length, gates: Vec<GateKind>| Layer {
+      bit_length,
+      gates,
+      ..Layer::new()
+    };
+    let minus_one = FieldElement::zero() - FieldElement::real_one();
+    // 4 inputs, one gate of each type, then gates reading them back
+    let mut sub = LayeredCircuit::new();
+    sub.input_count = 4;
+    sub.circuit = vec![
+      layer(2, vec![GateKind::Constant { value: 0 }; 4]),
+      layer(2, (0..4).map(|u| GateKind::DirectRelay { u }).collect()),
+      layer(
+        4,
+        vec![
+          GateKind::Add { u: 0, v: 1 },
+          GateKind::Mult { u: 2, v: 3 },
+          GateKind::Zero,
+          GateKind::Constant { value: 7 },
+          GateKind::Sum { start: 0, end: 4 },
+          GateKind::Not { u: 1 },
+          GateKind::Minus { u: 3, v: 0 },
+          GateKind::Xor { u: 0, v: 2 },
+          GateKind::Naab { u: 1, v: 3 },
+          GateKind::Relay { u: 3 },
+          GateKind::BitPack { start: 0, end: 2 },
+          GateKind::BitTest { u: 1 },
+          GateKind::Linear {
+            terms: vec![(0, FieldElement::from_real(2)), (3, minus_one)],
+          },
+          GateKind::Zero,
+          GateKind::Zero,
+          GateKind::Zero,
+        ],
+      ),
+      layer(
+        2,
+        vec![
+          GateKind::Mult { u: 0, v: 1 },
+          GateKind::Add { u: 4, v: 12 },
+          GateKind::Xor { u: 7, v: 5 },
+          GateKind::Linear {
+            terms: vec![(10, FieldElement::from_real(3))],
+          },
+        ],
+      ),
+    ];
+    sub.total_depth = sub.circuit.len();
+
+    let circuit = sub.replicate(8);
+    let stride = 1 << circuit.circuit[1].log_block_size;
+    let copy_inputs = |b: u64| [b + 1, 2 * b, 3, b].map(FieldElement::from_real);
+    let mut witness = vec![FieldElement::zero(); circuit.input_count];
+    for b in 0..8 {
+      witness[b * stride..b * stride + 4].copy_from_slice(&copy_inputs(b as u64));
+    }
+
+    let mut verifier = ZkVerifier::new();
+    verifier.aritmetic_circuit = circuit;
+    let bit_length = verifier.aritmetic_circuit.max_bit_length();
+    verifier.init_array(bit_length);
+    let mut prover = ZkProver::new();
+    prover.init_array(bit_length, &verifier.aritmetic_circuit);
+    prover.get_witness(witness.clone());
+    let proof = prover.prove(&mut Transcript::new(TRANSCRIPT_LABEL));
+    let public = outputs_of(&proof);
+
+    for b in 0..8 {
+      let mut sub_prover = ZkProver::new();
+      sub_prover.init_array(sub.max_bit_length(), &sub);
+      sub_prover.get_witness(copy_inputs(b as u64).to_vec());
+      assert_eq!(sub_prover.evaluate(), proof.output[b * 4..b * 4 + 4]);
+    }
+    assert!(verifier.verify(&proof, &public, &mut Transcript::new(TRANSCRIPT_LABEL)));
+
+    let mut tampered = proof.clone();
+    tampered.layers[1].v_u = tampered.layers[1].v_u + FieldElement::real_one();
+    assert!(!verifier.verify(&tampered, &public, &mut Transcript::new(TRANSCRIPT_LABEL)));
+
+    // the predicates over a block agree with the ones over the whole layer
+    for layer in &mut verifier.aritmetic_circuit.circuit {
+      layer.is_parallel = false;
+    }
+    assert!(verifier.verify(&proof, &public, &mut Transcript::new(TRANSCRIPT_LABEL)));
+  }
 }
@@ -0,0 +1,505 @@
+use infrastructure::transcript::{Absorb, Transcript};
+use poly_commitment::public_array_coefficients;
+use prime_field::Field;
+
+use super::ZkVerifier;
+use crate::multilinear::{DenseMultilinear, EqPolynomial};
+use crate::polynomial::{LinearPoly, QuadraticPoly, QuadruplePoly};
+use crate::proof::{weigh_public_inputs, GkrProof};
+use crate::witness::PublicIo;
+use crate::zk::{vanishing, LayerClaims, ZkLayout};
+
+/// Message of a round of the zero knowledge sumcheck
+trait RoundMessage<F>: Absorb {
+  fn at(&self, x: F) -> F;
+}
+
+impl<F: Field> RoundMessage<F> for LinearPoly<F> {
+  fn at(&self, x: F) -> F {
+    self.eval(x)
+  }
+}
+
+impl<F: Field> RoundMessage<F> for QuadraticPoly<F> {
+  fn at(&self, x: F) -> F {
+    self.eval(&x)
+  }
+}
+
+impl<F: Field> RoundMessage<F> for QuadruplePoly<F> {
+  fn at(&self, x: F) -> F {
+    self.eval(x)
+  }
+}
+
+/// Checks the message of a round against `sum`, which it replaces with its
+/// value at the challenge, `offset` being added to the message on both
+/// counts. Returns the challenge, drawn from `transcript` unless `fixed`.
+fn round<F: Field>(
+  transcript: &mut Transcript,
+  message: &dyn RoundMessage<F>,
+  sum: &mut F,
+  offset: F,
+  fixed: Option<F>,
+) -> Option<F> {
+  transcript.absorb(message);
+  if message.at(F::zero()) + message.at(F::one()) + offset != *sum {
+    return None;
+  }
+  let r = fixed.unwrap_or_else(|| transcript.challenge());
+  *sum = message.at(r) + offset;
+  Some(r)
+}
+
+impl<F: Field> ZkVerifier<F> {
+  /// Replays a proof produced by `ZkProver::prove` in the zero knowledge
+  /// mode. The input layer but the public inputs of `public` is private: the
+  /// proof shows that, along with them, the input layer committed to in the
+  /// proof gives the outputs claimed in `public`.
+  pub fn verify_zero_knowledge(
+    &mut self,
+    proof: &GkrProof<F>,
+    public: &PublicIo<F>,
+    transcript: &mut Transcript,
+  ) -> bool {
+    let total_depth = self.aritmetic_circuit.total_depth;
+    let capacity = self.aritmetic_circuit.circuit[total_depth - 1].bit_length;
+    let zk = match &proof.zk {
+      Some(zk) => zk,
+      None => {
+        println!("Verification fail, proof is not zero knowledge");
+        return false;
+      }
+    };
+    if proof.hash != transcript.hasher() {
+      println!("Verification fail, proof uses the {} hash", proof.hash);
+      return false;
+    }
+    if proof.output.len() != 1 << capacity
+      || proof.layers.len() != total_depth - 1
+      || zk.layers.len() != total_depth - 1
+    {
+      println!("Verification fail, malformed proof");
+      return false;
+    }
+    let output = match self.claimed_output(proof, public) {
+      Some(output) => output,
+      None => return false,
+    };
+    transcript.absorb(&output[..]);
+    transcript.absorb(&public.inputs[..]);
+    transcript.append_digest(proof.commitment);
+    let layout = ZkLayout::new(&self.aritmetic_circuit);
+
+    let (zero, one) = (F::zero(), F::one());
+    let mut alpha = one;
+    let mut beta = zero;
+    let mut r_0 = transcript.challenges(capacity);
+    let mut r_1 = transcript.challenges(capacity);
+    let mut claim = DenseMultilinear::evaluate_table(&output, &r_0);
+
+    let mut claims = Vec::with_capacity(total_depth - 1);
+    for ((i, layer), zk_layer) in (1..total_depth).rev().zip(&proof.layers).zip(&zk.layers) {
+      let bit_length = self.aritmetic_circuit.circuit[i - 1].bit_length;
+      if layer.phase1.len() + 1 != bit_length || layer.phase2.len() + 1 != bit_length {
+        println!("Verification fail, malformed proof, circuit {}", i);
+        return false;
+      }
+
+      transcript.append_field_element(zk_layer.mask_sum);
+      let rho: F = transcript.challenge();
+      let mut sum = claim + rho * zk_layer.mask_sum;
+      let r_w = match round(transcript, &zk_layer.w_message, &mut sum, zero, None) {
+        Some(r_w) => r_w,
+        None => {
+          println!("Verification fail, mask round, circuit {}", i);
+          return false;
+        }
+      };
+      let (alpha_w, beta_w) = (alpha * (one - r_w), beta * (one - r_w));
+
+      let phase1 = layer.phase1.iter().map(|poly| poly as &dyn RoundMessage<F>);
+      let mut r_u = Vec::with_capacity(bit_length);
+      for (j, poly) in phase1
+        .chain([&zk_layer.phase1_last as &dyn RoundMessage<F>])
+        .enumerate()
+      {
+        match round(transcript, poly, &mut sum, zero, None) {
+          Some(r) => r_u.push(r),
+          None => {
+            println!(
+              "Verification fail, phase1, circuit {}, current bit {}",
+              i, j
+            );
+            return false;
+          }
+        }
+      }
+
+      let direct_relay_value =
+        alpha_w * self.direct_relay(i, &r_0, &r_u) + beta_w * self.direct_relay(i, &r_1, &r_u);
+      transcript.append_field_element(layer.v_u);
+
+      // layer 1 only relays the input layer, so v is fixed to zero there
+      let fixed = (i == 1).then_some(zero);
+      let phase2 = layer.phase2.iter().map(|poly| poly as &dyn RoundMessage<F>);
+      let mut r_v = Vec::with_capacity(bit_length);
+      for (j, poly) in phase2
+        .chain([&zk_layer.phase2_last as &dyn RoundMessage<F>])
+        .enumerate()
+      {
+        match round(
+          transcript,
+          poly,
+          &mut sum,
+          direct_relay_value * layer.v_u,
+          fixed,
+        ) {
+          Some(r) => r_v.push(r),
+          None => {
+            println!(
+              "Verification fail, phase2, circuit {}, current bit {}",
+              i, j
+            );
+            return false;
+          }
+        }
+      }
+
+      let (v_u, v_v) = (layer.v_u, layer.v_v);
+      transcript.append_field_element(v_v);
+      transcript.absorb(&zk_layer.lde_mask[..]);
+      transcript.append_field_element(zk_layer.mask_value);
+
+      // T(r_w), the output layer being the only one without a mask
+      let lde = (i + 1 < total_depth).then(|| {
+        let [lde_0, lde_1] = zk_layer.lde_mask;
+        [(r_0[0], lde_0), (r_1[0], lde_1)]
+      });
+      let t_w = match lde {
+        Some([(_, lde_0), (_, lde_1)]) => {
+          alpha * vanishing(&r_0) * lde_0 + beta * vanishing(&r_1) * lde_1
+        }
+        None if zk_layer.lde_mask == [zero; 2] => zero,
+        None => {
+          println!("Verification fail, malformed proof, circuit {}", i);
+          return false;
+        }
+      };
+
+      self.beta_init(i, alpha_w, beta_w, &r_0, &r_1, &r_u, &r_v);
+      let predicates_value = self.predicates(i, v_u, v_v);
+      if sum
+        != predicates_value
+          + direct_relay_value * v_u
+          + EqPolynomial::new(&r_u).evaluate_at_zero()
+            * EqPolynomial::new(&r_v).evaluate_at_zero()
+            * t_w
+          + rho * zk_layer.mask_value
+      {
+        println!("Verification fail, semi final, circuit level {}", i);
+        return false;
+      }
+
+      let mut point = Vec::with_capacity(2 * bit_length + 1);
+      point.push(r_w);
+      point.extend_from_slice(&r_u);
+      point.extend_from_slice(&r_v);
+      claims.push(LayerClaims {
+        layer: i,
+        lde,
+        point,
+        mask_value: zk_layer.mask_value,
+      });
+
+      alpha = transcript.challenge();
+      beta = transcript.challenge();
+      claim = if i != 1 {
+        alpha * v_u + beta * v_v
+      } else {
+        v_u
+      };
+      r_0 = r_u;
+      r_1 = r_v;
+    }
+
+    // the randomized extension of the input layer at r_0, less the public
+    // inputs, the claims on the masks and the committed array being zero on
+    // the public inputs are checked against the commitment at once
+    let lambda = transcript.challenge();
+    let rho = transcript.challenge();
+    let private_claim = claim - Self::public_input_value(&r_0, &public.inputs);
+    let (mut public_array, value) = layout.opening(&r_0, private_claim, &claims, lambda);
+    weigh_public_inputs(&mut public_array, public.inputs.len(), rho);
+    let coefficients = public_array_coefficients(&public_array, layout.log_len);
+    if !self.verify_poly_commitment(proof, &coefficients, layout.log_len, value, transcript) {
+      return false;
+    }
+    println!("Verification pass");
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use infrastructure::constants::RS_CODE_RATE;
+  use infrastructure::my_hash::{HashAlgorithm, HashDigest};
+  use infrastructure::transcript::Transcript;
+  use poly_commitment::PolyCommitProver;
+  use prime_field::{FieldElement, MOD};
+
+  use crate::circuit_fast_track::{GateKind, Layer, LayeredCircuit};
+  use crate::proof::{GkrProof, ZkProof, TRANSCRIPT_LABEL};
+  use crate::prover::{SumcheckMode, ZkProver};
+  use crate::verifier::ZkVerifier;
+  use crate::witness::PublicIo;
+
+  // inputs a, b, c, d, then a b and c + d, then their product
+  fn circuit() -> LayeredCircuit {
+    let layer = |bit_length, gates| Layer {
+      bit_length,
+      gates,
+      ..Layer::new()
+    };
+    let mut circuit = LayeredCircuit::new();
+    circuit.input_count = 4;
+    circuit.circuit = vec![
+      layer(2, vec![GateKind::Zero; 4]),
+      layer(2, (0..4).map(|u| GateKind::DirectRelay { u }).collect()),
+      layer(
+        1,
+        vec![GateKind::Mult { u: 0, v: 1 }, GateKind::Add { u: 2, v: 3 }],
+      ),
+      layer(1, vec![GateKind::Mult { u: 0, v: 1 }, GateKind::Zero]),
+    ];
+    circuit.total_depth = circuit.circuit.len();
+    circuit
+  }
+
+  /// Blake3 hashes the quickest in debug builds, where the merkle trees of
+  /// the commitments take most of the time of a proof
+  fn transcript() -> Transcript {
+    Transcript::with_hasher(TRANSCRIPT_LABEL, HashAlgorithm::Blake3)
+  }
+
+  fn prover(circuit: &LayeredCircuit, witness: [u64; 4]) -> ZkProver {
+    let mut prover = ZkProver::new();
+    prover.init_array(circuit.max_bit_length(), circuit);
+    prover.get_witness(witness.map(FieldElement::from_real).to_vec());
+    prover
+  }
+
+  fn prove(circuit: &LayeredCircuit, witness: [u64; 4], zero_knowledge: bool) -> GkrProof {
+    let mut prover = prover(circuit, witness);
+    prover.zero_knowledge = zero_knowledge;
+    prover.prove(&mut transcript())
+  }
+
+  fn verifier(circuit: LayeredCircuit) -> ZkVerifier {
+    let mut verifier = ZkVerifier::new();
+    verifier.init_array(circuit.max_bit_length());
+    verifier.aritmetic_circuit = circuit;
+    verifier
+  }
+
+  #[test]
+  fn zero_knowledge_proofs_verify() {
+    let mut verifier = verifier(circuit());
+    // both witnesses give 60
+    let public = PublicIo {
+      inputs: vec![],
+      outputs: [60, 0].map(FieldElement::from_real).to_vec(),
+    };
+    let mut verify =
+      |proof: &GkrProof| verifier.verify_zero_knowledge(proof, &public, &mut transcript());
+    let proof = prove(&circuit(), [2, 6, 1, 4], true);
+    assert_eq!(proof.output[0], FieldElement::from_real(60));
+    assert!(verify(&proof));
+
+    let digest = HashDigest { h0: 3, h1: 4 };
+    assert_eq!(
+      GkrProof::decode(&proof.encode(digest), digest).unwrap(),
+      proof
+    );
+
+    let mut sparse = circuit();
+    sparse.index_wires();
+    let mut prover = ZkProver::new();
+    prover.init_array(sparse.max_bit_length(), &sparse);
+    prover.set_mode(SumcheckMode::Sparse);
+    prover.get_witness([3, 5, 2, 2].map(FieldElement::from_real).to_vec());
+    // the sparse sumcheck doesn't depend on the commitment, a plain one
+    // keeps this proof cheap
+    let other = prover.prove_masked(&mut transcript(), PolyCommitProver::commit_private_array);
+    assert!(verify(&other));
+
+    let one = FieldElement::real_one();
+    let tamper = |f: &dyn Fn(&mut GkrProof)| {
+      let mut tampered = proof.clone();
+      f(&mut tampered);
+      tampered
+    };
+    fn zk(proof: &mut GkrProof) -> &mut ZkProof {
+      proof.zk.as_mut().unwrap()
+    }
+    for tampered in [
+      tamper(&|proof| proof.output[0] = proof.output[0] + one),
+      tamper(&|proof| proof.layers[1].v_v = proof.layers[1].v_v + one),
+      tamper(&|proof| zk(proof).layers[0].mask_sum = zk(proof).layers[0].mask_sum + one),
+      tamper(&|proof| zk(proof).layers[1].phase1_last.a = zk(proof).layers[1].phase1_last.a + one),
+      tamper(&|proof| zk(proof).layers[1].lde_mask[0] = zk(proof).layers[1].lde_mask[0] + one),
+      tamper(&|proof| zk(proof).layers[2].mask_value = zk(proof).layers[2].mask_value + one),
+      tamper(&|proof| proof.commitment = HashDigest::default()),
+      tamper(&|proof| proof.zk = None),
+    ] {
+      assert!(!verify(&tampered));
+    }
+
+    // the input committed to is the one checked, whatever the layers claim
+    assert!(!verify(&tamper(
+      &|proof| proof.opening = other.opening.clone()
+    )));
+    assert!(!verifier.verify(&proof, &public, &mut transcript()));
+  }
+
+  /// Two sample chi-square statistic comparing the distribution of every
+  /// entry of `a` with the one of `b`, with the real part of the entries
+  /// split into `BUCKETS` ranges, and its number of degrees of freedom. With
+  /// `n` samples on each side, the sum of `(a - b)^2 / (a + b)` over the
+  /// buckets has mean `2n / (2n - 1)` times the degrees of freedom, which
+  /// the statistic is scaled back by.
+  fn chi_square(a: &[Vec<FieldElement>], b: &[Vec<FieldElement>]) -> (f64, usize) {
+    const BUCKETS: usize = 8;
+    assert_eq!(a.len(), b.len());
+    let histogram = |samples: &[Vec<FieldElement>], k: usize| {
+      let mut counts = [0u32; BUCKETS];
+      for sample in samples {
+        counts[(sample[k].real as u128 * BUCKETS as u128 / MOD as u128) as usize] += 1;
+      }
+      counts
+    };
+    let (mut statistic, mut freedom) = (0.0, 0);
+    for k in 0..a[0].len() {
+      let (a, b) = (histogram(a, k), histogram(b, k));
+      let mut buckets = 0;
+      for (&a, &b) in a.iter().zip(&b) {
+        if a + b > 0 {
+          buckets += 1;
+          statistic += (a as f64 - b as f64).powi(2) / (a + b) as f64;
+        }
+      }
+      freedom += buckets - 1;
+    }
+    let samples = 2 * a.len();
+    (statistic * (samples - 1) as f64 / samples as f64, freedom)
+  }
+
+  /// Samples of the values `values` takes from proofs of each witness, made
+  /// by `prove`
+  fn samples(
+    count: usize,
+    prove: impl Fn([u64; 4]) -> GkrProof,
+    values: fn(&GkrProof) -> Vec<FieldElement>,
+  ) -> [Vec<Vec<FieldElement>>; 2] {
+    // both witnesses give 12 * 5 = 15 * 4 = 60
+    [[2, 6, 1, 4], [3, 5, 2, 2]]
+      .map(|witness| (0..count).map(|_| values(&prove(witness))).collect())
+  }
+
+  fn threshold(freedom: usize) -> f64 {
+    freedom as f64 + 6.0 * (2.0 * freedom as f64).sqrt()
+  }
+
+  #[test]
+  fn transcript_does_not_depend_on_the_witness() {
+    // the masks hide the witness whatever the commitment, so a plain one
+    // keeps the proofs cheap
+    let masked = |witness: [u64; 4]| {
+      let mut prover = prover(&circuit(), witness);
+      prover.prove_masked(&mut transcript(), PolyCommitProver::commit_private_array)
+    };
+    let [a, b] = samples(16, masked, gkr_values);
+    let (statistic, freedom) = chi_square(&a, &b);
+    assert!(freedom > 100);
+    assert!(statistic < threshold(freedom), "{} {}", statistic, freedom);
+
+    // without the masks, the messages are the same for every proof of a
+    // witness and tell the witnesses apart
+    let [a, b] = samples(4, |witness| prove(&circuit(), witness, false), gkr_values);
+    let (statistic, freedom) = chi_square(&a, &b);
+    assert!(statistic > threshold(freedom), "{} {}", statistic, freedom);
+  }
+
+  /// Takes minutes in debug builds, as every proof builds a hiding
+  /// commitment, run it with `cargo test --release -- --ignored`
+  #[test]
+  #[ignore]
+  fn opening_does_not_depend_on_the_witness() {
+    let [a, b] = samples(
+      32,
+      |witness| prove(&circuit(), witness, true),
+      opening_values,
+    );
+    let (statistic, freedom) = chi_square(&a, &b);
+    assert!(freedom > 1000);
+    assert!(statistic < threshold(freedom), "{} {}", statistic, freedom);
+
+    // with a plain commitment, the opening tells the witnesses apart
+    let plain = |witness: [u64; 4]| {
+      let mut prover = prover(&circuit(), witness);
+      prover.prove_masked(&mut transcript(), PolyCommitProver::commit_private_array)
+    };
+    let [a, b] = samples(32, plain, opening_values);
+    let (statistic, freedom) = chi_square(&a, &b);
+    assert!(statistic > threshold(freedom), "{} {}", statistic, freedom);
+  }
+
+  /// Every field element of the GKR part of `proof` but the output
+  fn gkr_values(proof: &GkrProof) -> Vec<FieldElement> {
+    let mut values = vec![];
+    for (i, layer) in proof.layers.iter().enumerate() {
+      for poly in layer.phase1.iter().chain(&layer.phase2) {
+        values.extend([poly.a, poly.b, poly.c]);
+      }
+      values.extend([layer.v_u, layer.v_v]);
+      if let Some(zk) = &proof.zk {
+        let zk = &zk.layers[i];
+        values.extend([zk.mask_sum, zk.w_message.a, zk.w_message.b]);
+        for poly in [zk.phase1_last, zk.phase2_last] {
+          values.extend([poly.a, poly.b, poly.c, poly.d, poly.e]);
+        }
+        values.extend(zk.lde_mask);
+        values.push(zk.mask_value);
+      }
+    }
+    values
+  }
+
+  /// The values the opening of the commitment of `proof` reveals
+  fn opening_values(proof: &GkrProof) -> Vec<FieldElement> {
+    // the folded oracles are sampled once, their leaves repeating across
+    // queries as they shrink and their final codewords being constants
+    let opening = &proof.opening;
+    let pairs = |values: &[(FieldElement, FieldElement)]| {
+      values
+        .iter()
+        .flat_map(|(a, b)| [*a, *b])
+        .collect::<Vec<_>>()
+    };
+    let mut values: Vec<_> = opening
+      .mask
+      .as_ref()
+      .map(|mask| mask.sum)
+      .into_iter()
+      .collect();
+    values.extend(&opening.all_sum);
+    for response in &opening.queries.init {
+      values.extend(response.values.iter().flat_map(|values| pairs(values)));
+    }
+    for response in &opening.queries.steps {
+      values.extend(pairs(&response.values[0]));
+    }
+    values.extend(opening.ldt.final_rs_code.iter().step_by(1 << RS_CODE_RATE));
+    values
+  }
+}
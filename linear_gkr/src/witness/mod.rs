@@ -0,0 +1,367 @@
+use std::fmt;
+use std::fs::{self, read_to_string, File};
+use std::io::{self, BufWriter, Write};
+
+use prime_field::{Field, FieldElement};
+
+use crate::prover::from_string_radix;
+
+/// First bytes of a witness in the binary format
+pub const WITNESS_MAGIC: [u8; 4] = *b"GKRW";
+/// Version of the binary format written by [`Witness::to_bytes`]
+pub const WITNESS_VERSION: u16 = 1;
+
+/// Section headers of the text format
+const SECTIONS: [&str; 2] = ["public", "private"];
+
+fn invalid_data(msg: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn parse_value<F: Field>(token: &str) -> io::Result<F> {
+  token
+    .parse::<u64>()
+    .map(F::from_u64)
+    .map_err(|err| invalid_data(format!("bad value {:?}: {}", token, err)))
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum WitnessError {
+  Io(io::Error),
+  /// A token is neither a section header nor a number
+  BadValue {
+    line: usize,
+    token: String,
+  },
+  /// A value appears before the first section header
+  ValueOutsideSection {
+    line: usize,
+  },
+  DuplicateSection {
+    line: usize,
+    section: &'static str,
+  },
+  BadMagic,
+  /// The binary input ended before the announced number of values
+  Truncated,
+  UnsupportedVersion(u16),
+  /// A value of the binary format is not below the field prime
+  NonCanonical {
+    index: usize,
+  },
+  TrailingBytes(usize),
+  /// The witness doesn't have one value per input gate of the circuit
+  CountMismatch {
+    expected: usize,
+    found: usize,
+  },
+}
+
+impl fmt::Display for WitnessError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Io(err) => write!(f, "{}", err),
+      Self::BadValue { line, token } => write!(f, "line {}: bad value {:?}", line, token),
+      Self::ValueOutsideSection { line } => write!(
+        f,
+        "line {}: value before the public or private section",
+        line
+      ),
+      Self::DuplicateSection { line, section } => {
+        write!(f, "line {}: duplicate {} section", line, section)
+      }
+      Self::BadMagic => write!(f, "bad witness magic bytes"),
+      Self::Truncated => write!(f, "witness is truncated"),
+      Self::UnsupportedVersion(version) => write!(f, "unsupported witness version {}", version),
+      Self::NonCanonical { index } => write!(f, "value {} is not reduced", index),
+      Self::TrailingBytes(len) => write!(f, "{} trailing bytes after the last value", len),
+      Self::CountMismatch { expected, found } => write!(
+        f,
+        "witness has {} values but the circuit has {} input gates",
+        found, expected
+      ),
+    }
+  }
+}
+
+impl std::error::Error for WitnessError {}
+
+impl From<io::Error> for WitnessError {
+  fn from(err: io::Error) -> Self {
+    Self::Io(err)
+  }
+}
+
+/// Values of the input gates of a circuit, split between the public inputs,
+/// known to the verifier, and the private ones which follow them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Witness<F = FieldElement> {
+  pub public: Vec<F>,
+  pub private: Vec<F>,
+}
+
+impl<F: Field> Witness<F> {
+  pub fn len(&self) -> usize {
+    self.public.len() + self.private.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// All the values in input gate order
+  pub fn values(&self) -> impl Iterator<Item = F> + '_ {
+    self.public.iter().chain(&self.private).copied()
+  }
+
+  /// Reads a witness file, in the binary format if it starts with
+  /// [`WITNESS_MAGIC`] and in the text format otherwise
+  pub fn read(path: &str) -> Result<Self, WitnessError> {
+    let bytes = fs::read(path)?;
+    if bytes.starts_with(&WITNESS_MAGIC) {
+      return Self::from_bytes(&bytes);
+    }
+    let text = String::from_utf8(bytes).map_err(|err| invalid_data(err.to_string()))?;
+    Self::from_text(&text)
+  }
+
+  /// Parses the text format: a `public` and a `private` section header,
+  /// each followed by whitespace separated values, decimal or hexadecimal
+  /// with a `0x` prefix. Values may exceed the prime and are reduced, and
+  /// `#` starts a comment running to the end of the line.
+  pub fn from_text(text: &str) -> Result<Self, WitnessError> {
+    let mut witness = Self::default();
+    let mut seen = [false; 2];
+    let mut section = None;
+    for (i, line) in text.lines().enumerate() {
+      let line_no = i + 1;
+      let content = line.split('#').next().unwrap();
+      for token in content.split_whitespace() {
+        if let Some(index) = SECTIONS.iter().position(|name| *name == token) {
+          if seen[index] {
+            return Err(WitnessError::DuplicateSection {
+              line: line_no,
+              section: SECTIONS[index],
+            });
+          }
+          seen[index] = true;
+          section = Some(index);
+          continue;
+        }
+
+        let value = match token.strip_prefix("0x") {
+          Some(hex) => from_string_radix(hex, 16),
+          None => from_string_radix(token, 10),
+        }
+        .ok_or_else(|| WitnessError::BadValue {
+          line: line_no,
+          token: token.to_string(),
+        })?;
+        match section {
+          Some(0) => witness.public.push(value),
+          Some(_) => witness.private.push(value),
+          None => return Err(WitnessError::ValueOutsideSection { line: line_no }),
+        }
+      }
+    }
+    Ok(witness)
+  }
+
+  /// Parses the binary format: magic, version (u16), number of public and of
+  /// private values (u64 each), then the values as u64, all little endian
+  pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, WitnessError> {
+    if take(&mut bytes, 4)? != WITNESS_MAGIC {
+      return Err(WitnessError::BadMagic);
+    }
+    let version = u16::from_le_bytes(take(&mut bytes, 2)?.try_into().unwrap());
+    if version != WITNESS_VERSION {
+      return Err(WitnessError::UnsupportedVersion(version));
+    }
+    let public_count = take_u64(&mut bytes)?;
+    let private_count = take_u64(&mut bytes)?;
+
+    let mut values = vec![];
+    for index in 0..public_count.saturating_add(private_count) {
+      let x = take_u64(&mut bytes)?;
+      let value = F::from_canonical_u64(x).ok_or(WitnessError::NonCanonical {
+        index: index as usize,
+      })?;
+      values.push(value);
+    }
+    if !bytes.is_empty() {
+      return Err(WitnessError::TrailingBytes(bytes.len()));
+    }
+    let private = values.split_off(public_count as usize);
+    Ok(Self {
+      public: values,
+      private,
+    })
+  }
+
+  /// Encodes the witness in the binary format read by [`Witness::from_bytes`]
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes = vec![];
+    bytes.extend_from_slice(&WITNESS_MAGIC);
+    bytes.extend_from_slice(&WITNESS_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(self.public.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&(self.private.len() as u64).to_le_bytes());
+    for x in self.values() {
+      let x = x
+        .to_canonical_u64()
+        .unwrap_or_else(|| panic!("{:?} is not in the base field", x));
+      bytes.extend_from_slice(&x.to_le_bytes());
+    }
+    bytes
+  }
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], WitnessError> {
+  if bytes.len() < len {
+    return Err(WitnessError::Truncated);
+  }
+  let (head, tail) = bytes.split_at(len);
+  *bytes = tail;
+  Ok(head)
+}
+
+fn take_u64(bytes: &mut &[u8]) -> Result<u64, WitnessError> {
+  Ok(u64::from_le_bytes(take(bytes, 8)?.try_into().unwrap()))
+}
+
+/// Public inputs and claimed outputs of a circuit, shared between the prover
+/// and the verifier next to the proof
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PublicIo<F = FieldElement> {
+  pub inputs: Vec<F>,
+  pub outputs: Vec<F>,
+}
+
+impl<F: Field> PublicIo<F> {
+  /// Reads a file made of the number of inputs, the inputs, the number of
+  /// outputs and the outputs, all as whitespace separated decimals
+  pub fn read(path: &str) -> io::Result<Self> {
+    let content = read_to_string(path)?;
+    let mut tokens = content.split_whitespace();
+    let mut read_section = |name: &str| -> io::Result<Vec<F>> {
+      let len: usize = tokens
+        .next()
+        .ok_or_else(|| invalid_data(format!("missing number of {}", name)))?
+        .parse()
+        .map_err(|err| invalid_data(format!("bad number of {}: {}", name, err)))?;
+      (0..len)
+        .map(|i| match tokens.next() {
+          Some(token) => parse_value(token),
+          None => Err(invalid_data(format!(
+            "expected {} {}, got {}",
+            len, name, i
+          ))),
+        })
+        .collect()
+    };
+
+    let inputs = read_section("inputs")?;
+    let outputs = read_section("outputs")?;
+    if tokens.next().is_some() {
+      return Err(invalid_data("trailing data after outputs".to_string()));
+    }
+    Ok(Self { inputs, outputs })
+  }
+
+  pub fn write(&self, path: &str) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for values in [&self.inputs, &self.outputs] {
+      writeln!(writer, "{}", values.len())?;
+      for x in values {
+        let x = x
+          .to_canonical_u64()
+          .ok_or_else(|| invalid_data(format!("{:?} is not in the base field", x)))?;
+        writeln!(writer, "{}", x)?;
+      }
+    }
+    writer.flush()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use prime_field::{FieldElement, MOD};
+
+  use super::{Witness, WitnessError};
+
+  fn sample() -> Witness {
+    Witness {
+      public: [3, 5].map(FieldElement::from_real).to_vec(),
+      private: [7, 2, 0].map(FieldElement::from_real).to_vec(),
+    }
+  }
+
+  #[test]
+  fn text_variants_parse_to_the_same_witness() {
+    let decimal = "public\n3 5\nprivate\n7\n2 0\n";
+    let hex = "# hexadecimal values\npublic 0x3 0x5 private 0x7 0x2 0x0 # inline\n";
+    assert_eq!(
+      Witness::<FieldElement>::from_text(decimal).unwrap(),
+      sample()
+    );
+    assert_eq!(Witness::<FieldElement>::from_text(hex).unwrap(), sample());
+
+    // values are reduced modulo the prime
+    let witness =
+      Witness::<FieldElement>::from_text(&format!("private {} 0x{:x}", MOD + 3, MOD)).unwrap();
+    assert!(witness.public.is_empty());
+    assert_eq!(
+      witness.private,
+      vec![FieldElement::from_real(3), FieldElement::zero()]
+    );
+  }
+
+  #[test]
+  fn binary_roundtrip() {
+    let bytes = sample().to_bytes();
+    assert_eq!(
+      Witness::<FieldElement>::from_bytes(&bytes).unwrap(),
+      sample()
+    );
+
+    for len in 0..bytes.len() {
+      assert!(Witness::<FieldElement>::from_bytes(&bytes[..len]).is_err());
+    }
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert!(matches!(
+      Witness::<FieldElement>::from_bytes(&trailing),
+      Err(WitnessError::TrailingBytes(1))
+    ));
+    let mut unreduced = bytes;
+    let last = unreduced.len() - 8;
+    unreduced[last..].copy_from_slice(&MOD.to_le_bytes());
+    assert!(matches!(
+      Witness::<FieldElement>::from_bytes(&unreduced),
+      Err(WitnessError::NonCanonical { index: 4 })
+    ));
+  }
+
+  #[test]
+  fn malformed_text_is_rejected() {
+    assert!(matches!(
+      Witness::<FieldElement>::from_text("public 1\n2 x3"),
+      Err(WitnessError::BadValue { line: 2, .. })
+    ));
+    assert!(matches!(
+      Witness::<FieldElement>::from_text("public 1\nprivate 0x\n"),
+      Err(WitnessError::BadValue { line: 2, .. })
+    ));
+    assert!(matches!(
+      Witness::<FieldElement>::from_text("\n1 public"),
+      Err(WitnessError::ValueOutsideSection { line: 2 })
+    ));
+    assert!(matches!(
+      Witness::<FieldElement>::from_text("public 1 private 2 public 3"),
+      Err(WitnessError::DuplicateSection {
+        line: 1,
+        section: "public"
+      })
+    ));
+  }
+}
@@ -0,0 +1,344 @@
+//! Pieces of the zero knowledge mode shared by the prover and the verifier.
+//!
+//! Every layer `i` below the output one is extended as
+//! `V(z) + Z(z) (R_i(z_0, 0) + R_i(z_0, 1))`, with `Z(z) = Π z_j (1 - z_j)`
+//! vanishing on the hypercube and `R_i` a random `LdeMask`, so that the
+//! claims the verifier learns on layer `i` are uniformly random. The sumcheck
+//! of layer `i` runs over `(w, u, v)` on
+//!
+//! `(1 - w) P(u, v) + eq((u, v), 0) T(w) + rho g(w, u, v)`
+//!
+//! where `P` is the polynomial of the plain sumcheck, over the randomized
+//! extension of layer `i - 1`, `T(w) = alpha Z(r_0) R_i(r_0[0], w) + beta
+//! Z(r_1) R_i(r_1[0], w)` accounts for the mask of layer `i` in the claims,
+//! and `g` is a random `MaskingPolynomial` hiding the round messages. The
+//! input layer, the `R_i` and the `g` are committed to at once, and opened
+//! at the end of the proof on a random combination of the claims.
+
+use infrastructure::constants::LOG_SLICE_NUMBER;
+use prime_field::{Field, FieldElement};
+
+use crate::circuit_fast_track::LayeredCircuit;
+use crate::multilinear::EqPolynomial;
+use crate::proof::prefix_point;
+
+/// `Π r_j (1 - r_j)`, the polynomial vanishing on the hypercube
+pub(crate) fn vanishing<F: Field>(r: &[F]) -> F {
+  r.iter().fold(F::one(), |ret, &r| ret * r * (F::one() - r))
+}
+
+fn inner_product<F: Field>(a: &[F], b: &[F]) -> F {
+  a.iter().zip(b).fold(F::zero(), |ret, (&a, &b)| ret + a * b)
+}
+
+fn power_of_two<F: Field>(exponent: usize) -> F {
+  F::from_u64(2).pow(exponent as u128)
+}
+
+/// Mask of the randomized extension of a layer,
+/// `R(z, w) = c_0 + c_1 z + c_2 w + c_3 z w`
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LdeMask<F = FieldElement>(pub [F; 4]);
+
+impl<F: Field> LdeMask<F> {
+  pub fn random() -> Self {
+    Self([(); 4].map(|_| F::random()))
+  }
+
+  /// Public coefficients of `R(z, w)`
+  pub fn eval_coefficients(z: F, w: F) -> [F; 4] {
+    [F::one(), z, w, z * w]
+  }
+
+  /// Public coefficients of `R(z, 0) + R(z, 1)`
+  pub fn sum_coefficients(z: F) -> [F; 4] {
+    let two = F::from_u64(2);
+    [two, two * z, F::one(), z]
+  }
+
+  pub fn eval(&self, z: F, w: F) -> F {
+    inner_product(&self.0, &Self::eval_coefficients(z, w))
+  }
+
+  /// `R(z, 0) + R(z, 1)`
+  pub fn sum(&self, z: F) -> F {
+    inner_product(&self.0, &Self::sum_coefficients(z))
+  }
+
+  /// Difference between the randomized and the multilinear extension of the
+  /// layer at `r`
+  pub fn extension(&self, r: &[F]) -> F {
+    vanishing(r) * self.sum(r[0])
+  }
+}
+
+/// Degrees of the masking polynomial of the sumcheck of a layer whose
+/// previous layer has `bit_length` bits, in the order of the rounds: `w`,
+/// then `u` and `v`. The last round of each phase also carries the
+/// randomized extension, which raises its degree to 4.
+pub(crate) fn round_degrees(bit_length: usize) -> Vec<usize> {
+  let phase = (0..bit_length).map(|j| if j + 1 == bit_length { 4 } else { 2 });
+  std::iter::once(1)
+    .chain(phase.clone())
+    .chain(phase)
+    .collect()
+}
+
+/// Masking polynomial of a sumcheck, `g(x) = c + Σ_k g_k(x_k)` with `g_k` of
+/// degree `degrees[k]` and no constant term, stored as `c` followed by the
+/// coefficients of every `g_k` from degree 1 up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MaskingPolynomial<F = FieldElement> {
+  degrees: Vec<usize>,
+  coefficients: Vec<F>,
+}
+
+impl<F: Field> MaskingPolynomial<F> {
+  pub fn random(degrees: Vec<usize>) -> Self {
+    let coefficients = (0..Self::len(&degrees)).map(|_| F::random()).collect();
+    Self {
+      degrees,
+      coefficients,
+    }
+  }
+
+  /// Number of coefficients of a masking polynomial of degrees `degrees`
+  pub fn len(degrees: &[usize]) -> usize {
+    1 + degrees.iter().sum::<usize>()
+  }
+
+  pub fn coefficients(&self) -> &[F] {
+    &self.coefficients
+  }
+
+  /// Public coefficients of `g(point)`
+  pub fn powers(degrees: &[usize], point: &[F]) -> Vec<F> {
+    let mut powers = Vec::with_capacity(Self::len(degrees));
+    powers.push(F::one());
+    for (&degree, &x) in degrees.iter().zip(point) {
+      let mut power = F::one();
+      for _ in 0..degree {
+        power = power * x;
+        powers.push(power);
+      }
+    }
+    powers
+  }
+
+  pub fn eval(&self, point: &[F]) -> F {
+    inner_product(&self.coefficients, &Self::powers(&self.degrees, point))
+  }
+
+  /// Coefficients of `g_k`, from degree 1 up
+  fn term(&self, k: usize) -> &[F] {
+    let start = 1 + self.degrees[..k].iter().sum::<usize>();
+    &self.coefficients[start..start + self.degrees[k]]
+  }
+
+  /// `g_k(1)`
+  fn term_at_one(&self, k: usize) -> F {
+    self.term(k).iter().fold(F::zero(), |ret, &c| ret + c)
+  }
+
+  /// Sum of `g` over the hypercube
+  pub fn sum(&self) -> F {
+    let rounds = self.degrees.len();
+    let terms = (0..rounds).fold(F::zero(), |ret, k| ret + self.term_at_one(k));
+    power_of_two::<F>(rounds) * self.coefficients[0] + power_of_two::<F>(rounds - 1) * terms
+  }
+
+  /// Message of the round after the ones bound to `bound`: the sum of `g`
+  /// over the remaining variables but the current one, as coefficients from
+  /// degree 0 up
+  pub fn message(&self, bound: &[F]) -> Vec<F> {
+    let round = bound.len();
+    let rest = self.degrees.len() - round - 1;
+    let scale: F = power_of_two(rest);
+
+    let mut constant = self.coefficients[0];
+    for (k, &x) in bound.iter().enumerate() {
+      let powers = Self::powers(&self.degrees[k..k + 1], &[x]);
+      constant = constant + inner_product(self.term(k), &powers[1..]);
+    }
+    constant = scale * constant;
+    if rest > 0 {
+      let later =
+        (round + 1..self.degrees.len()).fold(F::zero(), |ret, k| ret + self.term_at_one(k));
+      constant = constant + power_of_two::<F>(rest - 1) * later;
+    }
+
+    let mut message = vec![constant];
+    message.extend(self.term(round).iter().map(|&c| scale * c));
+    message
+  }
+}
+
+/// Values the zero knowledge sumcheck of a layer leaves to check against the
+/// commitment
+#[derive(Debug, Clone)]
+pub(crate) struct LayerClaims<F = FieldElement> {
+  pub layer: usize,
+  /// `(z, R(z, r_w))` for `z` the first bit of `r_0` and of `r_1`, unless the
+  /// layer is the output one, which isn't masked
+  pub lde: Option<[(F, F); 2]>,
+  /// Point the sumcheck ends on, `r_w` first, and the value of the masking
+  /// polynomial there
+  pub point: Vec<F>,
+  pub mask_value: F,
+}
+
+/// Where the values the zero knowledge mode commits to lie in the committed
+/// array: the input layer, the `LdeMask` of every layer but the output one,
+/// then the masking polynomial of the sumcheck of every layer above the input
+#[derive(Debug, Clone)]
+pub(crate) struct ZkLayout {
+  input_bit_length: usize,
+  degrees: Vec<Vec<usize>>,
+  maskings: Vec<usize>,
+  pub log_len: usize,
+}
+
+impl ZkLayout {
+  pub fn new<F: Field>(circuit: &LayeredCircuit<F>) -> Self {
+    let depth = circuit.total_depth;
+    // the input layer is zero padding past the input gates
+    let input_bit_length = (circuit.input_count.next_power_of_two().trailing_zeros() as usize)
+      .min(circuit.circuit[0].bit_length);
+    // index 0 stands for the input layer, which has no sumcheck
+    let degrees: Vec<_> = (0..depth)
+      .map(|i| match i {
+        0 => vec![],
+        _ => round_degrees(circuit.circuit[i - 1].bit_length),
+      })
+      .collect();
+    let mut maskings = vec![0];
+    let mut end = (1 << input_bit_length) + 4 * (depth - 1);
+    for degrees in &degrees[1..] {
+      maskings.push(end);
+      end += MaskingPolynomial::<F>::len(degrees);
+    }
+    let log_len = (end.next_power_of_two().trailing_zeros() as usize).max(LOG_SLICE_NUMBER + 1);
+    Self {
+      input_bit_length,
+      degrees,
+      maskings,
+      log_len,
+    }
+  }
+
+  /// Degrees of the masking polynomial of the sumcheck of layer `layer`
+  pub fn degrees(&self, layer: usize) -> &[usize] {
+    &self.degrees[layer]
+  }
+
+  fn lde_mask(&self, layer: usize) -> usize {
+    (1 << self.input_bit_length) + 4 * layer
+  }
+
+  /// Array committed to by the prover, zero past the values
+  pub fn committed_array<F: Field>(
+    &self,
+    input: &[F],
+    lde_masks: &[LdeMask<F>],
+    maskings: &[MaskingPolynomial<F>],
+  ) -> Vec<F> {
+    let mut array = vec![F::zero(); 1 << self.log_len];
+    let inputs = input.len().min(1 << self.input_bit_length);
+    array[..inputs].copy_from_slice(&input[..inputs]);
+    for (layer, mask) in lde_masks.iter().enumerate() {
+      let start = self.lde_mask(layer);
+      array[start..start + 4].copy_from_slice(&mask.0);
+    }
+    for (layer, masking) in (1..).zip(maskings) {
+      let start = self.maskings[layer];
+      array[start..start + masking.coefficients().len()].copy_from_slice(masking.coefficients());
+    }
+    array
+  }
+
+  /// Combines the claim that the randomized extension of the input layer is
+  /// `input_value` at `input_point` with the claims of the sumchecks, with
+  /// the powers of `lambda` as weights, into the public array the committed
+  /// one is opened against and the value of their inner product
+  pub fn opening<F: Field>(
+    &self,
+    input_point: &[F],
+    input_value: F,
+    layers: &[LayerClaims<F>],
+    lambda: F,
+  ) -> (Vec<F>, F) {
+    let mut public = vec![F::zero(); 1 << self.log_len];
+    let (point, scale) = prefix_point(input_point, self.input_bit_length);
+    for (entry, eq) in public
+      .iter_mut()
+      .zip(EqPolynomial::new(&point).evaluations())
+    {
+      *entry = scale * eq;
+    }
+    let scale = vanishing(input_point);
+    let start = self.lde_mask(0);
+    for (entry, c) in public[start..start + 4]
+      .iter_mut()
+      .zip(LdeMask::sum_coefficients(input_point[0]))
+    {
+      *entry = scale * c;
+    }
+    let mut value = input_value;
+
+    let mut weight = F::one();
+    let mut add = |start: usize, coefficients: &[F], claim: F| {
+      weight = weight * lambda;
+      for (entry, &c) in public[start..].iter_mut().zip(coefficients) {
+        *entry = *entry + weight * c;
+      }
+      value = value + weight * claim;
+    };
+    for claims in layers {
+      if let Some(lde) = claims.lde {
+        for (z, claim) in lde {
+          let coefficients = LdeMask::eval_coefficients(z, claims.point[0]);
+          add(self.lde_mask(claims.layer), &coefficients, claim);
+        }
+      }
+      let powers = MaskingPolynomial::powers(self.degrees(claims.layer), &claims.point);
+      add(self.maskings[claims.layer], &powers, claims.mask_value);
+    }
+    (public, value)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use prime_field::FieldElement;
+
+  use super::{round_degrees, MaskingPolynomial};
+
+  #[test]
+  fn masking_messages_sum_up() {
+    let degrees = round_degrees(2);
+    let g = MaskingPolynomial::random(degrees.clone());
+    let at = |message: &[FieldElement], x: FieldElement| {
+      message
+        .iter()
+        .rev()
+        .fold(FieldElement::zero(), |ret, &c| ret * x + c)
+    };
+
+    // summing every message over {0, 1} gives the claim, which the message
+    // at the challenge carries to the next round
+    let mut claim = g.sum();
+    let mut bound = vec![];
+    for &degree in &degrees {
+      let message = g.message(&bound);
+      assert_eq!(message.len(), degree + 1);
+      assert_eq!(
+        at(&message, FieldElement::zero()) + at(&message, FieldElement::real_one()),
+        claim
+      );
+      let r = FieldElement::new_random();
+      claim = at(&message, r);
+      bound.push(r);
+    }
+    assert_eq!(g.eval(&bound), claim);
+  }
+}
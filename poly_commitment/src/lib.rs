@@ -1,141 +1,903 @@
-use std::time;
+use std::{fmt, time};
 
-use prime_field::FieldElement;
+use serde::{Deserialize, Serialize};
+
+use prime_field::batch::PowersTable;
+use prime_field::{Field, FieldElement};
 
 use infrastructure::constants::*;
-use infrastructure::my_hash::HashDigest;
-use infrastructure::rs_polynomial::{self, fast_fourier_transform, inverse_fast_fourier_transform};
+use infrastructure::my_hash::{HashAlgorithm, HashDigest};
+use infrastructure::rs_polynomial::{fast_fourier_transform, inverse_fast_fourier_transform};
+use infrastructure::transcript::Transcript;
 use infrastructure::utility;
 
-#[derive(Default)]
-pub struct LdtCommitment {
-  pub commitment_hash: Vec<HashDigest>,
-  pub randomness: Vec<FieldElement>,
-  pub final_rs_code: Vec<FieldElement>,
-  pub mx_depth: usize,
-  // repeat_no: usize,
-}
+pub use vpd::fri::LdtCommitment;
+use vpd::fri::{request_init_commit, FRIContext};
+use vpd::prover::vpd_prover_init;
+use vpd::verifier::{verify_ldt, FriError, FriQueries};
 
 #[derive(Default, Debug, Clone)]
-pub struct PolyCommitContext {
-  pub twiddle_factor: Vec<FieldElement>,
-  pub inv_twiddle_factor: Vec<FieldElement>,
+pub struct PolyCommitContext<F = FieldElement> {
+  pub twiddle_factor: Vec<F>,
+  pub inv_twiddle_factor: Vec<F>,
   pub twiddle_factor_size: usize,
-  pub inner_prod_evals: Vec<FieldElement>,
+  pub inner_prod_evals: Vec<F>,
 
-  pub l_coef: Vec<FieldElement>,
+  pub l_coef: Vec<F>,
   pub l_coef_len: usize,
 
-  pub l_eval: Vec<FieldElement>,
+  pub l_eval: Vec<F>,
   pub l_eval_len: usize,
-  pub q_coef: Vec<FieldElement>,
+  pub q_coef: Vec<F>,
   pub q_coef_len: usize,
 
-  pub q_eval: Vec<FieldElement>,
+  pub q_eval: Vec<F>,
   pub q_eval_len: usize,
 
-  pub lq_coef: Vec<FieldElement>,
-  pub lq_eval: Vec<FieldElement>,
-  pub h_coef: Vec<FieldElement>,
-  pub h_eval: Vec<FieldElement>,
+  pub lq_coef: Vec<F>,
+  pub lq_eval: Vec<F>,
+  pub h_coef: Vec<F>,
+  pub h_eval: Vec<F>,
+  pub p_coef: Vec<F>,
 
-  pub h_eval_arr: Vec<FieldElement>,
+  pub h_eval_arr: Vec<F>,
 
   pub slice_size: usize,
   pub slice_count: usize,
   pub slice_real_ele_cnt: usize,
   pub pre_prepare_executed: bool,
 }
+
+/// Opening of a committed array, proving its inner product with a public
+/// array.
+///
+/// Every slice `l` of the private array is multiplied with the matching slice
+/// `q` of the public array. The prover sends the sum of every slice and
+/// commits to `h`, where `l * q = g + (x^|H| - 1) * h`, which leaves the
+/// verifier with a low degree test of `l`, `h` and
+/// `p = (|H| * g - sum) / (|H| * x)` on the queried points. `x * p` is tested
+/// as well: `p` must have degree below `|H| - 1`, else a prover could shift
+/// `h` by a constant into the top coefficient of `p` and change the sums.
+///
+/// The opening of a hiding commitment, see
+/// [`PolyCommitProver::commit_hiding_array`], runs on the spread arrays,
+/// with `H` the coset of the spread slices and `x^|H| - c^|H|` its vanishing
+/// polynomial.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolyCommitProof<F = FieldElement> {
+  /// Merkle root of the RS codewords of the quotients `h`
+  pub h_root: HashDigest,
+  /// Masking of the opening of a hiding commitment
+  pub mask: Option<MaskCommitment<F>>,
+  /// Inner product of every slice with the public array
+  pub all_sum: Vec<F>,
+  /// Commit phase of the low degree test of
+  /// `l + alpha * h + (beta + gamma * x) * p`, plus `delta * r` when hiding
+  pub ldt: LdtCommitment<F>,
+  /// Openings of `l`, `h`, `r` when hiding, and the folded oracles at the
+  /// queried points
+  pub queries: FriQueries<F>,
+}
+
+/// What the opening of a hiding commitment adds to hide the slices: the
+/// spread public array is `zeta * q` on the values and `x^|H| - c^|H|`, for
+/// `H` the subgroup of the values, on the padding, so the slice sums are
+/// masked by the padding. The low degree test is masked by a random
+/// polynomial `r` of degree below the one of the slices.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaskCommitment<F = FieldElement> {
+  /// Inner product of the padding with the public array, sent before `zeta`
+  /// is drawn
+  pub sum: F,
+  /// Merkle root of the RS codewords of `r`
+  pub root: HashDigest,
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PolyCommitError {
+  /// The proof doesn't have the shape implied by the array length
+  MalformedProof,
+  /// The slice sums don't add up to the claimed value
+  SumMismatch,
+  /// The low degree test of the virtual oracle failed
+  LowDegreeTest(FriError),
+}
+
+impl From<FriError> for PolyCommitError {
+  fn from(err: FriError) -> Self {
+    Self::LowDegreeTest(err)
+  }
+}
+
+impl fmt::Display for PolyCommitError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::MalformedProof => write!(f, "malformed polynomial commitment proof"),
+      Self::SumMismatch => write!(f, "slice sums don't match the claimed value"),
+      Self::LowDegreeTest(err) => write!(f, "low degree test failed: {}", err),
+    }
+  }
+}
+
+impl std::error::Error for PolyCommitError {}
+
+/// Coefficients of the slices of `array`, of length `1 << log_array_length`,
+/// each interpolated on the subgroup `H` of order `array.len() / SLICE_NUMBER`
+pub fn public_array_coefficients<F: Field>(array: &[F], log_array_length: usize) -> Vec<F> {
+  assert!(log_array_length >= LOG_SLICE_NUMBER);
+  assert_eq!(array.len(), 1 << log_array_length);
+
+  let slice_real_ele_cnt = array.len() / SLICE_NUMBER;
+  let root_of_unity = F::root_of_unity(log_array_length - LOG_SLICE_NUMBER).unwrap();
+
+  let mut coefficients = vec![F::zero(); array.len()];
+  for (slice, coef) in array
+    .chunks(slice_real_ele_cnt)
+    .zip(coefficients.chunks_mut(slice_real_ele_cnt))
+  {
+    inverse_fast_fourier_transform(slice, slice_real_ele_cnt, root_of_unity, coef);
+  }
+  coefficients
+}
+
+/// Random values padding every slice of a hiding commitment, more than the
+/// values of a slice its opening reveals: `l` and `h` at the two points of
+/// every query, the slice sum and the mask sum
+const HIDING_PADDING: usize = 4 * LDT_REPEAT_NUM + 2;
+
+/// Log of the factor a hiding commitment spreads the slices of an array of
+/// length `1 << log_array_length` by
+fn log_spread(log_array_length: usize) -> usize {
+  let slice_len = 1usize << (log_array_length - LOG_SLICE_NUMBER);
+  let spread_len = (slice_len + HIDING_PADDING).next_power_of_two();
+  (spread_len.trailing_zeros() - slice_len.trailing_zeros()) as usize
+}
+
+/// `c` such that the spread slices of a hiding commitment of an array of
+/// length `1 << log_array_length` lie on a coset `cH` of a subgroup of the
+/// evaluation domain `L` of the codewords, with `cH` out of `L`
+fn coset_shift<F: Field>(log_array_length: usize) -> F {
+  let log_slice_size = log_array_length + log_spread(log_array_length) + RS_CODE_RATE;
+  F::root_of_unity(log_slice_size - LOG_SLICE_NUMBER + 1).unwrap()
+}
+
+/// Turns the coefficients of `f(c * x)` into the ones of `f(x)`
+fn unshift<F: Field>(coefficients: &mut [F], c: F) {
+  let inv_c = c.inverse();
+  let mut scale = F::one();
+  for coefficient in coefficients {
+    *coefficient = *coefficient * scale;
+    scale = scale * inv_c;
+  }
+}
+
+fn evaluate<F: Field>(coefficients: &[F], x: F) -> F {
+  coefficients
+    .iter()
+    .rev()
+    .fold(F::zero(), |acc, c| acc * x + *c)
+}
+
 #[derive(Default, Debug, Clone)]
-pub struct PolyCommitProver {
+pub struct PolyCommitProver<F = FieldElement> {
   pub total_time_pc_p: f64,
-  ctx: PolyCommitContext,
+  ctx: PolyCommitContext<F>,
+  fri_ctx: FRIContext<F>,
+  l_root: HashDigest,
+  /// Coset the slices are interpolated on, `1` unless hiding
+  shift: F,
+  mask: Option<Mask<F>>,
+}
+
+/// State of a hiding commitment
+#[derive(Default, Debug, Clone)]
+struct Mask<F> {
+  log_spread: usize,
+  /// Inner product of the padding with the public array
+  sum: F,
+  /// Coefficients of the masking polynomial `r` of every slice
+  r_coef: Vec<F>,
+  r_root: HashDigest,
 }
 
-impl PolyCommitProver {
+impl<F: Field> PolyCommitProver<F> {
+  /// Prover whose merkle trees are built with `hasher`
+  pub fn new(hasher: HashAlgorithm) -> Self {
+    let mut prover = Self::default();
+    prover.fri_ctx.hasher = hasher;
+    prover
+  }
+
+  /// Commits to `private_array`, of length `1 << log_array_length`, and
+  /// returns the merkle root of the RS codewords of its slices
   pub fn commit_private_array(
     &mut self,
-    private_array: &[FieldElement],
+    private_array: &[F],
+    log_array_length: usize,
+  ) -> HashDigest {
+    self.mask = None;
+    self.commit(private_array, log_array_length, F::one())
+  }
+
+  /// Commits to `private_array` like [`Self::commit_private_array`], such
+  /// that the commitment and its opening reveal nothing else than the inner
+  /// product.
+  ///
+  /// Every slice is spread on a coset `cH` of a larger subgroup, out of the
+  /// evaluation domain of the codewords, its values on the subgroup of `cH`
+  /// of its size and random values elsewhere, which hide the queried values.
+  pub fn commit_hiding_array(
+    &mut self,
+    private_array: &[F],
     log_array_length: usize,
   ) -> HashDigest {
+    assert!(log_array_length > LOG_SLICE_NUMBER);
+    assert_eq!(private_array.len(), 1 << log_array_length);
+
+    let log_spread = log_spread(log_array_length);
+    let spread = 1 << log_spread;
+    let mut spread_array: Vec<_> = (0..private_array.len() << log_spread)
+      .map(|_| F::random())
+      .collect();
+    for (k, value) in private_array.iter().enumerate() {
+      spread_array[k << log_spread] = *value;
+    }
+
+    // the public array is x^|H| - c^|H| on the padding, with x^|H| the
+    // powers of a root of unity of order `spread`
+    let slice_real_ele_cnt = private_array.len() / SLICE_NUMBER;
+    let shift: F = coset_shift(log_array_length);
+    let shift_power = shift.pow(slice_real_ele_cnt as u128);
+    let root_of_unity = F::root_of_unity(log_spread).unwrap();
+    let weights: Vec<_> = (0..spread)
+      .map(|t| shift_power * (root_of_unity.pow(t as u128) - F::one()))
+      .collect();
+    let sum = spread_array
+      .iter()
+      .enumerate()
+      .fold(F::zero(), |acc, (k, x)| acc + weights[k % spread] * *x);
+
+    self.mask = Some(Mask {
+      log_spread,
+      sum,
+      ..Mask::default()
+    });
+    self.commit(&spread_array, log_array_length + log_spread, shift)
+  }
+
+  fn commit(&mut self, private_array: &[F], log_array_length: usize, shift: F) -> HashDigest {
+    assert!(log_array_length > LOG_SLICE_NUMBER);
+    assert_eq!(private_array.len(), 1 << log_array_length);
+
     self.total_time_pc_p = 0.;
+    self.shift = shift;
 
-    self.ctx.pre_prepare_executed = true;
+    let ctx = &mut self.ctx;
+    ctx.pre_prepare_executed = true;
 
     let slice_count = 1 << LOG_SLICE_NUMBER;
-    self.ctx.slice_count = slice_count;
+    ctx.slice_count = slice_count;
 
     let slice_size = 1 << (log_array_length + RS_CODE_RATE - LOG_SLICE_NUMBER);
-    self.ctx.slice_size = slice_size;
+    ctx.slice_size = slice_size;
 
     let slice_real_ele_cnt = slice_size >> RS_CODE_RATE;
-    self.ctx.slice_real_ele_cnt = slice_real_ele_cnt;
+    ctx.slice_real_ele_cnt = slice_real_ele_cnt;
 
     let l_eval_len = slice_count * slice_size;
-    self.ctx.l_eval_len = l_eval_len;
+    ctx.l_eval_len = l_eval_len;
+    ctx.l_eval = vec![F::zero(); l_eval_len];
+
+    ctx.l_coef_len = slice_count * slice_real_ele_cnt;
+    ctx.l_coef = vec![F::zero(); ctx.l_coef_len];
+
+    let now = time::Instant::now();
+
+    let root_of_unity_h = F::root_of_unity(utility::my_log(slice_real_ele_cnt).unwrap()).unwrap();
+    let root_of_unity_l = F::root_of_unity(utility::my_log(slice_size).unwrap()).unwrap();
 
-    let l_eval = &mut self.ctx.l_eval;
-    l_eval.reserve(l_eval_len);
+    for i in 0..slice_count {
+      let slice = &private_array[i * slice_real_ele_cnt..(i + 1) * slice_real_ele_cnt];
+      // the codeword of an all zero slice is all zero
+      if slice.iter().all(|x| *x == F::zero()) {
+        continue;
+      }
 
-    let mut tmp = Vec::<FieldElement>::with_capacity(slice_real_ele_cnt);
+      let coef = &mut ctx.l_coef[i * slice_real_ele_cnt..(i + 1) * slice_real_ele_cnt];
+      inverse_fast_fourier_transform(slice, slice_real_ele_cnt, root_of_unity_h, coef);
+      unshift(coef, shift);
+      fast_fourier_transform(
+        coef,
+        slice_size,
+        root_of_unity_l,
+        &mut ctx.l_eval[i * slice_size..(i + 1) * slice_size],
+      );
+    }
 
-    let order = slice_size * slice_count;
+    self.l_root = vpd_prover_init(&mut self.fri_ctx, &ctx.l_eval, log_array_length);
+
+    self.total_time_pc_p += now.elapsed().as_secs_f64();
+    self.l_root
+  }
+
+  /// Computes the sum of every slice of the committed array multiplied with
+  /// `public_array`, and commits to the quotients `h` of the univariate
+  /// sumcheck. Returns the merkle root of `h` and the sums.
+  ///
+  /// For a hiding commitment, `public_array` is the spread one, see
+  /// [`MaskCommitment`].
+  pub fn commit_public_array(&mut self, public_array: &[F]) -> (HashDigest, Vec<F>) {
+    assert!(self.ctx.pre_prepare_executed);
 
     let now = time::Instant::now();
 
-    let mut scratch_pad = rs_polynomial::ScratchPad::from_order(order);
+    let ctx = &mut self.ctx;
+    let slice_count = ctx.slice_count;
+    let slice_size = ctx.slice_size;
+    let slice_real_ele_cnt = ctx.slice_real_ele_cnt;
+    let log_array_length = utility::my_log(slice_count * slice_real_ele_cnt).unwrap();
 
-    for i in 0..slice_count {
-      let mut all_zero = true;
-      let zero = FieldElement::zero();
+    ctx.q_coef = public_array_coefficients(public_array, log_array_length);
+    ctx.q_coef_len = ctx.q_coef.len();
+    for coef in ctx.q_coef.chunks_mut(slice_real_ele_cnt) {
+      unshift(coef, self.shift);
+    }
+    let shift_power = self.shift.pow(slice_real_ele_cnt as u128);
 
-      for j in 0..slice_real_ele_cnt {
-        if private_array[i * slice_real_ele_cnt + j] == zero {
-          continue;
-        }
-        all_zero = false;
-        break;
+    ctx.q_eval_len = slice_size;
+    ctx.q_eval = vec![F::zero(); slice_size];
+    ctx.lq_eval = vec![F::zero(); slice_size];
+    ctx.h_eval = vec![F::zero(); slice_size];
+    ctx.lq_coef = vec![F::zero(); slice_count * slice_size];
+    ctx.h_coef = vec![F::zero(); slice_count * slice_real_ele_cnt];
+    ctx.h_eval_arr = vec![F::zero(); slice_count * slice_size];
+    ctx.p_coef = vec![F::zero(); slice_count * slice_real_ele_cnt];
+
+    let root_of_unity_l = F::root_of_unity(utility::my_log(slice_size).unwrap()).unwrap();
+    let h_size = F::from_u64(slice_real_ele_cnt as u64);
+
+    let mut all_sum = vec![F::zero(); slice_count];
+    for (i, sum) in all_sum.iter_mut().enumerate() {
+      let q_coef = &ctx.q_coef[i * slice_real_ele_cnt..(i + 1) * slice_real_ele_cnt];
+      fast_fourier_transform(q_coef, slice_size, root_of_unity_l, &mut ctx.q_eval);
+
+      let l_eval = &ctx.l_eval[i * slice_size..(i + 1) * slice_size];
+      F::mul_slices(l_eval, &ctx.q_eval, &mut ctx.lq_eval);
+
+      // l * q has degree below 2|H| <= |L|, so the inverse FFT on L gives its
+      // coefficients exactly. Reducing it modulo x^|H| - c^|H| gives g, the
+      // quotient is h, and the sum over cH is |H| times the constant term of
+      // g.
+      let lq_coef = &mut ctx.lq_coef[i * slice_size..(i + 1) * slice_size];
+      inverse_fast_fourier_transform(&ctx.lq_eval, slice_size, root_of_unity_l, lq_coef);
+      *sum = h_size * (lq_coef[0] + shift_power * lq_coef[slice_real_ele_cnt]);
+
+      let h_coef = &mut ctx.h_coef[i * slice_real_ele_cnt..(i + 1) * slice_real_ele_cnt];
+      h_coef.copy_from_slice(&lq_coef[slice_real_ele_cnt..2 * slice_real_ele_cnt]);
+      fast_fourier_transform(h_coef, slice_size, root_of_unity_l, &mut ctx.h_eval);
+      ctx.h_eval_arr[i * slice_size..(i + 1) * slice_size].copy_from_slice(&ctx.h_eval);
+
+      // p is g without its constant term, divided by x
+      let p_coef = &mut ctx.p_coef[i * slice_real_ele_cnt..(i + 1) * slice_real_ele_cnt];
+      for (m, c) in p_coef[..slice_real_ele_cnt - 1].iter_mut().enumerate() {
+        *c = lq_coef[m + 1] + shift_power * lq_coef[slice_real_ele_cnt + m + 1];
       }
+    }
+
+    let h_root = request_init_commit(&mut self.fri_ctx, &ctx.h_eval_arr, log_array_length, 1);
+
+    self.total_time_pc_p += now.elapsed().as_secs_f64();
+    (h_root, all_sum)
+  }
+
+  /// Proves the inner product of the committed array with `public_array`,
+  /// continuing the protocol on `transcript`
+  pub fn open(&mut self, public_array: &[F], transcript: &mut Transcript) -> PolyCommitProof<F> {
+    if self.mask.is_none() {
+      let (h_root, all_sum) = self.commit_public_array(public_array);
+      return self.prove_low_degree(h_root, all_sum, transcript);
+    }
+
+    let mask = self.mask.as_ref().unwrap();
+    let log_spread = mask.log_spread;
+    transcript.absorb(&mask.sum);
+    let zeta: F = transcript.challenge();
+
+    let spread = 1 << log_spread;
+    let slice_real_ele_cnt = public_array.len() / SLICE_NUMBER;
+    let shift_power = self.shift.pow(slice_real_ele_cnt as u128);
+    let root_of_unity = F::root_of_unity(log_spread).unwrap();
+    let weights: Vec<_> = (0..spread)
+      .map(|t| shift_power * (root_of_unity.pow(t as u128) - F::one()))
+      .collect();
+    let mut spread_array = weights.repeat(public_array.len());
+    for (k, value) in public_array.iter().enumerate() {
+      spread_array[k << log_spread] = zeta * *value;
+    }
+    let (h_root, all_sum) = self.commit_public_array(&spread_array);
 
-      if all_zero {
-        for j in 0..slice_size {
-          l_eval[i * slice_size + j] = zero;
+    // r has the degree of the slices, so that l + alpha * h + ... + delta * r
+    // is a random codeword of that degree
+    let ctx = &self.ctx;
+    let slice_size = ctx.slice_size;
+    let slice_real_ele_cnt = ctx.slice_real_ele_cnt;
+    let root_of_unity_l = F::root_of_unity(utility::my_log(slice_size).unwrap()).unwrap();
+    let r_coef: Vec<_> = (0..ctx.slice_count * slice_real_ele_cnt)
+      .map(|_| F::random())
+      .collect();
+    let mut r_eval = vec![F::zero(); ctx.slice_count * slice_size];
+    for (coef, eval) in r_coef
+      .chunks(slice_real_ele_cnt)
+      .zip(r_eval.chunks_mut(slice_size))
+    {
+      fast_fourier_transform(coef, slice_size, root_of_unity_l, eval);
+    }
+    let log_array_length = utility::my_log(ctx.slice_count * slice_real_ele_cnt).unwrap();
+    let r_root = request_init_commit(&mut self.fri_ctx, &r_eval, log_array_length, 2);
+
+    let mask = self.mask.as_mut().unwrap();
+    mask.r_coef = r_coef;
+    mask.r_root = r_root;
+    self.prove_low_degree(h_root, all_sum, transcript)
+  }
+
+  /// Low degree test of the polynomials of [`Self::commit_public_array`],
+  /// the rest of [`Self::open`]
+  pub fn prove_low_degree(
+    &mut self,
+    h_root: HashDigest,
+    all_sum: Vec<F>,
+    transcript: &mut Transcript,
+  ) -> PolyCommitProof<F> {
+    let now = time::Instant::now();
+
+    transcript.append_digest(self.l_root);
+    transcript.append_digest(h_root);
+    if let Some(mask) = &self.mask {
+      transcript.append_digest(mask.r_root);
+    }
+    transcript.absorb(&all_sum[..]);
+    let alpha: F = transcript.challenge();
+    let beta: F = transcript.challenge();
+    let gamma: F = transcript.challenge();
+    let delta: F = match self.mask {
+      Some(_) => transcript.challenge(),
+      None => F::zero(),
+    };
+
+    // the virtual oracle l + alpha * h + (beta + gamma * x) * p has degree
+    // below |H| if and only if l, h and x * p do, FRI tests its RS codewords
+    // on L. Adding delta * r makes it a random codeword.
+    let ctx = &self.ctx;
+    let slice_size = ctx.slice_size;
+    let slice_real_ele_cnt = ctx.slice_real_ele_cnt;
+    let root_of_unity_l = F::root_of_unity(utility::my_log(slice_size).unwrap()).unwrap();
+
+    let mut virtual_oracle = vec![F::zero(); ctx.slice_count * slice_size];
+    let mut coef = vec![F::zero(); slice_real_ele_cnt];
+    for i in 0..ctx.slice_count {
+      for (m, c) in coef.iter_mut().enumerate() {
+        let k = i * slice_real_ele_cnt + m;
+        let x_p_coef = if m > 0 { ctx.p_coef[k - 1] } else { F::zero() };
+        *c = ctx.l_coef[k] + alpha * ctx.h_coef[k] + beta * ctx.p_coef[k] + gamma * x_p_coef;
+        if let Some(mask) = &self.mask {
+          *c = *c + delta * mask.r_coef[k];
         }
-      } else {
-        inverse_fast_fourier_transform(
-          &mut scratch_pad,
-          &private_array[i * slice_real_ele_cnt..],
-          slice_real_ele_cnt,
-          slice_real_ele_cnt,
-          FieldElement::get_root_of_unity(utility::my_log(slice_real_ele_cnt).unwrap()).unwrap(),
-          &mut tmp[..],
-        );
-
-        fast_fourier_transform(
-          &mut scratch_pad.dst,
-          &mut scratch_pad.twiddle_factor,
-          &mut scratch_pad.twiddle_factor_size,
-          &tmp[..],
-          slice_real_ele_cnt,
-          slice_size,
-          FieldElement::get_root_of_unity(utility::my_log(slice_size).unwrap()).unwrap(),
-          &mut l_eval[i * slice_size..],
-        )
       }
+      fast_fourier_transform(
+        &coef,
+        slice_size,
+        root_of_unity_l,
+        &mut virtual_oracle[i * slice_size..(i + 1) * slice_size],
+      );
     }
 
-    let elapsed_time = now.elapsed();
-    println!("FFT Prepare time: {} ms", elapsed_time.as_millis());
+    let log_array_length = utility::my_log(ctx.slice_count * slice_real_ele_cnt).unwrap();
+    self.fri_ctx.set_virtual_oracle(&virtual_oracle);
+    let ldt = self.fri_ctx.commit_phase(log_array_length, transcript);
+    let queries = self.fri_ctx.query_phase(transcript);
 
-    unimplemented!()
+    self.total_time_pc_p += now.elapsed().as_secs_f64();
+    PolyCommitProof {
+      h_root,
+      mask: self.mask.as_ref().map(|mask| MaskCommitment {
+        sum: mask.sum,
+        root: mask.r_root,
+      }),
+      all_sum,
+      ldt,
+      queries,
+    }
   }
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct PolyCommitVerifier {
-  pub pc_prover: PolyCommitProver,
+  pub total_time_pc_v: f64,
+  pub hasher: HashAlgorithm,
 }
 
-pub fn commit_phrase_step(r: FieldElement) -> HashDigest {
-  HashDigest::new()
+impl PolyCommitVerifier {
+  pub fn new(hasher: HashAlgorithm) -> Self {
+    Self {
+      hasher,
+      ..Self::default()
+    }
+  }
+
+  /// Checks that the array of length `1 << log_array_length` committed to by
+  /// `commitment` has inner product `claimed_value` with the public array
+  /// whose slice coefficients are `public_coefficients`, as computed by
+  /// [`public_array_coefficients`]. Hiding commitments are told apart by
+  /// their [`MaskCommitment`].
+  pub fn verify_poly_commitment<F: Field>(
+    &mut self,
+    commitment: HashDigest,
+    proof: &PolyCommitProof<F>,
+    public_coefficients: &[F],
+    log_array_length: usize,
+    claimed_value: F,
+    transcript: &mut Transcript,
+  ) -> Result<(), PolyCommitError> {
+    assert!(log_array_length > LOG_SLICE_NUMBER);
+    assert_eq!(public_coefficients.len(), 1 << log_array_length);
+
+    let now = time::Instant::now();
+
+    let slice_real_ele_cnt = 1 << (log_array_length - LOG_SLICE_NUMBER);
+    // the slices of a hiding commitment are spread on a coset of a larger
+    // subgroup, see `PolyCommitProver::commit_hiding_array`
+    let (log_spread, shift) = match proof.mask {
+      Some(_) => (log_spread(log_array_length), coset_shift(log_array_length)),
+      None => (0, F::one()),
+    };
+    let log_slice_size = log_array_length + log_spread + RS_CODE_RATE - LOG_SLICE_NUMBER;
+    let spread_slice_len = slice_real_ele_cnt << log_spread;
+
+    if proof.all_sum.len() != SLICE_NUMBER {
+      return Err(PolyCommitError::MalformedProof);
+    }
+    let mut zeta = F::one();
+    let mut claimed_value = claimed_value;
+    if let Some(mask) = &proof.mask {
+      transcript.absorb(&mask.sum);
+      zeta = transcript.challenge();
+      claimed_value = zeta * claimed_value + mask.sum;
+    }
+    let sum = proof.all_sum.iter().fold(F::zero(), |acc, x| acc + *x);
+    if sum != claimed_value {
+      return Err(PolyCommitError::SumMismatch);
+    }
+
+    transcript.append_digest(commitment);
+    transcript.append_digest(proof.h_root);
+    let mut init_roots = vec![commitment, proof.h_root];
+    if let Some(mask) = &proof.mask {
+      transcript.append_digest(mask.root);
+      init_roots.push(mask.root);
+    }
+    transcript.absorb(&proof.all_sum[..]);
+    let alpha: F = transcript.challenge();
+    let beta: F = transcript.challenge();
+    let gamma: F = transcript.challenge();
+    let delta: F = match proof.mask {
+      Some(_) => transcript.challenge(),
+      None => F::zero(),
+    };
+
+    let root_powers = PowersTable::new(F::root_of_unity(log_slice_size).unwrap(), log_slice_size);
+    let h_size = F::from_u64(spread_slice_len as u64);
+    let inv_shift = shift.inverse();
+    let shift_power = shift.pow(slice_real_ele_cnt as u128);
+    let spread_shift_power = shift.pow(spread_slice_len as u128);
+    // 1/(spread * c^(|H| (spread - 1))), for the indicator of the values
+    let inv_spread = (F::from_u64(1 << log_spread) * spread_shift_power).inverse() * shift_power;
+
+    // values of l + alpha * h + (beta + gamma * x) * p + delta * r at w^pow
+    // and -w^pow
+    let virtual_oracle = |pow: usize, init: &[&[(F, F)]]| {
+      let x = root_powers.pow(pow as u128);
+      let mut values = vec![(F::zero(), F::zero()); SLICE_NUMBER];
+      for (x, second) in [(x, false), (-x, true)] {
+        let vanishing = x.pow(spread_slice_len as u128) - spread_shift_power;
+        let inv_h_x = (h_size * x).inverse();
+        // the spread public array is zeta * q(x / c) on the values, where
+        // the indicator (x^|H'| - c^|H'|) / (spread c^(|H| (spread - 1))
+        // (x^|H| - c^|H|)) is 1, and x^|H| - c^|H| on the padding, where it
+        // is 0
+        let padding = x.pow(slice_real_ele_cnt as u128) - shift_power;
+        let indicator = match proof.mask {
+          Some(_) => zeta * vanishing * inv_spread * padding.inverse(),
+          None => F::one(),
+        };
+        for (i, value) in values.iter_mut().enumerate() {
+          let pick = |(a, b): (F, F)| if second { b } else { a };
+          let l = pick(init[0][i]);
+          let h = pick(init[1][i]);
+
+          let mut q = evaluate(
+            &public_coefficients[i * slice_real_ele_cnt..(i + 1) * slice_real_ele_cnt],
+            x * inv_shift,
+          );
+          let mut v = F::zero();
+          if proof.mask.is_some() {
+            q = indicator * q + padding;
+            v = delta * pick(init[2][i]);
+          }
+          let g = l * q - vanishing * h;
+          let p = (h_size * g - proof.all_sum[i]) * inv_h_x;
+          let v = v + l + alpha * h + (beta + gamma * x) * p;
+          if second {
+            value.1 = v;
+          } else {
+            value.0 = v;
+          }
+        }
+      }
+      values
+    };
+    verify_ldt(
+      &self.hasher,
+      &proof.ldt,
+      &init_roots,
+      &proof.queries,
+      log_slice_size,
+      transcript,
+      virtual_oracle,
+    )?;
+
+    self.total_time_pc_v += now.elapsed().as_secs_f64();
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use infrastructure::transcript::Transcript;
+  use prime_field::FieldElement;
+
+  use super::*;
+
+  const LOG_LENGTH: usize = 8;
+
+  fn setup() -> (Vec<FieldElement>, Vec<FieldElement>, FieldElement) {
+    let private_array: Vec<_> = (0..1 << LOG_LENGTH)
+      .map(|_| FieldElement::new_random())
+      .collect();
+    let public_array: Vec<_> = (0..1 << LOG_LENGTH)
+      .map(|_| FieldElement::new_random())
+      .collect();
+    let inner_product = private_array
+      .iter()
+      .zip(&public_array)
+      .fold(FieldElement::zero(), |acc, (a, b)| acc + *a * *b);
+    (private_array, public_array, inner_product)
+  }
+
+  fn prove(
+    private_array: &[FieldElement],
+    public_array: &[FieldElement],
+  ) -> (HashDigest, PolyCommitProof) {
+    prove_with(HashAlgorithm::default(), private_array, public_array)
+  }
+
+  fn prove_with(
+    hasher: HashAlgorithm,
+    private_array: &[FieldElement],
+    public_array: &[FieldElement],
+  ) -> (HashDigest, PolyCommitProof) {
+    let mut prover = PolyCommitProver::new(hasher);
+    let commitment = prover.commit_private_array(private_array, LOG_LENGTH);
+    let proof = prover.open(public_array, &mut Transcript::new(b"test"));
+    (commitment, proof)
+  }
+
+  fn verify(
+    commitment: HashDigest,
+    proof: &PolyCommitProof,
+    public_array: &[FieldElement],
+    claimed_value: FieldElement,
+  ) -> Result<(), PolyCommitError> {
+    verify_with(
+      HashAlgorithm::default(),
+      commitment,
+      proof,
+      public_array,
+      claimed_value,
+    )
+  }
+
+  fn verify_with(
+    hasher: HashAlgorithm,
+    commitment: HashDigest,
+    proof: &PolyCommitProof,
+    public_array: &[FieldElement],
+    claimed_value: FieldElement,
+  ) -> Result<(), PolyCommitError> {
+    PolyCommitVerifier::new(hasher).verify_poly_commitment(
+      commitment,
+      proof,
+      &public_array_coefficients(public_array, LOG_LENGTH),
+      LOG_LENGTH,
+      claimed_value,
+      &mut Transcript::new(b"test"),
+    )
+  }
+
+  #[test]
+  fn honest_opening_verifies() {
+    let (private_array, public_array, inner_product) = setup();
+    let (commitment, proof) = prove(&private_array, &public_array);
+    verify(commitment, &proof, &public_array, inner_product).unwrap();
+  }
+
+  #[test]
+  fn openings_verify_with_the_same_hash_only() {
+    let (private_array, public_array, inner_product) = setup();
+    for hasher in [
+      HashAlgorithm::Sha256,
+      HashAlgorithm::Blake3,
+      HashAlgorithm::Poseidon,
+    ] {
+      let (commitment, proof) = prove_with(hasher, &private_array, &public_array);
+      verify_with(hasher, commitment, &proof, &public_array, inner_product).unwrap();
+      assert!(matches!(
+        verify_with(
+          HashAlgorithm::Sha3_256,
+          commitment,
+          &proof,
+          &public_array,
+          inner_product
+        ),
+        Err(PolyCommitError::LowDegreeTest(
+          FriError::InitMerkleMismatch { oracle: 0 }
+        ))
+      ));
+    }
+  }
+
+  #[test]
+  fn sum_shifted_into_the_top_coefficient_of_p_is_rejected() {
+    let (private_array, public_array, inner_product) = setup();
+    let mut prover = PolyCommitProver::new(HashAlgorithm::default());
+    let commitment = prover.commit_private_array(&private_array, LOG_LENGTH);
+    let (_, mut all_sum) = prover.commit_public_array(&public_array);
+
+    // h - c and p + c * x^(|H| - 1) satisfy the identity of the slice with
+    // its sum lowered by |H| * c, and l, h and p all have degree below |H|
+    let c = FieldElement::from_u64(12345);
+    let ctx = &mut prover.ctx;
+    let h_size = ctx.slice_real_ele_cnt;
+    ctx.h_coef[0] = ctx.h_coef[0] - c;
+    for h in &mut ctx.h_eval_arr[..ctx.slice_size] {
+      *h = *h - c;
+    }
+    ctx.p_coef[h_size - 1] = ctx.p_coef[h_size - 1] + c;
+    let shift = FieldElement::from_u64(h_size as u64) * c;
+    all_sum[0] = all_sum[0] - shift;
+    let h_root = request_init_commit(&mut prover.fri_ctx, &ctx.h_eval_arr, LOG_LENGTH, 1);
+
+    let proof = prover.prove_low_degree(h_root, all_sum, &mut Transcript::new(b"test"));
+    assert!(verify(commitment, &proof, &public_array, inner_product - shift).is_err());
+  }
+
+  #[test]
+  fn hiding_opening_verifies() {
+    let (private_array, public_array, inner_product) = setup();
+    // Blake3 hashes the quickest in debug builds, where the merkle trees of
+    // the spread slices take most of the time
+    let hasher = HashAlgorithm::Blake3;
+    let mut prover = PolyCommitProver::new(hasher);
+    let commitment = prover.commit_hiding_array(&private_array, LOG_LENGTH);
+    let proof = prover.open(&public_array, &mut Transcript::new(b"test"));
+    let verify = |proof: &PolyCommitProof, claimed_value| {
+      verify_with(hasher, commitment, proof, &public_array, claimed_value)
+    };
+    verify(&proof, inner_product).unwrap();
+
+    // the padding masks the slice sums
+    let (plain_commitment, plain) = prove_with(hasher, &private_array, &public_array);
+    assert_ne!(commitment, plain_commitment);
+    assert!(proof
+      .all_sum
+      .iter()
+      .zip(&plain.all_sum)
+      .all(|(a, b)| a != b));
+
+    let one = FieldElement::real_one();
+    assert!(matches!(
+      verify(&proof, inner_product + one),
+      Err(PolyCommitError::SumMismatch)
+    ));
+
+    let mut bad_mask_sum = proof.clone();
+    let sum = &mut bad_mask_sum.mask.as_mut().unwrap().sum;
+    *sum = *sum + one;
+    assert!(verify(&bad_mask_sum, inner_product).is_err());
+
+    let mut bad_mask_root = proof.clone();
+    bad_mask_root.mask.as_mut().unwrap().root = HashDigest::default();
+    assert!(verify(&bad_mask_root, inner_product).is_err());
+
+    let mut unmasked = proof;
+    unmasked.mask = None;
+    assert!(verify(&unmasked, inner_product).is_err());
+  }
+
+  #[test]
+  fn tampered_opening_is_rejected() {
+    let (private_array, public_array, inner_product) = setup();
+    let (commitment, proof) = prove(&private_array, &public_array);
+    let one = FieldElement::real_one();
+
+    assert!(matches!(
+      verify(commitment, &proof, &public_array, inner_product + one),
+      Err(PolyCommitError::SumMismatch)
+    ));
+
+    let mut bad_value = proof.clone();
+    let value = &mut bad_value.queries.init[0].values[0][0].0;
+    *value = *value + one;
+    assert!(matches!(
+      verify(commitment, &bad_value, &public_array, inner_product),
+      Err(PolyCommitError::LowDegreeTest(
+        FriError::InitMerkleMismatch { oracle: 0 }
+      ))
+    ));
+
+    let mut bad_path = proof.clone();
+    bad_path.queries.init[1].proof.siblings[0] = HashDigest::default();
+    assert!(matches!(
+      verify(commitment, &bad_path, &public_array, inner_product),
+      Err(PolyCommitError::LowDegreeTest(
+        FriError::InitMerkleMismatch { oracle: 1 }
+      ))
+    ));
+
+    let mut bad_fold = proof.clone();
+    let value = &mut bad_fold.queries.steps[0].values[2][3].1;
+    *value = *value + one;
+    assert!(matches!(
+      verify(commitment, &bad_fold, &public_array, inner_product),
+      Err(PolyCommitError::LowDegreeTest(
+        FriError::StepMerkleMismatch { step: 0 }
+      ))
+    ));
+
+    let mut bad_final = proof.clone();
+    bad_final.ldt.final_rs_code[0] = bad_final.ldt.final_rs_code[0] + one;
+    assert!(matches!(
+      verify(commitment, &bad_final, &public_array, inner_product),
+      Err(PolyCommitError::LowDegreeTest(FriError::FinalRsCodeDegree))
+    ));
+
+    let mut truncated = proof.clone();
+    truncated.queries.steps.pop();
+    assert!(matches!(
+      verify(commitment, &truncated, &public_array, inner_product),
+      Err(PolyCommitError::LowDegreeTest(FriError::MalformedProof))
+    ));
+
+    // moving the sum between slices keeps the total but not the polynomials
+    let mut bad_sum = proof.clone();
+    bad_sum.all_sum[0] = bad_sum.all_sum[0] + one;
+    bad_sum.all_sum[1] = bad_sum.all_sum[1] - one;
+    assert!(verify(commitment, &bad_sum, &public_array, inner_product).is_err());
+
+    let mut other_public_array = public_array.clone();
+    other_public_array[0] = other_public_array[0] + one;
+    // a query in H, where the Lagrange polynomial of the changed entry
+    // vanishes, doesn't see the change
+    assert!(matches!(
+      verify(commitment, &proof, &other_public_array, inner_product),
+      Err(PolyCommitError::LowDegreeTest(FriError::FoldMismatch {
+        step: 0,
+        ..
+      }))
+    ));
+
+    let (other_commitment, _) = prove(&public_array, &public_array);
+    assert!(verify(other_commitment, &proof, &public_array, inner_product).is_err());
+  }
 }
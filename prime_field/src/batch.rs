@@ -0,0 +1,165 @@
+use rayon::prelude::*;
+
+use crate::Field;
+
+/// Elements handled by one task of the parallel variants, each paying a
+/// single inversion or exponentiation
+const PARALLEL_CHUNK: usize = 1 << 12;
+
+/// Bits of the exponent consumed per window of a [`PowersTable`]
+const WINDOW_BITS: usize = 4;
+
+/// Replaces every element of `values` by its inverse with Montgomery's trick,
+/// one inversion and three products per element. Zeros are left as they
+/// are, like `inverse` maps zero to zero.
+pub fn batch_inverse<F: Field>(values: &mut [F]) {
+    // prefix[i] is the product of the non zero elements before i
+    let mut prefix = Vec::with_capacity(values.len());
+    let mut acc = F::one();
+    for x in values.iter() {
+        prefix.push(acc);
+        if *x != F::zero() {
+            acc = acc * *x;
+        }
+    }
+
+    // acc runs over the inverses of the same products, backwards
+    let mut acc = acc.inverse();
+    for (x, prefix) in values.iter_mut().zip(prefix).rev() {
+        if *x != F::zero() {
+            let inv = acc * prefix;
+            acc = acc * *x;
+            *x = inv;
+        }
+    }
+}
+
+/// [`batch_inverse`] over chunks of `values` in parallel
+pub fn batch_inverse_par<F: Field>(values: &mut [F]) {
+    values
+        .par_chunks_mut(PARALLEL_CHUNK)
+        .for_each(|chunk| batch_inverse(chunk));
+}
+
+/// `[1, base, base^2, ..., base^(count - 1)]`
+pub fn powers<F: Field>(base: F, count: usize) -> Vec<F> {
+    let mut ret = Vec::with_capacity(count);
+    let mut x = F::one();
+    for _ in 0..count {
+        ret.push(x);
+        x = x * base;
+    }
+    ret
+}
+
+/// [`powers`] with the chunks of the table filled in parallel, each from
+/// its own starting power
+pub fn powers_par<F: Field>(base: F, count: usize) -> Vec<F> {
+    let mut ret = vec![F::zero(); count];
+    ret.par_chunks_mut(PARALLEL_CHUNK)
+        .enumerate()
+        .for_each(|(i, chunk)| {
+            let mut x = base.pow((i * PARALLEL_CHUNK) as u128);
+            for value in chunk {
+                *value = x;
+                x = x * base;
+            }
+        });
+    ret
+}
+
+/// Powers of a fixed base for exponents up to `max_bits` bits, so that
+/// raising the base to many exponents costs one product per window of
+/// [`WINDOW_BITS`] bits instead of a square and multiply per bit
+#[derive(Debug, Clone)]
+pub struct PowersTable<F> {
+    /// windows[i][d] is base^(d << (i * WINDOW_BITS))
+    windows: Vec<[F; 1 << WINDOW_BITS]>,
+}
+
+impl<F: Field> PowersTable<F> {
+    pub fn new(base: F, max_bits: usize) -> Self {
+        let window_count = (max_bits + WINDOW_BITS - 1) / WINDOW_BITS;
+        let mut windows = Vec::with_capacity(window_count);
+        let mut window_base = base;
+        for _ in 0..window_count {
+            let mut window = [F::one(); 1 << WINDOW_BITS];
+            for d in 1..window.len() {
+                window[d] = window[d - 1] * window_base;
+            }
+            window_base = window[window.len() - 1] * window_base;
+            windows.push(window);
+        }
+        Self { windows }
+    }
+
+    /// base^exponent, `exponent` fitting in the bits the table was built for
+    pub fn pow(&self, mut exponent: u128) -> F {
+        let mut ret = F::one();
+        for window in &self.windows {
+            let digit = (exponent & ((1 << WINDOW_BITS) - 1)) as usize;
+            if digit != 0 {
+                ret = ret * window[digit];
+            }
+            exponent >>= WINDOW_BITS;
+        }
+        assert_eq!(exponent, 0, "exponent larger than the table");
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FieldElement, Fp61};
+
+    #[test]
+    fn batch_inverse_matches_inverse() {
+        for len in [0, 1, 2, 7, PARALLEL_CHUNK + 3] {
+            let values: Vec<_> = (0..len)
+                .map(|i| match i % 5 {
+                    0 => FieldElement::zero(),
+                    _ => FieldElement::new_random(),
+                })
+                .collect();
+            let expected: Vec<_> = values.iter().map(|x| x.inverse()).collect();
+
+            let mut inverses = values.clone();
+            batch_inverse(&mut inverses);
+            assert_eq!(inverses, expected);
+            let mut inverses = values;
+            batch_inverse_par(&mut inverses);
+            assert_eq!(inverses, expected);
+        }
+
+        let mut values: Vec<_> = (0..33).map(|_| Fp61::new_random()).collect();
+        let expected: Vec<_> = values.iter().map(|x| x.inverse()).collect();
+        batch_inverse(&mut values);
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    fn powers_match_pow() {
+        let base = FieldElement::new_random();
+        let table = powers(base, 100);
+        for (i, x) in table.iter().enumerate() {
+            assert_eq!(*x, base.fast_pow(i as u128));
+        }
+        assert_eq!(
+            powers_par(base, 2 * PARALLEL_CHUNK + 5),
+            powers(base, 2 * PARALLEL_CHUNK + 5)
+        );
+
+        let fixed = PowersTable::new(base, 128);
+        for exponent in [0, 1, 15, 16, 17, u64::MAX as u128, u128::MAX] {
+            assert_eq!(fixed.pow(exponent), base.fast_pow(exponent));
+        }
+        for _ in 0..32 {
+            let exponent = rand::random::<u64>() as u128 >> 3;
+            assert_eq!(
+                PowersTable::new(base, 61).pow(exponent),
+                base.fast_pow(exponent)
+            );
+        }
+    }
+}
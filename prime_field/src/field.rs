@@ -0,0 +1,171 @@
+use std::fmt::Debug;
+use std::ops::{Add, Mul, Neg, Sub};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::RootOfUnityError;
+use crate::{FieldElement, MOD};
+
+/// Field the proof system runs over.
+///
+/// The polynomials, the commitments, the prover and the verifier are generic
+/// over it, with [`FieldElement`] as the default. Besides the arithmetic, a
+/// field defines how its elements are hashed, through their bytes, and how
+/// challenges are sampled from uniform bytes. The commitments need roots of
+/// unity of power of two orders.
+pub trait Field:
+    Copy
+    + Default
+    + Debug
+    + Eq
+    + Send
+    + Sync
+    + 'static
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Neg<Output = Self>
+    + Serialize
+    + DeserializeOwned
+{
+    /// Number of bytes read by [`Field::from_uniform_bytes`]
+    const UNIFORM_BYTES: usize;
+
+    fn zero() -> Self;
+
+    fn one() -> Self;
+
+    /// `x` reduced into the prime subfield
+    fn from_u64(x: u64) -> Self;
+
+    /// `x` if it is below the characteristic
+    fn from_canonical_u64(x: u64) -> Option<Self>;
+
+    /// Representative below the characteristic of `self`, if `self` lies in
+    /// the prime subfield and the representative fits in a `u64`
+    fn to_canonical_u64(&self) -> Option<u64>;
+
+    fn random() -> Self;
+
+    /// Multiplicative inverse, zero for zero
+    fn inverse(self) -> Self;
+
+    fn pow(self, mut exponent: u128) -> Self {
+        let mut ret = Self::one();
+        let mut base = self;
+        while exponent != 0 {
+            if exponent & 1 != 0 {
+                ret = ret * base;
+            }
+            base = base * base;
+            exponent >>= 1;
+        }
+        ret
+    }
+
+    /// `dst[i] = a[i] * b[i]`, which a field may compute with vector
+    /// instructions
+    fn mul_slices(a: &[Self], b: &[Self], dst: &mut [Self]) {
+        assert_eq!(a.len(), b.len());
+        assert_eq!(a.len(), dst.len());
+        for ((dst, &a), &b) in dst.iter_mut().zip(a).zip(b) {
+            *dst = a * b;
+        }
+    }
+
+    /// Root of unity of order `2^log_order`
+    fn root_of_unity(log_order: usize) -> Result<Self, RootOfUnityError>;
+
+    /// Little endian encoding, the same length for every element, which the
+    /// transcript and the merkle trees hash
+    fn to_bytes(&self) -> Vec<u8>;
+
+    /// Element whose distribution is statistically close to uniform when
+    /// the `UNIFORM_BYTES` bytes of `bytes` are
+    fn from_uniform_bytes(bytes: &[u8]) -> Self;
+}
+
+impl Field for FieldElement {
+    const UNIFORM_BYTES: usize = 16;
+
+    fn zero() -> Self {
+        Self::zero()
+    }
+
+    fn one() -> Self {
+        Self::real_one()
+    }
+
+    fn from_u64(x: u64) -> Self {
+        Self::from_real(x)
+    }
+
+    fn from_canonical_u64(x: u64) -> Option<Self> {
+        (x < MOD).then(|| Self::new(x, 0))
+    }
+
+    fn to_canonical_u64(&self) -> Option<u64> {
+        (self.img == 0).then_some(self.real)
+    }
+
+    fn random() -> Self {
+        Self::new_random()
+    }
+
+    fn inverse(self) -> Self {
+        self.inverse()
+    }
+
+    fn pow(self, exponent: u128) -> Self {
+        self.fast_pow(exponent)
+    }
+
+    fn mul_slices(a: &[Self], b: &[Self], dst: &mut [Self]) {
+        crate::simd::vector_mul(a, b, dst);
+    }
+
+    fn root_of_unity(log_order: usize) -> Result<Self, RootOfUnityError> {
+        Self::get_root_of_unity(log_order)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.real.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&self.img.to_le_bytes());
+        bytes
+    }
+
+    fn from_uniform_bytes(bytes: &[u8]) -> Self {
+        // The bias of reducing 64 uniform bits modulo 2^61 - 1 is below 2^-58
+        let word = |i: usize| u64::from_le_bytes(bytes[i * 8..(i + 1) * 8].try_into().unwrap());
+        Self::new(word(0) % MOD, word(1) % MOD)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_element_implements_the_trait_consistently() {
+        for _ in 0..16 {
+            let x = <FieldElement as Field>::random();
+            let exponent = u64::from_le_bytes(x.to_bytes()[..8].try_into().unwrap()) as u128;
+            assert_eq!(Field::pow(x, exponent), x.fast_pow(exponent));
+            assert_eq!(x * Field::inverse(x), FieldElement::one());
+            assert_eq!(FieldElement::from_uniform_bytes(&x.to_bytes()), x);
+        }
+
+        assert_eq!(
+            FieldElement::from_canonical_u64(MOD - 1),
+            Some(-FieldElement::one())
+        );
+        assert_eq!(FieldElement::from_canonical_u64(MOD), None);
+        assert_eq!(FieldElement::from_u64(MOD + 5).to_canonical_u64(), Some(5));
+        assert_eq!(FieldElement::new(0, 1).to_canonical_u64(), None);
+
+        let root = FieldElement::root_of_unity(10).unwrap();
+        assert_eq!(Field::pow(root, 1 << 10), FieldElement::one());
+        assert_ne!(Field::pow(root, 1 << 9), FieldElement::one());
+    }
+}
@@ -0,0 +1,301 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::RootOfUnityError;
+use crate::{my_mult, verify_lt_mod_once, Field, FieldElement, MOD};
+
+/// Element of the base field F_p, p = 2^61 - 1, of which [`FieldElement`] is
+/// the quadratic extension.
+///
+/// A product costs a single `my_mult` instead of the three of an extension
+/// product, so circuits whose values are all real are cheaper to evaluate in
+/// `Fp61`, lifting the values with `FieldElement::from` once challenges from
+/// the extension come in. The mixed operations with [`FieldElement`] avoid
+/// the lift where one operand is known to be real.
+///
+/// F_p^* only has a subgroup of order 2, so the commitments, which need
+/// large power of two subgroups, have to run over the extension.
+#[derive(
+    Serialize, Deserialize, Default, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone,
+)]
+#[serde(try_from = "u64")]
+pub struct Fp61(u64);
+
+impl TryFrom<u64> for Fp61 {
+    type Error = String;
+
+    fn try_from(x: u64) -> Result<Self, Self::Error> {
+        Self::from_canonical(x).ok_or_else(|| format!("{} is not reduced modulo 2^61 - 1", x))
+    }
+}
+
+impl Fp61 {
+    pub const fn new(x: u64) -> Self {
+        Self(x % MOD)
+    }
+
+    /// `x` if it is below the prime
+    pub const fn from_canonical(x: u64) -> Option<Self> {
+        if x < MOD {
+            Some(Self(x))
+        } else {
+            None
+        }
+    }
+
+    /// Representative below the prime
+    pub const fn value(self) -> u64 {
+        self.0
+    }
+
+    pub const fn zero() -> Self {
+        Self(0)
+    }
+
+    pub const fn one() -> Self {
+        Self(1)
+    }
+
+    pub fn new_random() -> Self {
+        Self(rand::random::<u64>() % MOD)
+    }
+
+    pub fn fast_pow(self, mut p: u128) -> Self {
+        let mut ret = Self::one();
+        let mut tmp = self;
+
+        while p != 0 {
+            if p & 1 != 0 {
+                ret = ret * tmp;
+            }
+            tmp = tmp * tmp;
+            p >>= 1;
+        }
+
+        ret
+    }
+
+    /// Multiplicative inverse, zero for zero
+    pub fn inverse(self) -> Self {
+        self.fast_pow(MOD as u128 - 2)
+    }
+}
+
+impl From<Fp61> for FieldElement {
+    fn from(x: Fp61) -> Self {
+        Self::new(x.0, 0)
+    }
+}
+
+impl TryFrom<FieldElement> for Fp61 {
+    type Error = FieldElement;
+
+    /// The real part of `x`, or `x` back if its imaginary part isn't zero
+    fn try_from(x: FieldElement) -> Result<Self, Self::Error> {
+        if x.img == 0 {
+            Ok(Self(x.real))
+        } else {
+            Err(x)
+        }
+    }
+}
+
+impl core::ops::Add for Fp61 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self(verify_lt_mod_once(self.0 + rhs.0))
+    }
+}
+
+impl core::ops::Sub for Fp61 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self(verify_lt_mod_once(self.0 + (rhs.0 ^ MOD)))
+    }
+}
+
+impl core::ops::Mul for Fp61 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        // my_mult is below 2 * MOD for reduced operands
+        Self(verify_lt_mod_once(my_mult(self.0, rhs.0)))
+    }
+}
+
+impl core::ops::Neg for Fp61 {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self((MOD - self.0) % MOD)
+    }
+}
+
+impl core::ops::Add<Fp61> for FieldElement {
+    type Output = Self;
+
+    fn add(self, rhs: Fp61) -> Self::Output {
+        Self::new(verify_lt_mod_once(self.real + rhs.0), self.img)
+    }
+}
+
+impl core::ops::Add<FieldElement> for Fp61 {
+    type Output = FieldElement;
+
+    fn add(self, rhs: FieldElement) -> Self::Output {
+        rhs + self
+    }
+}
+
+impl core::ops::Sub<Fp61> for FieldElement {
+    type Output = Self;
+
+    fn sub(self, rhs: Fp61) -> Self::Output {
+        self + -rhs
+    }
+}
+
+impl core::ops::Sub<FieldElement> for Fp61 {
+    type Output = FieldElement;
+
+    fn sub(self, rhs: FieldElement) -> Self::Output {
+        -rhs + self
+    }
+}
+
+impl core::ops::Mul<Fp61> for FieldElement {
+    type Output = Self;
+
+    /// Two base field products instead of the three of an extension product
+    fn mul(self, rhs: Fp61) -> Self::Output {
+        let scale = |x: u64| verify_lt_mod_once(my_mult(x, rhs.0));
+        Self::new(scale(self.real), scale(self.img))
+    }
+}
+
+impl core::ops::Mul<FieldElement> for Fp61 {
+    type Output = FieldElement;
+
+    fn mul(self, rhs: FieldElement) -> Self::Output {
+        rhs * self
+    }
+}
+
+impl Field for Fp61 {
+    const UNIFORM_BYTES: usize = 8;
+
+    fn zero() -> Self {
+        Self::zero()
+    }
+
+    fn one() -> Self {
+        Self::one()
+    }
+
+    fn from_u64(x: u64) -> Self {
+        Self::new(x)
+    }
+
+    fn from_canonical_u64(x: u64) -> Option<Self> {
+        Self::from_canonical(x)
+    }
+
+    fn to_canonical_u64(&self) -> Option<u64> {
+        Some(self.0)
+    }
+
+    fn random() -> Self {
+        Self::new_random()
+    }
+
+    fn inverse(self) -> Self {
+        self.inverse()
+    }
+
+    fn pow(self, exponent: u128) -> Self {
+        self.fast_pow(exponent)
+    }
+
+    /// Only the roots of order 1 and 2 lie in the base field
+    fn root_of_unity(log_order: usize) -> Result<Self, RootOfUnityError> {
+        match log_order {
+            0 => Ok(Self::one()),
+            1 => Ok(-Self::one()),
+            _ => Err(RootOfUnityError::LogOrderTooHigh),
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_le_bytes().to_vec()
+    }
+
+    fn from_uniform_bytes(bytes: &[u8]) -> Self {
+        Self::new(u64::from_le_bytes(bytes[..8].try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_pair() -> (Fp61, FieldElement) {
+        let x = Fp61::new_random();
+        (x, FieldElement::from(x))
+    }
+
+    #[test]
+    fn arithmetic_agrees_with_the_extension() {
+        let edges = [0, 1, 2, MOD - 2, MOD - 1].map(Fp61::new);
+        let randoms: Vec<_> = (0..32).map(|_| Fp61::new_random()).collect();
+        for &a in edges.iter().chain(&randoms) {
+            for &b in edges.iter().chain(&randoms) {
+                let (lift_a, lift_b) = (FieldElement::from(a), FieldElement::from(b));
+                assert_eq!(FieldElement::from(a + b), lift_a + lift_b);
+                assert_eq!(FieldElement::from(a - b), lift_a - lift_b);
+                assert_eq!(FieldElement::from(a * b), lift_a * lift_b);
+            }
+            assert_eq!(FieldElement::from(-a), -FieldElement::from(a));
+            assert!(a.value() < MOD);
+        }
+
+        for _ in 0..16 {
+            let (x, lift) = random_pair();
+            assert_eq!(x * x.inverse(), Fp61::one());
+            assert_eq!(FieldElement::from(x.inverse()), lift.inverse());
+            assert_eq!(Fp61::try_from(lift), Ok(x));
+        }
+        assert_eq!(Fp61::zero().inverse(), Fp61::zero());
+        assert!(Fp61::try_from(FieldElement::new(1, 1)).is_err());
+    }
+
+    #[test]
+    fn mixed_operations_agree_with_lifting() {
+        for _ in 0..64 {
+            let (x, lift) = random_pair();
+            let y = FieldElement::new_random();
+            assert_eq!(y * x, y * lift);
+            assert_eq!(x * y, lift * y);
+            assert_eq!(y + x, y + lift);
+            assert_eq!(x + y, lift + y);
+            assert_eq!(y - x, y - lift);
+            assert_eq!(x - y, lift - y);
+        }
+    }
+
+    #[test]
+    fn unreduced_values_are_rejected() {
+        let x = Fp61::new(MOD + 5);
+        assert_eq!(x.value(), 5);
+        assert_eq!(Fp61::from_canonical(MOD), None);
+        let bytes = bincode::serialize(&MOD).unwrap();
+        assert!(bincode::deserialize::<Fp61>(&bytes).is_err());
+        let bytes = bincode::serialize(&x).unwrap();
+        assert_eq!(bincode::deserialize::<Fp61>(&bytes).unwrap(), x);
+        assert_eq!(
+            Fp61::root_of_unity(1).unwrap() * Fp61::new(3),
+            -Fp61::new(3)
+        );
+        assert!(Fp61::root_of_unity(2).is_err());
+    }
+}
@@ -1,14 +1,24 @@
 #![feature(bigint_helper_methods)]
+#![feature(stdsimd, avx512_target_feature)]
 
+pub mod batch;
 pub mod error;
+pub mod field;
+pub mod fp61;
 pub mod ops;
+pub mod packed;
+pub mod simd;
 
 use std::sync::atomic::AtomicBool;
 
-use ethnum::{i256, AsI256};
-use serde::Serialize;
+use ethnum::i256;
+use serde::{Deserialize, Serialize};
 
 use self::error::{PrimeFieldError, RootOfUnityError};
+pub use self::field::Field;
+pub use self::fp61::Fp61;
+pub use self::packed::PackedFieldElement;
+pub use self::simd::FieldElementContext;
 
 pub const MOD: u64 = 2305843009213693951;
 
@@ -30,38 +40,73 @@ pub fn my_mult(x: u64, y: u64) -> u64 {
     ((hi << 3) | (lo >> 61)) + (lo & PRIME)
 }
 
+/// [`my_mult`] in each of the 4 lanes of `x` and `y`
+#[inline]
 pub fn packed_my_mult(x: i256, y: i256) -> i256 {
-    let x_shift = intrinsics::i256::srl(&x, 32);
-    let y_shift = intrinsics::i256::srl(&y, 32);
+    use intrinsics::i256::{add, mul_epu32, set1, sll, srl};
 
-    let ac = x_shift * y_shift;
-    let ad = x_shift * y;
-    let bc = x * y_shift;
-    let bd = x * y;
+    let x_shift = srl(&x, 32);
+    let y_shift = srl(&y, 32);
 
-    let ad_bc = ad + bc;
-    let bd_srl32 = intrinsics::i256::srl(&bd, 32);
-    let ad_bc_srl32 = intrinsics::i256::srl(&(ad_bc + bd_srl32), 32);
-    let ad_bc_sll32 = intrinsics::i256::sll(&ad_bc, 32);
+    let ac = mul_epu32(&x_shift, &y_shift);
+    let ad = mul_epu32(&x_shift, &y);
+    let bc = mul_epu32(&x, &y_shift);
+    let bd = mul_epu32(&x, &y);
 
-    let hi = ac + ad_bc_srl32;
-    let lo = bc + ad_bc_sll32;
+    let ad_bc = add(&ad, &bc);
+    let bd_srl32 = srl(&bd, 32);
+    let ad_bc_srl32 = srl(&add(&ad_bc, &bd_srl32), 32);
+    let ad_bc_sll32 = sll(&ad_bc, 32);
+
+    let hi = add(&ac, &ad_bc_srl32);
+    let lo = add(&bd, &ad_bc_sll32);
 
     // ((hi << 3) | (lo >> 61)) + (lo & PRIME)
-    (intrinsics::i256::sll(&hi, 3) | intrinsics::i256::srl(&lo, 61)) + (lo & PRIME.as_i256())
+    add(&(sll(&hi, 3) | srl(&lo, 61)), &(lo & set1(PRIME)))
 }
 
+/// [`my_mod`] in each of the 4 lanes of `x`
+#[inline]
 pub fn packed_my_mod(x: i256) -> i256 {
     // (x >> 61) + (x & mod)
-    intrinsics::i256::srl(&x, 61) + (x & MOD.as_i256())
+    intrinsics::i256::add(
+        &intrinsics::i256::srl(&x, 61),
+        &(x & intrinsics::i256::set1(MOD)),
+    )
 }
 
-#[derive(Serialize, Default, Debug, PartialEq, Eq, Copy, Clone)]
+/// `repr(C)` as the vector kernels of [`simd`] load slices of elements as
+/// sequences of `real, img` words
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq, Copy, Clone)]
+#[serde(try_from = "UncheckedFieldElement")]
+#[repr(C)]
 pub struct FieldElement {
     pub real: u64,
     pub img: u64,
 }
 
+/// Wire form of [`FieldElement`], rejected on deserialization unless both
+/// coordinates are reduced
+#[derive(Deserialize)]
+struct UncheckedFieldElement {
+    real: u64,
+    img: u64,
+}
+
+impl TryFrom<UncheckedFieldElement> for FieldElement {
+    type Error = String;
+
+    fn try_from(x: UncheckedFieldElement) -> Result<Self, Self::Error> {
+        if x.real >= MOD || x.img >= MOD {
+            return Err(format!(
+                "field element ({}, {}) is not reduced modulo 2^61 - 1",
+                x.real, x.img
+            ));
+        }
+        Ok(Self::new(x.real, x.img))
+    }
+}
+
 impl FieldElement {
     fn to_owned_bytes(self) -> Result<Vec<u8>, PrimeFieldError> {
         Ok(bincode::serialize(&self)?)
@@ -147,10 +192,6 @@ impl FieldElement {
 
         Ok(rou)
     }
-
-    pub fn random() -> Self {
-        unimplemented!()
-    }
 }
 
 fn verify_lt_mod_once(mut a: u64) -> u64 {
@@ -0,0 +1,248 @@
+use ethnum::i256;
+use intrinsics::i256::{cmpgt, from_lanes, set1, sub, to_lanes};
+
+use crate::{packed_my_mod, packed_my_mult, FieldElement, MOD};
+
+/// Number of field elements in a [`PackedFieldElement`]
+pub const PACKED_SIZE: usize = intrinsics::i256::LANES;
+
+/// `PACKED_SIZE` field elements, the real and imaginary parts in separate
+/// registers so that each operation runs lane by lane the same steps as the
+/// [`FieldElement`] one.
+#[derive(Default, Debug, PartialEq, Eq, Copy, Clone)]
+pub struct PackedFieldElement {
+    pub real: i256,
+    pub img: i256,
+}
+
+/// `MOD` in every lane
+const MODULUS: i256 = set1(MOD);
+
+/// [`crate::verify_lt_mod_once`] in every lane, the lanes staying below 2^63
+#[inline]
+fn verify_lt_mod_once(a: i256) -> i256 {
+    sub(&a, &(cmpgt(&a, &set1(MOD - 1)) & MODULUS))
+}
+
+#[inline]
+fn add(x: i256, y: i256) -> i256 {
+    intrinsics::i256::add(&x, &y)
+}
+
+impl PackedFieldElement {
+    #[inline]
+    pub fn new(values: [FieldElement; PACKED_SIZE]) -> Self {
+        Self {
+            real: from_lanes(values.map(|x| x.real)),
+            img: from_lanes(values.map(|x| x.img)),
+        }
+    }
+
+    /// `x` in every lane
+    #[inline]
+    pub fn broadcast(x: FieldElement) -> Self {
+        Self {
+            real: set1(x.real),
+            img: set1(x.img),
+        }
+    }
+
+    /// Packs the first `PACKED_SIZE` elements of `values`
+    #[inline]
+    pub fn from_slice(values: &[FieldElement]) -> Self {
+        Self::new(values[..PACKED_SIZE].try_into().unwrap())
+    }
+
+    #[inline]
+    pub fn unpack(self) -> [FieldElement; PACKED_SIZE] {
+        let (real, img) = (to_lanes(&self.real), to_lanes(&self.img));
+        std::array::from_fn(|i| FieldElement::new(real[i], img[i]))
+    }
+
+    /// Unpacks into the first `PACKED_SIZE` elements of `dst`
+    #[inline]
+    pub fn write_to_slice(self, dst: &mut [FieldElement]) {
+        dst[..PACKED_SIZE].copy_from_slice(&self.unpack());
+    }
+}
+
+impl core::ops::Add for PackedFieldElement {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            real: verify_lt_mod_once(add(self.real, rhs.real)),
+            img: verify_lt_mod_once(add(self.img, rhs.img)),
+        }
+    }
+}
+
+impl core::ops::Sub for PackedFieldElement {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self {
+            real: verify_lt_mod_once(add(self.real, rhs.real ^ MODULUS)),
+            img: verify_lt_mod_once(add(self.img, rhs.img ^ MODULUS)),
+        }
+    }
+}
+
+impl core::ops::Mul for PackedFieldElement {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, rhs: Self) -> Self::Output {
+        let all_prod = packed_my_mult(add(self.real, self.img), add(rhs.real, rhs.img));
+
+        let ac = packed_my_mult(self.real, rhs.real);
+        let bd = verify_lt_mod_once(packed_my_mult(self.img, rhs.img)) ^ MODULUS;
+        let nac = verify_lt_mod_once(ac) ^ MODULUS;
+
+        let t_img = verify_lt_mod_once(packed_my_mod(add(add(all_prod, nac), bd)));
+        // ac + bd is below 3 * MOD
+        let t_real = verify_lt_mod_once(verify_lt_mod_once(add(ac, bd)));
+
+        Self {
+            real: t_real,
+            img: t_img,
+        }
+    }
+}
+
+impl core::ops::Neg for PackedFieldElement {
+    type Output = Self;
+
+    #[inline]
+    fn neg(self) -> Self::Output {
+        Self {
+            real: verify_lt_mod_once(sub(&MODULUS, &self.real)),
+            img: verify_lt_mod_once(sub(&MODULUS, &self.img)),
+        }
+    }
+}
+
+/// Applies `op` to `a` and `b` into `dst` by packed chunks, the tail that
+/// doesn't fill a chunk with `scalar_op`
+fn zip_slices(
+    a: &[FieldElement],
+    b: &[FieldElement],
+    dst: &mut [FieldElement],
+    op: impl Fn(PackedFieldElement, PackedFieldElement) -> PackedFieldElement,
+    scalar_op: impl Fn(FieldElement, FieldElement) -> FieldElement,
+) {
+    assert_eq!(a.len(), b.len());
+    assert_eq!(a.len(), dst.len());
+
+    let mut a_chunks = a.chunks_exact(PACKED_SIZE);
+    let mut b_chunks = b.chunks_exact(PACKED_SIZE);
+    let mut dst_chunks = dst.chunks_exact_mut(PACKED_SIZE);
+    for ((a, b), dst) in (&mut a_chunks).zip(&mut b_chunks).zip(&mut dst_chunks) {
+        op(
+            PackedFieldElement::from_slice(a),
+            PackedFieldElement::from_slice(b),
+        )
+        .write_to_slice(dst);
+    }
+
+    let tail = a_chunks.remainder().iter().zip(b_chunks.remainder());
+    for (dst, (a, b)) in dst_chunks.into_remainder().iter_mut().zip(tail) {
+        *dst = scalar_op(*a, *b);
+    }
+}
+
+/// `dst[i] = a[i] + b[i]`
+pub fn add_slices(a: &[FieldElement], b: &[FieldElement], dst: &mut [FieldElement]) {
+    zip_slices(a, b, dst, |a, b| a + b, |a, b| a + b);
+}
+
+/// `dst[i] = a[i] - b[i]`
+pub fn sub_slices(a: &[FieldElement], b: &[FieldElement], dst: &mut [FieldElement]) {
+    zip_slices(a, b, dst, |a, b| a - b, |a, b| a - b);
+}
+
+/// `dst[i] = a[i] * b[i]`
+pub fn mul_slices(a: &[FieldElement], b: &[FieldElement], dst: &mut [FieldElement]) {
+    zip_slices(a, b, dst, |a, b| a * b, |a, b| a * b);
+}
+
+/// `dst[i] = lo[i] + r * (hi[i] - lo[i])`, the fold of the two halves of a
+/// multilinear table on its first variable, or the butterfly sum with a
+/// twiddle factor for `r`
+pub fn fold_slices(
+    lo: &[FieldElement],
+    hi: &[FieldElement],
+    r: FieldElement,
+    dst: &mut [FieldElement],
+) {
+    let packed_r = PackedFieldElement::broadcast(r);
+    zip_slices(
+        lo,
+        hi,
+        dst,
+        |lo, hi| lo + packed_r * (hi - lo),
+        |lo, hi| lo + r * (hi - lo),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_packed() -> ([FieldElement; PACKED_SIZE], PackedFieldElement) {
+        let mut values: [FieldElement; PACKED_SIZE] =
+            std::array::from_fn(|_| FieldElement::new_random());
+        // edge values in some lanes
+        values[0] = FieldElement::new(MOD - 1, MOD - 1);
+        values[1] = FieldElement::new(0, rand::random::<u64>() % MOD);
+        (values, PackedFieldElement::new(values))
+    }
+
+    #[test]
+    fn packed_operations_agree_lane_by_lane() {
+        for _ in 0..256 {
+            let (a, packed_a) = random_packed();
+            let (mut b, _) = random_packed();
+            b.reverse();
+            let packed_b = PackedFieldElement::new(b);
+            assert_eq!(packed_a.unpack(), a);
+
+            let lanes =
+                |f: fn(FieldElement, FieldElement) -> FieldElement| -> [FieldElement; PACKED_SIZE] {
+                    std::array::from_fn(|i| f(a[i], b[i]))
+                };
+            assert_eq!((packed_a + packed_b).unpack(), lanes(|a, b| a + b));
+            assert_eq!((packed_a - packed_b).unpack(), lanes(|a, b| a - b));
+            assert_eq!((packed_a * packed_b).unpack(), lanes(|a, b| a * b));
+            assert_eq!((-packed_a).unpack(), a.map(|a| -a));
+            assert_eq!(
+                (-PackedFieldElement::default()).unpack(),
+                [FieldElement::zero(); PACKED_SIZE]
+            );
+        }
+    }
+
+    #[test]
+    fn slice_operations_agree_with_scalar_ones() {
+        for len in [0, 3, 4, 17] {
+            let a: Vec<_> = (0..len).map(|_| FieldElement::new_random()).collect();
+            let b: Vec<_> = (0..len).map(|_| FieldElement::new_random()).collect();
+            let r = FieldElement::new_random();
+            let mut dst = vec![FieldElement::zero(); len];
+
+            add_slices(&a, &b, &mut dst);
+            assert!(dst.iter().enumerate().all(|(i, x)| *x == a[i] + b[i]));
+            sub_slices(&a, &b, &mut dst);
+            assert!(dst.iter().enumerate().all(|(i, x)| *x == a[i] - b[i]));
+            mul_slices(&a, &b, &mut dst);
+            assert!(dst.iter().enumerate().all(|(i, x)| *x == a[i] * b[i]));
+            fold_slices(&a, &b, r, &mut dst);
+            assert!(dst
+                .iter()
+                .enumerate()
+                .all(|(i, x)| *x == a[i] + r * (b[i] - a[i])));
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! Kernels on 4 lanes of 64 bits, a register holding the real and imaginary
+//! parts of 2 field elements.
+
+use std::arch::x86_64::*;
+
+use crate::{FieldElement, MOD};
+
+/// Field elements per register
+const STEP: usize = 2;
+
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn load(x: &[FieldElement]) -> __m256i {
+    _mm256_loadu_si256(x.as_ptr() as *const __m256i)
+}
+
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn store(x: __m256i, dst: &mut [FieldElement]) {
+    _mm256_storeu_si256(dst.as_mut_ptr() as *mut __m256i, x)
+}
+
+/// `verify_lt_mod_once` of every lane, the lanes staying below 2^63
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn verify_lt_mod_once(a: __m256i) -> __m256i {
+    let above = _mm256_cmpgt_epi64(a, _mm256_set1_epi64x(MOD as i64 - 1));
+    _mm256_sub_epi64(a, _mm256_and_si256(above, _mm256_set1_epi64x(MOD as i64)))
+}
+
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn my_mod(x: __m256i) -> __m256i {
+    _mm256_add_epi64(
+        _mm256_srli_epi64::<61>(x),
+        _mm256_and_si256(x, _mm256_set1_epi64x(MOD as i64)),
+    )
+}
+
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn my_mult(x: __m256i, y: __m256i) -> __m256i {
+    let x_shift = _mm256_srli_epi64::<32>(x);
+    let y_shift = _mm256_srli_epi64::<32>(y);
+
+    let ac = _mm256_mul_epu32(x_shift, y_shift);
+    let ad = _mm256_mul_epu32(x_shift, y);
+    let bc = _mm256_mul_epu32(x, y_shift);
+    let bd = _mm256_mul_epu32(x, y);
+
+    let ad_bc = _mm256_add_epi64(ad, bc);
+    let bd_srl32 = _mm256_srli_epi64::<32>(bd);
+    let ad_bc_srl32 = _mm256_srli_epi64::<32>(_mm256_add_epi64(ad_bc, bd_srl32));
+    let ad_bc_sll32 = _mm256_slli_epi64::<32>(ad_bc);
+
+    let hi = _mm256_add_epi64(ac, ad_bc_srl32);
+    let lo = _mm256_add_epi64(bd, ad_bc_sll32);
+
+    // ((hi << 3) | (lo >> 61)) + (lo & PRIME)
+    _mm256_add_epi64(
+        _mm256_or_si256(_mm256_slli_epi64::<3>(hi), _mm256_srli_epi64::<61>(lo)),
+        _mm256_and_si256(lo, _mm256_set1_epi64x(MOD as i64)),
+    )
+}
+
+/// Products of 4 field elements given by their real and imaginary parts,
+/// the steps of the scalar `Mul`
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn mul_parts(a: [__m256i; 2], b: [__m256i; 2]) -> [__m256i; 2] {
+    let modulus = _mm256_set1_epi64x(MOD as i64);
+    let all_prod = my_mult(_mm256_add_epi64(a[0], a[1]), _mm256_add_epi64(b[0], b[1]));
+
+    let ac = my_mult(a[0], b[0]);
+    let bd = _mm256_xor_si256(verify_lt_mod_once(my_mult(a[1], b[1])), modulus);
+    let nac = _mm256_xor_si256(verify_lt_mod_once(ac), modulus);
+
+    let t_img = my_mod(_mm256_add_epi64(_mm256_add_epi64(all_prod, nac), bd));
+    let t_real = verify_lt_mod_once(verify_lt_mod_once(_mm256_add_epi64(ac, bd)));
+    [t_real, verify_lt_mod_once(t_img)]
+}
+
+/// Real and imaginary parts of 4 field elements, in the order 0, 2, 1, 3
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn split(x: &[FieldElement]) -> [__m256i; 2] {
+    let (lo, hi) = (load(&x[..STEP]), load(&x[STEP..]));
+    [_mm256_unpacklo_epi64(lo, hi), _mm256_unpackhi_epi64(lo, hi)]
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn add(a: &[FieldElement], b: &[FieldElement], dst: &mut [FieldElement]) {
+    let mut chunks = dst.chunks_exact_mut(STEP);
+    for (i, dst) in (&mut chunks).enumerate() {
+        let range = i * STEP..(i + 1) * STEP;
+        let sum = _mm256_add_epi64(load(&a[range.clone()]), load(&b[range]));
+        store(verify_lt_mod_once(sum), dst);
+    }
+    let start = a.len() - chunks.into_remainder().len();
+    super::portable_add(&a[start..], &b[start..], &mut dst[start..]);
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn mul(a: &[FieldElement], b: &[FieldElement], dst: &mut [FieldElement]) {
+    let mut chunks = dst.chunks_exact_mut(2 * STEP);
+    for (i, dst) in (&mut chunks).enumerate() {
+        let range = i * 2 * STEP..(i + 1) * 2 * STEP;
+        let [real, img] = mul_parts(split(&a[range.clone()]), split(&b[range]));
+        let (lo, hi) = dst.split_at_mut(STEP);
+        store(_mm256_unpacklo_epi64(real, img), lo);
+        store(_mm256_unpackhi_epi64(real, img), hi);
+    }
+    let start = a.len() - chunks.into_remainder().len();
+    super::portable_mul(&a[start..], &b[start..], &mut dst[start..]);
+}
@@ -0,0 +1,116 @@
+//! Kernels on 8 lanes of 64 bits, a register holding the real and imaginary
+//! parts of 4 field elements.
+
+use std::arch::x86_64::*;
+
+use crate::{FieldElement, MOD};
+
+/// Field elements per register
+const STEP: usize = 4;
+
+#[target_feature(enable = "avx512f")]
+#[inline]
+unsafe fn load(x: &[FieldElement]) -> __m512i {
+    _mm512_loadu_si512(x.as_ptr() as *const i32)
+}
+
+#[target_feature(enable = "avx512f")]
+#[inline]
+unsafe fn store(x: __m512i, dst: &mut [FieldElement]) {
+    _mm512_storeu_si512(dst.as_mut_ptr() as *mut i32, x)
+}
+
+/// `verify_lt_mod_once` of every lane
+#[target_feature(enable = "avx512f")]
+#[inline]
+unsafe fn verify_lt_mod_once(a: __m512i) -> __m512i {
+    let above = _mm512_cmpgt_epu64_mask(a, _mm512_set1_epi64(MOD as i64 - 1));
+    _mm512_mask_sub_epi64(a, above, a, _mm512_set1_epi64(MOD as i64))
+}
+
+#[target_feature(enable = "avx512f")]
+#[inline]
+unsafe fn my_mod(x: __m512i) -> __m512i {
+    _mm512_add_epi64(
+        _mm512_srli_epi64::<61>(x),
+        _mm512_and_si512(x, _mm512_set1_epi64(MOD as i64)),
+    )
+}
+
+#[target_feature(enable = "avx512f")]
+#[inline]
+unsafe fn my_mult(x: __m512i, y: __m512i) -> __m512i {
+    let x_shift = _mm512_srli_epi64::<32>(x);
+    let y_shift = _mm512_srli_epi64::<32>(y);
+
+    let ac = _mm512_mul_epu32(x_shift, y_shift);
+    let ad = _mm512_mul_epu32(x_shift, y);
+    let bc = _mm512_mul_epu32(x, y_shift);
+    let bd = _mm512_mul_epu32(x, y);
+
+    let ad_bc = _mm512_add_epi64(ad, bc);
+    let bd_srl32 = _mm512_srli_epi64::<32>(bd);
+    let ad_bc_srl32 = _mm512_srli_epi64::<32>(_mm512_add_epi64(ad_bc, bd_srl32));
+    let ad_bc_sll32 = _mm512_slli_epi64::<32>(ad_bc);
+
+    let hi = _mm512_add_epi64(ac, ad_bc_srl32);
+    let lo = _mm512_add_epi64(bd, ad_bc_sll32);
+
+    // ((hi << 3) | (lo >> 61)) + (lo & PRIME)
+    _mm512_add_epi64(
+        _mm512_or_si512(_mm512_slli_epi64::<3>(hi), _mm512_srli_epi64::<61>(lo)),
+        _mm512_and_si512(lo, _mm512_set1_epi64(MOD as i64)),
+    )
+}
+
+/// Products of 8 field elements given by their real and imaginary parts,
+/// the steps of the scalar `Mul`
+#[target_feature(enable = "avx512f")]
+#[inline]
+unsafe fn mul_parts(a: [__m512i; 2], b: [__m512i; 2]) -> [__m512i; 2] {
+    let modulus = _mm512_set1_epi64(MOD as i64);
+    let all_prod = my_mult(_mm512_add_epi64(a[0], a[1]), _mm512_add_epi64(b[0], b[1]));
+
+    let ac = my_mult(a[0], b[0]);
+    let bd = _mm512_xor_si512(verify_lt_mod_once(my_mult(a[1], b[1])), modulus);
+    let nac = _mm512_xor_si512(verify_lt_mod_once(ac), modulus);
+
+    let t_img = my_mod(_mm512_add_epi64(_mm512_add_epi64(all_prod, nac), bd));
+    let t_real = verify_lt_mod_once(verify_lt_mod_once(_mm512_add_epi64(ac, bd)));
+    [t_real, verify_lt_mod_once(t_img)]
+}
+
+/// Real and imaginary parts of 8 field elements, in the order 0, 4, 1, 5,
+/// 2, 6, 3, 7
+#[target_feature(enable = "avx512f")]
+#[inline]
+unsafe fn split(x: &[FieldElement]) -> [__m512i; 2] {
+    let (lo, hi) = (load(&x[..STEP]), load(&x[STEP..]));
+    [_mm512_unpacklo_epi64(lo, hi), _mm512_unpackhi_epi64(lo, hi)]
+}
+
+#[target_feature(enable = "avx512f")]
+pub unsafe fn add(a: &[FieldElement], b: &[FieldElement], dst: &mut [FieldElement]) {
+    let mut chunks = dst.chunks_exact_mut(STEP);
+    for (i, dst) in (&mut chunks).enumerate() {
+        let range = i * STEP..(i + 1) * STEP;
+        let sum = _mm512_add_epi64(load(&a[range.clone()]), load(&b[range]));
+        store(verify_lt_mod_once(sum), dst);
+    }
+    let start = a.len() - chunks.into_remainder().len();
+    super::portable_add(&a[start..], &b[start..], &mut dst[start..]);
+}
+
+#[target_feature(enable = "avx512f")]
+pub unsafe fn mul(a: &[FieldElement], b: &[FieldElement], dst: &mut [FieldElement]) {
+    let mut chunks = dst.chunks_exact_mut(2 * STEP);
+    for (i, dst) in (&mut chunks).enumerate() {
+        let range = i * 2 * STEP..(i + 1) * 2 * STEP;
+        let [real, img] = mul_parts(split(&a[range.clone()]), split(&b[range]));
+        let (lo, hi) = dst.split_at_mut(STEP);
+        store(_mm512_unpacklo_epi64(real, img), lo);
+        store(_mm512_unpackhi_epi64(real, img), hi);
+    }
+    let start = a.len() - chunks.into_remainder().len();
+    super::portable_mul(&a[start..], &b[start..], &mut dst[start..]);
+}
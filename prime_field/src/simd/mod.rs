@@ -0,0 +1,174 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::{FieldElement, INITIALIZED};
+
+#[cfg(target_arch = "x86_64")]
+mod avx2;
+#[cfg(target_arch = "x86_64")]
+mod avx512;
+
+/// Implementation of the vector operations over `&[FieldElement]`, all of
+/// them giving the results of the scalar operators
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[repr(u8)]
+pub enum Backend {
+    Portable,
+    Avx2,
+    Avx512,
+}
+
+/// Backend picked by [`FieldElementContext::init`]
+static BACKEND: AtomicU8 = AtomicU8::new(Backend::Portable as u8);
+
+impl Backend {
+    pub const ALL: [Self; 3] = [Self::Portable, Self::Avx2, Self::Avx512];
+
+    /// Fastest backend the running CPU supports
+    pub fn detect() -> Self {
+        [Self::Avx512, Self::Avx2]
+            .into_iter()
+            .find(|backend| backend.is_available())
+            .unwrap_or(Self::Portable)
+    }
+
+    pub fn is_available(self) -> bool {
+        match self {
+            Self::Portable => true,
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx512 => is_x86_feature_detected!("avx512f"),
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => false,
+        }
+    }
+
+    fn check(self, a: &[FieldElement], b: &[FieldElement], dst: &[FieldElement]) {
+        assert!(
+            self.is_available(),
+            "{:?} is not supported by this CPU",
+            self
+        );
+        assert_eq!(a.len(), b.len());
+        assert_eq!(a.len(), dst.len());
+    }
+
+    /// `dst[i] = a[i] + b[i]`, panicking if the backend isn't available
+    pub fn add(self, a: &[FieldElement], b: &[FieldElement], dst: &mut [FieldElement]) {
+        self.check(a, b, dst);
+        match self {
+            Self::Portable => portable_add(a, b, dst),
+            // safe as the CPU supports the backend
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx2 => unsafe { avx2::add(a, b, dst) },
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx512 => unsafe { avx512::add(a, b, dst) },
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => unreachable!(),
+        }
+    }
+
+    /// `dst[i] = a[i] * b[i]`, panicking if the backend isn't available
+    pub fn mul(self, a: &[FieldElement], b: &[FieldElement], dst: &mut [FieldElement]) {
+        self.check(a, b, dst);
+        match self {
+            Self::Portable => portable_mul(a, b, dst),
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx2 => unsafe { avx2::mul(a, b, dst) },
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx512 => unsafe { avx512::mul(a, b, dst) },
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn portable_add(a: &[FieldElement], b: &[FieldElement], dst: &mut [FieldElement]) {
+    for ((dst, a), b) in dst.iter_mut().zip(a).zip(b) {
+        *dst = *a + *b;
+    }
+}
+
+fn portable_mul(a: &[FieldElement], b: &[FieldElement], dst: &mut [FieldElement]) {
+    for ((dst, a), b) in dst.iter_mut().zip(a).zip(b) {
+        *dst = *a * *b;
+    }
+}
+
+/// Process wide settings of the field arithmetic
+pub struct FieldElementContext;
+
+impl FieldElementContext {
+    /// Selects the backend of the vector operations from the features of the
+    /// CPU, meant to be called once at startup
+    pub fn init() {
+        BACKEND.store(Backend::detect() as u8, Ordering::Relaxed);
+        INITIALIZED.store(true, Ordering::Release);
+    }
+
+    /// Backend of [`vector_add`] and [`vector_mul`], initializing the
+    /// context on first use
+    pub fn backend() -> Backend {
+        if !INITIALIZED.load(Ordering::Acquire) {
+            Self::init();
+        }
+        Backend::ALL[BACKEND.load(Ordering::Relaxed) as usize]
+    }
+}
+
+/// `dst[i] = a[i] + b[i]` with the backend of the context
+pub fn vector_add(a: &[FieldElement], b: &[FieldElement], dst: &mut [FieldElement]) {
+    FieldElementContext::backend().add(a, b, dst);
+}
+
+/// `dst[i] = a[i] * b[i]` with the backend of the context
+pub fn vector_mul(a: &[FieldElement], b: &[FieldElement], dst: &mut [FieldElement]) {
+    FieldElementContext::backend().mul(a, b, dst);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MOD;
+
+    #[test]
+    fn backends_agree_with_the_scalar_operators() {
+        let edges = [
+            FieldElement::zero(),
+            FieldElement::new(MOD - 1, MOD - 1),
+            FieldElement::new(MOD - 1, 0),
+            FieldElement::new(1, MOD - 1),
+        ];
+        for len in (0..20).chain([1000]) {
+            let a: Vec<_> = (0..len)
+                .map(|i| {
+                    edges
+                        .get(i % 7)
+                        .copied()
+                        .unwrap_or_else(FieldElement::new_random)
+                })
+                .collect();
+            let b: Vec<_> = (0..len)
+                .map(|i| {
+                    edges
+                        .get(i % 5)
+                        .copied()
+                        .unwrap_or_else(FieldElement::new_random)
+                })
+                .collect();
+            let sums: Vec<_> = a.iter().zip(&b).map(|(a, b)| *a + *b).collect();
+            let products: Vec<_> = a.iter().zip(&b).map(|(a, b)| *a * *b).collect();
+
+            let mut dst = vec![FieldElement::zero(); len];
+            for backend in Backend::ALL.into_iter().filter(|x| x.is_available()) {
+                backend.add(&a, &b, &mut dst);
+                assert_eq!(dst, sums, "{:?}", backend);
+                backend.mul(&a, &b, &mut dst);
+                assert_eq!(dst, products, "{:?}", backend);
+            }
+            vector_mul(&a, &b, &mut dst);
+            assert_eq!(dst, products);
+        }
+        assert_eq!(FieldElementContext::backend(), Backend::detect());
+    }
+}
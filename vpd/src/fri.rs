@@ -1,23 +1,32 @@
 use std::{time, usize};
 
 use infrastructure::{
-  constants::{LOG_SLICE_NUMBER, MAX_BIT_LENGTH, MAX_FRI_DEPTH, RS_CODE_RATE, SLICE_NUMBER},
-  my_hash::HashDigest,
+  constants::{LOG_SLICE_NUMBER, MAX_FRI_DEPTH, RS_CODE_RATE, SLICE_NUMBER},
+  merkle_tree::{hash_double_field_element_merkle_damgard, MerkleTree},
+  my_hash::{HashAlgorithm, HashDigest, Hasher},
 };
-use poly_commitment::PolyCommitContext;
-use prime_field::FieldElement;
-
-#[derive(Default)]
-pub struct CommitPhaseData {
-  pub merkle: [Vec<HashDigest>; MAX_FRI_DEPTH],
-  pub merkle_size: [usize; MAX_FRI_DEPTH],
-  pub rs_codeword: [Vec<FieldElement>; MAX_FRI_DEPTH],
-  pub poly_coef: [Vec<FieldElement>; MAX_FRI_DEPTH],
+use prime_field::{Field, FieldElement};
+use serde::{Deserialize, Serialize};
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LdtCommitment<F = FieldElement> {
+  pub commitment_hash: Vec<HashDigest>,
+  pub randomness: Vec<F>,
+  pub final_rs_code: Vec<F>,
+  pub mx_depth: usize,
+  // repeat_no: usize,
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct CommitPhaseData<F = FieldElement> {
+  pub merkle: [MerkleTree; MAX_FRI_DEPTH],
+  pub rs_codeword: [Vec<F>; MAX_FRI_DEPTH],
+  pub poly_coef: [Vec<F>; MAX_FRI_DEPTH],
   pub rs_codeword_mapping: [Vec<usize>; MAX_FRI_DEPTH],
 }
 
 // namespace fri
-impl CommitPhaseData {
+impl<F: Field> CommitPhaseData<F> {
   pub fn new() -> Self {
     Default::default()
   }
@@ -27,17 +36,16 @@ impl CommitPhaseData {
   }
 }
 
-#[derive(Debug)]
-pub struct FieldElement64([Vec<FieldElement>; SLICE_NUMBER]);
+#[derive(Debug, Clone)]
+pub struct FieldElement64<F = FieldElement>([Vec<F>; SLICE_NUMBER]);
 
-impl Default for FieldElement64 {
+impl<F> Default for FieldElement64<F> {
   fn default() -> Self {
-    const EMPTY_VEC: Vec<FieldElement> = Vec::new();
-    FieldElement64([EMPTY_VEC; SLICE_NUMBER])
+    FieldElement64(std::array::from_fn(|_| Vec::new()))
   }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Mapping64([Vec<usize>; SLICE_NUMBER]);
 
 impl Default for Mapping64 {
@@ -47,136 +55,151 @@ impl Default for Mapping64 {
   }
 }
 
-#[derive(Default)]
-pub struct FRIContext {
+#[derive(Default, Debug, Clone)]
+pub struct FRIContext<F = FieldElement> {
   pub log_current_witness_size_per_slice: usize,
   pub witness_bit_length_per_slice: i64,
   pub current_step_no: usize,
-  pub cpd: CommitPhaseData,
+  pub cpd: CommitPhaseData<F>,
   pub fri_timer: f64,
-  pub witness_merkle: [Vec<HashDigest>; 2],
-  pub witness_rs_codeword_before_arrange: [FieldElement64; 2],
-  pub witness_rs_codeword_interleaved: [Vec<FieldElement>; 2],
-  pub witness_rs_mapping: [Mapping64; 2],
-  pub l_group: Vec<FieldElement>,
-  pub visited: [Vec<bool>; MAX_BIT_LENGTH],
-  pub visited_init: [Vec<bool>; 2],
-  pub visited_witness: [Vec<bool>; 2],
-  pub virtual_oracle_witness: Vec<FieldElement>,
+  /// Merkle trees of the initial oracles, by oracle indicator
+  pub witness_merkle: Vec<MerkleTree>,
+  pub witness_rs_codeword_before_arrange: Vec<FieldElement64<F>>,
+  pub witness_rs_codeword_interleaved: Vec<Vec<F>>,
+  pub witness_rs_mapping: Vec<Mapping64>,
+  pub l_group: Vec<F>,
+  pub virtual_oracle_witness: Vec<F>,
   pub virtual_oracle_witness_mapping: Vec<usize>,
 
-  pub r_extended: Vec<FieldElement>,
-  pub leaf_hash: [Vec<HashDigest>; 2],
+  pub r_extended: Vec<F>,
+  /// Hash function of every merkle tree of the proof
+  pub hasher: HashAlgorithm,
 }
 
-/// Given private input, calculate the first oracle commitment
-pub fn request_init_commit(
-  FRIContext {
-    log_current_witness_size_per_slice,
-    witness_bit_length_per_slice,
-    current_step_no,
-    cpd,
-    fri_timer,
-    witness_merkle,
-    witness_rs_codeword_before_arrange,
-    witness_rs_codeword_interleaved,
-    witness_rs_mapping,
-    l_group,
-    visited,
-    visited_init,
-    visited_witness,
-    virtual_oracle_witness,
-    virtual_oracle_witness_mapping,
-    r_extended,
-    leaf_hash,
-  }: &mut FRIContext,
-  PolyCommitContext {
-    slice_size,
-    slice_count,
-    l_eval,
-    h_eval_arr,
-    ..
-  }: PolyCommitContext,
-  bit_len: usize,
-  oracle_indicator: usize,
-) -> HashDigest {
-  assert_eq!(
-    slice_size * slice_count,
-    (1 << (bit_len + RS_CODE_RATE - LOG_SLICE_NUMBER)) * (1 << LOG_SLICE_NUMBER)
-  );
-
-  *fri_timer = 0.;
+/// Number of field elements in a leaf: a pair of values for every slice
+pub(crate) const LOG_LEAF_SIZE: usize = LOG_SLICE_NUMBER + 1;
+
+/// Rearranges the codewords of all slices, stored back to back, so that leaf
+/// `j` holds for every slice the values at `w^j` and `w^{j + half}`, which
+/// are the two points folded together by FRI.
+pub(crate) fn interleave<F: Field>(codewords: &[F], slice_size: usize) -> Vec<F> {
+  let half_slice_size = slice_size / 2;
+  let mut interleaved = vec![F::zero(); codewords.len()];
+  for (i, codeword) in codewords.chunks(slice_size).enumerate() {
+    for j in 0..half_slice_size {
+      interleaved[j << LOG_LEAF_SIZE | (i << 1)] = codeword[j];
+      interleaved[j << LOG_LEAF_SIZE | (i << 1) | 1] = codeword[j + half_slice_size];
+    }
+  }
+  interleaved
+}
 
-  std::mem::take(&mut witness_merkle[oracle_indicator]);
-  std::mem::take(&mut witness_rs_codeword_interleaved[oracle_indicator]);
+pub(crate) fn hash_leaf<H: Hasher + ?Sized, F: Field>(hasher: &H, values: &[(F, F)]) -> HashDigest {
+  values.iter().fold(HashDigest::default(), |hash, (x, y)| {
+    hash_double_field_element_merkle_damgard(hasher, *x, *y, hash)
+  })
+}
 
-  *current_step_no = 0;
+pub(crate) fn hash_leaves<H: Hasher + ?Sized, F: Field>(
+  hasher: &H,
+  interleaved: &[F],
+) -> Vec<HashDigest> {
+  interleaved
+    .chunks(1 << LOG_LEAF_SIZE)
+    .map(|leaf| {
+      let values: Vec<_> = leaf.chunks(2).map(|pair| (pair[0], pair[1])).collect();
+      hash_leaf(hasher, &values)
+    })
+    .collect()
+}
 
+/// Given private input, calculate the first oracle commitment.
+///
+/// `codewords` holds the RS codewords of all slices back to back, each
+/// evaluated on the `1 << (bit_len + RS_CODE_RATE - LOG_SLICE_NUMBER)` powers
+/// of the root of unity, and are committed to leaf by leaf as laid out by
+/// [`interleave`]. Oracle `0` starts a new low degree test, dropping the
+/// initial oracles of the previous one, the others are committed in order or
+/// replace the oracle of the same indicator.
+pub fn request_init_commit<F: Field>(
+  ctx: &mut FRIContext<F>,
+  codewords: &[F],
+  bit_len: usize,
+  oracle_indicator: usize,
+) -> HashDigest {
   assert_eq!(1 << LOG_SLICE_NUMBER, SLICE_NUMBER);
 
-  *log_current_witness_size_per_slice = bit_len + RS_CODE_RATE - LOG_SLICE_NUMBER;
-  *witness_bit_length_per_slice = (bit_len - LOG_SLICE_NUMBER).try_into().unwrap();
+  ctx.fri_timer = 0.;
+  ctx.current_step_no = 0;
+
+  ctx.log_current_witness_size_per_slice = bit_len + RS_CODE_RATE - LOG_SLICE_NUMBER;
+  ctx.witness_bit_length_per_slice = (bit_len - LOG_SLICE_NUMBER).try_into().unwrap();
 
   let now = time::Instant::now();
 
-  let sliced_input_length_per_block = 1 << *witness_bit_length_per_slice;
-  assert!(*witness_bit_length_per_slice >= 0);
+  let slice_size = 1 << ctx.log_current_witness_size_per_slice;
+  let half_slice_size = slice_size / 2;
+  assert_eq!(codewords.len(), slice_size * SLICE_NUMBER);
 
-  let mut root_of_unity =
-    FieldElement::get_root_of_unity(*log_current_witness_size_per_slice).unwrap();
   if oracle_indicator == 0 {
-    l_group.reserve(1 << *log_current_witness_size_per_slice);
-    l_group[0] = FieldElement::from_real(1);
-
-    for i in 1..(1 << *log_current_witness_size_per_slice) {
-      l_group[i] = l_group[i - 1] * root_of_unity;
+    ctx.witness_merkle.clear();
+    ctx.witness_rs_codeword_before_arrange.clear();
+    ctx.witness_rs_codeword_interleaved.clear();
+    ctx.witness_rs_mapping.clear();
+
+    let root_of_unity = F::root_of_unity(ctx.log_current_witness_size_per_slice).unwrap();
+    ctx.l_group = vec![F::one(); slice_size];
+    for i in 1..slice_size {
+      ctx.l_group[i] = ctx.l_group[i - 1] * root_of_unity;
     }
-    assert_eq!(
-      l_group[(1 << *log_current_witness_size_per_slice) - 1] * root_of_unity,
-      FieldElement::from_real(1)
-    );
+    assert_eq!(ctx.l_group[slice_size - 1] * root_of_unity, F::one());
   }
 
-  witness_rs_codeword_interleaved[oracle_indicator].reserve(1 << (bit_len + RS_CODE_RATE));
-
-  let log_leaf_size = LOG_SLICE_NUMBER + 1;
-  for i in 0..SLICE_NUMBER {
-    assert_eq!(
-      <usize as std::convert::TryInto<i64>>::try_into(*log_current_witness_size_per_slice).unwrap()
-        - RS_CODE_RATE as i64,
-      *witness_bit_length_per_slice
-    );
-    root_of_unity =
-      FieldElement::get_root_of_unity((*witness_bit_length_per_slice).try_into().unwrap()).unwrap();
-
-    if oracle_indicator == 0 {
-      witness_rs_codeword_before_arrange[0].0[i] = l_eval[i * slice_size..].to_vec();
-    } else {
-      witness_rs_codeword_before_arrange[1].0[i] = h_eval_arr[i * slice_size..].to_vec();
-    }
+  assert!(oracle_indicator <= ctx.witness_merkle.len());
 
-    root_of_unity = FieldElement::get_root_of_unity(*log_current_witness_size_per_slice).unwrap();
+  let interleaved = interleave(codewords, slice_size);
+  let mut before_arrange = FieldElement64::default();
+  let mut mappings = Mapping64::default();
+  for (i, codeword) in codewords.chunks(slice_size).enumerate() {
+    before_arrange.0[i] = codeword.to_vec();
 
-    witness_rs_mapping[oracle_indicator].0[i].reserve(1 << *log_current_witness_size_per_slice);
-
-    let a = FieldElement::zero();
-    for j in 0..(1 << (*log_current_witness_size_per_slice - 1)) {
-      assert!((j << log_leaf_size | (i << 1) | 1) < (1 << (bit_len + RS_CODE_RATE)));
-      assert!((j << log_leaf_size | (i << 1) | 1) < slice_size * slice_count);
-
-      witness_rs_mapping[oracle_indicator].0[i][j] = j << log_leaf_size | (i << 1) | 0;
-      witness_rs_mapping[oracle_indicator].0[i]
-        [j + (1 << *log_current_witness_size_per_slice) / 2] = j << log_leaf_size | (i << 1) | 0;
+    let mapping = &mut mappings.0[i];
+    *mapping = vec![0; slice_size];
+    for j in 0..half_slice_size {
+      mapping[j] = j << LOG_LEAF_SIZE | (i << 1);
+      mapping[j + half_slice_size] = j << LOG_LEAF_SIZE | (i << 1);
     }
   }
+  let merkle = MerkleTree::commit(&ctx.hasher, &hash_leaves(&ctx.hasher, &interleaved));
+  let root = merkle.root();
+
+  if oracle_indicator == ctx.witness_merkle.len() {
+    ctx.witness_merkle.push(merkle);
+    ctx.witness_rs_codeword_before_arrange.push(before_arrange);
+    ctx.witness_rs_codeword_interleaved.push(interleaved);
+    ctx.witness_rs_mapping.push(mappings);
+  } else {
+    ctx.witness_merkle[oracle_indicator] = merkle;
+    ctx.witness_rs_codeword_before_arrange[oracle_indicator] = before_arrange;
+    ctx.witness_rs_codeword_interleaved[oracle_indicator] = interleaved;
+    ctx.witness_rs_mapping[oracle_indicator] = mappings;
+  }
 
-  leaf_hash[oracle_indicator].reserve(1 << (*log_current_witness_size_per_slice - 1));
-  for i in 0..(1 << (*log_current_witness_size_per_slice - 1)) {
-    let tmp_hash = HashDigest::new();
-    let data = [HashDigest::new(), HashDigest::new()];
+  ctx.fri_timer += now.elapsed().as_secs_f64();
+  root
+}
 
-    for j in 0..(1 << log_leaf_size) {}
+impl<F: Field> FRIContext<F> {
+  /// Sets the oracle tested by the commit phase, from the codewords of all
+  /// slices stored back to back. It is never committed to, the verifier
+  /// computes its values from the initial oracles.
+  pub fn set_virtual_oracle(&mut self, codewords: &[F]) {
+    let log_slice_size = self.witness_bit_length_per_slice as usize + RS_CODE_RATE;
+    let slice_size = 1 << log_slice_size;
+    assert_eq!(codewords.len(), slice_size * SLICE_NUMBER);
+
+    self.virtual_oracle_witness = interleave(codewords, slice_size);
+    self.log_current_witness_size_per_slice = log_slice_size;
+    self.current_step_no = 0;
   }
-
-  unimplemented!()
 }
@@ -1,3 +1,3 @@
 pub mod fri;
-mod prover;
-mod verifier;
+pub mod prover;
+pub mod verifier;
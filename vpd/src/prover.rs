@@ -1,14 +1,13 @@
 use infrastructure::my_hash::HashDigest;
-use poly_commitment::PolyCommitContext;
+use prime_field::Field;
 
 use crate::fri::{request_init_commit, FRIContext};
 
 /// This will returns the merkle root
-pub fn vpd_prover_init(log_input_length: usize) -> HashDigest {
-  request_init_commit(
-    &mut FRIContext::default(),
-    PolyCommitContext::default(),
-    log_input_length,
-    0,
-  )
+pub fn vpd_prover_init<F: Field>(
+  fri_ctx: &mut FRIContext<F>,
+  l_eval: &[F],
+  log_input_length: usize,
+) -> HashDigest {
+  request_init_commit(fri_ctx, l_eval, log_input_length, 0)
 }
@@ -1,331 +1,408 @@
-use infrastructure::merkle_tree::{create_tree, hash_single_field_element};
-use std::{char::MAX, thread::current};
+use std::{fmt, time};
 
 use infrastructure::{
-  constants::{LOG_SLICE_NUMBER, RS_CODE_RATE, SLICE_NUMBER},
-  my_hash::{self, HashDigest},
+  constants::{LDT_REPEAT_NUM, LOG_SLICE_NUMBER, RS_CODE_RATE, SLICE_NUMBER},
+  merkle_tree::{MerkleTree, MultiProof},
+  my_hash::{HashDigest, Hasher},
+  transcript::Transcript,
 };
 
-use poly_commitment::LdtCommitment;
-use prime_field::FieldElement;
+use prime_field::batch::PowersTable;
+use prime_field::{Field, FieldElement};
+use serde::{Deserialize, Serialize};
 
-use crate::fri::FRIContext;
+use crate::fri::{hash_leaf, hash_leaves, FRIContext, LdtCommitment, LOG_LEAF_SIZE};
 
-pub fn verify_merkle(
-  hash_digest: HashDigest,
-  merkle_path: Vec<HashDigest>,
-  len: usize,
-  pow: i32,
-  values: Vec<(FieldElement, FieldElement)>,
-) -> bool {
-  // We need to make sure the len is always smaller than the size of merklePath.
-  assert!(merkle_path.len() >= len);
-
-  let mut pow = pow;
-  let current_hash: HashDigest = *merkle_path.last().unwrap();
-
-  // don't mutate the current_hash, this is the output of the loop following
-  let mut new_hash = HashDigest::new();
-
-  for i in 0..(len - 1) {
-    if (pow & i as i32).is_positive() {
-      let data: [HashDigest; 2] = [merkle_path[i], current_hash];
-      new_hash = my_hash::my_hash(data);
-    } else {
-      let data: [HashDigest; 2] = [current_hash, merkle_path[i]];
-      new_hash = my_hash::my_hash(data);
+/// Answer to the FRI queries on one oracle: for every queried leaf, in query
+/// order, the pairs of every slice, and the proof authenticating the leaves
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryResponse<F = FieldElement> {
+  pub values: Vec<Vec<(F, F)>>,
+  pub proof: MultiProof,
+}
+
+/// Answers to the queries of the low degree test
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FriQueries<F = FieldElement> {
+  /// Openings of the initial oracles at the queried pairs
+  pub init: Vec<QueryResponse<F>>,
+  /// Openings of the oracles committed by the commit phase, the last oracle
+  /// being sent in full
+  pub steps: Vec<QueryResponse<F>>,
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum FriError {
+  /// The proof doesn't have the shape implied by the codeword size
+  MalformedProof,
+  /// The final codeword is not of the claimed degree
+  FinalRsCodeDegree,
+  /// The openings of an initial oracle aren't authenticated by its root
+  InitMerkleMismatch { oracle: usize },
+  /// The openings of the oracle committed at `step` aren't authenticated
+  StepMerkleMismatch { step: usize },
+  /// A folded value doesn't match the next oracle
+  FoldMismatch { query: usize, step: usize },
+}
+
+impl fmt::Display for FriError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::MalformedProof => write!(f, "malformed low degree test proof"),
+      Self::FinalRsCodeDegree => write!(f, "final codeword exceeds the degree bound"),
+      Self::InitMerkleMismatch { oracle } => {
+        write!(f, "openings of initial oracle {} are invalid", oracle)
+      }
+      Self::StepMerkleMismatch { step } => {
+        write!(f, "openings of the oracle of step {} are invalid", step)
+      }
+      Self::FoldMismatch { query, step } => {
+        write!(f, "query {} is inconsistent at step {}", query, step)
+      }
     }
-    pow /= 2;
   }
+}
 
-  let mut value_hash = HashDigest::new();
+impl std::error::Error for FriError {}
 
-  unsafe {
-    for value in values {
-      let mut data_element: [HashDigest; 2] = [
-        hash_single_field_element(value.0),
-        hash_single_field_element(value.1),
-      ];
-      data_element[1] = value_hash;
-      value_hash = my_hash::my_hash(data_element);
-    }
+/// Value at `x^2` of the oracle folded with `r`, given the values `a` at `x`
+/// and `b` at `-x`, `inv_2` being the inverse of 2
+fn fold_pair<F: Field>(a: F, b: F, r: F, inv_x: F, inv_2: F) -> F {
+  inv_2 * ((a + b) + r * inv_x * (a - b))
+}
+
+/// Checks that `response` opens `leaves` of the merkle tree of depth `depth`
+/// with root `root`
+pub fn verify_merkle<H: Hasher + ?Sized, F: Field>(
+  hasher: &H,
+  root: HashDigest,
+  depth: usize,
+  leaves: &[usize],
+  response: &QueryResponse<F>,
+) -> bool {
+  if response.proof.depth != depth || response.values.len() != leaves.len() {
+    return false;
   }
+  let leaf_hash: Vec<_> = response
+    .values
+    .iter()
+    .map(|values| hash_leaf(hasher, values))
+    .collect();
+  MerkleTree::verify(hasher, root, leaves, &leaf_hash, &response.proof)
+}
 
-  hash_digest == new_hash && merkle_path.last() == Some(&value_hash)
+fn open_leaves<F: Field>(
+  interleaved: &[F],
+  tree: &MerkleTree,
+  leaves: &[usize],
+) -> QueryResponse<F> {
+  let values = leaves
+    .iter()
+    .map(|leaf| {
+      interleaved[leaf << LOG_LEAF_SIZE..(leaf + 1) << LOG_LEAF_SIZE]
+        .chunks(2)
+        .map(|pair| (pair[0], pair[1]))
+        .collect()
+    })
+    .collect();
+  QueryResponse {
+    values,
+    proof: tree.open(leaves),
+  }
 }
 
-impl FRIContext {
-  /// Request two values w^{pow0} and w^{pow1}, with merkle tree proof, where w is the root of unity and w^{pow0} and w^{pow1} are quad residue. Repeat ldt_repeat_num times, storing all result in vector.
+impl<F: Field> FRIContext<F> {
+  /// Request the values at w^{pow} and w^{pow + half} of an initial oracle for
+  /// every `pow` in `pows`, where w is the root of unity, with their merkle
+  /// proof.
   pub fn request_init_value_with_merkle(
-    &mut self,
-    pow_0: usize,
-    pow_1: usize,
-    // new_size: &i64,
+    &self,
+    pows: &[usize],
     oracle_indicator: usize,
-  ) -> (Vec<(FieldElement, FieldElement)>, Vec<HashDigest>) {
-    // we swap pow_0 and pow_1 when pow_0 > pow_1
-    let (pow_0, pow_1) = if pow_0 > pow_1 {
-      (pow_1, pow_0)
-    } else {
-      (pow_0, pow_1)
-    };
-
-    assert!(pow_0 + (1 << self.log_current_witness_size_per_slice) / 2 == pow_1);
-
-    let mut value: Vec<(FieldElement, FieldElement)> = vec![];
-    let log_leaf_size = LOG_SLICE_NUMBER + 1;
-
-    let mut new_size = 0;
-    for i in 0..SLICE_NUMBER {
-      let element_1 =
-        self.witness_rs_codeword_interleaved[oracle_indicator][pow_0 << log_leaf_size | i << 1 | 0];
+  ) -> QueryResponse<F> {
+    open_leaves(
+      &self.witness_rs_codeword_interleaved[oracle_indicator],
+      &self.witness_merkle[oracle_indicator],
+      pows,
+    )
+  }
 
-      let element_2 =
-        self.witness_rs_codeword_interleaved[oracle_indicator][pow_0 << log_leaf_size | i << 1 | 1];
+  /// Request the leaves `pows` of the oracle committed at step `lvl` of the
+  /// commit phase, with their merkle proof.
+  pub fn request_step_commit(&self, lvl: usize, pows: &[usize]) -> QueryResponse<F> {
+    open_leaves(&self.cpd.rs_codeword[lvl], &self.cpd.merkle[lvl], pows)
+  }
 
-      value.push((element_1, element_2));
+  fn current_oracle(&self) -> &[F] {
+    match self.current_step_no {
+      0 => &self.virtual_oracle_witness,
+      step => &self.cpd.rs_codeword[step - 1],
+    }
+  }
 
-      if !self.visited_witness[oracle_indicator][pow_0 << log_leaf_size | i << 1 | 0] {
-        self.visited_witness[oracle_indicator][pow_0 << log_leaf_size | i << 1 | 0] = true;
-      }
-      if !self.visited_witness[oracle_indicator][pow_0 << log_leaf_size | i << 1 | 1] {
-        self.visited_witness[oracle_indicator][pow_0 << log_leaf_size | i << 1 | 1] = true;
+  /// Folds the current oracle with `r` into an oracle of half its size
+  fn fold(&self, r: F) -> Vec<F> {
+    let previous = self.current_oracle();
+    let log_size = self.log_current_witness_size_per_slice;
+    let half_size = 1 << (log_size - 1);
+    let next_half_size = half_size / 2;
+    let inv_root_of_unity = F::root_of_unity(log_size).unwrap().inverse();
+    let inv_2 = F::from_u64(2).inverse();
+
+    let mut next = vec![F::zero(); half_size * SLICE_NUMBER];
+    let mut inv_x = F::one();
+    for p in 0..half_size {
+      // x^2 is the point p of the next oracle
+      let pos = (p % next_half_size) << LOG_LEAF_SIZE | (p / next_half_size);
+      for i in 0..SLICE_NUMBER {
+        let a = previous[p << LOG_LEAF_SIZE | i << 1];
+        let b = previous[p << LOG_LEAF_SIZE | i << 1 | 1];
+        next[pos | i << 1] = fold_pair(a, b, r, inv_x, inv_2);
       }
-      new_size += std::mem::size_of::<FieldElement>();
+      inv_x = inv_x * inv_root_of_unity;
     }
+    next
+  }
 
-    let depth = self.log_current_witness_size_per_slice - 1;
-    let mut com_hhash: Vec<HashDigest> = Vec::with_capacity(depth);
-
-    // minus 1 since each leaf have 2 values (qual resi)
-    let mut pos = pow_0 + (1 << (self.log_current_witness_size_per_slice - 1));
-
-    let mut test_hash = self.witness_merkle[oracle_indicator][pos];
-    com_hhash[depth] = test_hash;
+  /// Given fold parameter r, return the root of the merkle tree of next level.
+  pub fn commit_phrase_step(&mut self, r: F) -> HashDigest {
+    let next = self.fold(r);
 
-    for i in 0..depth {
-      if !self.visited_init[oracle_indicator][pos ^ 1] {
-        new_size += std::mem::size_of::<HashDigest>();
-      }
-      self.visited_init[oracle_indicator][pos] = true;
-      self.visited_init[oracle_indicator][pos ^ 1] = true;
+    let step = self.current_step_no;
+    self.cpd.merkle[step] = MerkleTree::commit(&self.hasher, &hash_leaves(&self.hasher, &next));
+    self.cpd.rs_codeword[step] = next;
 
-      let data = if (pos & 1) == 1 {
-        [self.witness_merkle[oracle_indicator][pos ^ 1], test_hash]
-      } else {
-        [test_hash, self.witness_merkle[oracle_indicator][pos ^ 1]]
-      };
-      test_hash = my_hash::my_hash(data);
+    self.log_current_witness_size_per_slice -= 1;
+    self.current_step_no += 1;
+    self.cpd.merkle[step].root()
+  }
 
-      com_hhash[i] = self.witness_merkle[oracle_indicator][pos ^ 1];
-      pos /= 2;
+  /// Return the final rs code since it is only constant size, the codewords
+  /// of all slices back to back
+  pub fn commit_phase_final(&self) -> Vec<F> {
+    let codeword = self.current_oracle();
+    let size = 1 << self.log_current_witness_size_per_slice;
+    let half_size = size / 2;
 
-      assert_eq!(test_hash, self.witness_merkle[oracle_indicator][pos]);
+    let mut final_rs_code = vec![F::zero(); size * SLICE_NUMBER];
+    for i in 0..SLICE_NUMBER {
+      for p in 0..half_size {
+        final_rs_code[i * size + p] = codeword[p << LOG_LEAF_SIZE | i << 1];
+        final_rs_code[i * size + p + half_size] = codeword[p << LOG_LEAF_SIZE | i << 1 | 1];
+      }
     }
-    assert!(pos == 1);
-    (value, com_hhash)
+    final_rs_code
   }
 
-  /// Request the merkle proof to lvl-th level oracle, at w^{pow}, will also return it's quad residue's proof.
-  /// returned value is unordered, meaning that one of them is the requested value and the other one is it's qual residue.
-  pub fn request_step_commit(
+  /// Folds the virtual oracle until its codewords encode constants, drawing
+  /// the fold parameters from `transcript`
+  pub fn commit_phase(
     &mut self,
-    lvl: usize,
-    pow: usize,
-    new_size: i64,
-  ) -> (Vec<(FieldElement, FieldElement)>, Vec<HashDigest>) {
-    let mut new_size = 0;
-    let mut pow_0 = 0;
-
-    let mut value_vec: Vec<(FieldElement, FieldElement)> = vec![];
-    let mut visited_element = false;
-
-    for i in 0..SLICE_NUMBER {
-      pow_0 = self.cpd.rs_codeword_mapping[lvl][pow << LOG_SLICE_NUMBER | i];
-      pow_0 /= 2;
-      if !self.visited[lvl][pow_0 * 2] {
-        self.visited[lvl][pow_0 * 2] = true;
+    log_length: usize,
+    transcript: &mut Transcript,
+  ) -> LdtCommitment<F> {
+    assert_eq!(
+      self.log_current_witness_size_per_slice,
+      log_length + RS_CODE_RATE - LOG_SLICE_NUMBER
+    );
+    assert_eq!(self.current_step_no, 0);
+
+    let now = time::Instant::now();
+
+    let mx_depth = log_length - LOG_SLICE_NUMBER;
+    let mut commitment_hash = Vec::with_capacity(mx_depth);
+    let mut randomness = Vec::with_capacity(mx_depth);
+    for step in 0..mx_depth {
+      let r = transcript.challenge();
+      randomness.push(r);
+      if step + 1 < mx_depth {
+        let root = self.commit_phrase_step(r);
+        transcript.append_digest(root);
+        commitment_hash.push(root);
       } else {
-        visited_element = true
+        // the last oracle is sent in full, no need to commit to it
+        self.cpd.rs_codeword[step] = self.fold(r);
+        self.log_current_witness_size_per_slice -= 1;
+        self.current_step_no += 1;
       }
-
-      value_vec.push((
-        self.cpd.rs_codeword[lvl][pow_0 * 2],
-        self.cpd.rs_codeword[lvl][pow_0 * 2 + 1],
-      ));
     }
+    assert_eq!(self.log_current_witness_size_per_slice, RS_CODE_RATE);
 
-    // this can be compressed into one by random linear combination
-    if !visited_element {
-      new_size += std::mem::size_of::<FieldElement>();
-    }
+    let final_rs_code = self.commit_phase_final();
+    transcript.absorb(&final_rs_code[..]);
 
-    let mut com_hhash: Vec<HashDigest> = vec![];
-    let merkle_size = self.cpd.merkle_size[lvl];
-    let val_hhash = self.cpd.merkle[lvl][pow_0];
-    pow_0 = (self.cpd.rs_codeword_mapping[lvl][pow << LOG_SLICE_NUMBER] >> (LOG_SLICE_NUMBER + 1))
-      + merkle_size;
-
-    while pow_0 != 1 {
-      if !self.visited[lvl][pow_0 ^ 1] {
-        new_size += std::mem::size_of::<HashDigest>();
-        self.visited[lvl][pow_0 ^ 1] = true;
-        self.visited[lvl][pow_0] = true;
-      }
-      com_hhash.push(self.cpd.merkle[lvl][pow_0 ^ 1]);
-      pow_0 /= 2;
+    self.fri_timer += now.elapsed().as_secs_f64();
+    LdtCommitment {
+      commitment_hash,
+      randomness,
+      final_rs_code,
+      mx_depth,
     }
+  }
 
-    com_hhash.push(val_hhash);
-    (value_vec, com_hhash)
+  /// Answers `LDT_REPEAT_NUM` queries drawn from `transcript`, once the
+  /// commit phase is done
+  pub fn query_phase(&self, transcript: &mut Transcript) -> FriQueries<F> {
+    let log_slice_size = self.witness_bit_length_per_slice as usize + RS_CODE_RATE;
+    let half_slice_size = 1 << (log_slice_size - 1);
+
+    let mut pows: Vec<_> = (0..LDT_REPEAT_NUM)
+      .map(|_| transcript.challenge_index(half_slice_size))
+      .collect();
+    let init = (0..self.witness_merkle.len())
+      .map(|oracle| self.request_init_value_with_merkle(&pows, oracle))
+      .collect();
+
+    let steps = (0..self.current_step_no.saturating_sub(1))
+      .map(|lvl| {
+        let leaf_count = self.cpd.merkle[lvl].leaf_count();
+        pows.iter_mut().for_each(|pow| *pow %= leaf_count);
+        self.request_step_commit(lvl, &pows)
+      })
+      .collect();
+    FriQueries { init, steps }
   }
+}
 
-  /// Given fold parameter r, return the root of the merkle tree of next level.
-  pub fn commit_phrase_step(&mut self, r: FieldElement) -> HashDigest {
-    let nxt_witness_size = (1 << self.log_current_witness_size_per_slice) / 2;
-    if self.cpd.rs_codeword[self.current_step_no].is_empty() {
-      self.cpd.rs_codeword[self.current_step_no] =
-        vec![FieldElement::default(); nxt_witness_size * (1 << SLICE_NUMBER)];
-    }
+/// Low degree test of a virtual oracle whose codewords have size
+/// `1 << log_codeword_size` per slice, and must have degree below
+/// `1 << (log_codeword_size - RS_CODE_RATE)`.
+///
+/// The initial oracles are authenticated against `init_roots` before
+/// `virtual_oracle` computes, from their openings at `pow`, the values of the
+/// virtual oracle of every slice at `w^pow` and `w^{pow + half}`.
+pub fn verify_ldt<H, F, O>(
+  hasher: &H,
+  commitment: &LdtCommitment<F>,
+  init_roots: &[HashDigest],
+  queries: &FriQueries<F>,
+  log_codeword_size: usize,
+  transcript: &mut Transcript,
+  mut virtual_oracle: O,
+) -> Result<(), FriError>
+where
+  H: Hasher + ?Sized,
+  F: Field,
+  O: FnMut(usize, &[&[(F, F)]]) -> Vec<(F, F)>,
+{
+  assert!(log_codeword_size > RS_CODE_RATE);
+  let mx_depth = log_codeword_size - RS_CODE_RATE;
+  let final_size = 1 << RS_CODE_RATE;
+
+  let well_formed = |response: &QueryResponse<F>| {
+    response.values.len() == LDT_REPEAT_NUM
+      && response
+        .values
+        .iter()
+        .all(|values| values.len() == SLICE_NUMBER)
+  };
+  if commitment.mx_depth != mx_depth
+    || commitment.randomness.len() != mx_depth
+    || commitment.commitment_hash.len() != mx_depth - 1
+    || commitment.final_rs_code.len() != final_size * SLICE_NUMBER
+    || queries.init.len() != init_roots.len()
+    || queries.steps.len() != mx_depth - 1
+    || !queries.init.iter().chain(&queries.steps).all(well_formed)
+  {
+    return Err(FriError::MalformedProof);
+  }
 
-    let mut previous_witness: Vec<FieldElement> = vec![];
-    let mut previous_witness_mapping: Vec<usize> = vec![];
-
-    let (previous_witness, previous_witness_mapping) = match self.current_step_no {
-      0 => (
-        self.virtual_oracle_witness.clone(),
-        self.virtual_oracle_witness_mapping.clone(),
-      ),
-      _ => (
-        self.cpd.rs_codeword[self.current_step_no - 1].clone(),
-        self.cpd.rs_codeword_mapping[self.current_step_no - 1].clone(),
-      ),
-    };
-
-    let inv_2 = FieldElement::default().inverse();
-
-    let log_leaf_size = LOG_SLICE_NUMBER + 1;
-
-    for i in 0..nxt_witness_size {
-      let qual_res_0 = i;
-      let qual_res_1 = (1 << (self.log_current_witness_size_per_slice - 1) + i) / 2;
-      let pos = usize::min(qual_res_0, qual_res_1);
-
-      let inv_mu = self.l_group[((1 << self.log_current_witness_size_per_slice) - i)
-        & ((1 << self.log_current_witness_size_per_slice) - 1)];
-
-      for j in 0..SLICE_NUMBER {
-        let real_pos = previous_witness_mapping[(pos) << LOG_SLICE_NUMBER | j];
-        // assert((i << LOG_SLICE_NUMBER | j) < nxt_witness_size * SLICE_COUNT);
-        // we should check this since the original code has BUG comment
-        self.cpd.rs_codeword[self.current_step_no][i << LOG_SLICE_NUMBER | j] = inv_2
-          * (previous_witness[real_pos] + previous_witness[real_pos | 1])
-          + inv_mu * r * (previous_witness[real_pos] - previous_witness[real_pos | 1]);
-      }
+  // a codeword of degree 0 is constant
+  for codeword in commitment.final_rs_code.chunks(final_size) {
+    if codeword.iter().any(|x| *x != codeword[0]) {
+      return Err(FriError::FinalRsCodeDegree);
     }
+  }
 
-    for i in 0..nxt_witness_size {
-      self.l_group[i] = self.l_group[i * 2];
+  for step in 0..mx_depth {
+    if transcript.challenge::<F>() != commitment.randomness[step] {
+      return Err(FriError::MalformedProof);
     }
-
-    // we assume poly_commit::slice_count is (1 << SLICE_NUMBER) here
-    let mut tmp: Vec<FieldElement> =
-      vec![FieldElement::new_random(); nxt_witness_size * (1 << SLICE_NUMBER)];
-    self.cpd.rs_codeword_mapping[self.current_step_no] =
-      vec![0; nxt_witness_size * (1 << SLICE_NUMBER)];
-
-    for i in 0..nxt_witness_size / 2 {
-      for j in 0..SLICE_NUMBER {
-        let a = i << LOG_SLICE_NUMBER | j;
-        let b = (i + nxt_witness_size / 2) << LOG_SLICE_NUMBER | j;
-        let c = (i) << log_leaf_size | (j << 1) | 0;
-        let d = (i) << log_leaf_size | (j << 1) | 1;
-
-        self.cpd.rs_codeword_mapping[self.current_step_no][a] = (i) << log_leaf_size | (j << 1) | 0;
-        self.cpd.rs_codeword_mapping[self.current_step_no][b] = (i) << log_leaf_size | (j << 1) | 0;
-
-        tmp[c] = self.cpd.rs_codeword[self.current_step_no][i << LOG_SLICE_NUMBER | j];
-        tmp[d] = self.cpd.rs_codeword[self.current_step_no]
-          [(i + nxt_witness_size / 2) << LOG_SLICE_NUMBER | j];
-
-        assert!(a < nxt_witness_size * SLICE_NUMBER);
-        assert!(b < nxt_witness_size * SLICE_NUMBER);
-        assert!(c < nxt_witness_size * SLICE_NUMBER);
-        assert!(d < nxt_witness_size * SLICE_NUMBER);
-      }
+    if let Some(root) = commitment.commitment_hash.get(step) {
+      transcript.append_digest(*root);
     }
+  }
+  transcript.absorb(&commitment.final_rs_code[..]);
+
+  let half_slice_size = 1 << (log_codeword_size - 1);
+  let mut pows: Vec<_> = (0..LDT_REPEAT_NUM)
+    .map(|_| transcript.challenge_index(half_slice_size))
+    .collect();
+  for (oracle, (root, response)) in init_roots.iter().zip(&queries.init).enumerate() {
+    if !verify_merkle(hasher, *root, log_codeword_size - 1, &pows, response) {
+      return Err(FriError::InitMerkleMismatch { oracle });
+    }
+  }
 
-    self.cpd.rs_codeword[self.current_step_no] = tmp;
-
-    self.visited[self.current_step_no] = vec![false; nxt_witness_size * (1 << SLICE_NUMBER) * 4];
-
-    let mut htmp: HashDigest = HashDigest::default();
-    let mut hash_val: Vec<HashDigest> = vec![HashDigest::default(); nxt_witness_size / 2];
-
-    unsafe {
-      for i in 0..nxt_witness_size / 2 {
-        for j in 0..(1 << LOG_SLICE_NUMBER) {
-          let mut data = [HashDigest::default(), HashDigest::default()];
-          let c = (i) << log_leaf_size | (j << 1) | 0;
-          let d = (i) << log_leaf_size | (j << 1) | 1;
-
-          let data_ele = [
-            self.cpd.rs_codeword[self.current_step_no][c],
-            self.cpd.rs_codeword[self.current_step_no][d],
-          ];
-
-          data[0] = hash_single_field_element(data_ele[0]);
-          data[1] = htmp;
+  let mut values: Vec<_> = pows
+    .iter()
+    .enumerate()
+    .map(|(query, pow)| {
+      let init: Vec<_> = queries
+        .init
+        .iter()
+        .map(|response| &response.values[query][..])
+        .collect();
+      let values = virtual_oracle(*pow, &init);
+      assert_eq!(values.len(), SLICE_NUMBER);
+      values
+    })
+    .collect();
+
+  let inv_2 = F::from_u64(2).inverse();
+  let mut log_size = log_codeword_size;
+  for (step, r) in commitment.randomness.iter().enumerate() {
+    let inv_root_of_unity = F::root_of_unity(log_size).unwrap().inverse();
+    let inv_root_powers = PowersTable::new(inv_root_of_unity, log_size);
+    let folded: Vec<Vec<_>> = values
+      .iter()
+      .zip(&pows)
+      .map(|(values, pow)| {
+        let inv_x = inv_root_powers.pow(*pow as u128);
+        values
+          .iter()
+          .map(|(a, b)| fold_pair(*a, *b, *r, inv_x, inv_2))
+          .collect()
+      })
+      .collect();
+
+    if step + 1 == mx_depth {
+      for (query, (folded, pow)) in folded.iter().zip(&pows).enumerate() {
+        for (i, value) in folded.iter().enumerate() {
+          if commitment.final_rs_code[i * final_size + pow] != *value {
+            return Err(FriError::FoldMismatch { query, step });
+          }
         }
-        hash_val[i] = htmp;
       }
-
-      // write merkle tree to self.cpd.merkle[self.current_step_no]
-      let current_step_no = self.cpd.merkle[self.current_step_no].clone();
-      create_tree(
-        hash_val,
-        nxt_witness_size / 2,
-        self.cpd.merkle[self.current_step_no].as_mut(),
-        Some(std::mem::size_of::<HashDigest>()),
-        Some(current_step_no.is_empty()),
-      )
+      break;
     }
 
-    self.cpd.merkle_size[self.current_step_no] = nxt_witness_size / 2;
-    self.log_current_witness_size_per_slice -= 1;
-
-    self.current_step_no += 1;
-    self.cpd.merkle[self.current_step_no - 1][1] // since we increment current_step_no up there
-  }
-
-  /// Return the final rs code since it is only constant size
-  pub fn commit_phase_final(&self) -> Vec<FieldElement> {
-    self.cpd.rs_codeword[self.current_step_no - 1].clone()
-  }
-
-  pub fn commit_phase(&mut self, log_length: usize) -> LdtCommitment {
-    // let log_current_witness_size_per_slice_cp = self.log_current_witness_size_per_slice;
-    // assumming we already have the initial commit
-    let mut codeword_size = 1 << (log_length + RS_CODE_RATE - LOG_SLICE_NUMBER);
-    // repeat until the codeword is constant
-    let mut ret: Vec<HashDigest> = Vec::with_capacity(log_length + RS_CODE_RATE - LOG_SLICE_NUMBER);
-    let mut randomness: Vec<FieldElement> =
-      Vec::with_capacity(log_length + RS_CODE_RATE - LOG_SLICE_NUMBER);
-
-    let mut ptr = 0;
-    while codeword_size > 1 << RS_CODE_RATE {
-      assert!(ptr < log_length + RS_CODE_RATE - LOG_SLICE_NUMBER);
-      randomness[ptr] = FieldElement::new_random();
-      ret[ptr] = self.commit_phrase_step(randomness[ptr]);
-      codeword_size /= 2;
-      ptr += 1;
+    // the folded values at x^2 are in leaf pow % half of the next oracle
+    let next_half_size = 1 << (log_size - 2);
+    let leaves: Vec<_> = pows.iter().map(|pow| pow % next_half_size).collect();
+    let response = &queries.steps[step];
+    if !verify_merkle(
+      hasher,
+      commitment.commitment_hash[step],
+      log_size - 2,
+      &leaves,
+      response,
+    ) {
+      return Err(FriError::StepMerkleMismatch { step });
     }
-
-    LdtCommitment {
-      commitment_hash: ret,
-      final_rs_code: self.commit_phase_final(),
-      randomness,
-      mx_depth: ptr,
+    for (query, (folded, pow)) in folded.iter().zip(&pows).enumerate() {
+      let second = *pow >= next_half_size;
+      for (value, (a, b)) in folded.iter().zip(&response.values[query]) {
+        if *value != if second { *b } else { *a } {
+          return Err(FriError::FoldMismatch { query, step });
+        }
+      }
     }
+
+    values = response.values.clone();
+    pows = leaves;
+    log_size -= 1;
   }
+  Ok(())
 }